@@ -0,0 +1,165 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use iglo::{
+    chess::{board::ChessBoardState, move_generator::generate_legal_moves, perft::perft},
+    engine::{
+        board_eval::{
+            BishopPairEvaluation, DoublePawnsEvaluation, EvaluationFunction,
+            KingPawnShieldEvaluation, PassedPawnEvaluation, PieceConnectivityEvaluation,
+            PieceCountEvaluation, PieceSquareTableEvaluation,
+        },
+        bot::ChessBot,
+        bots::nplytranspo_bot::NPlyTranspoBot,
+        time_control::TimeControl,
+    },
+};
+
+// Start position followed by the well known perft test positions
+const BENCH_FENS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+];
+
+fn bench_positions() -> Vec<ChessBoardState> {
+    BENCH_FENS
+        .iter()
+        .map(|fen| ChessBoardState::from_fen(fen).unwrap())
+        .collect()
+}
+
+fn movegen_benchmark(c: &mut Criterion) {
+    let positions = bench_positions();
+
+    c.bench_function("generate_legal_moves", |b| {
+        b.iter(|| {
+            for board_state in &positions {
+                black_box(generate_legal_moves::<false>(
+                    black_box(board_state),
+                    board_state.side,
+                ));
+            }
+        })
+    });
+    c.bench_function("generate_legal_moves captures only", |b| {
+        b.iter(|| {
+            for board_state in &positions {
+                black_box(generate_legal_moves::<true>(
+                    black_box(board_state),
+                    board_state.side,
+                ));
+            }
+        })
+    });
+    // The same generation into a move list on the stack, without the Vec allocation
+    c.bench_function("legal_moves", |b| {
+        b.iter(|| {
+            for board_state in &positions {
+                black_box(black_box(board_state).legal_moves::<false>());
+            }
+        })
+    });
+    c.bench_function("legal_moves captures only", |b| {
+        b.iter(|| {
+            for board_state in &positions {
+                black_box(black_box(board_state).legal_moves::<true>());
+            }
+        })
+    });
+}
+
+fn exec_move_benchmark(c: &mut Criterion) {
+    let positions: Vec<_> = bench_positions()
+        .into_iter()
+        .map(|board_state| {
+            let moves = board_state.generate_legal_moves_for_current_player::<false>();
+            (board_state, moves)
+        })
+        .collect();
+
+    c.bench_function("exec_move", |b| {
+        b.iter(|| {
+            for (board_state, moves) in &positions {
+                for mv in moves {
+                    black_box(black_box(board_state).exec_move(*mv));
+                }
+            }
+        })
+    });
+}
+
+fn bench_eval<E: EvaluationFunction>(c: &mut Criterion, name: &str, positions: &[ChessBoardState]) {
+    c.bench_function(&format!("eval {}", name), |b| {
+        b.iter(|| {
+            positions
+                .iter()
+                .map(|board_state| E::eval(black_box(board_state)))
+                .sum::<i32>()
+        })
+    });
+}
+
+fn eval_benchmark(c: &mut Criterion) {
+    let positions = bench_positions();
+
+    bench_eval::<PieceCountEvaluation>(c, "PieceCount", &positions);
+    bench_eval::<PieceSquareTableEvaluation>(c, "PieceSquareTable", &positions);
+    bench_eval::<PassedPawnEvaluation>(c, "PassedPawn", &positions);
+    bench_eval::<BishopPairEvaluation>(c, "BishopPair", &positions);
+    bench_eval::<KingPawnShieldEvaluation>(c, "KingPawnShield", &positions);
+    bench_eval::<PieceConnectivityEvaluation>(c, "PieceConnectivity", &positions);
+    bench_eval::<DoublePawnsEvaluation>(c, "DoublePawns", &positions);
+    bench_eval::<NPlyTranspoBot>(c, "NPlyTranspoBot", &positions);
+}
+
+fn perft_benchmark(c: &mut Criterion) {
+    let board_state = ChessBoardState::starting_state();
+
+    let mut group = c.benchmark_group("perft");
+    group.sample_size(10);
+    group.bench_function("perft 5 start position", |b| {
+        b.iter(|| perft(black_box(&board_state), 5))
+    });
+    group.finish();
+}
+
+fn fixed_depth_search_benchmark(c: &mut Criterion) {
+    let positions = bench_positions();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let mut group = c.benchmark_group("search");
+    group.sample_size(10);
+    for (i, board_state) in positions.iter().enumerate() {
+        // Every iteration starts with a fresh bot so the transposition table of a previous run
+        // does not make the search cheaper
+        group.bench_function(format!("depth 5 position {}", i + 1), |b| {
+            b.iter_batched(
+                || {
+                    let mut bot = NPlyTranspoBot::default();
+                    let _ = bot.apply_option("OpeningBook", Some("false"));
+                    bot
+                },
+                |mut bot| {
+                    let mut board_state = *board_state;
+                    bot.search_best_move(&mut board_state, TimeControl::FixedDepth(5), &stop)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    movegen_benchmark,
+    exec_move_benchmark,
+    eval_benchmark,
+    perft_benchmark,
+    fixed_depth_search_benchmark
+);
+criterion_main!(benches);
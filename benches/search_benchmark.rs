@@ -2,8 +2,8 @@ use std::sync::{atomic::AtomicBool, Arc};
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use iglo::{
-    chess::{board::ChessBoardState, chess_move::{Move, MoveType}, perft::perft, square::Square},
-    engine::{bot::ChessBot, bots::nplytranspo_bot::NPlyTranspoBot, move_ordering::order_moves, search::SearchInfo, time_control::TimeControl},
+    chess::{bitboard::BitBoard, board::{ChessBoard, ChessBoardState}, chess_move::{Move, MoveType}, perft::perft, square::Square},
+    engine::{bot::ChessBot, bots::nplytranspo_bot::NPlyTranspoBot, move_ordering::{order_moves, MovePicker}, search::SearchInfo, time_control::TimeControl},
 };
 
 fn run_move_search(
@@ -38,8 +38,38 @@ fn benchmark_order_moves(c: &mut Criterion) {
     let search_info = SearchInfo::default();
 
     c.bench_function("order_moves", |b| {
-        b.iter(|| order_moves(black_box(&mut moves), black_box(&board_state), black_box(&search_info), black_box(4)))
+        b.iter(|| order_moves(black_box(&mut moves), black_box(&board_state), black_box(&search_info), black_box(4), black_box(None)))
     });
+    let legal_moves = board_state.legal_moves::<false>();
+    c.bench_function("move_picker_first_move", |b| {
+        b.iter(|| MovePicker::new(black_box(legal_moves), black_box(&board_state), black_box(&search_info), black_box(4), black_box(None)).next())
+    });
+}
+
+fn sliding_attacks_benchmark(c: &mut Criterion) {
+    let board_state = ChessBoardState::from_fen(
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    )
+    .unwrap();
+    let blockers = board_state.board.occupancy();
+
+    let mut group = c.benchmark_group("Sliding attacks");
+    group.bench_function("Rook and bishop attacks on all squares", |b| {
+        b.iter(|| {
+            let mut attacks = BitBoard::EMPTY;
+            for index in 0..Square::NUM {
+                let square = Square::from_index(index);
+                attacks = attacks
+                    | ChessBoard::rook_attacks(square, black_box(blockers))
+                    | ChessBoard::bishop_attacks(square, black_box(blockers));
+            }
+            attacks
+        })
+    });
+    group.bench_function("Legal move generation", |b| {
+        b.iter(|| black_box(&board_state).generate_legal_moves_for_current_player::<false>())
+    });
+    group.finish();
 }
 
 fn search_benchmark(c: &mut Criterion) {
@@ -76,5 +106,11 @@ fn perft_benchmark(c: &mut Criterion) {
     group.finish()
 }
 
-criterion_group!(benches, search_benchmark, perft_benchmark, benchmark_order_moves);
+criterion_group!(
+    benches,
+    search_benchmark,
+    perft_benchmark,
+    benchmark_order_moves,
+    sliding_attacks_benchmark
+);
 criterion_main!(benches);
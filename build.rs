@@ -0,0 +1,104 @@
+use std::{env, fmt::Write, fs, path::Path};
+
+#[path = "src/chess/lookup_gens/magics.rs"]
+mod magics;
+
+use magics::{
+    generate_line_tables, generate_magics, generate_pext_table, BISHOP_OFFSETS, ROOK_OFFSETS,
+};
+
+// Move tables are emitted as plain u64 literals, wrapping every entry in a BitBoard
+// constructor makes them noticeably slower to compile. The large tables are statics, an
+// unoptimized build copies a const array onto the stack wherever it is indexed
+fn write_tables(out: &mut String, name: &str, offsets: &[(i32, i32)]) {
+    let magics = generate_magics(offsets);
+
+    writeln!(out, "const {}_MAGICS: [MagicEntry; 64] = [", name).unwrap();
+    for square in 0..64 {
+        writeln!(
+            out,
+            "    MagicEntry {{ blocker_mask: BitBoard({:#x}), magic: {:#x}, index_bits: {}, offset: {} }},",
+            magics.blocker_masks[square],
+            magics.magics[square],
+            magics.index_bits[square],
+            magics.offsets[square]
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    write!(
+        out,
+        "static {}_MOVES: [u64; {}] = [",
+        name,
+        magics.moves.len()
+    )
+    .unwrap();
+    for mv in &magics.moves {
+        write!(out, "{:#x},", mv).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+fn write_pext_tables(out: &mut String, name: &str, offsets: &[(i32, i32)]) {
+    let table = generate_pext_table(offsets);
+
+    writeln!(
+        out,
+        "const {}_MASKS: [u64; 64] = {:#x?};",
+        name, table.blocker_masks
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "const {}_OFFSETS: [usize; 64] = {:?};",
+        name, table.offsets
+    )
+    .unwrap();
+    write!(
+        out,
+        "static {}_MOVES: [u64; {}] = [",
+        name,
+        table.moves.len()
+    )
+    .unwrap();
+    for mv in &table.moves {
+        write!(out, "{:#x},", mv).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+fn write_square_pair_table(out: &mut String, name: &str, table: &[[u64; 64]]) {
+    writeln!(out, "static {}: [[u64; 64]; 64] = [", name).unwrap();
+    for row in table {
+        write!(out, "    [").unwrap();
+        for entry in row {
+            write!(out, "{:#x},", entry).unwrap();
+        }
+        writeln!(out, "],").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/chess/lookup_gens/magics.rs");
+
+    let mut out = String::new();
+    write_tables(&mut out, "ROOK", &ROOK_OFFSETS);
+    write_tables(&mut out, "BISHOP", &BISHOP_OFFSETS);
+
+    let lines = generate_line_tables();
+    write_square_pair_table(&mut out, "BETWEEN", &lines.between);
+    write_square_pair_table(&mut out, "LINE", &lines.line);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("magic_tables.rs"), out).unwrap();
+
+    if env::var_os("CARGO_FEATURE_PEXT").is_some() {
+        let mut out = String::new();
+        write_pext_tables(&mut out, "ROOK", &ROOK_OFFSETS);
+        write_pext_tables(&mut out, "BISHOP", &BISHOP_OFFSETS);
+        fs::write(Path::new(&out_dir).join("pext_tables.rs"), out).unwrap();
+    }
+}
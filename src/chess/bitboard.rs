@@ -1,12 +1,17 @@
+use std::fmt::Display;
+
+use super::square::Square;
+
 #[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Default, Hash)]
 pub struct BitBoard(pub u64);
 
 #[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Default, Hash)]
-#[repr(packed)]
 pub struct MagicEntry {
     pub blocker_mask: BitBoard,
     pub magic: u64,
     pub index_bits: u8,
+    // Start of the square's moves in the shared move table
+    pub offset: u32,
 }
 pub struct BitBoardSubsetIter {
     set: BitBoard,
@@ -25,10 +30,75 @@ impl BitBoard {
     pub const EMPTY: Self = Self(0);
     pub const FULL: Self = Self(0xFFFFFFFFFFFFFFFF);
 
-    pub const NOT_A_FILE: u64 = 0xfefefefefefefefe;
-    pub const NOT_H_FILE: u64 = 0x7f7f7f7f7f7f7f7f;
-    pub const RANK_4: u64 = 1095216660480;
-    pub const RANK_5: u64 = 4278190080;
+    pub const FILE_A: Self = Self::file_mask(0);
+    pub const FILE_B: Self = Self::file_mask(1);
+    pub const FILE_C: Self = Self::file_mask(2);
+    pub const FILE_D: Self = Self::file_mask(3);
+    pub const FILE_E: Self = Self::file_mask(4);
+    pub const FILE_F: Self = Self::file_mask(5);
+    pub const FILE_G: Self = Self::file_mask(6);
+    pub const FILE_H: Self = Self::file_mask(7);
+
+    pub const RANK_1: Self = Self::rank_mask(0);
+    pub const RANK_2: Self = Self::rank_mask(1);
+    pub const RANK_3: Self = Self::rank_mask(2);
+    pub const RANK_4: Self = Self::rank_mask(3);
+    pub const RANK_5: Self = Self::rank_mask(4);
+    pub const RANK_6: Self = Self::rank_mask(5);
+    pub const RANK_7: Self = Self::rank_mask(6);
+    pub const RANK_8: Self = Self::rank_mask(7);
+
+    /// Files indexed by `Square::file`.
+    pub const FILES: [Self; 8] = [
+        Self::FILE_A,
+        Self::FILE_B,
+        Self::FILE_C,
+        Self::FILE_D,
+        Self::FILE_E,
+        Self::FILE_F,
+        Self::FILE_G,
+        Self::FILE_H,
+    ];
+    /// Ranks indexed by `Square::rank`.
+    pub const RANKS: [Self; 8] = [
+        Self::RANK_1,
+        Self::RANK_2,
+        Self::RANK_3,
+        Self::RANK_4,
+        Self::RANK_5,
+        Self::RANK_6,
+        Self::RANK_7,
+        Self::RANK_8,
+    ];
+
+    /// Squares with `Square::square_color` white, a8 is one of them.
+    pub const LIGHT_SQUARES: Self = Self(0xAA55AA55AA55AA55);
+    pub const DARK_SQUARES: Self = Self(!Self::LIGHT_SQUARES.0);
+
+    pub const NOT_A_FILE: Self = Self(!Self::FILE_A.0);
+    pub const NOT_H_FILE: Self = Self(!Self::FILE_H.0);
+
+    const fn file_mask(file: u8) -> Self {
+        Self(0x0101010101010101 << file)
+    }
+
+    const fn rank_mask(rank: u8) -> Self {
+        Self(0xFF << (8 * (7 - rank)))
+    }
+
+    pub const fn from_square(square: Square) -> Self {
+        Self(1u64 << square.index())
+    }
+
+    pub const fn from_squares(squares: &[Square]) -> Self {
+        let mut value = 0;
+        let mut i = 0;
+        while i < squares.len() {
+            value |= 1u64 << squares[i].index();
+            i += 1;
+        }
+        Self(value)
+    }
 
     #[must_use]
     pub fn get_bit(&self, pos: usize) -> bool {
@@ -53,6 +123,29 @@ impl BitBoard {
         self.0 == 0
     }
 
+    /// Square of the lowest set bit, the one closest to A8.
+    pub fn lsb(&self) -> Option<Square> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(Square::from_index(self.0.trailing_zeros() as usize))
+    }
+
+    /// Square of the highest set bit, the one closest to H1.
+    pub fn msb(&self) -> Option<Square> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(Square::from_index(63 - self.0.leading_zeros() as usize))
+    }
+
+    /// Removes the lowest set bit and returns its square.
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
     pub fn iter_subsets(self) -> BitBoardSubsetIter {
         BitBoardSubsetIter {
             set: self,
@@ -63,7 +156,7 @@ impl BitBoard {
 
     #[must_use]
     pub fn s_no_we(&self) -> Self {
-        Self((self.0 & Self::NOT_A_FILE) >> 9) //
+        Self((self.0 & Self::NOT_A_FILE.0) >> 9) //
     }
     #[must_use]
     pub fn s_no(&self) -> Self {
@@ -72,22 +165,22 @@ impl BitBoard {
 
     #[must_use]
     pub fn s_no_ea(&self) -> Self {
-        Self((self.0 & Self::NOT_H_FILE) >> 7) //
+        Self((self.0 & Self::NOT_H_FILE.0) >> 7) //
     }
 
     #[must_use]
     pub fn s_we(&self) -> Self {
-        Self((self.0 & Self::NOT_A_FILE) >> 1) //
+        Self((self.0 & Self::NOT_A_FILE.0) >> 1) //
     }
 
     #[must_use]
     pub fn s_ea(&self) -> Self {
-        Self((self.0 & Self::NOT_H_FILE) << 1) //
+        Self((self.0 & Self::NOT_H_FILE.0) << 1) //
     }
 
     #[must_use]
     pub fn s_so_we(&self) -> Self {
-        Self((self.0 & Self::NOT_A_FILE) << 7) //
+        Self((self.0 & Self::NOT_A_FILE.0) << 7) //
     }
 
     #[must_use]
@@ -97,16 +190,17 @@ impl BitBoard {
 
     #[must_use]
     pub fn s_so_ea(&self) -> Self {
-        Self((self.0 & Self::NOT_H_FILE) << 9) //
+        Self((self.0 & Self::NOT_H_FILE.0) << 9) //
     }
 }
 
 impl MagicEntry {
+    /// Index of the moves for `blockers` in the shared move table.
     pub fn magic_index(&self, blockers: BitBoard) -> usize {
         let blockers = blockers & self.blocker_mask;
         let hash = blockers.0.wrapping_mul(self.magic);
         let index = (hash >> (64 - self.index_bits)) as usize;
-        index
+        self.offset as usize + index
     }
 }
 
@@ -121,20 +215,15 @@ impl BitBoardIterator {
 }
 
 impl Iterator for BitBoardIterator {
-    type Item = usize;
+    type Item = Square;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.value.0 == 0 {
-            return None;
-        }
-        let lsb = self.value.0.trailing_zeros() as usize;
-        self.value = self.value.clear_bit(lsb);
-        Some(lsb)
+        self.value.pop_lsb()
     }
 }
 
 impl IntoIterator for BitBoard {
-    type Item = usize;
+    type Item = Square;
 
     type IntoIter = BitBoardIterator;
 
@@ -178,6 +267,36 @@ impl std::ops::BitOr<BitBoard> for BitBoard {
     }
 }
 
+impl std::ops::BitXor<BitBoard> for BitBoard {
+    type Output = BitBoard;
+
+    fn bitxor(self, rhs: BitBoard) -> Self::Output {
+        BitBoard(self.0 ^ rhs.0)
+    }
+}
+
+impl std::ops::BitXorAssign<BitBoard> for BitBoard {
+    fn bitxor_assign(&mut self, rhs: BitBoard) {
+        *self = *self ^ rhs;
+    }
+}
+
+impl std::ops::Shl<u32> for BitBoard {
+    type Output = BitBoard;
+
+    fn shl(self, rhs: u32) -> Self::Output {
+        BitBoard(self.0 << rhs)
+    }
+}
+
+impl std::ops::Shr<u32> for BitBoard {
+    type Output = BitBoard;
+
+    fn shr(self, rhs: u32) -> Self::Output {
+        BitBoard(self.0 >> rhs)
+    }
+}
+
 impl std::ops::Not for BitBoard {
     type Output = BitBoard;
 
@@ -186,6 +305,26 @@ impl std::ops::Not for BitBoard {
     }
 }
 
+/// Prints the board as a grid with the eighth rank on top.
+impl Display for BitBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rank in (0..8).rev() {
+            write!(f, "{} ", rank + 1)?;
+            for file in 0..8 {
+                let square = Square::from_file_rank(file, rank).unwrap();
+                let c = if self.get_bit(square.index()) {
+                    'x'
+                } else {
+                    '.'
+                };
+                write!(f, " {}", c)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "   a b c d e f g h")
+    }
+}
+
 impl Iterator for BitBoardSubsetIter {
     type Item = BitBoard;
 
@@ -203,14 +342,14 @@ impl Iterator for BitBoardSubsetIter {
 
 #[cfg(test)]
 mod bitboard_tests {
-    use super::{BitBoard, BitBoardIterator};
+    use super::{BitBoard, BitBoardIterator, Square};
 
     #[test]
     fn test_iterator() {
         let bitboard = BitBoard(0b0001);
 
         let mut iter = BitBoardIterator::new(bitboard);
-        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next(), Some(Square::A8));
         assert_eq!(iter.next(), None);
     }
 
@@ -219,10 +358,10 @@ mod bitboard_tests {
         let bitboard = BitBoard(0b11001 | (1 << 63));
 
         let mut iter = BitBoardIterator::new(bitboard);
-        assert_eq!(iter.next(), Some(0));
-        assert_eq!(iter.next(), Some(3));
-        assert_eq!(iter.next(), Some(4));
-        assert_eq!(iter.next(), Some(63));
+        assert_eq!(iter.next(), Some(Square::A8));
+        assert_eq!(iter.next(), Some(Square::D8));
+        assert_eq!(iter.next(), Some(Square::E8));
+        assert_eq!(iter.next(), Some(Square::H1));
         assert_eq!(iter.next(), None);
     }
 
@@ -245,4 +384,51 @@ mod bitboard_tests {
         assert!(subsets.contains(&BitBoard(0 << 1 | 1 << 5 | 1 << 10)));
         assert!(subsets.contains(&BitBoard(1 << 1 | 1 << 5 | 1 << 10)));
     }
+
+    #[test]
+    fn ranks_and_files() {
+        assert_eq!(BitBoard::RANK_8, BitBoard(0xFF));
+        assert_eq!(BitBoard::RANK_1, BitBoard(0xFF << 56));
+        assert_eq!(BitBoard::RANK_4, BitBoard(1095216660480));
+        assert_eq!(BitBoard::RANK_5, BitBoard(4278190080));
+        assert_eq!(BitBoard::NOT_A_FILE, BitBoard(0xfefefefefefefefe));
+        assert_eq!(BitBoard::NOT_H_FILE, BitBoard(0x7f7f7f7f7f7f7f7f));
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            let bb = BitBoard::from_square(square);
+            assert!(!(bb & BitBoard::RANKS[square.rank() as usize]).is_empty());
+            assert!(!(bb & BitBoard::FILES[square.file() as usize]).is_empty());
+        }
+        assert_eq!(
+            BitBoard::from_squares(&[Square::F1, Square::G1]),
+            BitBoard(0x6000000000000000)
+        );
+    }
+
+    #[test]
+    fn lsb_msb_and_operators() {
+        let mut bb = BitBoard::from_square(Square::D8) | BitBoard::from_square(Square::E1);
+        assert_eq!(bb.lsb(), Some(Square::D8));
+        assert_eq!(bb.msb(), Some(Square::E1));
+        assert_eq!(bb.pop_lsb(), Some(Square::D8));
+        assert_eq!(bb.pop_lsb(), Some(Square::E1));
+        assert_eq!(bb.pop_lsb(), None);
+        assert_eq!(BitBoard::EMPTY.msb(), None);
+
+        assert_eq!(BitBoard::RANK_8 << 8, BitBoard::RANK_7);
+        assert_eq!(BitBoard::RANK_1 >> 56, BitBoard::RANK_8);
+        let mut bb = BitBoard::FILE_A ^ BitBoard::RANK_8;
+        bb ^= BitBoard::from_square(Square::B8);
+        assert_eq!(bb.bit_count(), 13);
+    }
+
+    #[test]
+    fn display_grid() {
+        let grid = BitBoard::from_squares(&[Square::A8, Square::H1]).to_string();
+        let lines: Vec<&str> = grid.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "8  x . . . . . . .");
+        assert_eq!(lines[7], "1  . . . . . . . x");
+        assert_eq!(lines[8], "   a b c d e f g h");
+    }
 }
@@ -1,8 +1,18 @@
 use std::ops::Not;
 
-use crate::chess::{chess_move::MoveType, square::Square};
+use crate::{
+    chess::{chess_move::MoveType, square::Square},
+    error::IgloError,
+};
 
-use super::{bitboard::BitBoard, chess_move::Move, zobrist_hash::ZHash};
+use super::{
+    bitboard::BitBoard,
+    chess_move::Move,
+    zobrist_hash::{ZHash, ZobristUpdater},
+};
+
+// Ranks 1 and 8, pawns can never stand there
+const BACK_RANKS: BitBoard = BitBoard(BitBoard::RANK_1.0 | BitBoard::RANK_8.0);
 
 #[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Hash)]
 pub enum ChessPiece {
@@ -105,12 +115,101 @@ pub struct ChessBoardState {
     pub board: ChessBoard,
     pub side: PieceColor,
     pub castling_rights: CastlingRights,
-    pub en_passant_target: Option<u8>,
-    pub half_moves: u8,
-    pub full_moves: u8,
+    pub en_passant_target: Option<Square>,
+    pub half_moves: u16,
+    pub full_moves: u16,
     pub zhash: ZHash,
+    // Hash of the pawns only, keys the pawn structure evaluation
+    pub pawn_hash: ZHash,
+    // Pieces giving check to the side to move
+    pub checkers: BitBoard,
+}
+
+/// Reason a FEN string was rejected by [`ChessBoardState::from_fen`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    InvalidPiece(char),
+    // Ranks are numbered 1 to 8 as on the board
+    RankOverflow(usize),
+    IncompleteRank(usize),
+    InvalidSide(String),
+    InvalidCastlingRights(String),
+    InvalidEnPassantSquare(String),
+    InvalidHalfMoveClock(String),
+    InvalidFullMoveNumber(String),
+    KingCount(PieceColor, u32),
+    PawnOnBackRank(Square),
+    SideNotToMoveInCheck,
+    // A castling right whose king or rook is not on its starting square
+    CastlingRightsWithoutPieces(String),
+    // No pawn could have just double pushed over the en passant square
+    EnPassantWithoutDoublePush(Square),
+    // The piece bitboards and the mailbox of a square disagree
+    InconsistentBoard(Square),
+    // More than two pieces give check, no move leads to such a position
+    TooManyCheckers(u32),
+    // More pieces than the starting ones and the promotions of the missing pawns
+    ImpossibleMaterial(PieceColor),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::InvalidPiece(chr) => write!(f, "invalid piece '{}'", chr),
+            FenError::RankOverflow(rank) => write!(f, "rank {} has more than 8 squares", rank),
+            FenError::IncompleteRank(rank) => write!(f, "rank {} has less than 8 squares", rank),
+            FenError::InvalidSide(text) => write!(f, "invalid side to move '{}'", text),
+            FenError::InvalidCastlingRights(text) => {
+                write!(f, "invalid castling rights '{}'", text)
+            }
+            FenError::InvalidEnPassantSquare(text) => {
+                write!(f, "invalid en passant square '{}'", text)
+            }
+            FenError::InvalidHalfMoveClock(text) => write!(f, "invalid half move clock '{}'", text),
+            FenError::InvalidFullMoveNumber(text) => {
+                write!(f, "invalid full move number '{}'", text)
+            }
+            FenError::KingCount(color, count) => write!(
+                f,
+                "{} has {} kings instead of one",
+                color.as_display_str(),
+                count
+            ),
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on the back rank at {}", square),
+            FenError::SideNotToMoveInCheck => write!(f, "the side not to move is in check"),
+            FenError::CastlingRightsWithoutPieces(text) => {
+                write!(f, "castling rights '{}' without king and rook", text)
+            }
+            FenError::EnPassantWithoutDoublePush(square) => {
+                write!(
+                    f,
+                    "en passant square {} without a double pushed pawn",
+                    square
+                )
+            }
+            FenError::InconsistentBoard(square) => {
+                write!(f, "the board representations disagree on {}", square)
+            }
+            FenError::TooManyCheckers(count) => {
+                write!(f, "the side to move is in check by {} pieces", count)
+            }
+            FenError::ImpossibleMaterial(color) => {
+                write!(
+                    f,
+                    "{} has more pieces than a game can produce",
+                    color.as_display_str()
+                )
+            }
+        }
+    }
 }
 
+impl std::error::Error for FenError {}
+
 impl From<usize> for ChessPiece {
     fn from(val: usize) -> Self {
         match val {
@@ -259,32 +358,63 @@ impl ChessBoard {
         &mut self,
         piece: ChessPiece,
         col: PieceColor,
-        index_to_set: usize,
+        square: Square,
         zhash: &mut ZHash,
     ) {
+        let index = square.index();
         let piece_bitboard = if col == PieceColor::White {
-            self.all_white_pieces = self.all_white_pieces.set_bit(index_to_set);
+            self.all_white_pieces = self.all_white_pieces.set_bit(index);
             &mut self.white_pieces[piece as usize]
         } else {
-            self.all_black_pieces = self.all_black_pieces.set_bit(index_to_set);
+            self.all_black_pieces = self.all_black_pieces.set_bit(index);
             &mut self.black_pieces[piece as usize]
         };
-        self.piece_board[index_to_set] = Some((piece, col));
-        *piece_bitboard = piece_bitboard.set_bit(index_to_set);
-        zhash.toggle_piece_at_pos(piece, col, index_to_set);
+        self.piece_board[index] = Some((piece, col));
+        *piece_bitboard = piece_bitboard.set_bit(index);
+        zhash.toggle_piece_at_pos(piece, col, square);
+    }
+
+    #[inline(always)]
+    pub fn piece_on(&self, square: Square) -> Option<(ChessPiece, PieceColor)> {
+        self.piece_board[square.index()]
+    }
+
+    /// Iterates over all pieces of the given color together with the square they stand on.
+    pub fn pieces(&self, col: PieceColor) -> impl Iterator<Item = (Square, ChessPiece)> + '_ {
+        let bitboards = if col == PieceColor::White {
+            &self.white_pieces
+        } else {
+            &self.black_pieces
+        };
+        bitboards.iter().enumerate().flat_map(|(piece, bitboard)| {
+            bitboard
+                .into_iter()
+                .map(move |square| (square, ChessPiece::from(piece)))
+        })
     }
 
-    pub fn get_piece_at_pos(&self, index: usize) -> Option<(ChessPiece, PieceColor)> {
-        self.piece_board[index]
+    #[inline(always)]
+    pub fn occupancy(&self) -> BitBoard {
+        self.all_white_pieces | self.all_black_pieces
+    }
+
+    #[inline(always)]
+    pub fn color_occupancy(&self, col: PieceColor) -> BitBoard {
+        if col == PieceColor::White {
+            self.all_white_pieces
+        } else {
+            self.all_black_pieces
+        }
     }
 
     pub fn remove_piece_at_pos(
         &mut self,
         piece: ChessPiece,
         col: PieceColor,
-        index: usize,
+        square: Square,
         zhash: &mut ZHash,
     ) {
+        let index = square.index();
         let piece_bitboard = if col == PieceColor::White {
             self.all_white_pieces = self.all_white_pieces.clear_bit(index);
             &mut self.white_pieces[piece as usize]
@@ -294,7 +424,7 @@ impl ChessBoard {
         };
         self.piece_board[index] = None;
         *piece_bitboard = piece_bitboard.clear_bit(index);
-        zhash.toggle_piece_at_pos(piece, col, index);
+        zhash.toggle_piece_at_pos(piece, col, square);
     }
 
     // only used for enpassant check reveal
@@ -322,57 +452,81 @@ impl ChessBoard {
 
     #[inline(always)]
     pub fn get_opposing_pieces(&self, col: PieceColor) -> BitBoard {
-        if col == PieceColor::White {
-            self.all_black_pieces
-        } else {
-            self.all_white_pieces
-        }
+        self.color_occupancy(!col)
     }
 
     #[inline(always)]
-    pub fn get_king_pos(&self, col: PieceColor) -> usize {
+    pub fn get_king_pos(&self, col: PieceColor) -> Square {
         self.get_piece_bitboard(ChessPiece::King, col)
             .into_iter()
             .nth(0)
             .unwrap()
     }
 
-    pub fn from_fen_notation(fen: &str, zhash: &mut ZHash) -> Result<Self, ()> {
+    pub fn from_fen_notation(fen: &str, zhash: &mut ZHash) -> Result<Self, FenError> {
         let mut board = Self::default();
-        let mut cur_index: usize = 0;
 
-        for chr in fen.chars() {
-            if chr.is_digit(10) {
-                cur_index += chr.to_digit(10).unwrap() as usize;
-                continue;
-            }
+        let ranks: Vec<&str> = fen.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
 
-            if chr == '/' {
-                continue;
-            }
+        for (y, rank_text) in ranks.iter().enumerate() {
+            let rank = 8 - y;
+            let mut x: usize = 0;
 
-            let piece_col = if chr.is_uppercase() {
-                PieceColor::White
-            } else {
-                PieceColor::Black
-            };
+            for chr in rank_text.chars() {
+                if let Some(empty_squares @ 1..=8) = chr.to_digit(10) {
+                    x += empty_squares as usize;
+                    continue;
+                }
+                if x >= 8 {
+                    return Err(FenError::RankOverflow(rank));
+                }
 
-            match &chr.to_lowercase().to_string() as &str {
-                "p" => board.place_piece_of_color(ChessPiece::Pawn, piece_col, cur_index, zhash),
-                "n" => board.place_piece_of_color(ChessPiece::Knight, piece_col, cur_index, zhash),
-                "b" => board.place_piece_of_color(ChessPiece::Bishop, piece_col, cur_index, zhash),
-                "r" => board.place_piece_of_color(ChessPiece::Rook, piece_col, cur_index, zhash),
-                "q" => board.place_piece_of_color(ChessPiece::Queen, piece_col, cur_index, zhash),
-                "k" => board.place_piece_of_color(ChessPiece::King, piece_col, cur_index, zhash),
-                _ => return Err(()),
+                let piece_col = if chr.is_uppercase() {
+                    PieceColor::White
+                } else {
+                    PieceColor::Black
+                };
+                let piece = match chr.to_ascii_lowercase() {
+                    'p' => ChessPiece::Pawn,
+                    'n' => ChessPiece::Knight,
+                    'b' => ChessPiece::Bishop,
+                    'r' => ChessPiece::Rook,
+                    'q' => ChessPiece::Queen,
+                    'k' => ChessPiece::King,
+                    _ => return Err(FenError::InvalidPiece(chr)),
+                };
+                board.place_piece_of_color(piece, piece_col, Square::from_index(y * 8 + x), zhash);
+                x += 1;
+            }
+
+            if x > 8 {
+                return Err(FenError::RankOverflow(rank));
+            } else if x < 8 {
+                return Err(FenError::IncompleteRank(rank));
             }
-            cur_index += 1;
         }
         Ok(board)
     }
 
     pub fn empty_squares(&self) -> BitBoard {
-        BitBoard(!(self.all_white_pieces.0 | self.all_black_pieces.0))
+        !self.occupancy()
+    }
+
+    /// The board turned around with the colors swapped: every piece changes color and
+    /// moves to the same file on the opposite rank, e.g. a white king on e1 becomes a black
+    /// king on e8.
+    pub fn mirrored(&self) -> Self {
+        let mut mirrored = Self::default();
+        let mut zhash = ZHash::default();
+        for color in [PieceColor::White, PieceColor::Black] {
+            for (square, piece) in self.pieces(color) {
+                mirrored.place_piece_of_color(piece, !color, square.flip_vertical(), &mut zhash);
+            }
+        }
+        mirrored
     }
 }
 
@@ -398,54 +552,192 @@ impl ChessBoardState {
         }
     }
 
-    fn update_enpassant_hash(&mut self, current_side: PieceColor, ep_target: Option<u8>) {
-        // Only toggle the enpassant target if there are any pieces that attack the target square
-        if let Some(ep_target) = ep_target {
-            let enpassant_attackers = self.board.pawns_able_to_enpassant(current_side, ep_target);
-            if enpassant_attackers.0 != 0 {
-                self.zhash.toggle_enpassant(ep_target as usize);
-            }
-        }
-    }
-
-    pub fn from_fen(text: &str) -> Result<Self, ()> {
+    pub fn from_fen(text: &str) -> Result<Self, FenError> {
         let fen_parts: Vec<&str> = text.trim().split(" ").collect();
         if fen_parts.len() != 6 {
-            return Err(());
+            return Err(FenError::WrongFieldCount(fen_parts.len()));
         }
 
         let mut zhash = ZHash::default();
+        let chess_board = ChessBoard::from_fen_notation(fen_parts[0], &mut zhash)?;
         let mut board = ChessBoardState {
-            board: ChessBoard::from_fen_notation(fen_parts[0], &mut zhash)?,
-            side: PieceColor::try_from(fen_parts[1])?,
-            castling_rights: CastlingRights::try_from(fen_parts[2])?,
-            en_passant_target: Square::from_square_name(fen_parts[3])?,
-            half_moves: fen_parts[4].parse::<u8>().map_err(|_| ())?,
-            full_moves: fen_parts[5].parse::<u8>().map_err(|_| ())?,
+            board: chess_board,
+            side: PieceColor::try_from(fen_parts[1])
+                .map_err(|_| FenError::InvalidSide(fen_parts[1].to_string()))?,
+            castling_rights: CastlingRights::try_from(fen_parts[2])
+                .map_err(|_| FenError::InvalidCastlingRights(fen_parts[2].to_string()))?,
+            en_passant_target: match fen_parts[3] {
+                "-" => None,
+                name => Some(
+                    Square::try_from(name)
+                        .map_err(|_| FenError::InvalidEnPassantSquare(name.to_string()))?,
+                ),
+            },
+            half_moves: fen_parts[4]
+                .parse::<u16>()
+                .map_err(|_| FenError::InvalidHalfMoveClock(fen_parts[4].to_string()))?,
+            full_moves: fen_parts[5]
+                .parse::<u16>()
+                .map_err(|_| FenError::InvalidFullMoveNumber(fen_parts[5].to_string()))?,
             zhash: zhash,
+            pawn_hash: ZHash::from_pawns(&chess_board),
+            checkers: BitBoard::EMPTY,
         };
+        board.validate()?;
+        board.checkers = board.board.checkers(board.side);
+        board.zhash = ZHash::from_scratch(&board);
 
-        if board.side == PieceColor::White {
-            board.zhash.toggle_side();
+        Ok(board)
+    }
+
+    /// Rejects positions the move generator can not handle: a corrupt board, missing or extra
+    /// kings, pawns on the back ranks, impossible checks and castling or en passant rights
+    /// the position does not back up. FEN loading runs it, positions built or edited by hand
+    /// should be checked with it before they are searched.
+    pub fn validate(&self) -> Result<(), FenError> {
+        let board = &self.board;
+
+        // Every square is in at most one piece bitboard, which agrees with the mailbox and the
+        // color occupancies
+        for square in Square::ALL {
+            let mut on_square = None;
+            for color in [PieceColor::White, PieceColor::Black] {
+                for piece in (0..ChessPiece::PIECE_TYPE_COUNT).map(ChessPiece::from) {
+                    if board
+                        .get_piece_bitboard(piece, color)
+                        .get_bit(square.index())
+                    {
+                        if on_square.is_some() {
+                            return Err(FenError::InconsistentBoard(square));
+                        }
+                        on_square = Some((piece, color));
+                    }
+                }
+            }
+            let white_occupied = board.all_white_pieces.get_bit(square.index());
+            let black_occupied = board.all_black_pieces.get_bit(square.index());
+            let expected_occupancy = match on_square {
+                Some((_, PieceColor::White)) => (true, false),
+                Some((_, PieceColor::Black)) => (false, true),
+                None => (false, false),
+            };
+            if board.piece_on(square) != on_square
+                || (white_occupied, black_occupied) != expected_occupancy
+            {
+                return Err(FenError::InconsistentBoard(square));
+            }
+        }
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            let king_count = board
+                .get_piece_bitboard(ChessPiece::King, color)
+                .bit_count();
+            if king_count != 1 {
+                return Err(FenError::KingCount(color, king_count));
+            }
+
+            // Pieces beyond the starting set are promoted pawns, which keeps the number of
+            // moves within what a move list holds
+            let count = |piece| board.get_piece_bitboard(piece, color).bit_count();
+            let promoted = [
+                (ChessPiece::Queen, 1),
+                (ChessPiece::Rook, 2),
+                (ChessPiece::Bishop, 2),
+                (ChessPiece::Knight, 2),
+            ]
+            .into_iter()
+            .map(|(piece, start)| count(piece).saturating_sub(start))
+            .sum::<u32>();
+            if count(ChessPiece::Pawn) + promoted > 8 {
+                return Err(FenError::ImpossibleMaterial(color));
+            }
         }
 
-        board
-            .zhash
-            .swap_castling_rights(&CastlingRights(0), &board.castling_rights);
+        let pawns = board.get_piece_bitboard(ChessPiece::Pawn, PieceColor::White)
+            | board.get_piece_bitboard(ChessPiece::Pawn, PieceColor::Black);
+        if let Some(square) = (pawns & BACK_RANKS).into_iter().next() {
+            return Err(FenError::PawnOnBackRank(square));
+        }
 
-        board.update_enpassant_hash(board.side, board.en_passant_target);
+        if !board.checkers(!self.side).is_empty() {
+            return Err(FenError::SideNotToMoveInCheck);
+        }
+        let checker_count = board.checkers(self.side).bit_count();
+        if checker_count > 2 {
+            return Err(FenError::TooManyCheckers(checker_count));
+        }
 
-        Ok(board)
+        let castling_requirements = [
+            (
+                self.castling_rights.white_king_side(),
+                PieceColor::White,
+                Square::E1,
+                Square::H1,
+            ),
+            (
+                self.castling_rights.white_queen_side(),
+                PieceColor::White,
+                Square::E1,
+                Square::A1,
+            ),
+            (
+                self.castling_rights.black_king_side(),
+                PieceColor::Black,
+                Square::E8,
+                Square::H8,
+            ),
+            (
+                self.castling_rights.black_queen_side(),
+                PieceColor::Black,
+                Square::E8,
+                Square::A8,
+            ),
+        ];
+        for (enabled, color, king_square, rook_square) in castling_requirements {
+            if enabled
+                && (board.piece_on(king_square) != Some((ChessPiece::King, color))
+                    || board.piece_on(rook_square) != Some((ChessPiece::Rook, color)))
+            {
+                return Err(FenError::CastlingRightsWithoutPieces(
+                    self.castling_rights.to_string(),
+                ));
+            }
+        }
+
+        if let Some(ep_target) = self.en_passant_target {
+            // The opponent just moved a pawn from the square in front of the target to the one
+            // behind it
+            let (pushed_color, ep_rank) = if self.side == PieceColor::White {
+                (PieceColor::Black, 5)
+            } else {
+                (PieceColor::White, 2)
+            };
+            if ep_target.rank() != ep_rank {
+                return Err(FenError::EnPassantWithoutDoublePush(ep_target));
+            }
+            let (origin, pawn_square) = if pushed_color == PieceColor::Black {
+                (ep_target.index() - 8, ep_target.index() + 8)
+            } else {
+                (ep_target.index() + 8, ep_target.index() - 8)
+            };
+            if board.piece_on(Square::from_index(pawn_square))
+                != Some((ChessPiece::Pawn, pushed_color))
+                || board.piece_on(ep_target).is_some()
+                || board.piece_on(Square::from_index(origin)).is_some()
+            {
+                return Err(FenError::EnPassantWithoutDoublePush(ep_target));
+            }
+        }
+        Ok(())
     }
 
     pub fn to_fen(&self) -> String {
         let mut fen = String::new();
 
-        for y in 0..8 {
+        for (y, row) in Square::ALL.chunks(8).enumerate() {
             let mut no_piece_count = 0;
-            for x in 0..8 {
-                let pos = x + 8 * y;
-                if let Some((piece, color)) = self.board.get_piece_at_pos(pos) {
+            for square in row {
+                if let Some((piece, color)) = self.board.piece_on(*square) {
                     if no_piece_count != 0 {
                         fen.push_str(&no_piece_count.to_string());
                         no_piece_count = 0;
@@ -469,7 +761,10 @@ impl ChessBoardState {
         fen.push_str(&self.castling_rights.to_string());
         fen.push(' ');
 
-        fen.push_str(&Square::to_square_name(self.en_passant_target));
+        match self.en_passant_target {
+            Some(square) => fen.push_str(&square.to_string()),
+            None => fen.push('-'),
+        }
         fen.push(' ');
         fen.push_str(&self.half_moves.to_string());
         fen.push(' ');
@@ -499,7 +794,7 @@ impl ChessBoardState {
             PieceColor,
             fn(&CastlingRights) -> bool,
             fn(&mut CastlingRights, bool),
-            u16,
+            Square,
         )] = &[
             (
                 PieceColor::White,
@@ -551,20 +846,28 @@ impl ChessBoardState {
         }
     }
 
+    /// Plays a move coming from outside the engine, e.g. a GUI or a file, which is
+    /// rejected unless it is legal in this position.
+    pub fn try_exec_move(&self, mv: Move) -> Result<Self, IgloError> {
+        if !self.is_legal(mv) {
+            return Err(IgloError::IllegalMove(format!("{:?}", mv)));
+        }
+        Ok(self.exec_move(mv))
+    }
+
+    /// Plays `mv`, which has to be legal. Moves from the move generator always are,
+    /// anything else goes through `try_exec_move`.
     pub fn exec_move(&self, mv: Move) -> Self {
         let mut new = *self;
-
-        if let Some(ep_target) = new.en_passant_target {
-            new.zhash.toggle_enpassant(ep_target as usize);
-        }
+        let mut keys = ZobristUpdater::new(self);
         new.en_passant_target = None;
 
-        let (src_piece, src_color) = match self.board.get_piece_at_pos(mv.get_src() as usize) {
+        let (src_piece, src_color) = match self.board.piece_on(mv.get_src()) {
             None => panic!("No piece at src pos!"),
             Some(e) => e,
         };
 
-        let dst_piece_col = self.board.get_piece_at_pos(mv.get_dst() as usize);
+        let dst_piece_col = self.board.piece_on(mv.get_dst());
 
         assert!(
             src_color == self.side,
@@ -575,18 +878,8 @@ impl ChessBoardState {
         if mv.is_capture() && !mv.is_en_passant() {
             let (dst_piece, dst_color) = dst_piece_col.unwrap();
             assert!(dst_color != src_color, "Can not capture own pieces");
-            new.board.remove_piece_at_pos(
-                dst_piece,
-                dst_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, dst_piece, dst_color, mv.get_dst());
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
 
             let new_piece = if mv.is_promotion() {
                 mv.promotion_target()
@@ -594,198 +887,157 @@ impl ChessBoardState {
                 src_piece
             };
 
-            new.board.place_piece_of_color(
-                new_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.place_piece(&mut new.board, new_piece, src_color, mv.get_dst());
         } else if mv.is_promotion() && !mv.is_capture() {
             // Promotion, non capture
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(
+                &mut new.board,
                 mv.promotion_target(),
                 src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
+                mv.get_dst(),
             );
         } else if mv.is_silent() {
             // Move is silent
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
-                src_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(&mut new.board, src_piece, src_color, mv.get_dst());
         } else if mv.is_double_push() {
             // Pawn double push
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
-                src_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(&mut new.board, src_piece, src_color, mv.get_dst());
             let new_ep_target = if src_color == PieceColor::White {
-                Some(mv.get_dst() as u8 + 8)
+                Some(Square::from_index(mv.get_dst().index() + 8))
             } else {
-                Some(mv.get_dst() as u8 - 8)
+                Some(Square::from_index(mv.get_dst().index() - 8))
             };
-            // Side to move is not updated yet, so we need to toggle it
-            new.update_enpassant_hash(!new.side, new_ep_target);
             new.en_passant_target = new_ep_target;
         } else if mv.is_en_passant() {
             // En passant
             let dst = if src_color == PieceColor::White {
-                mv.get_dst() + 8
+                Square::from_index(mv.get_dst().index() + 8)
             } else {
-                mv.get_dst() - 8
+                Square::from_index(mv.get_dst().index() - 8)
             };
-            let (dst_piece, dst_color) = match self.board.get_piece_at_pos(dst as usize) {
+            let (dst_piece, dst_color) = match self.board.piece_on(dst) {
                 None => panic!("No piece to capture"),
                 Some(e) => e,
             };
 
-            new.board
-                .remove_piece_at_pos(dst_piece, dst_color, dst as usize, &mut new.zhash);
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
-                src_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, dst_piece, dst_color, dst);
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(&mut new.board, src_piece, src_color, mv.get_dst());
         } else if mv.get_type() == MoveType::CastleKingSide {
             // Castling King Side
 
             assert!(src_piece == ChessPiece::King);
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
-                src_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(&mut new.board, src_piece, src_color, mv.get_dst());
             if src_color == PieceColor::White {
-                new.board.remove_piece_at_pos(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::H1 as usize,
-                    &mut new.zhash,
-                );
-                new.board.place_piece_of_color(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::F1 as usize,
-                    &mut new.zhash,
-                );
+                keys.remove_piece(&mut new.board, ChessPiece::Rook, src_color, Square::H1);
+                keys.place_piece(&mut new.board, ChessPiece::Rook, src_color, Square::F1);
             } else {
-                new.board.remove_piece_at_pos(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::H8 as usize,
-                    &mut new.zhash,
-                );
-                new.board.place_piece_of_color(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::F8 as usize,
-                    &mut new.zhash,
-                );
+                keys.remove_piece(&mut new.board, ChessPiece::Rook, src_color, Square::H8);
+                keys.place_piece(&mut new.board, ChessPiece::Rook, src_color, Square::F8);
             }
         } else if mv.get_type() == MoveType::CastleQueenSide {
             // Castling Queen Side
             assert!(src_piece == ChessPiece::King);
-            new.board.remove_piece_at_pos(
-                src_piece,
-                src_color,
-                mv.get_src() as usize,
-                &mut new.zhash,
-            );
-            new.board.place_piece_of_color(
-                src_piece,
-                src_color,
-                mv.get_dst() as usize,
-                &mut new.zhash,
-            );
+            keys.remove_piece(&mut new.board, src_piece, src_color, mv.get_src());
+            keys.place_piece(&mut new.board, src_piece, src_color, mv.get_dst());
             if src_color == PieceColor::White {
-                new.board.remove_piece_at_pos(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::A1 as usize,
-                    &mut new.zhash,
-                );
-                new.board.place_piece_of_color(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::D1 as usize,
-                    &mut new.zhash,
-                );
+                keys.remove_piece(&mut new.board, ChessPiece::Rook, src_color, Square::A1);
+                keys.place_piece(&mut new.board, ChessPiece::Rook, src_color, Square::D1);
             } else {
-                new.board.remove_piece_at_pos(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::A8 as usize,
-                    &mut new.zhash,
-                );
-                new.board.place_piece_of_color(
-                    ChessPiece::Rook,
-                    src_color,
-                    Square::D8 as usize,
-                    &mut new.zhash,
-                );
+                keys.remove_piece(&mut new.board, ChessPiece::Rook, src_color, Square::A8);
+                keys.place_piece(&mut new.board, ChessPiece::Rook, src_color, Square::D8);
             }
         }
 
         new.revoke_castling_rights(src_piece, src_color, dst_piece_col, &mv);
-        new.zhash
-            .swap_castling_rights(&self.castling_rights, &new.castling_rights);
 
         if mv.is_capture() || src_piece == ChessPiece::Pawn {
             new.half_moves = 0;
         } else {
             new.half_moves += 1;
         }
-        new.full_moves += 1;
+        if src_color == PieceColor::Black {
+            new.full_moves += 1;
+        }
         new.side = !new.side;
-        new.zhash.toggle_side();
+        new.checkers = new.board.checkers(new.side);
+        keys.finish(&mut new);
         new
     }
 
+    #[inline(always)]
     pub fn is_in_check(&self) -> bool {
-        let attacked_squares = self.board.squares_attacked_by_side(!self.side, false);
-        attacked_squares.get_bit(self.board.get_king_pos(self.side))
+        !self.checkers.is_empty()
     }
 
     pub fn total_piece_count(&self) -> u32 {
         self.board.all_black_pieces.0.count_ones() + self.board.all_white_pieces.0.count_ones()
     }
+
+    /// The same position for the other color: the board is [mirrored](ChessBoard::mirrored)
+    /// and the side to move, castling rights and en passant square change sides with it.
+    /// Both positions have the same moves and evaluation from the side to move's view.
+    pub fn color_flipped(&self) -> Self {
+        let board = self.board.mirrored();
+        let rights = self.castling_rights;
+        let mut castling_rights = CastlingRights::none();
+        castling_rights.set_white_king_side(rights.black_king_side());
+        castling_rights.set_white_queen_side(rights.black_queen_side());
+        castling_rights.set_black_king_side(rights.white_king_side());
+        castling_rights.set_black_queen_side(rights.white_queen_side());
+
+        let mut flipped = Self {
+            board,
+            side: !self.side,
+            castling_rights,
+            en_passant_target: self.en_passant_target.map(Square::flip_vertical),
+            half_moves: self.half_moves,
+            full_moves: self.full_moves,
+            zhash: ZHash::default(),
+            pawn_hash: ZHash::from_pawns(&board),
+            checkers: BitBoard::EMPTY,
+        };
+        flipped.checkers = board.checkers(flipped.side);
+        flipped.zhash = ZHash::from_scratch(&flipped);
+        flipped
+    }
+}
+
+// Both are written in their FEN notation, which keeps JSON readable and stays valid when the
+// internal representation changes
+#[cfg(feature = "serde")]
+impl serde::Serialize for CastlingRights {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CastlingRights {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        CastlingRights::try_from(text.as_str())
+            .map_err(|_| serde::de::Error::custom("invalid castling rights"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChessBoardState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChessBoardState {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        ChessBoardState::from_fen(&text).map_err(|_| serde::de::Error::custom("invalid FEN"))
+    }
 }
 
 #[cfg(test)]
@@ -793,8 +1045,12 @@ mod board_tests {
 
     use crate::bb;
     use crate::chess::board::BitBoard;
-    use crate::chess::board::{CastlingRights, ChessBoard, ChessBoardState, PieceColor};
+    use crate::chess::board::{
+        CastlingRights, ChessBoard, ChessBoardState, ChessPiece, FenError, PieceColor,
+    };
+    use crate::chess::board_builder::BoardBuilder;
     use crate::chess::chess_move::{Move, MoveType};
+    use crate::chess::perft::perft;
     use crate::chess::square::Square;
     use crate::chess::zobrist_hash::ZHash;
 
@@ -823,6 +1079,7 @@ mod board_tests {
         assert_eq!(state.en_passant_target, state_expected.en_passant_target);
         assert_eq!(state.half_moves, state_expected.half_moves);
         assert_eq!(state.full_moves, state_expected.full_moves);
+        assert_eq!(state.checkers, state_expected.checkers);
         assert_eq!(
             state.board.all_black_pieces,
             state_expected.board.all_black_pieces
@@ -860,11 +1117,49 @@ mod board_tests {
             half_moves: 0,
             full_moves: 0,
             zhash: ZHash::default(),
+            pawn_hash: ZHash::default(),
+            checkers: BitBoard::EMPTY,
         };
 
         check_board_equality(&board.unwrap(), &expected);
     }
 
+    #[test]
+    fn board_piece_iteration() {
+        let state = BoardBuilder::new()
+            .piece(Square::E8, ChessPiece::King, PieceColor::Black)
+            .piece(Square::D4, ChessPiece::Pawn, PieceColor::White)
+            .piece(Square::A1, ChessPiece::Rook, PieceColor::White)
+            .piece(Square::E1, ChessPiece::King, PieceColor::White)
+            .castling("Q")
+            .build()
+            .unwrap();
+
+        let mut white: Vec<_> = state.board.pieces(PieceColor::White).collect();
+        white.sort_by_key(|(square, _)| *square);
+        assert_eq!(
+            white,
+            vec![
+                (Square::D4, ChessPiece::Pawn),
+                (Square::A1, ChessPiece::Rook),
+                (Square::E1, ChessPiece::King),
+            ]
+        );
+        let black: Vec<_> = state.board.pieces(PieceColor::Black).collect();
+        assert_eq!(black, vec![(Square::E8, ChessPiece::King)]);
+
+        assert_eq!(state.board.occupancy().bit_count(), 4);
+        assert_eq!(
+            state.board.color_occupancy(PieceColor::White).bit_count(),
+            3
+        );
+        assert_eq!(
+            state.board.piece_on(Square::D4),
+            Some((ChessPiece::Pawn, PieceColor::White))
+        );
+        assert_eq!(state.board.piece_on(Square::D5), None);
+    }
+
     #[test]
     fn board_from_fen_complex() {
         let board = ChessBoardState::from_fen(
@@ -899,6 +1194,8 @@ mod board_tests {
             half_moves: 0,
             full_moves: 21,
             zhash: ZHash::default(),
+            pawn_hash: ZHash::default(),
+            checkers: BitBoard::EMPTY,
         };
 
         check_board_equality(&board.unwrap(), &expected);
@@ -948,6 +1245,157 @@ mod board_tests {
         }
     }
 
+    #[test]
+    fn test_fen_errors() {
+        let cases = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0",
+                FenError::WrongFieldCount(5),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::WrongRankCount(7),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBXR w KQkq - 0 1",
+                FenError::InvalidPiece('X'),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::RankOverflow(4),
+            ),
+            (
+                "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::IncompleteRank(7),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+                FenError::InvalidSide("x".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+                FenError::InvalidCastlingRights("KQkx".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+                FenError::InvalidEnPassantSquare("e9".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - a 1",
+                FenError::InvalidHalfMoveClock("a".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 -1",
+                FenError::InvalidFullMoveNumber("-1".to_string()),
+            ),
+            (
+                "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1",
+                FenError::KingCount(PieceColor::Black, 0),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/K3K3 w - - 0 1",
+                FenError::KingCount(PieceColor::White, 2),
+            ),
+            (
+                "4k2P/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::PawnOnBackRank(Square::H8),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KR3 w - - 0 1",
+                FenError::SideNotToMoveInCheck,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 70000 1",
+                FenError::InvalidHalfMoveClock("70000".to_string()),
+            ),
+            (
+                "4k2r/8/8/8/8/8/8/R3K3 w Kk - 0 1",
+                FenError::CastlingRightsWithoutPieces("Kk".to_string()),
+            ),
+            (
+                "4k3/8/8/8/1b6/3n4/8/r3K3 w - - 0 1",
+                FenError::TooManyCheckers(3),
+            ),
+            (
+                "Q4Qbk/1Q1Q2pp/1Q3QQQ/4Q3/Q1Q3Q1/6Q1/1Q1Q4/K5QQ w - - 0 1",
+                FenError::ImpossibleMaterial(PieceColor::White),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNQ w Qkq - 0 1",
+                FenError::ImpossibleMaterial(PieceColor::White),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 1",
+                FenError::EnPassantWithoutDoublePush(Square::E6),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/4P3/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                FenError::EnPassantWithoutDoublePush(Square::E3),
+            ),
+        ];
+
+        for (fen, expected_error) in cases {
+            assert_eq!(
+                ChessBoardState::from_fen(fen),
+                Err(expected_error),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_checkers_update() {
+        let board_state = BoardBuilder::new()
+            .piece(Square::E8, ChessPiece::King, PieceColor::Black)
+            .piece(Square::D1, ChessPiece::King, PieceColor::White)
+            .piece(Square::E1, ChessPiece::Rook, PieceColor::White)
+            .side(PieceColor::Black)
+            .build()
+            .unwrap();
+        assert_eq!(
+            board_state.checkers,
+            BitBoard::EMPTY.set_bit(Square::E1.index())
+        );
+        assert!(board_state.is_in_check());
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            for mv in board_state.generate_legal_moves_for_current_player::<false>() {
+                let new_state = board_state.exec_move(mv);
+                assert_eq!(new_state.checkers, new_state.board.checkers(new_state.side));
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_counters() {
+        let board_state = ChessBoardState::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 299 412").unwrap();
+        assert_eq!(board_state.half_moves, 299);
+        assert_eq!(board_state.full_moves, 412);
+        assert_eq!(board_state.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 299 412");
+
+        // The full move number only advances after black moved
+        let after_white =
+            board_state.exec_move(Move::new(Square::H1, Square::H2, MoveType::Silent));
+        assert_eq!(after_white.half_moves, 300);
+        assert_eq!(after_white.full_moves, 412);
+        let after_black =
+            after_white.exec_move(Move::new(Square::E8, Square::D8, MoveType::Silent));
+        assert_eq!(after_black.full_moves, 413);
+        assert_eq!(after_black.to_fen(), "3k4/8/8/8/8/8/7R/4K3 w - - 301 413");
+
+        let after_double_push = ChessBoardState::starting_state().exec_move(Move::new(
+            Square::E2,
+            Square::E4,
+            MoveType::DoublePush,
+        ));
+        assert!(ChessBoardState::from_fen(&after_double_push.to_fen()).is_ok());
+    }
+
     #[test]
     fn test_castling_right_update() {
         let mut board_state =
@@ -996,4 +1444,55 @@ mod board_tests {
             "Captured king side rook"
         );
     }
+
+    #[test]
+    fn bad_moves_are_rejected() {
+        use crate::error::IgloError;
+
+        let board_state = ChessBoardState::starting_state();
+        assert_eq!(
+            Move::try_from(("z9e4", &board_state)),
+            Err(IgloError::InvalidMove("z9e4".into()))
+        );
+        assert_eq!(
+            Move::try_from(("e7e5", &board_state)),
+            Err(IgloError::IllegalMove("e7e5".into()))
+        );
+        assert_eq!(
+            Move::try_from(("e2e5", &board_state)),
+            Err(IgloError::IllegalMove("e2e5".into()))
+        );
+
+        let black_pawn_push = Move::new(Square::E7, Square::E5, MoveType::DoublePush);
+        assert!(board_state.try_exec_move(black_pawn_push).is_err());
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        assert_eq!(board_state.try_exec_move(e4), Ok(board_state.exec_move(e4)));
+    }
+
+    #[test]
+    fn color_flipped() {
+        let board_state = ChessBoardState::from_fen(
+            "r3k2r/pP1pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/1PPBBPPP/R3K2R w Kq c6 0 2",
+        )
+        .unwrap();
+        let flipped = board_state.color_flipped();
+        assert_eq!(
+            flipped.to_fen(),
+            "r3k2r/1ppbbppp/2n2q1P/1P2p3/2Ppn3/BN2PNP1/Pp1PQPB1/R3K2R b Qk c3 0 2"
+        );
+        assert_eq!(
+            flipped,
+            ChessBoardState::from_fen(&flipped.to_fen()).unwrap()
+        );
+        assert!(flipped.validate().is_ok());
+        assert_eq!(flipped.color_flipped(), board_state);
+
+        // Both sides have the same moves, en passant and castling included
+        for depth in 1..=3 {
+            assert_eq!(perft(&flipped, depth), perft(&board_state, depth));
+        }
+
+        let check = ChessBoardState::from_fen("4k3/8/8/8/8/5n2/8/R3K3 w Q - 0 1").unwrap();
+        assert!(check.color_flipped().is_in_check());
+    }
 }
@@ -0,0 +1,163 @@
+//! Positions built piece by piece, for tests and tools which would otherwise have to write
+//! out a FEN string.
+
+use super::{
+    bitboard::BitBoard,
+    board::{CastlingRights, ChessBoard, ChessBoardState, ChessPiece, FenError, PieceColor},
+    square::Square,
+    zobrist_hash::ZHash,
+};
+
+/// Builds a [`ChessBoardState`] from its parts, e.g.
+/// `BoardBuilder::new().piece(Square::E1, King, White).piece(Square::E8, King, Black).build()`.
+/// Everything not given is empty: white to move, no castling rights, no en passant square
+/// and the move counters of a new game. `build` checks the position like FEN loading does.
+pub struct BoardBuilder {
+    board: ChessBoard,
+    side: PieceColor,
+    castling_rights: Result<CastlingRights, FenError>,
+    en_passant_target: Option<Square>,
+    half_moves: u16,
+    full_moves: u16,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self {
+            board: ChessBoard::default(),
+            side: PieceColor::White,
+            castling_rights: Ok(CastlingRights::none()),
+            en_passant_target: None,
+            half_moves: 0,
+            full_moves: 1,
+        }
+    }
+}
+
+impl BoardBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts the piece on the square, replacing the one standing there.
+    pub fn piece(mut self, square: Square, piece: ChessPiece, color: PieceColor) -> Self {
+        let mut zhash = ZHash::default();
+        if let Some((old_piece, old_color)) = self.board.piece_on(square) {
+            self.board
+                .remove_piece_at_pos(old_piece, old_color, square, &mut zhash);
+        }
+        self.board
+            .place_piece_of_color(piece, color, square, &mut zhash);
+        self
+    }
+
+    pub fn side(mut self, side: PieceColor) -> Self {
+        self.side = side;
+        self
+    }
+
+    /// Castling rights in FEN notation, e.g. `KQkq` or `-`.
+    pub fn castling(mut self, rights: &str) -> Self {
+        self.castling_rights = CastlingRights::try_from(rights)
+            .map_err(|_| FenError::InvalidCastlingRights(rights.to_string()));
+        self
+    }
+
+    /// Square a pawn skipped with a double push on the last move.
+    pub fn ep(mut self, square: Square) -> Self {
+        self.en_passant_target = Some(square);
+        self
+    }
+
+    pub fn half_moves(mut self, half_moves: u16) -> Self {
+        self.half_moves = half_moves;
+        self
+    }
+
+    pub fn full_moves(mut self, full_moves: u16) -> Self {
+        self.full_moves = full_moves;
+        self
+    }
+
+    /// The position with its hashes and checkers, if it passes
+    /// [`ChessBoardState::validate`].
+    pub fn build(self) -> Result<ChessBoardState, FenError> {
+        let mut board_state = ChessBoardState {
+            board: self.board,
+            side: self.side,
+            castling_rights: self.castling_rights?,
+            en_passant_target: self.en_passant_target,
+            half_moves: self.half_moves,
+            full_moves: self.full_moves,
+            zhash: ZHash::default(),
+            pawn_hash: ZHash::from_pawns(&self.board),
+            checkers: BitBoard::EMPTY,
+        };
+        board_state.validate()?;
+        board_state.checkers = board_state.board.checkers(board_state.side);
+        board_state.zhash = ZHash::from_scratch(&board_state);
+        Ok(board_state)
+    }
+}
+
+#[cfg(test)]
+mod board_builder_tests {
+    use super::BoardBuilder;
+    use crate::chess::{
+        board::{ChessBoardState, ChessPiece::*, FenError, PieceColor::*},
+        square::Square,
+    };
+
+    fn kings() -> BoardBuilder {
+        BoardBuilder::new()
+            .piece(Square::E1, King, White)
+            .piece(Square::E8, King, Black)
+    }
+
+    #[test]
+    fn builds_the_same_position_as_the_fen() {
+        let board_state = kings()
+            .piece(Square::H1, Rook, White)
+            .piece(Square::A8, Rook, Black)
+            .piece(Square::E5, Pawn, White)
+            .piece(Square::D5, Pawn, Black)
+            .side(White)
+            .castling("Kq")
+            .ep(Square::D6)
+            .half_moves(0)
+            .full_moves(30)
+            .build()
+            .unwrap();
+        let from_fen = ChessBoardState::from_fen("r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 0 30").unwrap();
+        assert_eq!(board_state, from_fen);
+        assert_eq!(board_state.zhash, from_fen.zhash);
+    }
+
+    #[test]
+    fn later_pieces_replace_earlier_ones() {
+        let board_state = kings()
+            .piece(Square::D4, Queen, Black)
+            .piece(Square::D4, Knight, White)
+            .build()
+            .unwrap();
+        assert_eq!(board_state.to_fen(), "4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        assert_eq!(
+            kings().castling("KX").build(),
+            Err(FenError::InvalidCastlingRights("KX".to_string()))
+        );
+        assert_eq!(
+            BoardBuilder::new().piece(Square::E1, King, White).build(),
+            Err(FenError::KingCount(Black, 0))
+        );
+        assert!(kings().castling("K").build().is_err());
+        assert!(kings().ep(Square::D6).build().is_err());
+        assert_eq!(
+            kings().piece(Square::E7, Rook, White).build(),
+            Err(FenError::SideNotToMoveInCheck)
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use super::bitboard::BitBoard;
 use super::board::{ChessBoardState, ChessPiece, PieceColor};
 use super::square::Square;
+use crate::error::IgloError;
 use core::fmt::Debug;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 
-#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Default, Hash, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Move(pub u16);
 
 const MOVE_SRC_MASK: u16 = 0x003F;
@@ -14,8 +17,8 @@ const MOVE_DST_SHIFT: u16 = 6;
 const MOVE_TYPE_MASK: u16 = 0xF000;
 const MOVE_TYPE_SHIFT: u16 = 12;
 
-const PAWN_START_SQUARE_BB: BitBoard = BitBoard(0xff00000000ff00);
-const PAWN_DOUBLE_PUSH_SQUARE_BB: BitBoard = BitBoard(0xffff000000);
+const PAWN_START_SQUARE_BB: BitBoard = BitBoard(BitBoard::RANK_2.0 | BitBoard::RANK_7.0);
+const PAWN_DOUBLE_PUSH_SQUARE_BB: BitBoard = BitBoard(BitBoard::RANK_4.0 | BitBoard::RANK_5.0);
 
 pub const PROMOTION_TARGETS: [MoveType; 4] = [
     MoveType::KnightPromotion,
@@ -82,16 +85,20 @@ impl MoveType {
 impl Move {
     pub const NULL_MOVE: Move = Move(0);
 
-    pub fn new(src: u16, dst: u16, ty: MoveType) -> Self {
-        Self(src | (dst << MOVE_DST_SHIFT) | ((ty as u16) << MOVE_TYPE_SHIFT))
+    pub fn new(src: Square, dst: Square, ty: MoveType) -> Self {
+        Self(
+            src.index() as u16
+                | ((dst.index() as u16) << MOVE_DST_SHIFT)
+                | ((ty as u16) << MOVE_TYPE_SHIFT),
+        )
     }
 
-    pub fn get_src(&self) -> u16 {
-        self.0 & MOVE_SRC_MASK
+    pub fn get_src(&self) -> Square {
+        Square::from_index((self.0 & MOVE_SRC_MASK) as usize)
     }
 
-    pub fn get_dst(&self) -> u16 {
-        (self.0 & MOVE_DST_MASK) >> MOVE_DST_SHIFT
+    pub fn get_dst(&self) -> Square {
+        Square::from_index(((self.0 & MOVE_DST_MASK) >> MOVE_DST_SHIFT) as usize)
     }
 
     pub fn get_type(&self) -> MoveType {
@@ -140,69 +147,207 @@ impl Move {
     }
 
     pub fn get_moved_piece(&self, board_state: &ChessBoardState) -> ChessPiece {
-        board_state
-            .board
-            .get_piece_at_pos(self.get_src() as usize)
-            .unwrap()
-            .0
+        board_state.board.piece_on(self.get_src()).unwrap().0
     }
 
     pub fn get_captured_piece(&self, board_state: &ChessBoardState) -> Option<ChessPiece> {
         if self.is_en_passant() {
             Some(ChessPiece::Pawn)
         } else {
-            board_state
-                .board
-                .get_piece_at_pos(self.get_dst() as usize)
-                .map(|(p, _)| p)
+            board_state.board.piece_on(self.get_dst()).map(|(p, _)| p)
+        }
+    }
+
+    /// Formats the move in standard algebraic notation (e.g. `Nbd7`, `exd6`, `O-O`, `e8=Q#`)
+    /// for the position it is played in.
+    pub fn to_san(&self, board_state: &ChessBoardState) -> String {
+        let mut san = String::new();
+        let src_name = self.get_src().to_string();
+
+        match self.get_type() {
+            MoveType::CastleKingSide => san.push_str("O-O"),
+            MoveType::CastleQueenSide => san.push_str("O-O-O"),
+            _ => {
+                let piece = self.get_moved_piece(board_state);
+                if piece == ChessPiece::Pawn {
+                    if self.is_capture() {
+                        san.push_str(&src_name[0..1]);
+                    }
+                } else {
+                    san.push(ChessBoardState::piece_to_fen_notation(
+                        piece,
+                        PieceColor::White,
+                    ));
+
+                    // Other pieces of the same kind that can reach the destination
+                    let ambiguous: Vec<Square> = board_state
+                        .generate_legal_moves_for_current_player::<false>()
+                        .iter()
+                        .filter(|mv| {
+                            mv.get_dst() == self.get_dst()
+                                && mv.get_src() != self.get_src()
+                                && mv.get_moved_piece(board_state) == piece
+                        })
+                        .map(|mv| mv.get_src())
+                        .collect();
+                    if !ambiguous.is_empty() {
+                        let shares_file = ambiguous
+                            .iter()
+                            .any(|src| src.file() == self.get_src().file());
+                        let shares_rank = ambiguous
+                            .iter()
+                            .any(|src| src.rank() == self.get_src().rank());
+                        if !shares_file {
+                            san.push_str(&src_name[0..1]);
+                        } else if !shares_rank {
+                            san.push_str(&src_name[1..2]);
+                        } else {
+                            san.push_str(&src_name);
+                        }
+                    }
+                }
+
+                if self.is_capture() {
+                    san.push('x');
+                }
+                san.push_str(&self.get_dst().to_string());
+
+                if self.is_promotion() {
+                    san.push('=');
+                    san.push(ChessBoardState::piece_to_fen_notation(
+                        self.promotion_target(),
+                        PieceColor::White,
+                    ));
+                }
+            }
+        }
+
+        if let Some(suffix) = self.check_suffix(board_state) {
+            san.push(suffix);
+        }
+        san
+    }
+
+    /// `+` or `#` if the move gives check or mate in the position it is played in.
+    fn check_suffix(&self, board_state: &ChessBoardState) -> Option<char> {
+        let new_state = board_state.exec_move(*self);
+        if !new_state.is_in_check() {
+            None
+        } else if new_state
+            .generate_legal_moves_for_current_player::<false>()
+            .is_empty()
+        {
+            Some('#')
+        } else {
+            Some('+')
         }
     }
+
+    /// Formats the move in long algebraic notation with check and mate suffixes for the
+    /// position it is played in (e.g. `Ng1-f3`, `e5xd6 e.p.`, `O-O`, `e7-e8=Q+`), followed
+    /// by ` (stalemate)` if the opponent is left without moves.
+    pub fn to_annotated_string(&self, board_state: &ChessBoardState) -> String {
+        let mut annotated = String::new();
+        match self.get_type() {
+            MoveType::CastleKingSide => annotated.push_str("O-O"),
+            MoveType::CastleQueenSide => annotated.push_str("O-O-O"),
+            _ => {
+                let piece = self.get_moved_piece(board_state);
+                if piece != ChessPiece::Pawn {
+                    annotated.push(ChessBoardState::piece_to_fen_notation(
+                        piece,
+                        PieceColor::White,
+                    ));
+                }
+                annotated.push_str(&self.get_src().to_string());
+                annotated.push(if self.is_capture() { 'x' } else { '-' });
+                annotated.push_str(&self.get_dst().to_string());
+                if self.is_promotion() {
+                    annotated.push('=');
+                    annotated.push(ChessBoardState::piece_to_fen_notation(
+                        self.promotion_target(),
+                        PieceColor::White,
+                    ));
+                }
+            }
+        }
+
+        match self.check_suffix(board_state) {
+            Some(suffix) => annotated.push(suffix),
+            None if board_state
+                .exec_move(*self)
+                .generate_legal_moves_for_current_player::<false>()
+                .is_empty() =>
+            {
+                annotated.push_str(" (stalemate)")
+            }
+            None => {}
+        }
+        if self.is_en_passant() {
+            annotated.push_str(" e.p.");
+        }
+        annotated
+    }
+
+    /// Parses a move in standard algebraic notation for the given position. Check, mate and
+    /// annotation suffixes are optional, castling may also be written with zeros.
+    pub fn from_san(san: &str, board_state: &ChessBoardState) -> Result<Move, IgloError> {
+        let normalize = |text: &str| -> String {
+            text.trim()
+                .chars()
+                .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | '='))
+                .map(|c| if c == '0' { 'O' } else { c })
+                .collect()
+        };
+        let san = normalize(san);
+
+        board_state
+            .generate_legal_moves_for_current_player::<false>()
+            .into_iter()
+            .find(|mv| normalize(&mv.to_san(board_state)) == san)
+            .ok_or(IgloError::IllegalMove(san))
+    }
 }
 
 impl TryFrom<(&str, &ChessBoardState)> for Move {
-    type Error = ();
+    type Error = IgloError;
 
     fn try_from(v: (&str, &ChessBoardState)) -> Result<Self, Self::Error> {
         let (value, board_state) = v;
+        let invalid = || IgloError::InvalidMove(value.to_string());
+        let illegal = || IgloError::IllegalMove(value.to_string());
         if value.len() < 4 {
-            return Err(());
+            return Err(invalid());
         }
         let current_side = board_state.side;
         let opposing_side = !current_side;
-        let parse_square_from_slice = |str_slc: &str| -> Result<u16, Self::Error> {
-            if str_slc.len() != 2 {
-                return Err(());
-            }
-            let square = Square::from_square_name(str_slc)?;
-            if let Some(sq) = square {
-                Ok(sq as u16)
-            } else {
-                return Err(());
-            }
+        let parse_square = |range| {
+            let name = value.get(range).ok_or_else(invalid)?;
+            Square::try_from(name).map_err(|_| invalid())
         };
-        let mv_src = parse_square_from_slice(&value[0..2])?;
-        let mv_dst = parse_square_from_slice(&value[2..4])?;
+        let mv_src = parse_square(0..2)?;
+        let mv_dst = parse_square(2..4)?;
         let mut resulting_move = Move::new(mv_src, mv_dst, MoveType::Silent);
 
-        // Promotion Move
-        match value.chars().nth(4) {
+        // Promotion Move, the capture bit is added below and turns it into a capture-promotion
+        match value.chars().nth(4).map(|c| c.to_ascii_lowercase()) {
             Some('r') => resulting_move.set_move_type(MoveType::RookPromotion),
             Some('b') => resulting_move.set_move_type(MoveType::BishopPromotion),
             Some('n') => resulting_move.set_move_type(MoveType::KnightPromotion),
             Some('q') => resulting_move.set_move_type(MoveType::QueenPromotion),
-            Some(_) => {}
+            Some(_) => return Err(invalid()),
             None => {}
         };
 
-        let (src_piece, src_color) = match board_state.board.get_piece_at_pos(mv_src as usize) {
+        let (src_piece, src_color) = match board_state.board.piece_on(mv_src) {
             Some(e) => e,
-            _ => return Err(()),
+            _ => return Err(illegal()),
         };
         // Capture Move
-        if let Some((_piece, col)) = board_state.board.get_piece_at_pos(mv_dst as usize) {
+        if let Some((_piece, col)) = board_state.board.piece_on(mv_dst) {
             if col == current_side {
                 // Capturing Own Piece??
-                return Err(());
+                return Err(illegal());
             } else {
                 resulting_move.set_is_capture(true);
             }
@@ -210,26 +355,21 @@ impl TryFrom<(&str, &ChessBoardState)> for Move {
             // Check for Pawn Moves
             if src_piece == ChessPiece::Pawn {
                 // En Passant
-                if board_state.en_passant_target.is_some()
-                    && mv_dst == board_state.en_passant_target.unwrap() as u16
-                {
+                if board_state.en_passant_target == Some(mv_dst) {
                     let enpassanted_pawn = if current_side == PieceColor::White {
-                        board_state.en_passant_target.unwrap() + 8
+                        Square::from_index(mv_dst.index() + 8)
                     } else {
-                        board_state.en_passant_target.unwrap() - 8
+                        Square::from_index(mv_dst.index() - 8)
                     };
-                    match board_state
-                        .board
-                        .get_piece_at_pos(enpassanted_pawn as usize)
-                    {
+                    match board_state.board.piece_on(enpassanted_pawn) {
                         Some((ChessPiece::Pawn, side)) if side == opposing_side => {
                             resulting_move.set_move_type(MoveType::EnPassant)
                         }
                         _ => {}
                     }
                 } else {
-                    if PAWN_START_SQUARE_BB.get_bit(mv_src as usize)
-                        && PAWN_DOUBLE_PUSH_SQUARE_BB.get_bit(mv_dst as usize)
+                    if PAWN_START_SQUARE_BB.get_bit(mv_src.index())
+                        && PAWN_DOUBLE_PUSH_SQUARE_BB.get_bit(mv_dst.index())
                     {
                         resulting_move.set_move_type(MoveType::DoublePush);
                     }
@@ -244,7 +384,7 @@ impl TryFrom<(&str, &ChessBoardState)> for Move {
                             resulting_move.set_move_type(MoveType::CastleKingSide);
                         } else {
                             // Attempt to perform non legal castle
-                            return Err(());
+                            return Err(illegal());
                         }
                     }
                     (PieceColor::White, Square::E1, Square::WHITE_QUEEN_SIDE_CASTLE_SQUARE) => {
@@ -252,7 +392,7 @@ impl TryFrom<(&str, &ChessBoardState)> for Move {
                             resulting_move.set_move_type(MoveType::CastleQueenSide);
                         } else {
                             // Attempt to perform non legal castle
-                            return Err(());
+                            return Err(illegal());
                         }
                     }
                     (PieceColor::Black, Square::E8, Square::BLACK_KING_SIDE_CASTLE_SQAURE) => {
@@ -260,7 +400,7 @@ impl TryFrom<(&str, &ChessBoardState)> for Move {
                             resulting_move.set_move_type(MoveType::CastleKingSide);
                         } else {
                             // Attempt to perform non legal castle
-                            return Err(());
+                            return Err(illegal());
                         }
                     }
                     (PieceColor::Black, Square::E8, Square::BLACK_QUEEN_SIDE_CASTLE_SQAURE) => {
@@ -268,27 +408,24 @@ impl TryFrom<(&str, &ChessBoardState)> for Move {
                             resulting_move.set_move_type(MoveType::CastleQueenSide);
                         } else {
                             // Attempt to perform non legal castle
-                            return Err(());
+                            return Err(illegal());
                         }
                     }
-                    _ => {
-                        // TODO: check king moves
-                    }
+                    _ => {}
                 }
             }
         }
 
+        if !board_state.is_legal(resulting_move) {
+            return Err(illegal());
+        }
         Ok(resulting_move)
     }
 }
 
 impl Debug for Move {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!(
-            "{}{}",
-            &Square::to_square_name(Some(self.get_src() as u8)),
-            &Square::to_square_name(Some(self.get_dst() as u8)),
-        ))?;
+        f.write_fmt(format_args!("{}{}", self.get_src(), self.get_dst()))?;
         if self.is_promotion() {
             f.write_char(ChessBoardState::piece_to_fen_notation(
                 self.promotion_target(),
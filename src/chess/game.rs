@@ -0,0 +1,325 @@
+use std::fmt::Display;
+
+use super::{
+    board::{ChessBoardState, ChessPiece, PieceColor},
+    chess_move::Move,
+    pgn::{game_result, to_pgn, GameRecord},
+    zobrist_hash::ZHash,
+};
+use crate::error::IgloError;
+
+/// Remaining time and increment of both players in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameClock {
+    pub white_time: u64,
+    pub black_time: u64,
+    pub white_inc: u64,
+    pub black_inc: u64,
+}
+
+/// Why a game is drawn by the rules, as opposed to a draw agreed on or adjudicated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl Display for DrawReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            DrawReason::Stalemate => "stalemate",
+            DrawReason::FiftyMoveRule => "fifty move rule",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::InsufficientMaterial => "insufficient material",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Neither side has a pawn, rook or queen and at most a single minor piece is left.
+fn has_insufficient_material(board_state: &ChessBoardState) -> bool {
+    let board = &board_state.board;
+    let count = |piece| {
+        board
+            .get_piece_bitboard(piece, PieceColor::White)
+            .bit_count()
+            + board
+                .get_piece_bitboard(piece, PieceColor::Black)
+                .bit_count()
+    };
+    count(ChessPiece::Pawn) == 0
+        && count(ChessPiece::Rook) == 0
+        && count(ChessPiece::Queen) == 0
+        && count(ChessPiece::Knight) + count(ChessPiece::Bishop) <= 1
+}
+
+/// A game in progress: the start position, the moves played from it and every position
+/// reached, so earlier positions and repetitions can be looked up without replaying. With
+/// the `serde` feature only the start position and moves are stored, the positions are
+/// replayed when loading.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "GameData", try_from = "GameData")
+)]
+pub struct Game {
+    // Position before each move followed by the current position
+    states: Vec<ChessBoardState>,
+    moves: Vec<Move>,
+    pub clock: Option<GameClock>,
+    // Result decided away from the board, like a resignation or time forfeit
+    result: Option<String>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(ChessBoardState::starting_state())
+    }
+}
+
+impl Game {
+    pub fn new(start_state: ChessBoardState) -> Self {
+        Self {
+            states: vec![start_state],
+            moves: Vec::new(),
+            clock: None,
+            result: None,
+        }
+    }
+
+    pub fn start_state(&self) -> &ChessBoardState {
+        &self.states[0]
+    }
+
+    pub fn current_state(&self) -> &ChessBoardState {
+        self.states.last().unwrap()
+    }
+
+    /// Position before the move of the given ply, the current position after the last one.
+    pub fn state_at(&self, ply: usize) -> Option<&ChessBoardState> {
+        self.states.get(ply)
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Plays a move, which is rejected unless it is legal in the current position.
+    pub fn push_move(&mut self, mv: Move) -> Result<(), IgloError> {
+        let new_state = self.current_state().try_exec_move(mv)?;
+        self.states.push(new_state);
+        self.moves.push(mv);
+        Ok(())
+    }
+
+    /// Takes back the last move, which also clears a result set by `set_result`.
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.moves.pop()?;
+        self.states.pop();
+        self.result = None;
+        Some(mv)
+    }
+
+    /// Zobrist keys of all positions of the game, from the start to the current one.
+    pub fn zobrist_keys(&self) -> impl Iterator<Item = ZHash> + '_ {
+        self.states.iter().map(|state| state.zhash)
+    }
+
+    /// How often the current position occurred in the game, itself included.
+    pub fn repetition_count(&self) -> usize {
+        let zhash = self.current_state().zhash;
+        self.zobrist_keys().filter(|key| *key == zhash).count()
+    }
+
+    /// Rule by which the current position is drawn. A mate delivered with the fiftieth move
+    /// still wins, so a checkmated side is never drawn.
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        let state = self.current_state();
+        if state
+            .generate_legal_moves_for_current_player::<false>()
+            .is_empty()
+        {
+            return (!state.is_in_check()).then_some(DrawReason::Stalemate);
+        }
+        if state.half_moves >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else if self.repetition_count() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if has_insufficient_material(state) {
+            Some(DrawReason::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Result in PGN notation: the one set by `set_result`, otherwise decided by the board
+    /// including all draws by rule, `*` while the game is still going on.
+    pub fn result(&self) -> &str {
+        match &self.result {
+            Some(result) => result,
+            None if self.draw_reason().is_some() => "1/2-1/2",
+            None => game_result(self.current_state()),
+        }
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.result = Some(result.to_string());
+    }
+
+    /// Moves of the game together with the position each one is played in.
+    pub fn iter(&self) -> impl Iterator<Item = (&ChessBoardState, Move)> + '_ {
+        self.states.iter().zip(self.moves.iter().copied())
+    }
+
+    pub fn to_pgn(&self, tags: &[(&str, String)]) -> String {
+        to_pgn(tags, self.start_state(), &self.moves, self.result())
+    }
+}
+
+impl From<&Game> for GameRecord {
+    fn from(game: &Game) -> Self {
+        GameRecord {
+            tags: Vec::new(),
+            start_state: *game.start_state(),
+            moves: game.moves.clone(),
+            result: game.result().to_string(),
+        }
+    }
+}
+
+impl TryFrom<&GameRecord> for Game {
+    type Error = IgloError;
+
+    fn try_from(record: &GameRecord) -> Result<Self, Self::Error> {
+        let mut game = Game::new(record.start_state);
+        for mv in &record.moves {
+            game.push_move(*mv)?;
+        }
+        if record.result != "*" {
+            game.set_result(&record.result);
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct GameData {
+    start_state: ChessBoardState,
+    moves: Vec<Move>,
+    clock: Option<GameClock>,
+    result: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Game> for GameData {
+    fn from(game: Game) -> Self {
+        GameData {
+            start_state: game.states[0],
+            moves: game.moves,
+            clock: game.clock,
+            result: game.result,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GameData> for Game {
+    type Error = IgloError;
+
+    fn try_from(data: GameData) -> Result<Self, Self::Error> {
+        let mut game = Game::new(data.start_state);
+        for mv in data.moves {
+            game.push_move(mv)?;
+        }
+        game.clock = data.clock;
+        game.result = data.result;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod game_tests {
+    use super::{DrawReason, Game};
+    use crate::chess::{board::ChessBoardState, chess_move::Move};
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for text in moves {
+            let mv = Move::try_from((*text, game.current_state())).unwrap();
+            game.push_move(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn push_undo_and_result() {
+        let mut game = Game::default();
+        play(
+            &mut game,
+            &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"],
+        );
+        assert_eq!(game.result(), "*");
+        assert_eq!(game.repetition_count(), 2);
+
+        play(&mut game, &["f6g8"]);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.result(), "1/2-1/2");
+        assert_eq!(game.zobrist_keys().count(), game.moves().len() + 1);
+
+        assert_eq!(format!("{:?}", game.undo().unwrap()), "f6g8");
+        assert_eq!(game.result(), "*");
+        assert_eq!(
+            game.state_at(1),
+            Some(&game.start_state().exec_move(game.moves()[0]))
+        );
+
+        game.set_result("0-1");
+        assert_eq!(game.result(), "0-1");
+        let illegal = Move::try_from(("e2e4", &ChessBoardState::starting_state())).unwrap();
+        assert!(game.push_move(illegal).is_err());
+        assert_eq!(game.iter().count(), 7);
+    }
+
+    #[test]
+    fn draw_reasons() {
+        let game = |fen: &str| Game::new(ChessBoardState::from_fen(fen).unwrap());
+
+        let fifty_moves = game("4k3/8/8/8/8/8/4P3/R3K3 w - - 100 80");
+        assert_eq!(fifty_moves.draw_reason(), Some(DrawReason::FiftyMoveRule));
+        assert_eq!(fifty_moves.result(), "1/2-1/2");
+
+        // Mate with the fiftieth move counts
+        let mate = game("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80");
+        assert_eq!(mate.draw_reason(), None);
+        assert_eq!(mate.result(), "1-0");
+
+        let stalemate = game("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(stalemate.draw_reason(), Some(DrawReason::Stalemate));
+        let bare_kings = game("4k3/8/8/8/8/8/8/2B1K3 b - - 0 1");
+        assert_eq!(
+            bare_kings.draw_reason(),
+            Some(DrawReason::InsufficientMaterial)
+        );
+        assert_eq!(Game::default().draw_reason(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let mut game = Game::default();
+        play(&mut game, &["e2e4", "e7e5", "d1h5"]);
+        game.clock = Some(super::GameClock {
+            white_time: 1000,
+            black_time: 2000,
+            white_inc: 10,
+            black_inc: 10,
+        });
+
+        let bytes = bincode::serialize(&game).unwrap();
+        let decoded: Game = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, game);
+    }
+}
@@ -0,0 +1,225 @@
+//! Magic bitboard and PEXT table generation for the sliding pieces.
+//!
+//! This file is compiled into the build script, so it only works on plain `u64` bitboards and
+//! must not depend on anything else from the crate. Square 0 is A8 and square 63 is H1.
+
+pub const ROOK_OFFSETS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+pub const BISHOP_OFFSETS: [(i32, i32); 4] = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+
+// Fixed seed so that every build produces the same tables
+const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+pub struct Magics {
+    pub blocker_masks: Vec<u64>,
+    pub magics: Vec<u64>,
+    pub index_bits: Vec<u8>,
+    pub offsets: Vec<usize>,
+    pub moves: Vec<u64>,
+}
+
+// xorshift64*, good enough to search magics and reproducible without external crates
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Magics require a low number of active bits
+    fn next_sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+fn step(square: usize, (dx, dy): (i32, i32)) -> Option<usize> {
+    let x = (square % 8) as i32 + dx;
+    let y = (square / 8) as i32 + dy;
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((y * 8 + x) as usize)
+    } else {
+        None
+    }
+}
+
+/// Squares along the rays whose occupancy changes the moves, the last square of a ray never
+/// blocks anything behind it and is left out.
+pub fn blocker_mask(square: usize, offsets: &[(i32, i32)]) -> u64 {
+    let mut mask = 0;
+    for &offset in offsets {
+        let mut current = square;
+        while let Some(next) = step(current, offset) {
+            if step(next, offset).is_none() {
+                break;
+            }
+            mask |= 1 << next;
+            current = next;
+        }
+    }
+    mask
+}
+
+pub fn sliding_moves(square: usize, blockers: u64, offsets: &[(i32, i32)]) -> u64 {
+    let mut moves = 0;
+    for &offset in offsets {
+        let mut current = square;
+        while let Some(next) = step(current, offset) {
+            moves |= 1 << next;
+            if blockers & (1 << next) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    moves
+}
+
+/// All blocker configurations of the square together with the resulting moves, enumerated by
+/// the carry-rippler trick in increasing order.
+fn blocker_subsets(square: usize, blocker_mask: u64, offsets: &[(i32, i32)]) -> Vec<(u64, u64)> {
+    let mut subsets = Vec::new();
+    let mut blockers: u64 = 0;
+    loop {
+        subsets.push((blockers, sliding_moves(square, blockers, offsets)));
+        blockers = blockers.wrapping_sub(blocker_mask) & blocker_mask;
+        if blockers == 0 {
+            break;
+        }
+    }
+    subsets
+}
+
+pub fn magic_index(blockers: u64, magic: u64, index_bits: u8) -> usize {
+    (blockers.wrapping_mul(magic) >> (64 - index_bits)) as usize
+}
+
+fn find_magic(
+    rng: &mut Rng,
+    blocker_mask: u64,
+    subsets: &[(u64, u64)],
+    index_bits: u8,
+) -> (u64, Vec<u64>) {
+    let mut table = vec![0; 1 << index_bits];
+    // Slot was written in the attempt with this number, saves clearing the table every time
+    let mut written = vec![0u32; 1 << index_bits];
+    let mut attempt = 0;
+    loop {
+        let magic = rng.next_sparse();
+        if (blocker_mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+
+        let is_valid = subsets.iter().all(|&(blockers, moves)| {
+            let index = magic_index(blockers, magic, index_bits);
+            if written[index] != attempt {
+                written[index] = attempt;
+                table[index] = moves;
+                true
+            } else {
+                // Two different move sets in the same slot is a hash collision
+                table[index] == moves
+            }
+        });
+        if is_valid {
+            for (slot, &written_in) in table.iter_mut().zip(&written) {
+                if written_in != attempt {
+                    *slot = 0;
+                }
+            }
+            return (magic, table);
+        }
+    }
+}
+
+/// Finds a magic for every square and packs the move tables of all squares into one array, the
+/// moves for `blockers` are found at `offsets[square] + magic_index(blockers, ...)`. Each square
+/// only uses as many index bits as its blocker mask has squares, so a bishop in the corner needs
+/// 64 entries instead of the 4096 of a rook.
+pub fn generate_magics(offsets: &[(i32, i32)]) -> Magics {
+    let mut rng = Rng(SEED);
+    let mut magics = Magics {
+        blocker_masks: Vec::new(),
+        magics: Vec::new(),
+        index_bits: Vec::new(),
+        offsets: Vec::new(),
+        moves: Vec::new(),
+    };
+
+    for square in 0..64 {
+        let blocker_mask = blocker_mask(square, offsets);
+        let index_bits = blocker_mask.count_ones() as u8;
+        let subsets = blocker_subsets(square, blocker_mask, offsets);
+        let (magic, moves) = find_magic(&mut rng, blocker_mask, &subsets, index_bits);
+        magics.blocker_masks.push(blocker_mask);
+        magics.magics.push(magic);
+        magics.index_bits.push(index_bits);
+        magics.offsets.push(magics.moves.len());
+        magics.moves.extend(moves);
+    }
+    magics
+}
+
+pub struct PextTable {
+    pub blocker_masks: Vec<u64>,
+    pub offsets: Vec<usize>,
+    pub moves: Vec<u64>,
+}
+
+/// Move tables of all squares packed into one array, the moves for `blockers` are found at
+/// `offsets[square] + pext(blockers, blocker_masks[square])`.
+pub fn generate_pext_table(offsets: &[(i32, i32)]) -> PextTable {
+    let mut table = PextTable {
+        blocker_masks: Vec::new(),
+        offsets: Vec::new(),
+        moves: Vec::new(),
+    };
+
+    for square in 0..64 {
+        let blocker_mask = blocker_mask(square, offsets);
+        table.blocker_masks.push(blocker_mask);
+        table.offsets.push(table.moves.len());
+        // Extracting the bits of the n-th subset in increasing order yields n, so the subsets
+        // are already sorted by their PEXT index
+        table.moves.extend(
+            blocker_subsets(square, blocker_mask, offsets)
+                .into_iter()
+                .map(|(_, moves)| moves),
+        );
+    }
+    table
+}
+
+pub struct LineTables {
+    pub between: Vec<[u64; 64]>,
+    pub line: Vec<[u64; 64]>,
+}
+
+/// `between[a][b]` holds the squares strictly between two squares on a common rank, file or
+/// diagonal and `line[a][b]` the whole line through both, including them. Both are empty for
+/// squares which do not share a line.
+pub fn generate_line_tables() -> LineTables {
+    let mut tables = LineTables {
+        between: vec![[0; 64]; 64],
+        line: vec![[0; 64]; 64],
+    };
+
+    for square in 0..64 {
+        for &(dx, dy) in ROOK_OFFSETS.iter().chain(&BISHOP_OFFSETS) {
+            let line = (1 << square)
+                | sliding_moves(square, 0, &[(dx, dy)])
+                | sliding_moves(square, 0, &[(-dx, -dy)]);
+            let mut between = 0;
+            let mut current = square;
+            while let Some(next) = step(current, (dx, dy)) {
+                tables.between[square][next] = between;
+                tables.line[square][next] = line;
+                between |= 1 << next;
+                current = next;
+            }
+        }
+    }
+    tables
+}
@@ -1,7 +1,16 @@
 pub mod bitboard;
 pub mod board;
+pub mod board_builder;
 pub mod chess_move;
+pub mod game;
 pub mod move_generator;
+pub mod move_list;
+#[cfg(test)]
+mod movegen_fuzz;
+pub mod nn_input;
 pub mod perft;
+#[cfg(all(feature = "pext", target_arch = "x86_64"))]
+pub mod pext;
+pub mod pgn;
 pub mod square;
 pub mod zobrist_hash;
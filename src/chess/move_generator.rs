@@ -2,33 +2,109 @@ use super::{
     bitboard::{BitBoard, MagicEntry},
     board::{ChessBoard, ChessBoardState, ChessPiece, PieceColor},
     chess_move::{Move, MoveType, PROMOTION_CAPTURE_TARGETS, PROMOTION_TARGETS},
+    move_list::MoveList,
     square::Square,
     zobrist_hash::ZHash,
 };
 
-const BLACK_KING_SIDE_CASTLE_SQUARES: BitBoard = BitBoard(0x60);
-const WHITE_KING_SIDE_CASTLE_SQUARES: BitBoard = BitBoard(0x6000000000000000);
+#[cfg(all(feature = "pext", target_arch = "x86_64"))]
+use super::pext;
+
+const BLACK_KING_SIDE_CASTLE_SQUARES: BitBoard = BitBoard::from_squares(&[Square::F8, Square::G8]);
+const WHITE_KING_SIDE_CASTLE_SQUARES: BitBoard = BitBoard::from_squares(&[Square::F1, Square::G1]);
+
+const WHITE_QUEEN_SIDE_CASTLE_ATTACKED_SQAURES: BitBoard =
+    BitBoard::from_squares(&[Square::C1, Square::D1]);
+const WHITE_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES: BitBoard =
+    BitBoard::from_squares(&[Square::B1, Square::C1, Square::D1]);
+const BLACK_QUEEN_SIDE_CASTLE_ATTACKED_SQUARES: BitBoard =
+    BitBoard::from_squares(&[Square::C8, Square::D8]);
+const BLACK_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES: BitBoard =
+    BitBoard::from_squares(&[Square::B8, Square::C8, Square::D8]);
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-1, -2),
+    (1, -2),
+    (-2, -1),
+    (2, -1),
+    (-2, 1),
+    (2, 1),
+    (-1, 2),
+    (1, 2),
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const KNIGHT_MOVE_LOOKUP: [BitBoard; 64] = jump_piece_lookup(&KNIGHT_OFFSETS);
+
+const KING_MOVE_LOOKUP: [BitBoard; 64] = jump_piece_lookup(&KING_OFFSETS);
+
+/// Squares a piece jumping by the given (file, rank) offsets reaches from every square, jumps
+/// leaving the board are dropped.
+const fn jump_piece_lookup(offsets: &[(i32, i32)]) -> [BitBoard; 64] {
+    let mut lookup = [BitBoard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut targets = 0;
+        let mut i = 0;
+        while i < offsets.len() {
+            let (files, ranks) = offsets[i];
+            if let Some(target) = Square::from_index(square).offset(files, ranks) {
+                targets |= 1u64 << target.index();
+            }
+            i += 1;
+        }
+        lookup[square] = BitBoard(targets);
+        square += 1;
+    }
+    lookup
+}
+
+// Defines ROOK_MAGICS, ROOK_MOVES, BISHOP_MAGICS, BISHOP_MOVES, BETWEEN and LINE, generated by
+// build.rs from lookup_gens/magics.rs
+include!(concat!(env!("OUT_DIR"), "/magic_tables.rs"));
 
-const WHITE_QUEEN_SIDE_CASTLE_ATTACKED_SQAURES: BitBoard = BitBoard(0xc00000000000000);
-const WHITE_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES: BitBoard = BitBoard(0xe00000000000000);
-const BLACK_QUEEN_SIDE_CASTLE_ATTACKED_SQUARES: BitBoard = BitBoard(0xc);
-const BLACK_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES: BitBoard = BitBoard(0xe);
+/// Pieces giving check to a king and the pieces pinned to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CheckInfo {
+    checkers: BitBoard,
+    pinned: BitBoard,
+    per_piece: [BitBoard; 6],
+}
 
-const KNIGHT_MOVE_LOOKUP: [BitBoard; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/knight_lookup.bin")) };
+impl CheckInfo {
+    pub fn checkers(&self) -> BitBoard {
+        self.checkers
+    }
 
-const KING_MOVE_LOOKUP: [BitBoard; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/king_lookup.bin")) };
+    pub fn checker_count(&self) -> u32 {
+        self.checkers.bit_count()
+    }
+
+    pub fn in_check(&self) -> bool {
+        !self.checkers.is_empty()
+    }
 
-const ROOK_MAGICS: [MagicEntry; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/rook_magics.bin")) };
-const ROOK_MOVES: [[BitBoard; 4096]; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/rook_moves.bin")) };
+    /// Checking pieces of the given type.
+    pub fn checkers_of(&self, piece: ChessPiece) -> BitBoard {
+        self.per_piece[piece as usize]
+    }
 
-const BISHOP_MAGICS: [MagicEntry; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/bishop_magics.bin")) };
-const BISHOP_MOVES: [[BitBoard; 4096]; 64] =
-    unsafe { std::mem::transmute(*include_bytes!("lookup_gens/bishop_moves.bin")) };
+    /// Pieces of the king's side which can not leave the line between their king and an
+    /// enemy slider.
+    pub fn pinned(&self) -> BitBoard {
+        self.pinned
+    }
+}
 
 impl ChessBoard {
     #[inline(always)]
@@ -72,8 +148,12 @@ impl ChessBoard {
     }
 
     #[inline(always)]
-    pub fn pawns_able_to_enpassant(&self, color: PieceColor, en_passant_target: u8) -> BitBoard {
-        let target_bb = BitBoard::EMPTY.set_bit(en_passant_target as usize);
+    pub fn pawns_able_to_enpassant(
+        &self,
+        color: PieceColor,
+        en_passant_target: Square,
+    ) -> BitBoard {
+        let target_bb = BitBoard::EMPTY.set_bit(en_passant_target.index());
         if color == PieceColor::White {
             (target_bb.s_so_ea() | target_bb.s_so_we())
                 & self.white_pieces[ChessPiece::Pawn as usize]
@@ -113,7 +193,7 @@ impl ChessBoard {
         }
 
         for knight_pos in side_pieces[ChessPiece::Knight as usize] {
-            attacked_map |= KNIGHT_MOVE_LOOKUP[knight_pos];
+            attacked_map |= KNIGHT_MOVE_LOOKUP[knight_pos.index()];
         }
         for bishop in side_pieces[ChessPiece::Bishop as usize] {
             attacked_map |= Self::bishop_attacks(bishop, blockers);
@@ -125,78 +205,191 @@ impl ChessBoard {
             attacked_map |= Self::queen_attack(queen, blockers);
         }
         for king in side_pieces[ChessPiece::King as usize] {
-            attacked_map |= KING_MOVE_LOOKUP[king];
+            attacked_map |= KING_MOVE_LOOKUP[king.index()];
         }
 
         attacked_map
     }
 
     #[inline(always)]
-    pub fn rook_attacks(rook_square: usize, blockers: BitBoard) -> BitBoard {
-        (&ROOK_MOVES[rook_square])[ROOK_MAGICS[rook_square].magic_index(blockers)]
+    pub fn rook_attacks(rook_square: Square, blockers: BitBoard) -> BitBoard {
+        #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+        if pext::is_available() {
+            // SAFETY: BMI2 support was checked
+            return unsafe { pext::rook_attacks(rook_square, blockers) };
+        }
+        let index = rook_square.index();
+        BitBoard(ROOK_MOVES[ROOK_MAGICS[index].magic_index(blockers)])
     }
 
     #[inline(always)]
-    pub fn bishop_attacks(bishop_square: usize, blockers: BitBoard) -> BitBoard {
-        (&BISHOP_MOVES[bishop_square])[BISHOP_MAGICS[bishop_square].magic_index(blockers)]
+    pub fn bishop_attacks(bishop_square: Square, blockers: BitBoard) -> BitBoard {
+        #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+        if pext::is_available() {
+            // SAFETY: BMI2 support was checked
+            return unsafe { pext::bishop_attacks(bishop_square, blockers) };
+        }
+        let index = bishop_square.index();
+        BitBoard(BISHOP_MOVES[BISHOP_MAGICS[index].magic_index(blockers)])
     }
 
     #[inline(always)]
-    pub fn queen_attack(queen_square: usize, blockers: BitBoard) -> BitBoard {
+    pub fn queen_attack(queen_square: Square, blockers: BitBoard) -> BitBoard {
         Self::rook_attacks(queen_square, blockers) | Self::bishop_attacks(queen_square, blockers)
     }
 
+    /// Squares attacked by a pawn of `color` standing on `square`.
     #[inline(always)]
-    pub fn king_attackers(&self, color: PieceColor) -> [BitBoard; 7] {
-        let mut attacker_maps = [BitBoard::EMPTY; 7];
-        let king_pos = self.get_king_pos(color);
+    pub fn pawn_attacks(square: Square, color: PieceColor) -> BitBoard {
+        let square_bb = BitBoard::from_square(square);
+        if color == PieceColor::White {
+            square_bb.s_no_we() | square_bb.s_no_ea()
+        } else {
+            square_bb.s_so_we() | square_bb.s_so_ea()
+        }
+    }
+
+    /// Squares strictly between `a` and `b` if they share a rank, file or diagonal, empty
+    /// otherwise.
+    #[inline(always)]
+    pub fn between(a: Square, b: Square) -> BitBoard {
+        BitBoard(BETWEEN[a.index()][b.index()])
+    }
 
+    /// The whole rank, file or diagonal through `a` and `b` including both, empty if they do
+    /// not share one.
+    #[inline(always)]
+    pub fn line(a: Square, b: Square) -> BitBoard {
+        BitBoard(LINE[a.index()][b.index()])
+    }
+
+    /// Enemy sliders which would attack the king of `color` on an empty board.
+    #[inline(always)]
+    fn pin_snipers(&self, king_pos: Square, color: PieceColor) -> BitBoard {
         let opposing_pieces = if color == PieceColor::White {
             &self.black_pieces
         } else {
             &self.white_pieces
         };
+        let queens = opposing_pieces[ChessPiece::Queen as usize];
+        (Self::rook_attacks(king_pos, BitBoard::EMPTY)
+            & (opposing_pieces[ChessPiece::Rook as usize] | queens))
+            | (Self::bishop_attacks(king_pos, BitBoard::EMPTY)
+                & (opposing_pieces[ChessPiece::Bishop as usize] | queens))
+    }
+
+    #[inline(always)]
+    pub fn knight_attacks(square: Square) -> BitBoard {
+        KNIGHT_MOVE_LOOKUP[square.index()]
+    }
+
+    #[inline(always)]
+    pub fn king_attacks(square: Square) -> BitBoard {
+        KING_MOVE_LOOKUP[square.index()]
+    }
+
+    /// Rook attacks through the first piece of `blockers` on each ray, only the squares
+    /// behind that piece are returned. `occupancy` has to contain `blockers`.
+    pub fn xray_rook_attacks(square: Square, occupancy: BitBoard, blockers: BitBoard) -> BitBoard {
+        let attacks = Self::rook_attacks(square, occupancy);
+        let blockers = blockers & attacks;
+        attacks ^ Self::rook_attacks(square, occupancy ^ blockers)
+    }
+
+    /// Bishop attacks through the first piece of `blockers` on each ray, only the
+    /// squares behind that piece are returned. `occupancy` has to contain `blockers`.
+    pub fn xray_bishop_attacks(
+        square: Square,
+        occupancy: BitBoard,
+        blockers: BitBoard,
+    ) -> BitBoard {
+        let attacks = Self::bishop_attacks(square, occupancy);
+        let blockers = blockers & attacks;
+        attacks ^ Self::bishop_attacks(square, occupancy ^ blockers)
+    }
+
+    /// Pieces of `color` attacking `square`. Sliders are blocked by `occupancy` and only
+    /// pieces still part of `occupancy` count, so removing pieces from it reveals the
+    /// attackers behind them.
+    #[inline(always)]
+    pub fn color_attackers_to(
+        &self,
+        square: Square,
+        color: PieceColor,
+        occupancy: BitBoard,
+    ) -> BitBoard {
+        let pieces = if color == PieceColor::White {
+            &self.white_pieces
+        } else {
+            &self.black_pieces
+        };
+        let queens = pieces[ChessPiece::Queen as usize];
+
+        let attackers = (Self::pawn_attacks(square, !color) & pieces[ChessPiece::Pawn as usize])
+            | (Self::knight_attacks(square) & pieces[ChessPiece::Knight as usize])
+            | (Self::bishop_attacks(square, occupancy)
+                & (pieces[ChessPiece::Bishop as usize] | queens))
+            | (Self::rook_attacks(square, occupancy)
+                & (pieces[ChessPiece::Rook as usize] | queens))
+            | (Self::king_attacks(square) & pieces[ChessPiece::King as usize]);
+        attackers & occupancy
+    }
+
+    /// Pieces of both sides attacking `square`, see `color_attackers_to`.
+    pub fn attackers_to(&self, square: Square, occupancy: BitBoard) -> BitBoard {
+        self.color_attackers_to(square, PieceColor::White, occupancy)
+            | self.color_attackers_to(square, PieceColor::Black, occupancy)
+    }
+
+    pub fn is_square_attacked(&self, square: Square, by_color: PieceColor) -> bool {
+        let occupancy = self.all_white_pieces | self.all_black_pieces;
+        !self
+            .color_attackers_to(square, by_color, occupancy)
+            .is_empty()
+    }
 
+    /// Pieces giving check to the king of `color`.
+    #[inline(always)]
+    pub fn checkers(&self, color: PieceColor) -> BitBoard {
         let blockers = self.all_black_pieces | self.all_white_pieces;
+        self.color_attackers_to(self.get_king_pos(color), !color, blockers)
+    }
+
+    /// Pieces of `color` pinned to their king.
+    pub fn pinned_pieces(&self, color: PieceColor) -> BitBoard {
+        let king_pos = self.get_king_pos(color);
+        let occupancy = self.occupancy();
+        let own_pieces = self.color_occupancy(color);
+
+        // A piece is pinned if it is the only one between the king and an enemy slider
+        let mut pinned = BitBoard::EMPTY;
+        for sniper in self.pin_snipers(king_pos, color) {
+            let between = Self::between(king_pos, sniper) & occupancy;
+            if between.bit_count() == 1 {
+                pinned |= between & own_pieces;
+            }
+        }
+        pinned
+    }
 
-        // Check for knights
-        let knight_attackers = &mut attacker_maps[ChessPiece::Knight as usize];
-        *knight_attackers = *knight_attackers
-            | (KNIGHT_MOVE_LOOKUP[king_pos] & opposing_pieces[ChessPiece::Knight as usize]);
-
-        // Check for bishops and queens attack as bishops
-        let bishop_attackers = &mut attacker_maps[ChessPiece::Bishop as usize];
-        let bishop_attacks = Self::bishop_attacks(king_pos, blockers);
-        *bishop_attackers |= bishop_attacks
-            & (opposing_pieces[ChessPiece::Bishop as usize]
-                | opposing_pieces[ChessPiece::Queen as usize]);
-
-        // Check for rooks and queens attack as rooks
-        let rook_attackers = &mut attacker_maps[ChessPiece::Rook as usize];
-        let rook_attacks = Self::rook_attacks(king_pos, blockers);
-        *rook_attackers |= rook_attacks
-            & (opposing_pieces[ChessPiece::Rook as usize]
-                | opposing_pieces[ChessPiece::Queen as usize]);
-
-        // Check for pawns
-        let pawn_attackers = &mut attacker_maps[ChessPiece::Pawn as usize];
-        let king_board = BitBoard(1 << king_pos);
-        let attackers = if color == PieceColor::White {
-            (king_board.s_no_we() | king_board.s_no_ea())
-                & opposing_pieces[ChessPiece::Pawn as usize]
+    /// Checks and pins against the king of `color`.
+    pub fn check_info(&self, color: PieceColor) -> CheckInfo {
+        let opposing_pieces = if color == PieceColor::White {
+            &self.black_pieces
         } else {
-            (king_board.s_so_we() | king_board.s_so_ea())
-                & opposing_pieces[ChessPiece::Pawn as usize]
+            &self.white_pieces
         };
-        *pawn_attackers |= attackers;
+        let checkers = self.checkers(color);
+        let mut per_piece = [BitBoard::EMPTY; 6];
+        for (checkers_of_type, pieces) in per_piece.iter_mut().zip(opposing_pieces) {
+            *checkers_of_type = checkers & *pieces;
+        }
 
-        attacker_maps[6] = attacker_maps[0]
-            | attacker_maps[1]
-            | attacker_maps[2]
-            | attacker_maps[3]
-            | attacker_maps[4]
-            | attacker_maps[5];
-        attacker_maps
+        CheckInfo {
+            checkers,
+            pinned: self.pinned_pieces(color),
+            per_piece,
+        }
     }
 }
 
@@ -206,39 +399,282 @@ impl ChessBoardState {
     ) -> Vec<Move> {
         generate_legal_moves::<GEN_CAPTURES_ONLY>(self, self.side)
     }
+
+    /// Legal moves of the side to move in a fixed capacity list, without allocating.
+    pub fn legal_moves<const GEN_CAPTURES_ONLY: bool>(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        generate_legal_moves_into::<GEN_CAPTURES_ONLY>(self, self.side, &mut moves);
+        moves
+    }
+
+    /// Legal moves of the side to move's piece on `square`. Only the generator of that piece
+    /// type runs, with the same check and pin masks as the full generation.
+    pub fn moves_from(&self, square: Square) -> Vec<Move> {
+        let color = self.side;
+        let mut moves = MoveList::new();
+        let piece = match self.board.piece_on(square) {
+            Some((piece, piece_color)) if piece_color == color => piece,
+            _ => return Vec::new(),
+        };
+
+        if piece == ChessPiece::King {
+            generate_king_moves::<false>(self, &mut moves, color);
+            return moves.to_vec();
+        }
+        // If there are two checking pieces, only king moves are legal
+        if self.checkers.bit_count() >= 2 {
+            return Vec::new();
+        }
+
+        let king_pos = self.board.get_king_pos(color);
+        let (legal_move_mask, en_passant_capture_mask) =
+            generate_legal_move_mask(self, king_pos, self.checkers);
+        let pinned_move_masks = generate_pinned_piece_mask(self, king_pos, color);
+
+        match piece {
+            ChessPiece::Pawn => generate_pawn_moves::<false>(
+                self,
+                color,
+                &mut moves,
+                legal_move_mask,
+                en_passant_capture_mask,
+                &pinned_move_masks,
+            ),
+            ChessPiece::Knight => generate_knight_moves::<false>(
+                self,
+                color,
+                &mut moves,
+                legal_move_mask,
+                &pinned_move_masks,
+            ),
+            ChessPiece::Bishop => generate_bishop_moves::<false>(
+                self,
+                color,
+                &mut moves,
+                legal_move_mask,
+                &pinned_move_masks,
+            ),
+            ChessPiece::Rook => generate_rook_moves::<false>(
+                self,
+                color,
+                &mut moves,
+                legal_move_mask,
+                &pinned_move_masks,
+            ),
+            ChessPiece::Queen => generate_queen_moves::<false>(
+                self,
+                color,
+                &mut moves,
+                legal_move_mask,
+                &pinned_move_masks,
+            ),
+            ChessPiece::King => unreachable!(),
+        }
+        moves.retain(|mv| mv.get_src() == square);
+        moves.to_vec()
+    }
+
+    /// Checks whether the move can be played by the side to move without generating all legal
+    /// moves, the move type has to match the one the move generator would produce.
+    pub fn is_legal(&self, mv: Move) -> bool {
+        let color = self.side;
+        let (src, dst) = (mv.get_src(), mv.get_dst());
+
+        let piece = match self.board.piece_on(src) {
+            Some((piece, piece_color)) if piece_color == color => piece,
+            _ => return false,
+        };
+        if self.board.color_occupancy(color).get_bit(dst.index()) {
+            return false;
+        }
+
+        let blockers = self.board.all_white_pieces | self.board.all_black_pieces;
+        let is_empty = !blockers.get_bit(dst.index());
+        let is_capture = self.board.get_opposing_pieces(color).get_bit(dst.index());
+        let move_type = mv.get_type();
+
+        if piece == ChessPiece::King {
+            let attacked_by_enemy = self.board.squares_attacked_by_side(!color, true);
+            return match move_type {
+                MoveType::Silent | MoveType::Capture => {
+                    let type_matches = if move_type == MoveType::Capture {
+                        is_capture
+                    } else {
+                        is_empty
+                    };
+                    type_matches
+                        && KING_MOVE_LOOKUP[src.index()].get_bit(dst.index())
+                        && !attacked_by_enemy.get_bit(dst.index())
+                }
+                MoveType::CastleKingSide | MoveType::CastleQueenSide => {
+                    let (
+                        right,
+                        attacked_squares,
+                        occupied_squares,
+                        king_square,
+                        rook_square,
+                        target_square,
+                    ) = match (color, move_type) {
+                        (PieceColor::White, MoveType::CastleKingSide) => (
+                            self.castling_rights.white_king_side(),
+                            WHITE_KING_SIDE_CASTLE_SQUARES,
+                            WHITE_KING_SIDE_CASTLE_SQUARES,
+                            Square::E1,
+                            Square::H1,
+                            Square::G1,
+                        ),
+                        (PieceColor::White, _) => (
+                            self.castling_rights.white_queen_side(),
+                            WHITE_QUEEN_SIDE_CASTLE_ATTACKED_SQAURES,
+                            WHITE_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES,
+                            Square::E1,
+                            Square::A1,
+                            Square::C1,
+                        ),
+                        (PieceColor::Black, MoveType::CastleKingSide) => (
+                            self.castling_rights.black_king_side(),
+                            BLACK_KING_SIDE_CASTLE_SQUARES,
+                            BLACK_KING_SIDE_CASTLE_SQUARES,
+                            Square::E8,
+                            Square::H8,
+                            Square::G8,
+                        ),
+                        (PieceColor::Black, _) => (
+                            self.castling_rights.black_queen_side(),
+                            BLACK_QUEEN_SIDE_CASTLE_ATTACKED_SQUARES,
+                            BLACK_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES,
+                            Square::E8,
+                            Square::A8,
+                            Square::C8,
+                        ),
+                    };
+                    // The king may neither castle out of, through nor into check
+                    let king_path = attacked_squares.set_bit(src.index());
+                    right
+                        && src == king_square
+                        && dst == target_square
+                        && self
+                            .board
+                            .get_piece_bitboard(ChessPiece::Rook, color)
+                            .get_bit(rook_square.index())
+                        && (attacked_by_enemy & king_path).is_empty()
+                        && (blockers & occupied_squares).is_empty()
+                }
+                _ => false,
+            };
+        }
+
+        // If there are two checking pieces, only king moves are legal
+        if self.checkers.bit_count() >= 2 {
+            return false;
+        }
+
+        let pattern_matches = if piece == ChessPiece::Pawn {
+            let src_board = BitBoard::EMPTY.set_bit(src.index());
+            let (push_board, attack_board, start_rank, promotion_rank) =
+                if color == PieceColor::White {
+                    (
+                        src_board.s_no(),
+                        src_board.s_no_we() | src_board.s_no_ea(),
+                        BitBoard::RANK_2,
+                        BitBoard::RANK_8,
+                    )
+                } else {
+                    (
+                        src_board.s_so(),
+                        src_board.s_so_we() | src_board.s_so_ea(),
+                        BitBoard::RANK_7,
+                        BitBoard::RANK_1,
+                    )
+                };
+            let is_push = push_board.get_bit(dst.index()) && is_empty;
+            let is_attack = attack_board.get_bit(dst.index()) && is_capture;
+            let reaches_promotion_rank = promotion_rank.get_bit(dst.index());
+
+            match move_type {
+                MoveType::Silent => is_push && !reaches_promotion_rank,
+                MoveType::Capture => is_attack && !reaches_promotion_rank,
+                MoveType::DoublePush => {
+                    let double_push_board = if color == PieceColor::White {
+                        push_board.s_no()
+                    } else {
+                        push_board.s_so()
+                    };
+                    start_rank.get_bit(src.index())
+                        && (push_board & blockers).is_empty()
+                        && double_push_board.get_bit(dst.index())
+                        && is_empty
+                }
+                MoveType::EnPassant => {
+                    self.en_passant_target == Some(dst)
+                        && attack_board.get_bit(dst.index())
+                        && !does_enpassant_reveal_friendly_check(self, color, dst, src)
+                }
+                MoveType::CastleKingSide | MoveType::CastleQueenSide => false,
+                _ if move_type.is_capture() => is_attack && reaches_promotion_rank,
+                _ => is_push && reaches_promotion_rank,
+            }
+        } else {
+            let attacks = match piece {
+                ChessPiece::Knight => KNIGHT_MOVE_LOOKUP[src.index()],
+                ChessPiece::Bishop => ChessBoard::bishop_attacks(src, blockers),
+                ChessPiece::Rook => ChessBoard::rook_attacks(src, blockers),
+                _ => ChessBoard::queen_attack(src, blockers),
+            };
+            let type_matches = match move_type {
+                MoveType::Silent => is_empty,
+                MoveType::Capture => is_capture,
+                _ => false,
+            };
+            type_matches && attacks.get_bit(dst.index())
+        };
+        if !pattern_matches {
+            return false;
+        }
+
+        let king_pos = self.board.get_king_pos(color);
+        let (mut legal_move_mask, en_passant_capture_mask) =
+            generate_legal_move_mask(self, king_pos, self.checkers);
+        if move_type == MoveType::EnPassant {
+            legal_move_mask |= en_passant_capture_mask;
+        }
+        let pinned_move_masks = generate_pinned_piece_mask(self, king_pos, color);
+
+        (legal_move_mask & pinned_move_masks[src.index()]).get_bit(dst.index())
+    }
 }
 
 #[inline(always)]
 fn does_enpassant_reveal_friendly_check(
     board_state: &ChessBoardState,
     color: PieceColor,
-    en_passant_target: usize,
-    en_passant_attacker: usize,
+    en_passant_target: Square,
+    en_passant_attacker: Square,
 ) -> bool {
     let en_passanted_victim = if color == PieceColor::White {
-        en_passant_target + 8
+        en_passant_target.index() + 8
     } else {
-        en_passant_target - 8
+        en_passant_target.index() - 8
     };
 
     let pawn_board = BitBoard::EMPTY
         .set_bit(en_passanted_victim)
-        .set_bit(en_passant_attacker);
+        .set_bit(en_passant_attacker.index());
     let mut hash = ZHash::default();
     let mut board_without_pawns = board_state.board.remove_any_piece_by_mask(pawn_board);
     board_without_pawns.place_piece_of_color(ChessPiece::Pawn, color, en_passant_target, &mut hash);
 
-    board_without_pawns.king_attackers(color)[6] != BitBoard::EMPTY
+    !board_without_pawns.checkers(color).is_empty()
 }
 
 #[inline(always)]
 fn generate_pawn_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     legal_move_mask: BitBoard,
     en_passant_capture_mask: BitBoard,
-    pinned_move_masks: &[BitBoard; Square::NUM as usize],
+    pinned_move_masks: &[BitBoard; Square::NUM],
 ) {
     let side_pawn_board = board_state
         .board
@@ -255,41 +691,45 @@ fn generate_pawn_moves<const GEN_CAPTURES_ONLY: bool>(
         56..64
     };
 
-    if !GEN_CAPTURES_ONLY {
-        for pushable_pawn in board_state.board.pawns_able_to_push(color) {
-            let target = pushable_pawn as i32 + 8 * push_dir;
-            if target < 0
-                || target > 63
-                || !legal_move_mask.get_bit(target as usize)
-                || !pinned_move_masks[pushable_pawn].get_bit(target as usize)
-            {
-                continue;
-            }
+    // Promotions change the material balance, so they are generated in captures-only mode too
+    for pushable_pawn in board_state.board.pawns_able_to_push(color) {
+        let target = pushable_pawn.index() as i32 + 8 * push_dir;
+        if !(0..=63).contains(&target)
+            || !legal_move_mask.get_bit(target as usize)
+            || !pinned_move_masks[pushable_pawn.index()].get_bit(target as usize)
+        {
+            continue;
+        }
 
-            if promotion_range.contains(&target) {
-                // Promote Pawn
-                for p in PROMOTION_TARGETS {
-                    moves.push(Move::new(pushable_pawn as u16, target as u16, p));
-                }
-            } else {
+        if promotion_range.contains(&target) {
+            // Promote Pawn
+            for p in PROMOTION_TARGETS {
                 moves.push(Move::new(
-                    pushable_pawn as u16,
-                    target as u16,
-                    MoveType::Silent,
+                    pushable_pawn,
+                    Square::from_index(target as usize),
+                    p,
                 ));
             }
+        } else if !GEN_CAPTURES_ONLY {
+            moves.push(Move::new(
+                pushable_pawn,
+                Square::from_index(target as usize),
+                MoveType::Silent,
+            ));
         }
+    }
 
+    if !GEN_CAPTURES_ONLY {
         for double_pushable_pawn in board_state.board.pawns_able_to_double_push(color) {
-            let target = double_pushable_pawn as i32 + 16 * push_dir;
+            let target = double_pushable_pawn.index() as i32 + 16 * push_dir;
             if target >= 0
                 && target <= 63
                 && legal_move_mask.get_bit(target as usize)
-                && pinned_move_masks[double_pushable_pawn].get_bit(target as usize)
+                && pinned_move_masks[double_pushable_pawn.index()].get_bit(target as usize)
             {
                 moves.push(Move::new(
-                    double_pushable_pawn as u16,
-                    target as u16,
+                    double_pushable_pawn,
+                    Square::from_index(target as usize),
                     MoveType::DoublePush,
                 ));
             }
@@ -301,23 +741,27 @@ fn generate_pawn_moves<const GEN_CAPTURES_ONLY: bool>(
         .board
         .pawns_able_to_attack_east(side_pawn_board, color)
     {
-        let target = east_attacking_pawn as i32 + east_attack_dir;
+        let target = east_attacking_pawn.index() as i32 + east_attack_dir;
         if target < 0
             || target > 63
             || !legal_move_mask.get_bit(target as usize)
-            || !pinned_move_masks[east_attacking_pawn].get_bit(target as usize)
+            || !pinned_move_masks[east_attacking_pawn.index()].get_bit(target as usize)
         {
             continue;
         }
 
         if promotion_range.contains(&target) {
             for p in PROMOTION_CAPTURE_TARGETS {
-                moves.push(Move::new(east_attacking_pawn as u16, target as u16, p));
+                moves.push(Move::new(
+                    east_attacking_pawn,
+                    Square::from_index(target as usize),
+                    p,
+                ));
             }
         } else {
             moves.push(Move::new(
-                east_attacking_pawn as u16,
-                target as u16,
+                east_attacking_pawn,
+                Square::from_index(target as usize),
                 MoveType::Capture,
             ));
         }
@@ -328,47 +772,51 @@ fn generate_pawn_moves<const GEN_CAPTURES_ONLY: bool>(
         .board
         .pawns_able_to_attack_west(side_pawn_board, color)
     {
-        let target = west_atacking_pawn as i32 + west_attack_dir;
+        let target = west_atacking_pawn.index() as i32 + west_attack_dir;
         if target < 0
             || target > 63
             || !legal_move_mask.get_bit(target as usize)
-            || !pinned_move_masks[west_atacking_pawn].get_bit(target as usize)
+            || !pinned_move_masks[west_atacking_pawn.index()].get_bit(target as usize)
         {
             continue;
         }
 
         if promotion_range.contains(&target) {
             for p in PROMOTION_CAPTURE_TARGETS {
-                moves.push(Move::new(west_atacking_pawn as u16, target as u16, p));
+                moves.push(Move::new(
+                    west_atacking_pawn,
+                    Square::from_index(target as usize),
+                    p,
+                ));
             }
         } else {
             moves.push(Move::new(
-                west_atacking_pawn as u16,
-                target as u16,
+                west_atacking_pawn,
+                Square::from_index(target as usize),
                 MoveType::Capture,
             ));
         }
     }
 
     if let Some(en_passant_target) = board_state.en_passant_target {
-        if legal_move_mask.get_bit(en_passant_target as usize)
-            || en_passant_capture_mask.get_bit(en_passant_target as usize)
+        if legal_move_mask.get_bit(en_passant_target.index())
+            || en_passant_capture_mask.get_bit(en_passant_target.index())
         {
             for en_passant_pawns in board_state
                 .board
                 .pawns_able_to_enpassant(color, en_passant_target)
             {
-                if pinned_move_masks[en_passant_pawns].get_bit(en_passant_target as usize)
+                if pinned_move_masks[en_passant_pawns.index()].get_bit(en_passant_target.index())
                     && !does_enpassant_reveal_friendly_check(
                         board_state,
                         color,
-                        en_passant_target as usize,
+                        en_passant_target,
                         en_passant_pawns,
                     )
                 {
                     moves.push(Move::new(
-                        en_passant_pawns as u16,
-                        en_passant_target as u16,
+                        en_passant_pawns,
+                        en_passant_target,
                         MoveType::EnPassant,
                     ));
                 }
@@ -381,9 +829,9 @@ fn generate_pawn_moves<const GEN_CAPTURES_ONLY: bool>(
 fn generate_knight_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     legal_move_mask: BitBoard,
-    pinned_move_masks: &[BitBoard; Square::NUM as usize],
+    pinned_move_masks: &[BitBoard; Square::NUM],
 ) {
     let side_knight_board = board_state
         .board
@@ -397,25 +845,18 @@ fn generate_knight_moves<const GEN_CAPTURES_ONLY: bool>(
     let opposite_board = board_state.board.get_opposing_pieces(color);
 
     for knight_pos in side_knight_board {
-        let attack_map =
-            KNIGHT_MOVE_LOOKUP[knight_pos] & legal_move_mask & pinned_move_masks[knight_pos];
+        let attack_map = KNIGHT_MOVE_LOOKUP[knight_pos.index()]
+            & legal_move_mask
+            & pinned_move_masks[knight_pos.index()];
 
         if !GEN_CAPTURES_ONLY {
             for silent_jump_target in attack_map & empty_squares {
-                moves.push(Move::new(
-                    knight_pos as u16,
-                    silent_jump_target as u16,
-                    MoveType::Silent,
-                ));
+                moves.push(Move::new(knight_pos, silent_jump_target, MoveType::Silent));
             }
         }
 
         for capture_jump in attack_map & opposite_board {
-            moves.push(Move::new(
-                knight_pos as u16,
-                capture_jump as u16,
-                MoveType::Capture,
-            ));
+            moves.push(Move::new(knight_pos, capture_jump, MoveType::Capture));
         }
     }
 }
@@ -423,7 +864,7 @@ fn generate_knight_moves<const GEN_CAPTURES_ONLY: bool>(
 #[inline(always)]
 fn generate_king_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     color: PieceColor,
 ) {
     let side_king_board = board_state
@@ -438,64 +879,85 @@ fn generate_king_moves<const GEN_CAPTURES_ONLY: bool>(
 
     let king_pos = side_king_board.into_iter().nth(0).unwrap();
 
-    let attack_map = KING_MOVE_LOOKUP[king_pos] & !attacked_by_enemy;
+    let attack_map = KING_MOVE_LOOKUP[king_pos.index()] & !attacked_by_enemy;
 
     if !GEN_CAPTURES_ONLY {
         for silent_move_target in attack_map & empty_squares {
-            moves.push(Move::new(
-                king_pos as u16,
-                silent_move_target as u16,
-                MoveType::Silent,
-            ));
+            moves.push(Move::new(king_pos, silent_move_target, MoveType::Silent));
         }
     }
     for capture_move in attack_map & opposite_board {
-        moves.push(Move::new(
-            king_pos as u16,
-            capture_move as u16,
-            MoveType::Capture,
-        ));
+        moves.push(Move::new(king_pos, capture_move, MoveType::Capture));
     }
 
     let in_check = !(attacked_by_enemy & side_king_board).is_empty();
 
     if !GEN_CAPTURES_ONLY && !in_check {
-        // Check for Castling Rights
+        // Check for Castling Rights. The king and rook are checked as well, a position set up
+        // by hand may keep a right after one of them left its square.
         let combinations = [
             (
+                PieceColor::White,
                 board_state.castling_rights.white_king_side(),
                 WHITE_KING_SIDE_CASTLE_SQUARES,
                 WHITE_KING_SIDE_CASTLE_SQUARES,
                 MoveType::CastleKingSide,
+                Square::E1,
+                Square::H1,
                 Square::G1,
             ),
             (
+                PieceColor::White,
                 board_state.castling_rights.white_queen_side(),
                 WHITE_QUEEN_SIDE_CASTLE_ATTACKED_SQAURES,
                 WHITE_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES,
                 MoveType::CastleQueenSide,
+                Square::E1,
+                Square::A1,
                 Square::C1,
             ),
             (
+                PieceColor::Black,
                 board_state.castling_rights.black_king_side(),
                 BLACK_KING_SIDE_CASTLE_SQUARES,
                 BLACK_KING_SIDE_CASTLE_SQUARES,
                 MoveType::CastleKingSide,
+                Square::E8,
+                Square::H8,
                 Square::G8,
             ),
             (
+                PieceColor::Black,
                 board_state.castling_rights.black_queen_side(),
                 BLACK_QUEEN_SIDE_CASTLE_ATTACKED_SQUARES,
                 BLACK_QUEEN_SIDE_CASTLE_OCCUPIED_SQUARES,
                 MoveType::CastleQueenSide,
+                Square::E8,
+                Square::A8,
                 Square::C8,
             ),
         ];
-        for (right, attacked_squares, occupied_squares, mv_type, target_square) in &combinations {
+        let side_rooks = board_state
+            .board
+            .get_piece_bitboard(ChessPiece::Rook, color);
+        for (
+            castle_color,
+            right,
+            attacked_squares,
+            occupied_squares,
+            mv_type,
+            king_square,
+            rook_square,
+            target_square,
+        ) in &combinations
+        {
+            let pieces_in_place = *castle_color == color
+                && king_pos == *king_square
+                && side_rooks.get_bit(rook_square.index());
             let squares_not_attacked = (attacked_by_enemy & *attacked_squares).is_empty();
             let squares_not_occupied = (blockers & *occupied_squares).is_empty();
-            if *right && squares_not_occupied && squares_not_attacked {
-                moves.push(Move::new(king_pos as u16, *target_square, *mv_type));
+            if *right && pieces_in_place && squares_not_occupied && squares_not_attacked {
+                moves.push(Move::new(king_pos, *target_square, *mv_type));
             }
         }
     }
@@ -505,9 +967,9 @@ fn generate_king_moves<const GEN_CAPTURES_ONLY: bool>(
 fn generate_rook_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     legal_move_mask: BitBoard,
-    pinned_move_masks: &[BitBoard; Square::NUM as usize],
+    pinned_move_masks: &[BitBoard; Square::NUM],
 ) {
     let side_rook_board = board_state
         .board
@@ -524,18 +986,19 @@ fn generate_rook_moves<const GEN_CAPTURES_ONLY: bool>(
 
     for rook_pos in side_rook_board {
         let move_bitboard = ChessBoard::rook_attacks(rook_pos, blockers);
-        let legal_move_bitboard = move_bitboard & legal_move_mask & pinned_move_masks[rook_pos];
+        let legal_move_bitboard =
+            move_bitboard & legal_move_mask & pinned_move_masks[rook_pos.index()];
 
         if GEN_CAPTURES_ONLY {
             for mv_dst in legal_move_bitboard & opposing_pieces {
-                moves.push(Move::new(rook_pos as u16, mv_dst as u16, MoveType::Capture));
+                moves.push(Move::new(rook_pos, mv_dst, MoveType::Capture));
             }
         } else {
             for mv_dst in legal_move_bitboard {
-                if opposing_pieces.get_bit(mv_dst) {
-                    moves.push(Move::new(rook_pos as u16, mv_dst as u16, MoveType::Capture));
-                } else if empty_squares.get_bit(mv_dst) {
-                    moves.push(Move::new(rook_pos as u16, mv_dst as u16, MoveType::Silent));
+                if opposing_pieces.get_bit(mv_dst.index()) {
+                    moves.push(Move::new(rook_pos, mv_dst, MoveType::Capture));
+                } else if empty_squares.get_bit(mv_dst.index()) {
+                    moves.push(Move::new(rook_pos, mv_dst, MoveType::Silent));
                 }
             }
         }
@@ -546,9 +1009,9 @@ fn generate_rook_moves<const GEN_CAPTURES_ONLY: bool>(
 fn generate_bishop_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     legal_move_mask: BitBoard,
-    pinned_move_masks: &[BitBoard; Square::NUM as usize],
+    pinned_move_masks: &[BitBoard; Square::NUM],
 ) {
     let side_bishop_board = board_state
         .board
@@ -565,30 +1028,19 @@ fn generate_bishop_moves<const GEN_CAPTURES_ONLY: bool>(
 
     for bishop_pos in side_bishop_board {
         let move_bitboard = ChessBoard::bishop_attacks(bishop_pos, blockers);
-        let legal_move_bitboard = move_bitboard & legal_move_mask & pinned_move_masks[bishop_pos];
+        let legal_move_bitboard =
+            move_bitboard & legal_move_mask & pinned_move_masks[bishop_pos.index()];
 
         if GEN_CAPTURES_ONLY {
             for mv_dst in legal_move_bitboard & opposing_pieces {
-                moves.push(Move::new(
-                    bishop_pos as u16,
-                    mv_dst as u16,
-                    MoveType::Capture,
-                ));
+                moves.push(Move::new(bishop_pos, mv_dst, MoveType::Capture));
             }
         } else {
             for mv_dst in legal_move_bitboard {
-                if opposing_pieces.get_bit(mv_dst) {
-                    moves.push(Move::new(
-                        bishop_pos as u16,
-                        mv_dst as u16,
-                        MoveType::Capture,
-                    ));
-                } else if empty_squares.get_bit(mv_dst) {
-                    moves.push(Move::new(
-                        bishop_pos as u16,
-                        mv_dst as u16,
-                        MoveType::Silent,
-                    ));
+                if opposing_pieces.get_bit(mv_dst.index()) {
+                    moves.push(Move::new(bishop_pos, mv_dst, MoveType::Capture));
+                } else if empty_squares.get_bit(mv_dst.index()) {
+                    moves.push(Move::new(bishop_pos, mv_dst, MoveType::Silent));
                 }
             }
         }
@@ -599,9 +1051,9 @@ fn generate_bishop_moves<const GEN_CAPTURES_ONLY: bool>(
 fn generate_queen_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
-    moves: &mut Vec<Move>,
+    moves: &mut MoveList,
     legal_move_mask: BitBoard,
-    pinned_move_masks: &[BitBoard; Square::NUM as usize],
+    pinned_move_masks: &[BitBoard; Square::NUM],
 ) {
     let side_queen_board = board_state
         .board
@@ -618,30 +1070,18 @@ fn generate_queen_moves<const GEN_CAPTURES_ONLY: bool>(
     for queen_pos in side_queen_board {
         let queen_move_bitboard = ChessBoard::queen_attack(queen_pos, blockers);
         let legal_move_bitboard =
-            queen_move_bitboard & legal_move_mask & pinned_move_masks[queen_pos];
+            queen_move_bitboard & legal_move_mask & pinned_move_masks[queen_pos.index()];
 
         if GEN_CAPTURES_ONLY {
             for queen_dst in legal_move_bitboard & opposing_pieces {
-                moves.push(Move::new(
-                    queen_pos as u16,
-                    queen_dst as u16,
-                    MoveType::Capture,
-                ));
+                moves.push(Move::new(queen_pos, queen_dst, MoveType::Capture));
             }
         } else {
             for queen_dst in legal_move_bitboard {
-                if opposing_pieces.get_bit(queen_dst) {
-                    moves.push(Move::new(
-                        queen_pos as u16,
-                        queen_dst as u16,
-                        MoveType::Capture,
-                    ));
-                } else if empty_squares.get_bit(queen_dst) {
-                    moves.push(Move::new(
-                        queen_pos as u16,
-                        queen_dst as u16,
-                        MoveType::Silent,
-                    ));
+                if opposing_pieces.get_bit(queen_dst.index()) {
+                    moves.push(Move::new(queen_pos, queen_dst, MoveType::Capture));
+                } else if empty_squares.get_bit(queen_dst.index()) {
+                    moves.push(Move::new(queen_pos, queen_dst, MoveType::Silent));
                 }
             }
         }
@@ -651,50 +1091,23 @@ fn generate_queen_moves<const GEN_CAPTURES_ONLY: bool>(
 #[inline(always)]
 fn generate_legal_move_mask(
     board_state: &ChessBoardState,
-    king_pos: usize,
-    king_attackers: &[BitBoard; 7],
+    king_pos: Square,
+    checkers: BitBoard,
 ) -> (BitBoard, BitBoard) {
-    let checker_count = king_attackers[6].bit_count();
+    let checker_count = checkers.bit_count();
     if checker_count == 0 {
         return (BitBoard::FULL, BitBoard::FULL);
     }
     assert!(checker_count == 1);
 
-    let checking_piece_type: ChessPiece = king_attackers
-        .iter()
-        .position(|bb| !bb.is_empty())
-        .unwrap()
-        .into();
-    let checking_piece_pos = king_attackers[checking_piece_type as usize]
-        .into_iter()
-        .nth(0)
-        .unwrap();
-
-    let capture_mask = king_attackers[6];
-    let blockers = board_state.board.all_black_pieces | board_state.board.all_white_pieces;
-
-    let push_mask = if checking_piece_type.is_slider() {
-        match checking_piece_type {
-            ChessPiece::Rook => {
-                ChessBoard::rook_attacks(king_pos, blockers)
-                    & ChessBoard::rook_attacks(checking_piece_pos, blockers)
-            }
-            ChessPiece::Bishop => {
-                ChessBoard::bishop_attacks(king_pos, blockers)
-                    & ChessBoard::bishop_attacks(checking_piece_pos, blockers)
-            }
-            ChessPiece::Queen => {
-                ChessBoard::queen_attack(king_pos, blockers)
-                    & ChessBoard::queen_attack(checking_piece_pos, blockers)
-            }
-            _ => panic!("Unknown slider"),
-        }
-    } else {
-        BitBoard::EMPTY
-    };
+    // Only a slider's check can be blocked, nothing lies between the king and a checking
+    // knight or pawn
+    let checking_piece_pos = checkers.into_iter().next().unwrap();
+    let capture_mask = checkers;
+    let push_mask = ChessBoard::between(king_pos, checking_piece_pos);
 
     let en_passant_capture_mask = if let Some(en_passant_target) = board_state.en_passant_target {
-        BitBoard(1 << en_passant_target as usize)
+        BitBoard(1 << en_passant_target.index())
     } else {
         BitBoard::EMPTY
     };
@@ -702,138 +1115,164 @@ fn generate_legal_move_mask(
     (capture_mask | push_mask, en_passant_capture_mask)
 }
 
-// Returns Bitboard of pinned pieces and the pinners
+/// Squares every piece of `color` may move to without exposing its king: the line through
+/// the king and the pinner for a pinned piece, everything for the others.
 #[inline(always)]
 pub fn generate_pinned_piece_mask(
     board_state: &ChessBoardState,
-    king_pos: usize,
+    king_pos: Square,
     color: PieceColor,
-) -> [BitBoard; Square::NUM as usize] {
-    let mut pinned_move_masks = [BitBoard::FULL; Square::NUM as usize];
-
-    let (opposing_pieces, side_pieces) = if color == PieceColor::White {
-        (
-            &board_state.board.black_pieces,
-            board_state.board.all_white_pieces,
-        )
-    } else {
-        (
-            &board_state.board.white_pieces,
-            board_state.board.all_black_pieces,
-        )
-    };
-
-    let blockers = board_state.board.all_white_pieces | board_state.board.all_black_pieces;
-
-    for opp_bishop in opposing_pieces[ChessPiece::Bishop as usize] {
-        let bishop_attacks = ChessBoard::bishop_attacks(opp_bishop, blockers);
-        let king_bishop_attack = ChessBoard::bishop_attacks(king_pos, blockers);
-
-        let pinned_by_bishop = king_bishop_attack & bishop_attacks & side_pieces;
-        let blockers_without_pin = blockers & !pinned_by_bishop;
-        let bishop_attacks = ChessBoard::bishop_attacks(opp_bishop, blockers_without_pin);
-        if !bishop_attacks.get_bit(king_pos) {
-            continue;
-        }
-
-        for pinned in pinned_by_bishop {
-            pinned_move_masks[pinned] &= (bishop_attacks
-                & ChessBoard::bishop_attacks(king_pos, blockers_without_pin))
-            .set_bit(opp_bishop);
-        }
-    }
-
-    for opp_rook in opposing_pieces[ChessPiece::Rook as usize] {
-        let rook_attack = ChessBoard::rook_attacks(opp_rook, blockers);
-        let king_rook_atack = ChessBoard::rook_attacks(king_pos, blockers);
-
-        let pinned_by_rook = rook_attack & king_rook_atack & side_pieces;
-        let blockers_without_pin = blockers & !pinned_by_rook;
-        let rook_attack = ChessBoard::rook_attacks(opp_rook, blockers_without_pin);
-        if !rook_attack.get_bit(king_pos) {
+) -> [BitBoard; Square::NUM] {
+    let mut pinned_move_masks = [BitBoard::FULL; Square::NUM];
+
+    let board = &board_state.board;
+    let occupancy = board.occupancy();
+    let own_pieces = board.color_occupancy(color);
+    for sniper in board.pin_snipers(king_pos, color) {
+        let between = ChessBoard::between(king_pos, sniper) & occupancy;
+        if between.bit_count() != 1 {
             continue;
         }
-        for pinned in pinned_by_rook {
-            pinned_move_masks[pinned] &= (rook_attack
-                & ChessBoard::rook_attacks(king_pos, blockers_without_pin))
-            .set_bit(opp_rook);
+        for pinned in between & own_pieces {
+            // The piece's own moves stop at its king and the pinner
+            pinned_move_masks[pinned.index()] = ChessBoard::line(king_pos, sniper);
         }
     }
 
-    for opp_queen in opposing_pieces[ChessPiece::Queen as usize] {
-        let queen_attack = ChessBoard::queen_attack(opp_queen, blockers);
-        let king_queen_attack = ChessBoard::queen_attack(king_pos, blockers);
-
-        let pinned_by_queen = queen_attack & king_queen_attack & side_pieces;
-
-        for pinned in pinned_by_queen {
-            let blockers_without_pin = blockers & !BitBoard(1 << pinned);
-
-            let queen_rook_without = ChessBoard::rook_attacks(opp_queen, blockers_without_pin);
-            let queen_bishop_without = ChessBoard::bishop_attacks(opp_queen, blockers_without_pin);
-            let queen_attack = queen_rook_without | queen_bishop_without;
-
-            if !(queen_attack).get_bit(king_pos) {
-                continue;
-            }
-            let king_as_rook = ChessBoard::rook_attacks(king_pos, blockers_without_pin);
-            let king_as_bishop = ChessBoard::bishop_attacks(king_pos, blockers_without_pin);
-
-            /* When intersecting the rays form the king
-            Only factor in a rook,bishop ray if then queen is in it*/
-            let mut king_attack_without = BitBoard::EMPTY;
-            let mut queen_attack_without = BitBoard::EMPTY;
+    pinned_move_masks
+}
 
-            if king_as_rook.get_bit(opp_queen) {
-                king_attack_without = king_as_rook;
-                queen_attack_without = queen_rook_without;
-            }
+/// Moves out of a single check besides the king moves: captures of the checking piece and
+/// interpositions between it and the king. A pinned piece can never do either, so instead of
+/// computing the pin rays the pinned pieces are left out, and only the piece types which
+/// reach one of the evasion squares are generated.
+#[inline(always)]
+fn generate_evasions<const GEN_CAPTURES_ONLY: bool>(
+    board_state: &ChessBoardState,
+    color: PieceColor,
+    moves: &mut MoveList,
+    king_pos: Square,
+    checkers: BitBoard,
+) {
+    let (evasion_mask, en_passant_capture_mask) =
+        generate_legal_move_mask(board_state, king_pos, checkers);
 
-            if king_as_bishop.get_bit(opp_queen) {
-                king_attack_without = king_attack_without | king_as_bishop;
-                queen_attack_without = queen_attack_without | queen_bishop_without;
-            }
+    let mut pinned_move_masks = [BitBoard::FULL; Square::NUM];
+    for pinned in board_state.board.pinned_pieces(color) {
+        pinned_move_masks[pinned.index()] = BitBoard::EMPTY;
+    }
 
-            pinned_move_masks[pinned] &=
-                (queen_attack_without & king_attack_without).set_bit(opp_queen);
-        }
+    // Squares a piece has to come from to land on an evasion square
+    let blockers = board_state.board.occupancy();
+    let mut knight_sources = BitBoard::EMPTY;
+    let mut bishop_sources = BitBoard::EMPTY;
+    let mut rook_sources = BitBoard::EMPTY;
+    for target in evasion_mask {
+        knight_sources |= KNIGHT_MOVE_LOOKUP[target.index()];
+        bishop_sources |= ChessBoard::bishop_attacks(target, blockers);
+        rook_sources |= ChessBoard::rook_attacks(target, blockers);
     }
+    let reaches = |piece: ChessPiece, sources: BitBoard| {
+        !(board_state.board.get_piece_bitboard(piece, color) & sources).is_empty()
+    };
 
-    pinned_move_masks
+    if reaches(ChessPiece::Knight, knight_sources) {
+        generate_knight_moves::<GEN_CAPTURES_ONLY>(
+            board_state,
+            color,
+            moves,
+            evasion_mask,
+            &pinned_move_masks,
+        );
+    }
+    generate_pawn_moves::<GEN_CAPTURES_ONLY>(
+        board_state,
+        color,
+        moves,
+        evasion_mask,
+        en_passant_capture_mask,
+        &pinned_move_masks,
+    );
+    if reaches(ChessPiece::Rook, rook_sources) {
+        generate_rook_moves::<GEN_CAPTURES_ONLY>(
+            board_state,
+            color,
+            moves,
+            evasion_mask,
+            &pinned_move_masks,
+        );
+    }
+    if reaches(ChessPiece::Bishop, bishop_sources) {
+        generate_bishop_moves::<GEN_CAPTURES_ONLY>(
+            board_state,
+            color,
+            moves,
+            evasion_mask,
+            &pinned_move_masks,
+        );
+    }
+    if reaches(ChessPiece::Queen, bishop_sources | rook_sources) {
+        generate_queen_moves::<GEN_CAPTURES_ONLY>(
+            board_state,
+            color,
+            moves,
+            evasion_mask,
+            &pinned_move_masks,
+        );
+    }
 }
 
 pub fn generate_legal_moves<const GEN_CAPTURES_ONLY: bool>(
     board_state: &ChessBoardState,
     color: PieceColor,
 ) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::with_capacity(128);
-    let king_attackers = board_state.board.king_attackers(color);
-    let checker_count = king_attackers[6].bit_count();
+    let mut moves = MoveList::new();
+    generate_legal_moves_into::<GEN_CAPTURES_ONLY>(board_state, color, &mut moves);
+    moves.to_vec()
+}
+
+/// Appends the legal moves of `color` to `moves`, the generators write into the list
+/// directly.
+pub fn generate_legal_moves_into<const GEN_CAPTURES_ONLY: bool>(
+    board_state: &ChessBoardState,
+    color: PieceColor,
+    moves: &mut MoveList,
+) {
+    let checkers = if color == board_state.side {
+        board_state.checkers
+    } else {
+        board_state.board.checkers(color)
+    };
+    let checker_count = checkers.bit_count();
     let king_pos = board_state.board.get_king_pos(color);
 
-    generate_king_moves::<GEN_CAPTURES_ONLY>(board_state, &mut moves, color);
+    generate_king_moves::<GEN_CAPTURES_ONLY>(board_state, moves, color);
 
     // If there are two checking pieces, only king moves are legal
     if checker_count >= 2 {
-        return moves;
+        return;
+    }
+    if checker_count == 1 {
+        generate_evasions::<GEN_CAPTURES_ONLY>(board_state, color, moves, king_pos, checkers);
+        return;
     }
 
     let (legal_move_mask, en_passant_capture_mask) =
-        generate_legal_move_mask(board_state, king_pos, &king_attackers);
+        generate_legal_move_mask(board_state, king_pos, checkers);
 
     let pinned_move_masks = generate_pinned_piece_mask(board_state, king_pos, color);
 
     generate_knight_moves::<GEN_CAPTURES_ONLY>(
         board_state,
         color,
-        &mut moves,
+        moves,
         legal_move_mask,
         &pinned_move_masks,
     );
     generate_pawn_moves::<GEN_CAPTURES_ONLY>(
         board_state,
         color,
-        &mut moves,
+        moves,
         legal_move_mask,
         en_passant_capture_mask,
         &pinned_move_masks,
@@ -841,34 +1280,34 @@ pub fn generate_legal_moves<const GEN_CAPTURES_ONLY: bool>(
     generate_rook_moves::<GEN_CAPTURES_ONLY>(
         board_state,
         color,
-        &mut moves,
+        moves,
         legal_move_mask,
         &pinned_move_masks,
     );
     generate_bishop_moves::<GEN_CAPTURES_ONLY>(
         board_state,
         color,
-        &mut moves,
+        moves,
         legal_move_mask,
         &pinned_move_masks,
     );
     generate_queen_moves::<GEN_CAPTURES_ONLY>(
         board_state,
         color,
-        &mut moves,
+        moves,
         legal_move_mask,
         &pinned_move_masks,
     );
-    moves
 }
 
 #[cfg(test)]
 mod move_gen_tests {
     use crate::chess::{
         bitboard::BitBoard,
-        board::{ChessBoardState, PieceColor},
+        board::{ChessBoard, ChessBoardState, ChessPiece, FenError, PieceColor},
         chess_move::{Move, MoveType},
         move_generator::{generate_knight_moves, generate_legal_moves, generate_pawn_moves},
+        move_list::MoveList,
         square::Square,
     };
 
@@ -880,11 +1319,140 @@ mod move_gen_tests {
         }
     }
 
+    fn squares_to_bitboard(squares: &[Square]) -> BitBoard {
+        squares
+            .iter()
+            .fold(BitBoard::EMPTY, |bb, square| bb.set_bit(square.index()))
+    }
+
+    #[test]
+    fn check_info() {
+        // Nc3 is pinned by Ba5, Re2 by the rook on e8, Nd3 gives check and the bishop on h4
+        // is blocked by two pawns
+        let board_state =
+            ChessBoardState::from_fen("4r1k1/8/8/b7/7b/2Nn2P1/4RP2/4K3 w - - 0 1").unwrap();
+        let check_info = board_state.board.check_info(PieceColor::White);
+        assert_eq!(check_info.checker_count(), 1);
+        assert!(check_info.in_check());
+        assert_eq!(check_info.checkers(), board_state.checkers);
+        assert_eq!(
+            check_info.checkers_of(ChessPiece::Knight),
+            BitBoard::from_square(Square::D3)
+        );
+        assert!(check_info.checkers_of(ChessPiece::Bishop).is_empty());
+        assert_eq!(
+            check_info.pinned(),
+            BitBoard::from_squares(&[Square::C3, Square::E2])
+        );
+
+        let start = ChessBoardState::starting_state();
+        assert_eq!(
+            start.board.check_info(PieceColor::Black),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn between_and_line() {
+        assert_eq!(
+            ChessBoard::between(Square::A1, Square::D4),
+            BitBoard::from_squares(&[Square::B2, Square::C3])
+        );
+        assert_eq!(
+            ChessBoard::between(Square::E8, Square::E5),
+            BitBoard::from_squares(&[Square::E7, Square::E6])
+        );
+        assert!(ChessBoard::between(Square::C3, Square::D4).is_empty());
+        assert!(ChessBoard::between(Square::A1, Square::B3).is_empty());
+
+        let long_diagonal = ChessBoard::line(Square::C3, Square::E5);
+        assert_eq!(long_diagonal.bit_count(), 8);
+        assert!(
+            long_diagonal.get_bit(Square::A1.index()) && long_diagonal.get_bit(Square::H8.index())
+        );
+        assert_eq!(
+            ChessBoard::line(Square::B2, Square::G2),
+            ChessBoard::line(Square::H2, Square::A2)
+        );
+        assert!(ChessBoard::line(Square::A1, Square::B3).is_empty());
+    }
+
+    #[test]
+    fn sliding_attacks() {
+        let blockers = squares_to_bitboard(&[Square::D6, Square::F4, Square::B4, Square::G7]);
+        let expected_rook = squares_to_bitboard(&[
+            Square::D5,
+            Square::D6,
+            Square::D3,
+            Square::D2,
+            Square::D1,
+            Square::E4,
+            Square::F4,
+            Square::C4,
+            Square::B4,
+        ]);
+        assert_eq!(
+            ChessBoard::rook_attacks(Square::D4, blockers),
+            expected_rook
+        );
+
+        let expected_bishop = squares_to_bitboard(&[
+            Square::C5,
+            Square::B6,
+            Square::A7,
+            Square::E5,
+            Square::F6,
+            Square::G7,
+            Square::C3,
+            Square::B2,
+            Square::A1,
+            Square::E3,
+            Square::F2,
+            Square::G1,
+        ]);
+        assert_eq!(
+            ChessBoard::bishop_attacks(Square::D4, blockers),
+            expected_bishop
+        );
+
+        // Blockers on the edge of the board are not part of the blocker mask
+        let corner_blockers = squares_to_bitboard(&[Square::A1, Square::H8]);
+        assert_eq!(
+            ChessBoard::rook_attacks(Square::A8, corner_blockers).bit_count(),
+            14
+        );
+    }
+
+    #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+    #[test]
+    fn pext_matches_magic_attacks() {
+        use crate::chess::{
+            move_generator::{BISHOP_MAGICS, BISHOP_MOVES, ROOK_MAGICS, ROOK_MOVES},
+            pext,
+        };
+
+        if !pext::is_available() {
+            return;
+        }
+        for index in 0..Square::NUM {
+            let square = Square::from_index(index);
+            for _ in 0..64 {
+                let blockers = BitBoard(rand::random::<u64>() & rand::random::<u64>());
+                let rook = BitBoard(ROOK_MOVES[ROOK_MAGICS[index].magic_index(blockers)]);
+                let bishop = BitBoard(BISHOP_MOVES[BISHOP_MAGICS[index].magic_index(blockers)]);
+                unsafe {
+                    assert_eq!(pext::rook_attacks(square, blockers), rook);
+                    assert_eq!(pext::bishop_attacks(square, blockers), bishop);
+                }
+            }
+        }
+    }
+
     #[test]
     fn pawns_moves_from_fen_simple() {
         let board_state = ChessBoardState::starting_state();
 
-        let mut white_pawn_moves = Vec::new();
+        let mut white_pawn_moves = MoveList::new();
 
         let expected_moves_white = [
             Move::new(Square::H2, Square::H3, MoveType::Silent),
@@ -933,7 +1501,7 @@ mod move_gen_tests {
             Move::new(Square::B7, Square::B5, MoveType::DoublePush),
             Move::new(Square::A7, Square::A5, MoveType::DoublePush),
         ];
-        let mut black_pawn_moves = Vec::new();
+        let mut black_pawn_moves = MoveList::new();
         generate_pawn_moves::<false>(
             &board_state,
             PieceColor::Black,
@@ -948,11 +1516,11 @@ mod move_gen_tests {
     #[test]
     fn pawns_attacks() {
         let board_state =
-            ChessBoardState::from_fen("k6p/6P1/2r5/p1qP4/1P3p2/5P2/P2p4/7K w QKqk - 0 0");
+            ChessBoardState::from_fen("k6r/6P1/2r5/p1qP4/1P3p2/5P2/P2p4/7K w - - 0 0");
         assert!(board_state.is_ok());
         let board_state = board_state.unwrap();
 
-        let mut white_pawn_moves = Vec::new();
+        let mut white_pawn_moves = MoveList::new();
         generate_pawn_moves::<false>(
             &board_state,
             PieceColor::White,
@@ -980,7 +1548,7 @@ mod move_gen_tests {
         ];
         compare_moves(&white_pawn_moves, &expected_moves_white);
 
-        let mut black_pawn_moves = Vec::new();
+        let mut black_pawn_moves = MoveList::new();
         generate_pawn_moves::<false>(
             &board_state,
             PieceColor::Black,
@@ -996,19 +1564,17 @@ mod move_gen_tests {
             Move::new(Square::D2, Square::D1, MoveType::BishopPromotion),
             Move::new(Square::D2, Square::D1, MoveType::RookPromotion),
             Move::new(Square::D2, Square::D1, MoveType::QueenPromotion),
-            Move::new(Square::H8, Square::G7, MoveType::Capture),
-            Move::new(Square::H8, Square::H7, MoveType::Silent),
         ];
         compare_moves(&black_pawn_moves, &expected_black_moves);
     }
 
     #[test]
     fn knight_attacks() {
-        let board_state = ChessBoardState::from_fen("3R4/5n1k/7N/R3B3/3q4/1N6/K7/2b5 w QKqk - 0 0");
+        let board_state = ChessBoardState::from_fen("3R4/5n1k/7N/R3B3/3q4/1N6/K7/2b5 w - - 0 0");
         assert!(board_state.is_ok());
         let board_state = board_state.unwrap();
 
-        let mut white_knight_moves = Vec::new();
+        let mut white_knight_moves = MoveList::new();
         generate_knight_moves::<false>(
             &board_state,
             PieceColor::White,
@@ -1029,7 +1595,7 @@ mod move_gen_tests {
         ];
         compare_moves(&white_knight_moves, &expected_white_knight_moves);
 
-        let mut black_knight_moves = Vec::new();
+        let mut black_knight_moves = MoveList::new();
         generate_knight_moves::<false>(
             &board_state,
             PieceColor::Black,
@@ -1143,4 +1709,259 @@ mod move_gen_tests {
             );
         }
     }
+
+    #[test]
+    fn castling_needs_rook() {
+        let mut board_state =
+            ChessBoardState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        // Removed like in the board editor, the castling rights stay
+        board_state.board.remove_piece_at_pos(
+            ChessPiece::Rook,
+            PieceColor::White,
+            Square::H1,
+            &mut board_state.zhash,
+        );
+
+        let moves = generate_legal_moves::<false>(&board_state, PieceColor::White);
+        let king_side = Move::new(Square::E1, Square::G1, MoveType::CastleKingSide);
+        let queen_side = Move::new(Square::E1, Square::C1, MoveType::CastleQueenSide);
+        assert!(!moves.contains(&king_side));
+        assert!(!board_state.is_legal(king_side));
+        assert!(moves.contains(&queen_side));
+        assert!(board_state.is_legal(queen_side));
+        assert_eq!(
+            board_state.validate(),
+            Err(FenError::CastlingRightsWithoutPieces("KQkq".to_string()))
+        );
+
+        // A knight on top of the black king
+        let mut board_state = ChessBoardState::starting_state();
+        board_state.board.white_pieces[ChessPiece::Knight as usize] =
+            board_state.board.white_pieces[ChessPiece::Knight as usize].set_bit(Square::E8.index());
+        assert_eq!(
+            board_state.validate(),
+            Err(FenError::InconsistentBoard(Square::E8))
+        );
+    }
+
+    #[test]
+    fn is_legal_matches_generated_moves() {
+        let move_types = [
+            MoveType::Silent,
+            MoveType::DoublePush,
+            MoveType::CastleKingSide,
+            MoveType::CastleQueenSide,
+            MoveType::Capture,
+            MoveType::EnPassant,
+            MoveType::KnightPromotion,
+            MoveType::BishopPromotion,
+            MoveType::RookPromotion,
+            MoveType::QueenPromotion,
+            MoveType::KnightCapPromotion,
+            MoveType::BishopCapPromotion,
+            MoveType::RookCapPromotion,
+            MoveType::QueenCapPromotion,
+        ];
+        // Covers pins, single and double checks, en passant, castling and promotions
+        let test_set = [
+            "r3k2r/p1pp1pb1/bn2Qnp1/2qPN3/1p2P3/2N5/PPPBBPPP/R3K2R b KQkq - 3 2",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3",
+            "8/8/8/8/k1pP3Q/8/8/5K2 b - d3 0 3",
+            "4k3/4r3/4Q3/8/8/8/8/3K4 b - - 5 4",
+            "8/5k2/4q3/3B4/5R2/8/8/3K4 b - - 0 1",
+            "n1n1r1r1/1P1P1P1P/2NBN2k/3B4/R7/3Q4/1K3P1P/6R1 w - - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBPPP3/q4N2/Pp4PP/R2Q1RK1 b kq d3 0 2",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/5n2/R3K2R w KQkq - 0 1",
+        ];
+
+        for fen in test_set {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            let legal_moves = generate_legal_moves::<false>(&board_state, board_state.side);
+
+            for src in (0..Square::NUM).map(Square::from_index) {
+                for dst in (0..Square::NUM).map(Square::from_index) {
+                    for move_type in move_types {
+                        let mv = Move::new(src, dst, move_type);
+                        assert_eq!(
+                            board_state.is_legal(mv),
+                            legal_moves.contains(&mv),
+                            "{} disagrees on {:?} {:?}",
+                            fen,
+                            mv,
+                            move_type
+                        );
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn captures_only_includes_promotions() {
+        let test_set = [
+            "n1n1r1r1/1P1P1P1P/2NBN2k/3B4/R7/3Q4/1K3P1P/6R1 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBPPP3/q4N2/Pp4PP/R2Q1RK1 b kq d3 0 2",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        for fen in test_set {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            let mut expected: Vec<Move> =
+                generate_legal_moves::<false>(&board_state, board_state.side)
+                    .into_iter()
+                    .filter(|mv| mv.is_capture() || mv.is_promotion())
+                    .collect();
+            let mut tactical = generate_legal_moves::<true>(&board_state, board_state.side);
+            expected.sort_by_key(|mv| mv.0);
+            tactical.sort_by_key(|mv| mv.0);
+            assert_eq!(tactical, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn moves_from_matches_generated_moves() {
+        let test_set = [
+            "r3k2r/p1pp1pb1/bn2Qnp1/2qPN3/1p2P3/2N5/PPPBBPPP/R3K2R b KQkq - 3 2",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3",
+            "4k3/4r3/4Q3/8/8/8/8/3K4 b - - 5 4",
+            "8/5k2/4q3/3B4/5R2/8/8/3K4 b - - 0 1",
+            "n1n1r1r1/1P1P1P1P/2NBN2k/3B4/R7/3Q4/1K3P1P/6R1 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBPPP3/q4N2/Pp4PP/R2Q1RK1 b kq d3 0 2",
+            "r3k2r/8/8/8/8/8/5n2/R3K2R w KQkq - 0 1",
+        ];
+
+        for fen in test_set {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            let legal_moves = generate_legal_moves::<false>(&board_state, board_state.side);
+
+            for square in (0..Square::NUM).map(Square::from_index) {
+                let mut expected: Vec<Move> = legal_moves
+                    .iter()
+                    .filter(|mv| mv.get_src() == square)
+                    .copied()
+                    .collect();
+                let mut moves = board_state.moves_from(square);
+                expected.sort_by_key(|mv| mv.0);
+                moves.sort_by_key(|mv| mv.0);
+                assert_eq!(moves, expected, "{} {:?}", fen, square);
+            }
+        }
+    }
+
+    #[test]
+    fn evasions_match_masked_generation() {
+        // Single checks by sliders, a knight and pawns, with pinned pieces, an en passant
+        // capture of the checker and a capture by promotion
+        let test_set = [
+            "4k3/8/2n1Q3/8/8/8/8/3K4 b - - 0 1",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3",
+            "4r1k1/8/8/b7/7b/2Nn2P1/4RP2/4K3 w - - 0 1",
+            "3r2k1/2P5/8/8/8/8/1B6/3K4 w - - 0 1",
+            "4k3/8/8/8/1b6/8/3N4/r3K3 w - - 0 1",
+            "rnbqk1nr/pppp1ppp/8/4p3/1b1P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 2 3",
+        ];
+
+        for fen in test_set {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            assert_eq!(board_state.checkers.bit_count(), 1, "{}", fen);
+            let mut evasions = generate_legal_moves::<false>(&board_state, board_state.side);
+            // moves_from always computes the check and pin masks of the full generation
+            let mut expected: Vec<Move> = Square::ALL
+                .iter()
+                .flat_map(|&square| board_state.moves_from(square))
+                .collect();
+            evasions.sort_by_key(|mv| mv.0);
+            expected.sort_by_key(|mv| mv.0);
+            assert_eq!(evasions, expected, "{}", fen);
+
+            for capture in generate_legal_moves::<true>(&board_state, board_state.side) {
+                assert!(evasions.contains(&capture), "{} {:?}", fen, capture);
+            }
+        }
+    }
+
+    #[test]
+    fn attackers_to_square() {
+        let board_state =
+            ChessBoardState::from_fen("4k3/8/2n5/3p4/1B2P3/8/3R4/3QK3 w - - 0 1").unwrap();
+        let board = &board_state.board;
+        let occupancy = board.all_white_pieces | board.all_black_pieces;
+
+        assert_eq!(
+            board.attackers_to(Square::D5, occupancy),
+            squares_to_bitboard(&[Square::E4, Square::D2])
+        );
+        assert_eq!(
+            board.color_attackers_to(Square::D4, PieceColor::Black, occupancy),
+            squares_to_bitboard(&[Square::C6])
+        );
+        // Removing the rook reveals the queen behind it
+        assert_eq!(
+            board.attackers_to(Square::D5, occupancy & !squares_to_bitboard(&[Square::D2])),
+            squares_to_bitboard(&[Square::E4, Square::D1])
+        );
+
+        assert!(board.is_square_attacked(Square::C5, PieceColor::White));
+        assert!(board.is_square_attacked(Square::E5, PieceColor::Black));
+        assert!(!board.is_square_attacked(Square::A6, PieceColor::White));
+    }
+
+    #[test]
+    fn xray_attacks() {
+        let board_state =
+            ChessBoardState::from_fen("4k3/8/2n5/3p4/1B2P3/8/3R4/3QK3 w - - 0 1").unwrap();
+        let board = &board_state.board;
+        let occupancy = board.all_white_pieces | board.all_black_pieces;
+
+        // Through the rook the queen sees the d-file up to the black pawn and through
+        // the king the rest of the first rank
+        assert_eq!(
+            ChessBoard::xray_rook_attacks(Square::D1, occupancy, board.all_white_pieces),
+            squares_to_bitboard(&[
+                Square::D3,
+                Square::D4,
+                Square::D5,
+                Square::F1,
+                Square::G1,
+                Square::H1
+            ])
+        );
+        assert_eq!(
+            ChessBoard::xray_bishop_attacks(Square::B4, occupancy, board.all_white_pieces),
+            squares_to_bitboard(&[Square::E1])
+        );
+        assert_eq!(
+            ChessBoard::xray_bishop_attacks(Square::B4, occupancy, board.all_black_pieces),
+            BitBoard::EMPTY
+        );
+        assert_eq!(
+            ChessBoard::xray_bishop_attacks(Square::E8, occupancy, board.all_black_pieces),
+            squares_to_bitboard(&[Square::B5, Square::A4])
+        );
+    }
+
+    #[test]
+    fn jump_piece_lookups() {
+        assert_eq!(
+            ChessBoard::knight_attacks(Square::A1),
+            squares_to_bitboard(&[Square::B3, Square::C2])
+        );
+        assert_eq!(
+            ChessBoard::knight_attacks(Square::G7),
+            squares_to_bitboard(&[Square::E8, Square::E6, Square::F5, Square::H5])
+        );
+        assert_eq!(ChessBoard::knight_attacks(Square::D4).bit_count(), 8);
+        assert_eq!(
+            ChessBoard::king_attacks(Square::H8),
+            squares_to_bitboard(&[Square::G8, Square::G7, Square::H7])
+        );
+        assert_eq!(
+            ChessBoard::king_attacks(Square::E1),
+            squares_to_bitboard(&[Square::D1, Square::D2, Square::E2, Square::F2, Square::F1])
+        );
+        assert_eq!(ChessBoard::king_attacks(Square::B5).bit_count(), 8);
+    }
 }
@@ -0,0 +1,151 @@
+//! Fixed capacity move buffer the move generator writes into, so generating the moves of
+//! a position never allocates.
+
+use std::ops::{Deref, DerefMut};
+
+use super::chess_move::Move;
+
+/// Most legal moves any reachable position has.
+pub const MAX_MOVES: usize = 218;
+
+/// Moves of a single position, stored inline. Dereferences to a slice of the moves.
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self {
+            moves: [Move::NULL_MOVE; MAX_MOVES],
+            len: 0,
+        }
+    }
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keeps the moves `keep` returns true for, in their order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    #[inline(always)]
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for MoveList {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for MoveList {}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Move, MAX_MOVES>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.into_iter().take(self.len)
+    }
+}
+
+impl From<MoveList> for Vec<Move> {
+    fn from(moves: MoveList) -> Self {
+        moves.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod move_list_tests {
+    use super::MoveList;
+    use crate::chess::{
+        board::ChessBoardState,
+        chess_move::{Move, MoveType},
+        square::Square,
+    };
+
+    #[test]
+    fn push_and_retain() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        let d4 = Move::new(Square::D2, Square::D4, MoveType::DoublePush);
+        let nf3 = Move::new(Square::G1, Square::F3, MoveType::Silent);
+        for mv in [e4, d4, nf3] {
+            moves.push(mv);
+        }
+        assert_eq!(&*moves, &[e4, d4, nf3]);
+
+        moves.retain(|mv| mv.get_type() == MoveType::DoublePush);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves.into_iter().collect::<Vec<_>>(), vec![e4, d4]);
+    }
+
+    #[test]
+    fn holds_the_most_moves_of_any_position() {
+        let board_state =
+            ChessBoardState::from_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1")
+                .unwrap();
+        let moves = board_state.legal_moves::<false>();
+        assert_eq!(moves.len(), 218);
+        assert_eq!(
+            board_state.legal_moves::<true>().to_vec(),
+            moves
+                .iter()
+                .copied()
+                .filter(|mv| mv.is_capture() || mv.is_promotion())
+                .collect::<Vec<_>>()
+        );
+    }
+}
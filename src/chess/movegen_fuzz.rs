@@ -0,0 +1,410 @@
+//! Randomized cross-check of the move generator against a slow reference generator, which
+//! works on a plain array of squares and tests legality by playing each move and looking
+//! for attacks on the king. The single square generation and the legality check have to
+//! agree with the full generation on the same positions.
+
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::{
+    board::{CastlingRights, ChessBoardState, ChessPiece, PieceColor},
+    chess_move::Move,
+    square::Square,
+    zobrist_hash::ZHash,
+};
+
+type Mailbox = [Option<(ChessPiece, PieceColor)>; Square::NUM];
+// Source, destination and promotion piece, comparable between both generators
+type PlainMove = (Square, Square, Option<ChessPiece>);
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const PROMOTION_PIECES: [ChessPiece; 4] = [
+    ChessPiece::Queen,
+    ChessPiece::Rook,
+    ChessPiece::Bishop,
+    ChessPiece::Knight,
+];
+
+fn forward(color: PieceColor) -> i32 {
+    match color {
+        PieceColor::White => 1,
+        PieceColor::Black => -1,
+    }
+}
+
+fn mailbox(board_state: &ChessBoardState) -> Mailbox {
+    let mut squares = [None; Square::NUM];
+    for (index, square) in squares.iter_mut().enumerate() {
+        *square = board_state.board.piece_on(Square::from_index(index));
+    }
+    squares
+}
+
+fn is_attacked(squares: &Mailbox, target: Square, by: PieceColor) -> bool {
+    let holds = |square: Option<Square>, pieces: &[ChessPiece]| {
+        square
+            .and_then(|square| squares[square.index()])
+            .is_some_and(|(piece, color)| color == by && pieces.contains(&piece))
+    };
+
+    // Pawns attack forward, so they stand behind the target from their point of view
+    let pawn_rank = -forward(by);
+    if holds(target.offset(1, pawn_rank), &[ChessPiece::Pawn])
+        || holds(target.offset(-1, pawn_rank), &[ChessPiece::Pawn])
+    {
+        return true;
+    }
+    if KNIGHT_OFFSETS
+        .iter()
+        .any(|(f, r)| holds(target.offset(*f, *r), &[ChessPiece::Knight]))
+    {
+        return true;
+    }
+    if KING_OFFSETS
+        .iter()
+        .any(|(f, r)| holds(target.offset(*f, *r), &[ChessPiece::King]))
+    {
+        return true;
+    }
+
+    let sliders = [
+        (ROOK_DIRECTIONS, ChessPiece::Rook),
+        (BISHOP_DIRECTIONS, ChessPiece::Bishop),
+    ];
+    for (directions, slider) in sliders {
+        for (f, r) in directions {
+            let mut square = target.offset(f, r);
+            while let Some(current) = square {
+                if squares[current.index()].is_some() {
+                    if holds(square, &[slider, ChessPiece::Queen]) {
+                        return true;
+                    }
+                    break;
+                }
+                square = current.offset(f, r);
+            }
+        }
+    }
+    false
+}
+
+fn king_square(squares: &Mailbox, color: PieceColor) -> Square {
+    let index = squares
+        .iter()
+        .position(|square| *square == Some((ChessPiece::King, color)))
+        .unwrap();
+    Square::from_index(index)
+}
+
+/// Plays a pseudo legal move on the mailbox.
+fn play(squares: &Mailbox, ep_target: Option<Square>, (src, dst, promotion): PlainMove) -> Mailbox {
+    let mut squares = *squares;
+    let (piece, color) = squares[src.index()].take().unwrap();
+    if piece == ChessPiece::Pawn && Some(dst) == ep_target {
+        let victim = dst.offset(0, -forward(color)).unwrap();
+        squares[victim.index()] = None;
+    }
+    if piece == ChessPiece::King && src.file_distance(dst) == 2 {
+        let (rook_file, rook_dst_file) = if dst.file() == 6 { (7, 5) } else { (0, 3) };
+        let rook = Square::from_file_rank(rook_file, src.rank()).unwrap();
+        let rook_dst = Square::from_file_rank(rook_dst_file, src.rank()).unwrap();
+        squares[rook_dst.index()] = squares[rook.index()].take();
+    }
+    squares[dst.index()] = Some((promotion.unwrap_or(piece), color));
+    squares
+}
+
+fn castling_moves(board_state: &ChessBoardState, squares: &Mailbox, moves: &mut Vec<PlainMove>) {
+    let color = board_state.side;
+    let rights = board_state.castling_rights;
+    let (rank, king_side, queen_side) = match color {
+        PieceColor::White => (0, rights.white_king_side(), rights.white_queen_side()),
+        PieceColor::Black => (7, rights.black_king_side(), rights.black_queen_side()),
+    };
+    let square = |file| Square::from_file_rank(file, rank).unwrap();
+    let king = square(4);
+    if squares[king.index()] != Some((ChessPiece::King, color))
+        || is_attacked(squares, king, !color)
+    {
+        return;
+    }
+
+    // Rook file, files which have to be empty and files the king passes or lands on
+    let sides: [(bool, u8, &[u8], &[u8]); 2] = [
+        (king_side, 7, &[5, 6], &[5, 6]),
+        (queen_side, 0, &[1, 2, 3], &[3, 2]),
+    ];
+    for (allowed, rook_file, empty, passed) in sides {
+        if allowed
+            && squares[square(rook_file).index()] == Some((ChessPiece::Rook, color))
+            && empty
+                .iter()
+                .all(|file| squares[square(*file).index()].is_none())
+            && passed
+                .iter()
+                .all(|file| !is_attacked(squares, square(*file), !color))
+        {
+            moves.push((king, square(*passed.last().unwrap()), None));
+        }
+    }
+}
+
+/// All legal moves of the side to move, generated square by square.
+fn reference_moves(board_state: &ChessBoardState) -> Vec<PlainMove> {
+    let color = board_state.side;
+    let squares = mailbox(board_state);
+    let ep_target = board_state.en_passant_target;
+    let mut moves = Vec::new();
+
+    let is_enemy = |square: Square| squares[square.index()].is_some_and(|(_, c)| c != color);
+    let is_free = |square: Square| squares[square.index()].is_none_or(|(_, c)| c != color);
+
+    for index in 0..Square::NUM {
+        let src = Square::from_index(index);
+        let piece = match squares[index] {
+            Some((piece, piece_color)) if piece_color == color => piece,
+            _ => continue,
+        };
+
+        match piece {
+            ChessPiece::Pawn => {
+                let promotion_rank = if color == PieceColor::White { 7 } else { 0 };
+                let start_rank = if color == PieceColor::White { 1 } else { 6 };
+                let mut targets = Vec::new();
+                if let Some(dst) = src.offset(0, forward(color)) {
+                    if squares[dst.index()].is_none() {
+                        targets.push(dst);
+                        let double = dst.offset(0, forward(color));
+                        if let Some(double) = double.filter(|double| {
+                            src.rank() == start_rank && squares[double.index()].is_none()
+                        }) {
+                            targets.push(double);
+                        }
+                    }
+                }
+                for file in [-1, 1] {
+                    if let Some(dst) = src.offset(file, forward(color)) {
+                        if is_enemy(dst) || Some(dst) == ep_target {
+                            targets.push(dst);
+                        }
+                    }
+                }
+                for dst in targets {
+                    if dst.rank() == promotion_rank {
+                        moves.extend(PROMOTION_PIECES.map(|promotion| (src, dst, Some(promotion))));
+                    } else {
+                        moves.push((src, dst, None));
+                    }
+                }
+            }
+            ChessPiece::Knight | ChessPiece::King => {
+                let offsets = if piece == ChessPiece::Knight {
+                    KNIGHT_OFFSETS
+                } else {
+                    KING_OFFSETS
+                };
+                for (f, r) in offsets {
+                    if let Some(dst) = src.offset(f, r).filter(|dst| is_free(*dst)) {
+                        moves.push((src, dst, None));
+                    }
+                }
+            }
+            ChessPiece::Bishop | ChessPiece::Rook | ChessPiece::Queen => {
+                let directions = match piece {
+                    ChessPiece::Bishop => &BISHOP_DIRECTIONS[..],
+                    ChessPiece::Rook => &ROOK_DIRECTIONS[..],
+                    _ => &KING_OFFSETS[..],
+                };
+                for (f, r) in directions {
+                    let mut square = src.offset(*f, *r);
+                    while let Some(dst) = square.filter(|dst| is_free(*dst)) {
+                        moves.push((src, dst, None));
+                        if is_enemy(dst) {
+                            break;
+                        }
+                        square = dst.offset(*f, *r);
+                    }
+                }
+            }
+        }
+    }
+    castling_moves(board_state, &squares, &mut moves);
+
+    moves.retain(|mv| {
+        let after = play(&squares, ep_target, *mv);
+        !is_attacked(&after, king_square(&after, color), !color)
+    });
+    moves.sort_by_key(|(src, dst, promotion)| {
+        (src.index(), dst.index(), promotion.map(|p| p as u8))
+    });
+    moves
+}
+
+fn generated_moves(board_state: &ChessBoardState) -> Vec<PlainMove> {
+    let mut moves: Vec<PlainMove> = board_state
+        .generate_legal_moves_for_current_player::<false>()
+        .into_iter()
+        .map(|mv| {
+            let promotion = mv.is_promotion().then(|| mv.promotion_target());
+            (mv.get_src(), mv.get_dst(), promotion)
+        })
+        .collect();
+    moves.sort_by_key(|(src, dst, promotion)| {
+        (src.index(), dst.index(), promotion.map(|p| p as u8))
+    });
+    moves
+}
+
+fn generators_agree(board_state: &ChessBoardState) -> bool {
+    generated_moves(board_state) == reference_moves(board_state)
+}
+
+/// Removes pieces other than the kings from a position the generators disagree on, as long
+/// as they keep disagreeing, to leave a small position to debug with.
+fn minimize(board_state: &ChessBoardState) -> ChessBoardState {
+    let mut smallest = *board_state;
+    'shrink: loop {
+        for index in 0..Square::NUM {
+            let square = Square::from_index(index);
+            let Some((piece, color)) = smallest.board.piece_on(square) else {
+                continue;
+            };
+            if piece == ChessPiece::King {
+                continue;
+            }
+            let mut candidate = smallest;
+            candidate
+                .board
+                .remove_piece_at_pos(piece, color, square, &mut ZHash::default());
+            // Castling rights of a removed rook are no longer valid
+            let fen = candidate.to_fen();
+            let candidate = ChessBoardState::from_fen(&fen).or_else(|_| {
+                candidate.castling_rights = CastlingRights::none();
+                ChessBoardState::from_fen(&candidate.to_fen())
+            });
+            if let Ok(candidate) = candidate {
+                if !generators_agree(&candidate) {
+                    smallest = candidate;
+                    continue 'shrink;
+                }
+            }
+        }
+        return smallest;
+    }
+}
+
+/// Plays `games` games of up to `plies` random legal moves, cycling through `starts`, and
+/// hands every position reached to `visit`, the starting positions included.
+pub(super) fn random_playouts(
+    starts: &[ChessBoardState],
+    seed: u64,
+    games: usize,
+    plies: usize,
+    mut visit: impl FnMut(&ChessBoardState),
+) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for game in 0..games {
+        let mut board_state = starts[game % starts.len()];
+        visit(&board_state);
+        for _ in 0..plies {
+            let moves = board_state.generate_legal_moves_for_current_player::<false>();
+            let Some(mv) = moves.choose(&mut rng) else {
+                break;
+            };
+            board_state = board_state.exec_move(*mv);
+            visit(&board_state);
+        }
+    }
+}
+
+fn assert_generators_agree(board_state: &ChessBoardState) {
+    if generators_agree(board_state) {
+        return;
+    }
+    let smallest = minimize(board_state);
+    let (generated, reference) = (generated_moves(&smallest), reference_moves(&smallest));
+    let only_in = |moves: &[PlainMove], other: &[PlainMove]| {
+        let names: Vec<String> = moves
+            .iter()
+            .filter(|mv| !other.contains(mv))
+            .map(|(src, dst, promotion)| match promotion {
+                Some(piece) => format!("{}{}={:?}", src, dst, piece),
+                None => format!("{}{}", src, dst),
+            })
+            .collect();
+        names.join(" ")
+    };
+    panic!(
+        "move generators disagree in {}, smallest such position {}\nonly generated: {}\nonly in reference: {}",
+        board_state.to_fen(),
+        smallest.to_fen(),
+        only_in(&generated, &reference),
+        only_in(&reference, &generated)
+    );
+}
+
+#[test]
+fn movegen_matches_reference() {
+    let starts = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ]
+    .map(|fen| ChessBoardState::from_fen(fen).unwrap());
+    // Moves of the previous position, mostly illegal in the current one
+    let mut earlier_moves = Vec::new();
+
+    random_playouts(&starts, 0x599, 300, 100, |board_state| {
+        assert_generators_agree(board_state);
+
+        let fen = board_state.to_fen();
+        let moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let legal: HashSet<Move> = moves.iter().copied().collect();
+        let mut by_square = Vec::new();
+        for index in 0..Square::NUM {
+            by_square.extend(board_state.moves_from(Square::from_index(index)));
+        }
+        assert_eq!(by_square.len(), moves.len(), "{}", fen);
+        assert_eq!(
+            by_square.into_iter().collect::<HashSet<_>>(),
+            legal,
+            "{}",
+            fen
+        );
+
+        for mv in moves.iter().chain(&earlier_moves) {
+            assert_eq!(
+                board_state.is_legal(*mv),
+                legal.contains(mv),
+                "{:?} in {}",
+                mv,
+                fen
+            );
+        }
+        earlier_moves = moves;
+    });
+}
@@ -0,0 +1,135 @@
+use super::{
+    board::{ChessBoardState, ChessPiece, PieceColor},
+    square::Square,
+};
+
+const PIECE_TYPE_COUNT: usize = 6;
+// Pieces other than the kings, HalfKP encodes those relative to the own king
+const NON_KING_PIECE_TYPES: usize = 5;
+const HALF_KP_SIZE: usize = Square::NUM * 2 * NON_KING_PIECE_TYPES * Square::NUM;
+
+/// Input layouts of a neural network evaluation, all of them one-hot: every input is
+/// either 0.0 or 1.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NNEncoding {
+    /// One input per color, piece type and square, white pieces first.
+    OneHot768,
+    /// Like `OneHot768` with the side to move's pieces first and the board flipped
+    /// vertically when black is to move, so both sides look the same to the network.
+    SideRelative768,
+    /// For both perspectives, the side to move's first, one input per king square and
+    /// non-king piece on a square. Each perspective sees the board from its side.
+    HalfKP,
+}
+
+impl NNEncoding {
+    /// Number of inputs the encoding writes.
+    pub const fn input_size(&self) -> usize {
+        match self {
+            NNEncoding::OneHot768 | NNEncoding::SideRelative768 => {
+                2 * PIECE_TYPE_COUNT * Square::NUM
+            }
+            NNEncoding::HalfKP => 2 * HALF_KP_SIZE,
+        }
+    }
+}
+
+/// Square as seen by `perspective`, black sees the board flipped vertically.
+fn relative_square(square: Square, perspective: PieceColor) -> usize {
+    square.relative_to(perspective).index()
+}
+
+impl ChessBoardState {
+    /// Writes the position as network inputs in the given encoding, `inputs` has to
+    /// hold exactly `encoding.input_size()` values.
+    pub fn get_neuralnetwork_representation(&self, encoding: NNEncoding, inputs: &mut [f32]) {
+        assert_eq!(
+            inputs.len(),
+            encoding.input_size(),
+            "Input buffer does not fit {:?}",
+            encoding
+        );
+        inputs.fill(0.0);
+
+        let pieces = Square::ALL
+            .into_iter()
+            .filter_map(|square| self.board.piece_on(square).map(|(p, c)| (square, p, c)));
+
+        match encoding {
+            NNEncoding::OneHot768 => {
+                for (square, piece, color) in pieces {
+                    let index = (color as usize * PIECE_TYPE_COUNT + piece as usize) * Square::NUM
+                        + square.index();
+                    inputs[index] = 1.0;
+                }
+            }
+            NNEncoding::SideRelative768 => {
+                for (square, piece, color) in pieces {
+                    let side = (color != self.side) as usize;
+                    let index = (side * PIECE_TYPE_COUNT + piece as usize) * Square::NUM
+                        + relative_square(square, self.side);
+                    inputs[index] = 1.0;
+                }
+            }
+            NNEncoding::HalfKP => {
+                let perspectives = [self.side, !self.side];
+                let king_squares = perspectives.map(|perspective| {
+                    relative_square(self.board.get_king_pos(perspective), perspective)
+                });
+                for (square, piece, color) in pieces.filter(|(_, p, _)| *p != ChessPiece::King) {
+                    for (i, perspective) in perspectives.into_iter().enumerate() {
+                        let side = (color != perspective) as usize;
+                        let piece_index = side * NON_KING_PIECE_TYPES + piece as usize;
+                        let index = i * HALF_KP_SIZE
+                            + (king_squares[i] * 2 * NON_KING_PIECE_TYPES + piece_index)
+                                * Square::NUM
+                            + relative_square(square, perspective);
+                        inputs[index] = 1.0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod nn_input_tests {
+    use super::NNEncoding;
+    use crate::chess::board::ChessBoardState;
+
+    fn encode(fen: &str, encoding: NNEncoding) -> Vec<f32> {
+        let board_state = ChessBoardState::from_fen(fen).unwrap();
+        let mut inputs = vec![0.0; encoding.input_size()];
+        board_state.get_neuralnetwork_representation(encoding, &mut inputs);
+        inputs
+    }
+
+    #[test]
+    fn encodings() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let count = |inputs: &[f32]| inputs.iter().filter(|v| **v == 1.0).count();
+
+        assert_eq!(count(&encode(start, NNEncoding::OneHot768)), 32);
+        assert_eq!(count(&encode(start, NNEncoding::HalfKP)), 2 * 30);
+
+        // The same position from the other side's view encodes identically
+        let white = "4k3/8/8/8/8/8/4P3/4K2R w - - 0 1";
+        let black = "4k2r/4p3/8/8/8/8/8/4K3 b - - 0 1";
+        for encoding in [NNEncoding::SideRelative768, NNEncoding::HalfKP] {
+            assert_eq!(encode(white, encoding), encode(black, encoding));
+        }
+        assert_ne!(
+            encode(white, NNEncoding::OneHot768),
+            encode(black, NNEncoding::OneHot768)
+        );
+
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let flipped = ChessBoardState::from_fen(kiwipete).unwrap().color_flipped();
+        for encoding in [NNEncoding::SideRelative768, NNEncoding::HalfKP] {
+            assert_eq!(
+                encode(kiwipete, encoding),
+                encode(&flipped.to_fen(), encoding)
+            );
+        }
+    }
+}
@@ -1,10 +1,14 @@
-use super::board::ChessBoardState;
+//! Move path enumeration, the standard check of a move generator against known node counts.
 
+use super::{board::ChessBoardState, chess_move::Move};
+
+/// Number of leaf nodes of the legal move tree `depth` plies below the position. The last
+/// ply is counted in bulk from the length of the move list, without making the moves.
 pub fn perft(board_state: &ChessBoardState, depth: u32) -> u64 {
     if depth < 1 {
         1
     } else {
-        let moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let moves = board_state.legal_moves::<false>();
 
         if depth == 1 {
             moves.len() as u64
@@ -20,10 +24,24 @@ pub fn perft(board_state: &ChessBoardState, depth: u32) -> u64 {
     }
 }
 
+/// `perft` split by the legal moves of the position, the count of each move is the perft
+/// of `depth - 1` after it. Comparing the split against another engine narrows a wrong
+/// count down to the move whose subtree differs.
+pub fn perft_divide(board_state: &ChessBoardState, depth: u32) -> Vec<(Move, u64)> {
+    if depth < 1 {
+        return Vec::new();
+    }
+    board_state
+        .generate_legal_moves_for_current_player::<false>()
+        .into_iter()
+        .map(|mv| (mv, perft(&board_state.exec_move(mv), depth - 1)))
+        .collect()
+}
+
 #[cfg(test)]
 mod perft_tests {
     use crate::chess::board::ChessBoardState;
-    use crate::chess::perft::perft;
+    use crate::chess::perft::{perft, perft_divide};
 
     #[test]
     fn base_perft() {
@@ -61,4 +79,49 @@ mod perft_tests {
         assert_eq!(perft(&board_state, 4), 2103487);
         assert_eq!(perft(&board_state, 5), 89941194);
     }
+
+    #[test]
+    fn chess_wiki_position_2() {
+        let board_state = ChessBoardState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(perft(&board_state, 1), 48);
+        assert_eq!(perft(&board_state, 2), 2039);
+        assert_eq!(perft(&board_state, 3), 97862);
+        assert_eq!(perft(&board_state, 4), 4085603);
+    }
+
+    #[test]
+    fn chess_wiki_position_3() {
+        let board_state =
+            ChessBoardState::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&board_state, 1), 14);
+        assert_eq!(perft(&board_state, 2), 191);
+        assert_eq!(perft(&board_state, 3), 2812);
+        assert_eq!(perft(&board_state, 4), 43238);
+        assert_eq!(perft(&board_state, 5), 674624);
+    }
+
+    #[test]
+    fn chess_wiki_position_6() {
+        let board_state = ChessBoardState::from_fen(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        )
+        .unwrap();
+        assert_eq!(perft(&board_state, 1), 46);
+        assert_eq!(perft(&board_state, 2), 2079);
+        assert_eq!(perft(&board_state, 3), 89890);
+        assert_eq!(perft(&board_state, 4), 3894594);
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let board_state = ChessBoardState::starting_state();
+        let divide = perft_divide(&board_state, 3);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.iter().all(|(_, nodes)| *nodes >= 361));
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        assert!(perft_divide(&board_state, 0).is_empty());
+    }
 }
@@ -0,0 +1,37 @@
+//! Sliding piece attacks indexed by the BMI2 `pext` instruction instead of magic multiplication.
+//! The tables need no magics and hold exactly one entry per blocker configuration. Note that
+//! AMD CPUs before Zen 3 implement `pext` in microcode and are faster with the magic tables.
+
+use std::arch::x86_64::_pext_u64;
+
+use super::{bitboard::BitBoard, square::Square};
+
+// Defines the masks, offsets and packed move tables of both pieces, generated by build.rs from
+// lookup_gens/magics.rs
+include!(concat!(env!("OUT_DIR"), "/pext_tables.rs"));
+
+/// Whether the CPU supports `pext`, the result of the detection is cached by the standard library.
+#[inline(always)]
+pub fn is_available() -> bool {
+    std::is_x86_feature_detected!("bmi2")
+}
+
+/// # Safety
+/// The CPU has to support BMI2, see [`is_available`].
+#[inline]
+#[target_feature(enable = "bmi2")]
+pub unsafe fn rook_attacks(square: Square, blockers: BitBoard) -> BitBoard {
+    let index = square.index();
+    let offset = ROOK_OFFSETS[index] + _pext_u64(blockers.0, ROOK_MASKS[index]) as usize;
+    BitBoard(ROOK_MOVES[offset])
+}
+
+/// # Safety
+/// The CPU has to support BMI2, see [`is_available`].
+#[inline]
+#[target_feature(enable = "bmi2")]
+pub unsafe fn bishop_attacks(square: Square, blockers: BitBoard) -> BitBoard {
+    let index = square.index();
+    let offset = BISHOP_OFFSETS[index] + _pext_u64(blockers.0, BISHOP_MASKS[index]) as usize;
+    BitBoard(BISHOP_MOVES[offset])
+}
@@ -0,0 +1,311 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    board::{ChessBoardState, PieceColor},
+    chess_move::Move,
+};
+
+const PGN_LINE_LENGTH: usize = 80;
+
+/// A finished or ongoing game that can be exchanged between tools, with the `serde` feature
+/// the start position is stored as FEN and the moves in their packed form.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    pub tags: Vec<(String, String)>,
+    pub start_state: ChessBoardState,
+    pub moves: Vec<Move>,
+    pub result: String,
+}
+
+impl GameRecord {
+    pub fn new(start_state: ChessBoardState) -> Self {
+        Self {
+            tags: Vec::new(),
+            start_state,
+            moves: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+
+    pub fn final_state(&self) -> ChessBoardState {
+        self.moves.iter().fold(self.start_state, |board_state, mv| {
+            board_state.exec_move(*mv)
+        })
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let tags: Vec<(&str, String)> = self
+            .tags
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        to_pgn(&tags, &self.start_state, &self.moves, &self.result)
+    }
+}
+
+/// Result of the game in PGN notation for its final position, `*` while it is still going on.
+pub fn game_result(final_state: &ChessBoardState) -> &'static str {
+    if final_state
+        .generate_legal_moves_for_current_player::<false>()
+        .is_empty()
+    {
+        if !final_state.is_in_check() {
+            "1/2-1/2"
+        } else if final_state.side == PieceColor::White {
+            "0-1"
+        } else {
+            "1-0"
+        }
+    } else if final_state.half_moves >= 100 {
+        "1/2-1/2"
+    } else {
+        "*"
+    }
+}
+
+/// Today's date in the `YYYY.MM.DD` format of the PGN Date tag.
+pub fn pgn_date_today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Writes a game as PGN. The seven tag roster is completed with defaults for missing tags,
+/// games that do not start from the initial position get `SetUp`/`FEN` tags.
+pub fn to_pgn(
+    tags: &[(&str, String)],
+    start_state: &ChessBoardState,
+    moves: &[Move],
+    result: &str,
+) -> String {
+    let mut pgn = String::new();
+
+    let tag_value = |name: &str, default: String| {
+        tags.iter()
+            .find(|(tag, _)| *tag == name)
+            .map(|(_, value)| value.clone())
+            .unwrap_or(default)
+    };
+    let roster = [
+        ("Event", tag_value("Event", "?".to_string())),
+        ("Site", tag_value("Site", "?".to_string())),
+        ("Date", tag_value("Date", pgn_date_today())),
+        ("Round", tag_value("Round", "-".to_string())),
+        ("White", tag_value("White", "?".to_string())),
+        ("Black", tag_value("Black", "?".to_string())),
+        ("Result", result.to_string()),
+    ];
+    for (name, value) in &roster {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    for (name, value) in tags {
+        if !roster.iter().any(|(tag, _)| tag == name) {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+    }
+    if start_state.zhash != ChessBoardState::starting_state().zhash {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", start_state.to_fen()));
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::with_capacity(moves.len() * 2 + 1);
+    let mut board_state = *start_state;
+    let mut move_number = start_state.full_moves.max(1) as u32;
+    for (i, mv) in moves.iter().enumerate() {
+        if board_state.side == PieceColor::White {
+            tokens.push(format!("{}.", move_number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(mv.to_san(&board_state));
+
+        if board_state.side == PieceColor::Black {
+            move_number += 1;
+        }
+        board_state = board_state.exec_move(*mv);
+    }
+    tokens.push(result.to_string());
+
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > PGN_LINE_LENGTH {
+            pgn.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+
+    pgn
+}
+
+#[cfg(test)]
+mod pgn_tests {
+    use super::*;
+    use crate::chess::{chess_move::MoveType, square::Square};
+
+    fn play(board_state: &ChessBoardState, moves: &[&str]) -> Vec<Move> {
+        let mut board_state = *board_state;
+        moves
+            .iter()
+            .map(|text| {
+                let mv = Move::try_from((*text, &board_state)).unwrap();
+                board_state = board_state.exec_move(mv);
+                mv
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_san() {
+        let board_state =
+            ChessBoardState::from_fen("r3k2r/1P6/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let san = |text: &str| {
+            Move::try_from((text, &board_state))
+                .unwrap()
+                .to_san(&board_state)
+        };
+
+        assert_eq!(san("e1g1"), "O-O");
+        assert_eq!(san("e1c1"), "O-O-O");
+        assert_eq!(san("e5d6"), "exd6");
+        assert_eq!(san("b3d4"), "Nbd4");
+        assert_eq!(san("b7a8q"), "bxa8=Q+");
+        assert_eq!(san("b7b8n"), "b8=N");
+        assert_eq!(san("a1a7"), "Ra7");
+    }
+
+    #[test]
+    fn test_san_rank_disambiguation_and_mate() {
+        let board_state = ChessBoardState::from_fen("6k1/5ppp/8/R7/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mv = Move::new(Square::A1, Square::A3, MoveType::Silent);
+        assert_eq!(mv.to_san(&board_state), "R1a3");
+
+        let mv = Move::new(Square::A5, Square::A8, MoveType::Silent);
+        assert_eq!(mv.to_san(&board_state), "Ra8#");
+    }
+
+    #[test]
+    fn test_annotated_moves() {
+        let board_state =
+            ChessBoardState::from_fen("r3k2r/1P6/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let annotated = |text: &str| {
+            Move::try_from((text, &board_state))
+                .unwrap()
+                .to_annotated_string(&board_state)
+        };
+
+        assert_eq!(annotated("e1g1"), "O-O");
+        assert_eq!(annotated("e5d6"), "e5xd6 e.p.");
+        assert_eq!(annotated("b3d4"), "Nb3-d4");
+        assert_eq!(annotated("b7a8q"), "b7xa8=Q+");
+
+        let board_state = ChessBoardState::from_fen("6k1/5ppp/8/R7/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mv = Move::new(Square::A5, Square::A8, MoveType::Silent);
+        assert_eq!(mv.to_annotated_string(&board_state), "Ra5-a8#");
+
+        let board_state = ChessBoardState::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        let mv = Move::new(Square::F1, Square::F7, MoveType::Silent);
+        assert_eq!(mv.to_annotated_string(&board_state), "Qf1-f7 (stalemate)");
+    }
+
+    #[test]
+    fn test_from_san() {
+        let board_state =
+            ChessBoardState::from_fen("r3k2r/1P6/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let uci = |san: &str| format!("{:?}", Move::from_san(san, &board_state).unwrap());
+
+        assert_eq!(uci("O-O"), "e1g1");
+        assert_eq!(uci("0-0-0"), "e1c1");
+        assert_eq!(uci("exd6"), "e5d6");
+        assert_eq!(uci("Nbd4"), "b3d4");
+        assert_eq!(uci("bxa8=Q+"), "b7a8q");
+        assert_eq!(uci("b8N!"), "b7b8n");
+        assert!(Move::from_san("Nd4", &board_state).is_err());
+        assert!(Move::from_san("Ke3", &board_state).is_err());
+    }
+
+    #[test]
+    fn test_game_result() {
+        let mated = ChessBoardState::from_fen(
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        )
+        .unwrap();
+        assert_eq!(game_result(&mated), "0-1");
+        let stalemate = ChessBoardState::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game_result(&stalemate), "1/2-1/2");
+        assert_eq!(game_result(&ChessBoardState::starting_state()), "*");
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let start = ChessBoardState::starting_state();
+        let moves = play(&start, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let pgn = to_pgn(
+            &[
+                ("White", "Iglo".to_string()),
+                ("Date", "2024.01.01".to_string()),
+            ],
+            &start,
+            &moves,
+            "0-1",
+        );
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"2024.01.01\"]\n[Round \"-\"]\n\
+             [White \"Iglo\"]\n[Black \"?\"]\n[Result \"0-1\"]\n\n\
+             1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+
+        let start = ChessBoardState::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 12").unwrap();
+        let moves = play(&start, &["e8d7", "e1g1"]);
+        let pgn = to_pgn(&[], &start, &moves, "*");
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n12... Kd7 13. O-O *\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_game_record_serde_roundtrip() {
+        let mut record = GameRecord::new(
+            ChessBoardState::from_fen("r3k2r/1P6/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap(),
+        );
+        record.tags.push(("White".to_string(), "Iglo".to_string()));
+        record.moves = play(&record.start_state, &["e5d6", "h8h2", "b7b8q"]);
+
+        let bytes = bincode::serialize(&record).unwrap();
+        let decoded: GameRecord = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.final_state(), record.final_state());
+        assert!(decoded.to_pgn().ends_with("1. exd6 Rh2 2. b8=Q+ *\n"));
+    }
+
+    #[test]
+    fn test_pgn_date_format() {
+        let date = pgn_date_today();
+        assert_eq!(date.len(), 10);
+        assert_eq!(&date[4..5], ".");
+        assert_eq!(&date[7..8], ".");
+    }
+}
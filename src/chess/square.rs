@@ -1,154 +1,350 @@
+use std::fmt::Display;
+
 use super::board::PieceColor;
 
-pub struct Square;
+/// Index of a square on the board, `A8` is 0 and `H1` is 63 matching the bit order of
+/// [`BitBoard`](super::bitboard::BitBoard).
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash, Default)]
+pub struct Square(u8);
 
 impl Square {
-    pub const A8: u16 = 0;
-    pub const B8: u16 = 1;
-    pub const C8: u16 = 2;
-    pub const D8: u16 = 3;
-    pub const E8: u16 = 4;
-    pub const F8: u16 = 5;
-    pub const G8: u16 = 6;
-    pub const H8: u16 = 7;
-    pub const A7: u16 = 8;
-    pub const B7: u16 = 9;
-    pub const C7: u16 = 10;
-    pub const D7: u16 = 11;
-    pub const E7: u16 = 12;
-    pub const F7: u16 = 13;
-    pub const G7: u16 = 14;
-    pub const H7: u16 = 15;
-    pub const A6: u16 = 16;
-    pub const B6: u16 = 17;
-    pub const C6: u16 = 18;
-    pub const D6: u16 = 19;
-    pub const E6: u16 = 20;
-    pub const F6: u16 = 21;
-    pub const G6: u16 = 22;
-    pub const H6: u16 = 23;
-    pub const A5: u16 = 24;
-    pub const B5: u16 = 25;
-    pub const C5: u16 = 26;
-    pub const D5: u16 = 27;
-    pub const E5: u16 = 28;
-    pub const F5: u16 = 29;
-    pub const G5: u16 = 30;
-    pub const H5: u16 = 31;
-    pub const A4: u16 = 32;
-    pub const B4: u16 = 33;
-    pub const C4: u16 = 34;
-    pub const D4: u16 = 35;
-    pub const E4: u16 = 36;
-    pub const F4: u16 = 37;
-    pub const G4: u16 = 38;
-    pub const H4: u16 = 39;
-    pub const A3: u16 = 40;
-    pub const B3: u16 = 41;
-    pub const C3: u16 = 42;
-    pub const D3: u16 = 43;
-    pub const E3: u16 = 44;
-    pub const F3: u16 = 45;
-    pub const G3: u16 = 46;
-    pub const H3: u16 = 47;
-    pub const A2: u16 = 48;
-    pub const B2: u16 = 49;
-    pub const C2: u16 = 50;
-    pub const D2: u16 = 51;
-    pub const E2: u16 = 52;
-    pub const F2: u16 = 53;
-    pub const G2: u16 = 54;
-    pub const H2: u16 = 55;
-    pub const A1: u16 = 56;
-    pub const B1: u16 = 57;
-    pub const C1: u16 = 58;
-    pub const D1: u16 = 59;
-    pub const E1: u16 = 60;
-    pub const F1: u16 = 61;
-    pub const G1: u16 = 62;
-    pub const H1: u16 = 63;
-
-    pub const NUM: u16 = 64;
-
-    pub const WHITE_KING_SIDE_CASTLE_SQUARE: u16 = Self::G1;
-    pub const WHITE_QUEEN_SIDE_CASTLE_SQUARE: u16 = Self::C1;
-
-    pub const BLACK_KING_SIDE_CASTLE_SQAURE: u16 = Self::G8;
-    pub const BLACK_QUEEN_SIDE_CASTLE_SQAURE: u16 = Self::C8;
-
-    pub fn designator_str_from_index(index: u16) -> String {
-        let file = index % 8;
-        let rank = 7 - (index / 8);
-
-        let file_char = char::from_u32('A' as u32 + file as u32).unwrap();
-        let rank_char = char::from_u32('1' as u32 + rank as u32).unwrap();
-        [file_char, rank_char].iter().collect()
-    }
-
-    pub fn square_from_pos(x: u16, y: u16) -> u16 {
-        x + y * 8
-    }
-
-    pub fn add_offset(square: u16, x: i32, y: i32) -> Option<u16> {
-        let rank = (square as i32 / 8_i32) + y;
-        let file = (square as i32 % 8_i32) + x;
-
-        if rank < 0 || rank > 7 || file < 0 || file > 7 {
+    pub const A8: Square = Square(0);
+    pub const B8: Square = Square(1);
+    pub const C8: Square = Square(2);
+    pub const D8: Square = Square(3);
+    pub const E8: Square = Square(4);
+    pub const F8: Square = Square(5);
+    pub const G8: Square = Square(6);
+    pub const H8: Square = Square(7);
+    pub const A7: Square = Square(8);
+    pub const B7: Square = Square(9);
+    pub const C7: Square = Square(10);
+    pub const D7: Square = Square(11);
+    pub const E7: Square = Square(12);
+    pub const F7: Square = Square(13);
+    pub const G7: Square = Square(14);
+    pub const H7: Square = Square(15);
+    pub const A6: Square = Square(16);
+    pub const B6: Square = Square(17);
+    pub const C6: Square = Square(18);
+    pub const D6: Square = Square(19);
+    pub const E6: Square = Square(20);
+    pub const F6: Square = Square(21);
+    pub const G6: Square = Square(22);
+    pub const H6: Square = Square(23);
+    pub const A5: Square = Square(24);
+    pub const B5: Square = Square(25);
+    pub const C5: Square = Square(26);
+    pub const D5: Square = Square(27);
+    pub const E5: Square = Square(28);
+    pub const F5: Square = Square(29);
+    pub const G5: Square = Square(30);
+    pub const H5: Square = Square(31);
+    pub const A4: Square = Square(32);
+    pub const B4: Square = Square(33);
+    pub const C4: Square = Square(34);
+    pub const D4: Square = Square(35);
+    pub const E4: Square = Square(36);
+    pub const F4: Square = Square(37);
+    pub const G4: Square = Square(38);
+    pub const H4: Square = Square(39);
+    pub const A3: Square = Square(40);
+    pub const B3: Square = Square(41);
+    pub const C3: Square = Square(42);
+    pub const D3: Square = Square(43);
+    pub const E3: Square = Square(44);
+    pub const F3: Square = Square(45);
+    pub const G3: Square = Square(46);
+    pub const H3: Square = Square(47);
+    pub const A2: Square = Square(48);
+    pub const B2: Square = Square(49);
+    pub const C2: Square = Square(50);
+    pub const D2: Square = Square(51);
+    pub const E2: Square = Square(52);
+    pub const F2: Square = Square(53);
+    pub const G2: Square = Square(54);
+    pub const H2: Square = Square(55);
+    pub const A1: Square = Square(56);
+    pub const B1: Square = Square(57);
+    pub const C1: Square = Square(58);
+    pub const D1: Square = Square(59);
+    pub const E1: Square = Square(60);
+    pub const F1: Square = Square(61);
+    pub const G1: Square = Square(62);
+    pub const H1: Square = Square(63);
+
+    pub const NUM: usize = 64;
+
+    /// All squares in index order, from a8 to h1.
+    pub const ALL: [Square; Self::NUM] = {
+        let mut squares = [Square(0); Self::NUM];
+        let mut i = 0;
+        while i < Self::NUM {
+            squares[i] = Square(i as u8);
+            i += 1;
+        }
+        squares
+    };
+
+    pub const WHITE_KING_SIDE_CASTLE_SQUARE: Square = Self::G1;
+    pub const WHITE_QUEEN_SIDE_CASTLE_SQUARE: Square = Self::C1;
+
+    pub const BLACK_KING_SIDE_CASTLE_SQAURE: Square = Self::G8;
+    pub const BLACK_QUEEN_SIDE_CASTLE_SQAURE: Square = Self::C8;
+
+    pub const fn new(index: u8) -> Option<Square> {
+        if (index as usize) < Self::NUM {
+            Some(Square(index))
+        } else {
             None
+        }
+    }
+
+    /// Square for a bit index of a bitboard, which is always below 64.
+    #[inline(always)]
+    pub const fn from_index(index: usize) -> Square {
+        debug_assert!(index < Self::NUM);
+        Square(index as u8)
+    }
+
+    /// Square from its file (0 = a) and rank (0 = first rank).
+    pub const fn from_file_rank(file: u8, rank: u8) -> Option<Square> {
+        if file < 8 && rank < 8 {
+            Some(Square(file + (7 - rank) * 8))
         } else {
-            Some(file as u16 + rank as u16 * 8)
+            None
         }
     }
 
-    pub fn from_square_name(name: &str) -> Result<Option<u8>, ()> {
-        if name == "-" {
-            return Ok(None);
+    /// Square on the given file and rank, unlike `from_file_rank` this can not fail.
+    pub const fn at(file: File, rank: Rank) -> Square {
+        Square(file.index() + (7 - rank.index()) * 8)
+    }
+
+    #[inline(always)]
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// File of the square, 0 for the a-file.
+    #[inline(always)]
+    pub const fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    /// Rank of the square, 0 for the first rank.
+    #[inline(always)]
+    pub const fn rank(self) -> u8 {
+        7 - self.0 / 8
+    }
+
+    /// File of the square as a `File`.
+    pub const fn file_of(self) -> File {
+        File::ALL[self.file() as usize]
+    }
+
+    /// Rank of the square as a `Rank`.
+    pub const fn rank_of(self) -> Rank {
+        Rank::ALL[self.rank() as usize]
+    }
+
+    /// Square `files` towards the h-file and `ranks` towards the eighth rank away, if that
+    /// is still on the board.
+    pub const fn offset(self, files: i32, ranks: i32) -> Option<Square> {
+        let file = self.file() as i32 + files;
+        let rank = self.rank() as i32 + ranks;
+        if file < 0 || file > 7 || rank < 0 || rank > 7 {
+            None
+        } else {
+            Self::from_file_rank(file as u8, rank as u8)
         }
+    }
 
-        if name.len() != 2 {
-            return Err(());
+    /// Same file on the opposite rank, e.g. e2 becomes e7.
+    #[inline(always)]
+    pub const fn flip_vertical(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    /// Same rank on the opposite file, e.g. b1 becomes g1.
+    #[inline(always)]
+    pub const fn flip_horizontal(self) -> Square {
+        Square(self.0 ^ 7)
+    }
+
+    /// The square as seen from `color`'s side of the board, flipped vertically for black.
+    #[inline(always)]
+    pub const fn relative_to(self, color: PieceColor) -> Square {
+        match color {
+            PieceColor::White => self,
+            PieceColor::Black => self.flip_vertical(),
         }
+    }
+
+    pub const fn file_distance(self, other: Square) -> u8 {
+        self.file().abs_diff(other.file())
+    }
 
-        let col_designator = name.chars().nth(0).unwrap();
-        let row_designator = name.chars().nth(1).unwrap();
+    pub const fn rank_distance(self, other: Square) -> u8 {
+        self.rank().abs_diff(other.rank())
+    }
 
-        if col_designator < 'a' || col_designator > 'h' {
-            return Err(());
+    /// Number of king moves between the squares.
+    pub const fn distance(self, other: Square) -> u8 {
+        let files = self.file_distance(other);
+        let ranks = self.rank_distance(other);
+        if files > ranks {
+            files
+        } else {
+            ranks
         }
+    }
 
-        if row_designator < '1' || row_designator > '9' {
-            return Err(());
+    /// Color of the square on the board, a1 is dark.
+    pub const fn color(self) -> PieceColor {
+        if (self.file() + self.rank()) % 2 == 1 {
+            PieceColor::White
+        } else {
+            PieceColor::Black
         }
+    }
+}
+
+/// Column of the board, `A` is the queen side edge.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+pub enum File {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+}
 
-        let col = col_designator as u8 - 'a' as u8;
-        let row = 7 - (row_designator as u8 - '1' as u8);
+impl File {
+    pub const ALL: [File; 8] = [
+        File::A,
+        File::B,
+        File::C,
+        File::D,
+        File::E,
+        File::F,
+        File::G,
+        File::H,
+    ];
 
-        Ok(Some(col + row * 8))
+    /// Index as returned by `Square::file`, 0 for the a-file.
+    pub const fn index(self) -> u8 {
+        self as u8
     }
+}
+
+/// Row of the board, counted from white's side.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+pub enum Rank {
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+    Eighth,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 8] = [
+        Rank::First,
+        Rank::Second,
+        Rank::Third,
+        Rank::Fourth,
+        Rank::Fifth,
+        Rank::Sixth,
+        Rank::Seventh,
+        Rank::Eighth,
+    ];
 
-    pub fn to_square_name(square: Option<u8>) -> String {
-        if square.is_none() {
-            return "-".to_string();
+    /// Index as returned by `Square::rank`, 0 for the first rank.
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// The rank as seen from `color`'s side, e.g. a black pawn starts on its second rank.
+    pub const fn relative_to(self, color: PieceColor) -> Rank {
+        match color {
+            PieceColor::White => self,
+            PieceColor::Black => Rank::ALL[7 - self as usize],
         }
-        let mut name = String::with_capacity(2);
+    }
+}
 
-        let rank = square.unwrap() / 8;
-        let file = square.unwrap() % 8;
+impl Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", (b'a' + self.index()) as char)
+    }
+}
 
-        let new_file: char = ('a' as u8 + file as u8).into();
-        let new_rank: char = ('8' as u8 - rank as u8).into();
+impl Display for Rank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", (b'1' + self.index()) as char)
+    }
+}
 
-        name.push(new_file);
-        name.push(new_rank);
+impl Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.file_of(), self.rank_of())
+    }
+}
+
+impl TryFrom<&str> for Square {
+    type Error = ();
+
+    /// Parses a lowercase square name like `e4`.
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Square::from_file_rank(file - b'a', rank - b'1').ok_or(())
+            }
+            _ => Err(()),
+        }
+    }
+}
 
-        name
+impl From<Square> for usize {
+    fn from(square: Square) -> Self {
+        square.index()
     }
+}
 
-    pub fn square_color(square: u16) -> PieceColor {
-        if square % 2 == 0 {
-            PieceColor::White
-        } else {
-            PieceColor::Black
+#[cfg(test)]
+mod square_tests {
+    use super::{File, Rank, Square};
+    use crate::chess::board::PieceColor;
+
+    #[test]
+    fn square_api() {
+        for (i, square) in Square::ALL.iter().enumerate() {
+            assert_eq!(square.index(), i);
+            assert_eq!(Square::at(square.file_of(), square.rank_of()), *square);
+            assert_eq!(Square::try_from(square.to_string().as_str()), Ok(*square));
+            assert_eq!(square.flip_vertical().flip_vertical(), *square);
         }
+        assert_eq!(Square::at(File::E, Rank::Fourth), Square::E4);
+        assert!(Square::try_from("i1").is_err());
+        assert!(Square::try_from("E4").is_err());
+        assert!(Square::try_from("-").is_err());
+
+        assert_eq!(Square::E2.flip_vertical(), Square::E7);
+        assert_eq!(Square::B1.flip_horizontal(), Square::G1);
+        assert_eq!(Square::E2.relative_to(PieceColor::Black), Square::E7);
+        assert_eq!(Rank::Second.relative_to(PieceColor::Black), Rank::Seventh);
+
+        assert_eq!(Square::A1.offset(1, 2), Some(Square::B3));
+        assert_eq!(Square::H8.offset(1, 0), None);
+        assert_eq!(Square::A1.distance(Square::H8), 7);
+        assert_eq!(Square::E4.distance(Square::F6), 2);
+        assert_eq!(Square::E4.file_distance(Square::B4), 3);
     }
 }
@@ -1,4 +1,6 @@
-use super::board::{CastlingRights, ChessPiece, PieceColor};
+use super::board::{CastlingRights, ChessBoard, ChessBoardState, ChessPiece, PieceColor};
+use crate::chess::square::Square;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 const ZHASH_TABLE: [u64; 781] = [
@@ -202,7 +204,8 @@ const ZHASH_TABLE: [u64; 781] = [
 
 
 
-#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Hash, Default, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ZHash(pub u64);
 
 impl ZHash {
@@ -213,21 +216,19 @@ impl ZHash {
     }
 
     #[inline(always)]
-    fn to_zobrist_pos(pos: usize) -> usize {
-        let file = pos % 8;
-        let rank = 7 - pos / 8;
-        8 * rank + file
+    fn to_zobrist_pos(square: Square) -> usize {
+        8 * square.rank() as usize + square.file() as usize
     }
 
     #[inline(always)]
-    pub fn toggle_piece_at_pos(&mut self, piece: ChessPiece, color: PieceColor, pos: usize) {
-        let hash_pos = Self::kind_of_piece(piece, color) * 64 + Self::to_zobrist_pos(pos);
+    pub fn toggle_piece_at_pos(&mut self, piece: ChessPiece, color: PieceColor, square: Square) {
+        let hash_pos = Self::kind_of_piece(piece, color) * 64 + Self::to_zobrist_pos(square);
         self.0 ^= ZHASH_TABLE[hash_pos];
     }
 
     #[inline(always)]
-    pub fn toggle_enpassant(&mut self, pos: usize) {
-        self.0 ^= ZHASH_TABLE[772 + pos % 8];
+    pub fn toggle_enpassant(&mut self, square: Square) {
+        self.0 ^= ZHASH_TABLE[772 + square.file() as usize];
     }
 
     #[inline(always)]
@@ -256,12 +257,106 @@ impl ZHash {
     pub fn toggle_side(&mut self) {
         self.0 ^= ZHASH_TABLE[780];
     }
+
+    /// Key over the pawns only, equal for all positions sharing the same pawn structure.
+    pub fn from_pawns(board: &ChessBoard) -> Self {
+        let mut zhash = ZHash::default();
+        for color in [PieceColor::White, PieceColor::Black] {
+            for square in board.get_piece_bitboard(ChessPiece::Pawn, color) {
+                zhash.toggle_piece_at_pos(ChessPiece::Pawn, color, square);
+            }
+        }
+        zhash
+    }
+
+    /// Key of the position computed from scratch, the incremental updates always have to
+    /// end up with the same one.
+    pub fn from_scratch(board_state: &ChessBoardState) -> Self {
+        let mut zhash = Self::state_key(board_state);
+        for color in [PieceColor::White, PieceColor::Black] {
+            for (square, piece) in board_state.board.pieces(color) {
+                zhash.toggle_piece_at_pos(piece, color, square);
+            }
+        }
+        zhash
+    }
+
+    /// Key of everything but the pieces: the side to move, castling rights and the en
+    /// passant square. Like in polyglot the en passant square only counts if a pawn of the
+    /// side to move can capture there.
+    fn state_key(board_state: &ChessBoardState) -> Self {
+        let mut zhash = ZHash::default();
+        if board_state.side == PieceColor::White {
+            zhash.toggle_side();
+        }
+        zhash.swap_castling_rights(&CastlingRights(0), &board_state.castling_rights);
+        if let Some(ep_target) = board_state.en_passant_target {
+            if !board_state
+                .board
+                .pawns_able_to_enpassant(board_state.side, ep_target)
+                .is_empty()
+            {
+                zhash.toggle_enpassant(ep_target);
+            }
+        }
+        zhash
+    }
+}
+
+/// Keeps the position key and pawn key in step while a move is played. Pieces are moved
+/// through it, and the keys of the side to move, castling rights and en passant square
+/// are removed for the old position on creation and added for the new one by `finish`,
+/// so none of them can be toggled twice or forgotten.
+pub struct ZobristUpdater {
+    zhash: ZHash,
+    pawn_hash: ZHash,
+}
+
+impl ZobristUpdater {
+    pub fn new(board_state: &ChessBoardState) -> Self {
+        Self {
+            zhash: ZHash(board_state.zhash.0 ^ ZHash::state_key(board_state).0),
+            pawn_hash: board_state.pawn_hash,
+        }
+    }
+
+    pub fn place_piece(
+        &mut self,
+        board: &mut ChessBoard,
+        piece: ChessPiece,
+        color: PieceColor,
+        square: Square,
+    ) {
+        board.place_piece_of_color(piece, color, square, &mut self.zhash);
+        if piece == ChessPiece::Pawn {
+            self.pawn_hash.toggle_piece_at_pos(piece, color, square);
+        }
+    }
+
+    pub fn remove_piece(
+        &mut self,
+        board: &mut ChessBoard,
+        piece: ChessPiece,
+        color: PieceColor,
+        square: Square,
+    ) {
+        board.remove_piece_at_pos(piece, color, square, &mut self.zhash);
+        if piece == ChessPiece::Pawn {
+            self.pawn_hash.toggle_piece_at_pos(piece, color, square);
+        }
+    }
+
+    /// Writes the keys to the new position, which has to be complete apart from them.
+    pub fn finish(self, board_state: &mut ChessBoardState) {
+        board_state.zhash = ZHash(self.zhash.0 ^ ZHash::state_key(board_state).0);
+        board_state.pawn_hash = self.pawn_hash;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chess::board::ChessBoardState;
+    use crate::chess::{board::ChessBoardState, movegen_fuzz::random_playouts};
 
     #[test]
     fn test_kind_of_piece() {
@@ -361,4 +456,43 @@ mod tests {
             0x5c3f9b829b279560,
         );
     }
+
+    #[test]
+    fn test_pawn_hash_incremental_update() {
+        // Covers captures of pawns, en passant and promotions with and without capture
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        ];
+
+        for fen in fens {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            for mv in board_state.generate_legal_moves_for_current_player::<false>() {
+                let new_state = board_state.exec_move(mv);
+                assert_eq!(new_state.pawn_hash, ZHash::from_pawns(&new_state.board));
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_hash_matches_scratch_hash() {
+        let starts = [
+            ChessBoardState::starting_state(),
+            // Castling, en passant and promotions come up early from here
+            ChessBoardState::from_fen(
+                "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/P1PBBPpP/R3K2R w KQkq - 0 1",
+            )
+            .unwrap(),
+        ];
+        random_playouts(&starts, 0x1910, 2000, 80, |board_state| {
+            assert_eq!(
+                board_state.zhash,
+                ZHash::from_scratch(board_state),
+                "{}",
+                board_state.to_fen()
+            );
+            assert_eq!(board_state.pawn_hash, ZHash::from_pawns(&board_state.board));
+        });
+    }
 }
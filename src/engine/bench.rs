@@ -0,0 +1,113 @@
+use std::{
+    fmt::Display,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use super::{bot::ChessBot, clock::Instant, time_control::TimeControl};
+use crate::{chess::board::ChessBoardState, log_debug};
+
+/// Depth every position of the bench is searched to unless another one is given.
+pub const BENCH_DEPTH: u16 = 5;
+
+/// Positions of the bench, openings after the book, middlegames and endgames with both
+/// sides to move. Changing them changes the node signature.
+pub const BENCH_POSITIONS: [&str; 12] = [
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "rnbqkb1r/pp3ppp/4pn2/2pp4/3P4/2P1PN2/PP3PPP/RNBQKB1R w KQkq - 0 5",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R b KQ - 2 8",
+    "2rq1rk1/pb1nbppp/1p2pn2/2pp4/2PP4/1PN1PN2/PB2BPPP/2RQ1RK1 w - - 4 11",
+    "r2q1rk1/1b2bppp/p2ppn2/1p6/3BP3/1BN5/PPP2PPP/R2Q1RK1 b - - 1 13",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "6k1/pp3ppp/2p5/3r4/3P4/2P3P1/PP3P1P/4R1K1 b - - 0 25",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P1K2/8/8/8 w - - 0 50",
+    "8/5pk1/6p1/3Q4/8/6P1/5PK1/3q4 b - - 0 40",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    /// Nodes of all searches, the signature which stays the same as long as the search
+    /// does.
+    pub nodes: u64,
+    pub time_ms: u64,
+}
+
+impl BenchResult {
+    pub fn nps(&self) -> u64 {
+        1000 * self.nodes / self.time_ms.max(1)
+    }
+}
+
+impl Display for BenchResult {
+    /// Ends with `<nodes> nodes <nps> nps`, the line OpenBench reads the signature and
+    /// speed from.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Total time (ms) : {}", self.time_ms)?;
+        writeln!(f, "Nodes searched  : {}", self.nodes)?;
+        writeln!(f, "Nodes/second    : {}", self.nps())?;
+        write!(f, "{} nodes {} nps", self.nodes, self.nps())
+    }
+}
+
+/// Searches every bench position with a fresh game to a fixed depth. The node count only
+/// depends on the bot's search, so it verifies that a refactoring did not change it. The
+/// searches print nothing, the result is the only output of a bench.
+pub fn run_bench<B: ChessBot>(bot: &mut B, depth: u16) -> BenchResult {
+    // Book moves skip the search, bots without a book reject the option
+    let _ = bot.apply_option("OpeningBook", Some("false"));
+    bot.set_quiet(true);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let start = Instant::now();
+    let mut nodes = 0;
+    for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+        let mut board_state = ChessBoardState::from_fen(fen).unwrap();
+        bot.new_game();
+        bot.search_best_move(
+            &mut board_state,
+            TimeControl::FixedDepth(depth as u64),
+            &stop,
+        );
+        log_debug!(
+            "Bench position {}/{}: {} nodes",
+            i + 1,
+            BENCH_POSITIONS.len(),
+            bot.nodes_searched()
+        );
+        nodes += bot.nodes_searched();
+    }
+
+    BenchResult {
+        nodes,
+        time_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use super::{run_bench, BENCH_POSITIONS};
+    use crate::{
+        chess::board::ChessBoardState,
+        engine::{bot::ChessBot, bots::nplytranspo_bot::NPlyTranspoBot},
+    };
+
+    #[test]
+    fn bench_is_deterministic() {
+        for fen in BENCH_POSITIONS {
+            assert!(ChessBoardState::from_fen(fen).is_ok(), "{}", fen);
+        }
+
+        let mut bot = NPlyTranspoBot::default();
+        let first = run_bench(&mut bot, 3);
+        assert!(first.nodes > 0);
+        // A used bot and a fresh one agree
+        assert_eq!(run_bench(&mut bot, 3).nodes, first.nodes);
+        assert_eq!(
+            run_bench(&mut NPlyTranspoBot::default(), 3).nodes,
+            first.nodes
+        );
+        assert!(bot.nodes_searched() > 0);
+    }
+}
@@ -1,39 +1,122 @@
+use std::cell::RefCell;
+
 use lerp::{num_traits::clamp, Lerp};
 
 use crate::chess::{
     bitboard::BitBoard,
-    board::{ChessBoardState, ChessPiece, PieceColor},
+    board::{ChessBoard, ChessBoardState, ChessPiece, PieceColor},
     square::Square,
 };
 
+use super::eval_cache::EvalCache;
+
+const PAWN_HASH_TABLE_SIZE: usize = 16 * 1024;
+
+thread_local! {
+    static PAWN_HASH_TABLE: RefCell<EvalCache<PawnStructure, PAWN_HASH_TABLE_SIZE>> =
+        RefCell::new(EvalCache::default());
+}
+
 pub trait EvaluationFunction {
     fn eval(board_state: &ChessBoardState) -> i32;
+
+    /// Contribution of every term to `eval`, `None` for evaluations which are not split into
+    /// terms.
+    fn eval_breakdown(_board_state: &ChessBoardState) -> Option<EvalBreakdown> {
+        None
+    }
+}
+
+/// Score of one evaluation term for each side, the term adds `white - black` to the
+/// evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub name: &'static str,
+    pub white: i32,
+    pub black: i32,
+}
+
+impl EvalTerm {
+    pub fn total(&self) -> i32 {
+        self.white - self.black
+    }
+}
+
+/// Evaluation split into its terms, printed as a table by the UCI `eval` command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalBreakdown {
+    pub terms: Vec<EvalTerm>,
+    /// 0.0 in the opening up to 1.0 in the endgame, how far the tables and terms are shifted
+    /// towards their endgame values.
+    pub phase: f32,
+    pub total: i32,
+}
+
+impl std::fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pawns = |centipawns: i32| format!("{:.2}", centipawns as f32 / 100.0);
+        writeln!(f, "      Term        |  White  |  Black  |  Total")?;
+        writeln!(f, "------------------+---------+---------+---------")?;
+        for term in &self.terms {
+            writeln!(
+                f,
+                "{:>17} | {:>7} | {:>7} | {:>7}",
+                term.name,
+                pawns(term.white),
+                pawns(term.black),
+                pawns(term.total())
+            )?;
+        }
+        writeln!(f, "------------------+---------+---------+---------")?;
+        writeln!(
+            f,
+            "{:>17} |         |         | {:>6.0}%",
+            "endgame phase",
+            self.phase * 100.0
+        )?;
+        write!(
+            f,
+            "{:>17} |         |         | {:>7}",
+            "total",
+            pawns(self.total)
+        )
+    }
 }
 
 // Strategy: Value per Piece on either side
 pub struct PieceCountEvaluation;
 impl EvaluationFunction for PieceCountEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
-        let calc_piece_val_sum = |bitboards: &[BitBoard]| -> i32 {
-            bitboards
-                .iter()
-                .enumerate()
-                .map(|(p, board)| board.bit_count() * (ChessPiece::from(p).eval_value()))
-                .sum::<u32>() as i32
-        };
+        Self::eval_side(board_state, PieceColor::White)
+            - Self::eval_side(board_state, PieceColor::Black)
+    }
+}
 
-        calc_piece_val_sum(&board_state.board.white_pieces)
-            - calc_piece_val_sum(&board_state.board.black_pieces)
+impl PieceCountEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        let bitboards = match color {
+            PieceColor::White => &board_state.board.white_pieces,
+            PieceColor::Black => &board_state.board.black_pieces,
+        };
+        bitboards
+            .iter()
+            .enumerate()
+            .map(|(p, board)| board.bit_count() * (ChessPiece::from(p).eval_value()))
+            .sum::<u32>() as i32
     }
 }
 
-fn endgame_lerp_value(board_state: &ChessBoardState) -> f32 {
+/// Game phase between 0.0 with all pieces on the board and 1.0 once few of them are left.
+pub fn endgame_lerp_value(board_state: &ChessBoardState) -> f32 {
     // Get number of pieces (non-pawns and non-kings)
-    let piece_count: f32 = ((board_state.board.all_black_pieces
-        | board_state.board.all_white_pieces)
-        & (!board_state.board.white_pieces[ChessPiece::Pawn as usize])
-        & (!board_state.board.black_pieces[ChessPiece::Pawn as usize]))
-        .bit_count() as f32
+    let piece_count: f32 = (board_state.board.occupancy()
+        & !board_state
+            .board
+            .get_piece_bitboard(ChessPiece::Pawn, PieceColor::White)
+        & !board_state
+            .board
+            .get_piece_bitboard(ChessPiece::Pawn, PieceColor::Black))
+    .bit_count() as f32
         - 2.0;
 
     clamp(-0.1 * piece_count + 1.4, 0.0, 1.0)
@@ -138,255 +221,428 @@ pub struct PieceSquareTableEvaluation;
 impl EvaluationFunction for PieceSquareTableEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
         let endgame_factor = endgame_lerp_value(board_state);
+        Self::eval_sqt(&board_state.board, PieceColor::White, endgame_factor)
+            - Self::eval_sqt(&board_state.board, PieceColor::Black, endgame_factor)
+    }
+}
 
-        #[inline(always)]
-        fn eval_sqt(bitboards: &[BitBoard], color: PieceColor, endgame_factor: f32) -> i32 {
-            bitboards
-                .iter()
-                .enumerate()
-                .fold(0, |mut sum, (piece, board)| {
-                    if board.0 == 0 {
-                        return sum;
-                    }
-
-                    let square_table = &GLOBAL_PIECE_SQUARE_TABLE[piece];
-
-                    for i in board.into_iter() {
-                        let table_pos = if color == PieceColor::White {
-                            i
-                        } else {
-                            63 - i
-                        };
-
-                        let piece_value = match ChessPiece::from(piece) {
-                            ChessPiece::King => (square_table[table_pos] as f32)
-                                .lerp(KING_END_GAME_TABLE[table_pos] as f32, endgame_factor)
-                                as i32,
-                            ChessPiece::Pawn => (square_table[table_pos] as f32)
-                                .lerp(PAWN_END_GAME_TABLE[table_pos] as f32, endgame_factor)
-                                as i32,
-                            _ => square_table[table_pos],
-                        };
-
-                        sum += piece_value;
-                    }
-
-                    sum
-                })
+impl PieceSquareTableEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        Self::eval_sqt(&board_state.board, color, endgame_lerp_value(board_state))
+    }
+
+    #[inline(always)]
+    fn eval_sqt(board: &ChessBoard, color: PieceColor, endgame_factor: f32) -> i32 {
+        board
+            .pieces(color)
+            .map(|(square, piece)| {
+                // The tables are drawn from white's side, black looks them up mirrored
+                let table_pos = square.relative_to(color).index();
+
+                let square_table = &GLOBAL_PIECE_SQUARE_TABLE[piece as usize];
+                match piece {
+                    ChessPiece::King => (square_table[table_pos] as f32)
+                        .lerp(KING_END_GAME_TABLE[table_pos] as f32, endgame_factor)
+                        as i32,
+                    ChessPiece::Pawn => (square_table[table_pos] as f32)
+                        .lerp(PAWN_END_GAME_TABLE[table_pos] as f32, endgame_factor)
+                        as i32,
+                    _ => square_table[table_pos],
+                }
+            })
+            .sum()
+    }
+}
+
+/// Evaluation terms that only depend on the pawns, cached by the pawn hash of the position.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct PawnStructure {
+    pub passed_pawns: [BitBoard; 2],
+    pub doubled_pawns: [BitBoard; 2],
+    /// Pawns without own pawns on the neighbor files.
+    pub isolated_pawns: [BitBoard; 2],
+    /// Pawns behind all own pawns on the neighbor files whose stop square is held by an
+    /// enemy pawn, so they can neither advance nor be defended by a pawn.
+    pub backward_pawns: [BitBoard; 2],
+    /// Groups of pawns on adjacent files.
+    pub pawn_islands: [u8; 2],
+}
+
+impl PawnStructure {
+    pub fn new(board: &ChessBoard) -> Self {
+        let mut pawn_structure = Self::default();
+        for color in [PieceColor::White, PieceColor::Black] {
+            let own_pawns = board.get_piece_bitboard(ChessPiece::Pawn, color);
+            let opposing_pawns = board.get_piece_bitboard(ChessPiece::Pawn, !color);
+
+            for pawn in own_pawns {
+                let pp_mask = PassedPawnEvaluation::mask_infront_of_pawn(pawn, color)
+                    & PassedPawnEvaluation::mask_neighbor_file_of_pawn(pawn);
+                if opposing_pawns & pp_mask == BitBoard::EMPTY {
+                    pawn_structure.passed_pawns[color as usize] =
+                        pawn_structure.passed_pawns[color as usize].set_bit(pawn.index());
+                }
+            }
+
+            let shifted_pawns = match color {
+                PieceColor::White => own_pawns.s_no(),
+                PieceColor::Black => own_pawns.s_so(),
+            };
+            pawn_structure.doubled_pawns[color as usize] = own_pawns & shifted_pawns;
+
+            let enemy_pawn_attacks = match color {
+                PieceColor::White => opposing_pawns.s_so_we() | opposing_pawns.s_so_ea(),
+                PieceColor::Black => opposing_pawns.s_no_we() | opposing_pawns.s_no_ea(),
+            };
+            for pawn in own_pawns {
+                let neighbor_files = PassedPawnEvaluation::mask_neighbor_file_of_pawn(pawn)
+                    & !BitBoard::FILES[pawn.file() as usize];
+                let neighbors = own_pawns & neighbor_files;
+                if neighbors.is_empty() {
+                    pawn_structure.isolated_pawns[color as usize] =
+                        pawn_structure.isolated_pawns[color as usize].set_bit(pawn.index());
+                    continue;
+                }
+                let level_or_behind = !PassedPawnEvaluation::mask_infront_of_pawn(pawn, color);
+                let stop_square = PassedPawnEvaluation::stop_square(pawn, color);
+                if (neighbors & level_or_behind).is_empty()
+                    && !(stop_square & enemy_pawn_attacks).is_empty()
+                {
+                    pawn_structure.backward_pawns[color as usize] =
+                        pawn_structure.backward_pawns[color as usize].set_bit(pawn.index());
+                }
+            }
+
+            let pawn_files = (0..8)
+                .filter(|file| !(own_pawns & BitBoard::FILES[*file]).is_empty())
+                .fold(0u8, |files, file| files | 1 << file);
+            pawn_structure.pawn_islands[color as usize] =
+                (pawn_files & !(pawn_files << 1)).count_ones() as u8;
         }
+        pawn_structure
+    }
 
-        eval_sqt(
-            &board_state.board.white_pieces,
-            PieceColor::White,
-            endgame_factor,
-        ) - eval_sqt(
-            &board_state.board.black_pieces,
-            PieceColor::Black,
-            endgame_factor,
-        )
+    /// Pawn structure of the position, only computed if it is missing from the pawn hash table
+    /// of the current thread.
+    pub fn probe(board_state: &ChessBoardState) -> Self {
+        PAWN_HASH_TABLE.with(|table| {
+            table
+                .borrow_mut()
+                .get_or_insert_with(board_state.pawn_hash, || Self::new(&board_state.board))
+        })
     }
 }
 
-// Strategy: Give Bonus for Passed Pawns
+// Strategy: Give Bonus for Passed Pawns, more if other pawns support them and less if
+// the enemy holds the square in front of them
 pub struct PassedPawnEvaluation;
 impl EvaluationFunction for PassedPawnEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
         let endgame_factor = endgame_lerp_value(board_state);
-        let eval_passed_pawns = |color: PieceColor| -> i32 {
-            let own_pawns = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Pawn, color);
-            let opposing_pawns = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Pawn, !color);
+        let pawn_structure = PawnStructure::probe(board_state);
+        Self::eval_passed_pawns(
+            board_state,
+            &pawn_structure,
+            endgame_factor,
+            PieceColor::White,
+        ) - Self::eval_passed_pawns(
+            board_state,
+            &pawn_structure,
+            endgame_factor,
+            PieceColor::Black,
+        )
+    }
+}
 
-            let mut bonus = 0;
+impl PassedPawnEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        let pawn_structure = PawnStructure::probe(board_state);
+        Self::eval_passed_pawns(
+            board_state,
+            &pawn_structure,
+            endgame_lerp_value(board_state),
+            color,
+        )
+    }
 
-            for pawn in own_pawns {
-                let pp_mask = Self::mask_infront_of_pawn(pawn as u64, color)
-                    & Self::mask_neighbor_file_of_pawn(pawn as u64);
-                if opposing_pawns & pp_mask == BitBoard::EMPTY {
-                    bonus +=
-                        (endgame_factor * Self::bonus_for_passed_pawn(pawn, color) as f32) as i32;
+    fn eval_passed_pawns(
+        board_state: &ChessBoardState,
+        pawn_structure: &PawnStructure,
+        endgame_factor: f32,
+        color: PieceColor,
+    ) -> i32 {
+        const PROTECTED_PASSER_BONUS: i32 = 20;
+        const CONNECTED_PASSER_BONUS: i32 = 15;
+
+        let passed_pawns = pawn_structure.passed_pawns[color as usize];
+        if passed_pawns.is_empty() {
+            return 0;
+        }
+        let board = &board_state.board;
+        let own_pawns = board.get_piece_bitboard(ChessPiece::Pawn, color);
+        let enemy_pieces = board.color_occupancy(!color);
+        let enemy_attacks = board.squares_attacked_by_side(!color, false);
+
+        passed_pawns
+            .into_iter()
+            .map(|pawn| {
+                let mut bonus = Self::bonus_for_passed_pawn(pawn, color);
+                if !(ChessBoard::pawn_attacks(pawn, !color) & own_pawns).is_empty() {
+                    bonus += PROTECTED_PASSER_BONUS;
                 }
-            }
-            bonus
+                if !(Self::mask_connected_to_pawn(pawn) & passed_pawns).is_empty() {
+                    bonus += CONNECTED_PASSER_BONUS;
+                }
+                // Halved while the pawn can not safely advance
+                let stop_square = Self::stop_square(pawn, color);
+                if !((enemy_pieces | enemy_attacks) & stop_square).is_empty() {
+                    bonus /= 2;
+                }
+                (endgame_factor * bonus as f32) as i32
+            })
+            .sum()
+    }
+
+    /// Ranks the pawn still has to cross, on all files.
+    fn mask_infront_of_pawn(square: Square, color: PieceColor) -> BitBoard {
+        let rank = square.rank() as usize;
+        let ranks = match color {
+            PieceColor::White => &BitBoard::RANKS[rank + 1..],
+            PieceColor::Black => &BitBoard::RANKS[..rank],
         };
+        ranks
+            .iter()
+            .fold(BitBoard::EMPTY, |mask, rank| mask | *rank)
+    }
 
-        eval_passed_pawns(PieceColor::White) - eval_passed_pawns(PieceColor::Black)
+    /// Squares next to the pawn and diagonally next to it, where a pawn is connected to it.
+    fn mask_connected_to_pawn(square: Square) -> BitBoard {
+        let rank = square.rank() as usize;
+        let ranks = &BitBoard::RANKS[rank.saturating_sub(1)..(rank + 2).min(8)];
+        let neighbor_files =
+            Self::mask_neighbor_file_of_pawn(square) & !BitBoard::FILES[square.file() as usize];
+        neighbor_files
+            & ranks
+                .iter()
+                .fold(BitBoard::EMPTY, |mask, rank| mask | *rank)
     }
-}
 
-impl PassedPawnEvaluation {
-    fn mask_infront_of_pawn(pos: u64, color: PieceColor) -> BitBoard {
-        let rank_index = pos / 8;
-        if color == PieceColor::White {
-            BitBoard(0xFFFFFFFFFFFFFFFF >> (8 * (7 - rank_index + 1)))
-        } else {
-            BitBoard(0xFFFFFFFFFFFFFFFF << (8 * (rank_index + 1)))
+    /// Square the pawn advances to next, empty for a pawn on its last rank.
+    fn stop_square(square: Square, color: PieceColor) -> BitBoard {
+        let square_bb = BitBoard::from_square(square);
+        match color {
+            PieceColor::White => square_bb.s_no(),
+            PieceColor::Black => square_bb.s_so(),
         }
     }
 
-    pub fn mask_neighbor_file_of_pawn(pos: u64) -> BitBoard {
-        let file_index = pos % 8;
-        const A_FILE_MASK: u64 = 0x101010101010101;
+    pub fn mask_neighbor_file_of_pawn(square: Square) -> BitBoard {
+        let file_index = square.file() as usize;
 
-        let mut mask = A_FILE_MASK << file_index;
+        let mut mask = BitBoard::FILES[file_index];
         if file_index > 0 {
-            mask |= A_FILE_MASK << (file_index - 1);
+            mask |= BitBoard::FILES[file_index - 1];
         }
         if file_index < 7 {
-            mask |= A_FILE_MASK << (file_index + 1);
+            mask |= BitBoard::FILES[file_index + 1];
         }
-        BitBoard(mask)
+        mask
     }
 
-    pub fn bonus_for_passed_pawn(pos: usize, color: PieceColor) -> i32 {
-        const BONUS_FOR_PASSED_PAWN: [i32; 8] = [0, 120, 80, 50, 30, 15, 15, 0];
-        let rank = pos / 8;
-        if color == PieceColor::White {
-            BONUS_FOR_PASSED_PAWN[rank]
-        } else {
-            BONUS_FOR_PASSED_PAWN[7 - rank]
-        }
+    pub fn bonus_for_passed_pawn(square: Square, color: PieceColor) -> i32 {
+        // Indexed by the rank relative to the pawn's side
+        const BONUS_FOR_PASSED_PAWN: [i32; 8] = [0, 15, 15, 30, 50, 80, 120, 0];
+        BONUS_FOR_PASSED_PAWN[square.rank_of().relative_to(color).index() as usize]
     }
 }
 
-// Strategy Give Bonus for having the bishop pair
+// Strategy: Give Bonus for having bishops on both square colors and punish bishops
+// hemmed in by own pawns on their color, blocked pawns count twice
 pub struct BishopPairEvaluation;
 impl EvaluationFunction for BishopPairEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
-        let eval_bishop_pair = |color: PieceColor| -> i32 {
-            let bishop_board = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Bishop, color);
-
-            let mut white_bishop_count = 0;
-            let mut black_bishop_count = 0;
-
-            for bishop in bishop_board {
-                match Square::square_color(bishop as u16) {
-                    PieceColor::White => white_bishop_count += 1,
-                    PieceColor::Black => black_bishop_count += 1,
-                }
-            }
+        Self::eval_side(board_state, PieceColor::White)
+            - Self::eval_side(board_state, PieceColor::Black)
+    }
+}
 
-            /* Half a pawn bonus for the pair */
-            if white_bishop_count >= 1 && black_bishop_count >= 1 {
-                ChessPiece::Pawn.eval_value() as i32 / 2
-            } else {
-                0
-            }
+impl BishopPairEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        const BAD_BISHOP_PENALTY_PER_PAWN: i32 = -3;
+
+        let board = &board_state.board;
+        let bishops = board.get_piece_bitboard(ChessPiece::Bishop, color);
+        if bishops.is_empty() {
+            return 0;
+        }
+        let occupancy = board.all_white_pieces | board.all_black_pieces;
+        let own_pawns = board.get_piece_bitboard(ChessPiece::Pawn, color);
+        let blocked_pawns = match color {
+            PieceColor::White => own_pawns & occupancy.s_so(),
+            PieceColor::Black => own_pawns & occupancy.s_no(),
         };
 
-        eval_bishop_pair(PieceColor::White) - eval_bishop_pair(PieceColor::Black)
+        let mut eval = 0;
+        for square_color in [BitBoard::LIGHT_SQUARES, BitBoard::DARK_SQUARES] {
+            let bishop_count = (bishops & square_color).bit_count() as i32;
+            let pawns_on_color =
+                (own_pawns & square_color).bit_count() + (blocked_pawns & square_color).bit_count();
+            eval += bishop_count * pawns_on_color as i32 * BAD_BISHOP_PENALTY_PER_PAWN;
+        }
+
+        /* Half a pawn bonus for the pair, two bishops of the same color are no pair */
+        if !(bishops & BitBoard::LIGHT_SQUARES).is_empty()
+            && !(bishops & BitBoard::DARK_SQUARES).is_empty()
+        {
+            eval += ChessPiece::Pawn.eval_value() as i32 / 2;
+        }
+        eval
     }
 }
 
 pub struct KingPawnShieldEvaluation;
 impl EvaluationFunction for KingPawnShieldEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
-        const PUNISHMENT_PER_PAWN: f32 = -20.0;
-
         // The earlier in the game the more important
         let end_game_factor = 1.0 - endgame_lerp_value(board_state);
+        Self::eval_shield(board_state, end_game_factor, PieceColor::White)
+            - Self::eval_shield(board_state, end_game_factor, PieceColor::Black)
+    }
+}
 
-        let white_punishment = {
-            let king_bb = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::King, PieceColor::White);
-            let pawn_bb = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Pawn, PieceColor::White);
-
-            let white_king_ks_pawns = BitBoard(0xe0000000000000);
-            let white_king_ks_squares = BitBoard(0xe000000000000000);
-
-            let white_king_qs_squares = BitBoard(0x700000000000000);
-            let white_king_qs_pawns = BitBoard(0x7000000000000);
-
-            if !(king_bb & white_king_ks_squares).is_empty() {
-                // King tucked away king side
-                let missing_pawns = 3 - (pawn_bb & white_king_ks_pawns).bit_count();
-                (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
-            } else if !(king_bb & white_king_qs_squares).is_empty() {
-                // King tucked away queen side
-                let missing_pawns = 3 - (pawn_bb & white_king_qs_pawns).bit_count();
-                (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
-            } else {
-                0
-            }
-        };
+impl KingPawnShieldEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        Self::eval_shield(board_state, 1.0 - endgame_lerp_value(board_state), color)
+    }
 
-        let black_punishment = {
-            let king_bb = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::King, PieceColor::Black);
-            let pawn_bb = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Pawn, PieceColor::Black);
-
-            let black_king_ks_pawns = BitBoard(0xe000);
-            let black_king_ks_squares = BitBoard(0xe0);
-
-            let black_king_qs_pawns = BitBoard(0x700);
-            let black_king_qs_squares = BitBoard(0x7);
-
-            if !(king_bb & black_king_ks_squares).is_empty() {
-                // King tucked away king side
-                let missing_pawns = 3 - (pawn_bb & black_king_ks_pawns).bit_count();
-                (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
-            } else if !(king_bb & black_king_qs_squares).is_empty() {
-                // King tucked away queen side
-                let missing_pawns = 3 - (pawn_bb & black_king_qs_pawns).bit_count();
-                (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
-            } else {
-                0
-            }
+    fn eval_shield(board_state: &ChessBoardState, end_game_factor: f32, color: PieceColor) -> i32 {
+        const PUNISHMENT_PER_PAWN: f32 = -20.0;
+
+        let king_bb = board_state
+            .board
+            .get_piece_bitboard(ChessPiece::King, color);
+        let pawn_bb = board_state
+            .board
+            .get_piece_bitboard(ChessPiece::Pawn, color);
+
+        let (ks_squares, ks_pawns, qs_squares, qs_pawns) = match color {
+            PieceColor::White => (
+                BitBoard::from_squares(&[Square::F1, Square::G1, Square::H1]),
+                BitBoard::from_squares(&[Square::F2, Square::G2, Square::H2]),
+                BitBoard::from_squares(&[Square::A1, Square::B1, Square::C1]),
+                BitBoard::from_squares(&[Square::A2, Square::B2, Square::C2]),
+            ),
+            PieceColor::Black => (
+                BitBoard::from_squares(&[Square::F8, Square::G8, Square::H8]),
+                BitBoard::from_squares(&[Square::F7, Square::G7, Square::H7]),
+                BitBoard::from_squares(&[Square::A8, Square::B8, Square::C8]),
+                BitBoard::from_squares(&[Square::A7, Square::B7, Square::C7]),
+            ),
         };
 
-        white_punishment - black_punishment
+        if !(king_bb & ks_squares).is_empty() {
+            // King tucked away king side
+            let missing_pawns = 3 - (pawn_bb & ks_pawns).bit_count();
+            (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
+        } else if !(king_bb & qs_squares).is_empty() {
+            // King tucked away queen side
+            let missing_pawns = 3 - (pawn_bb & qs_pawns).bit_count();
+            (end_game_factor * missing_pawns as f32 * PUNISHMENT_PER_PAWN) as i32
+        } else {
+            0
+        }
     }
 }
 
-pub struct PieceConnectivityEvaluation;
-impl EvaluationFunction for PieceConnectivityEvaluation {
+// Strategy: Bonus for the side to move, which gets to improve its position first
+pub struct TempoEvaluation;
+impl EvaluationFunction for TempoEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
-        let eval_connectivity = |color: PieceColor| -> i32 {
-            let attacked_squares = board_state.board.squares_attacked_by_side(color, false);
+        Self::eval_side(board_state, PieceColor::White)
+            - Self::eval_side(board_state, PieceColor::Black)
+    }
+}
 
-            let defended_pieces = match color {
-                PieceColor::White => attacked_squares & board_state.board.all_white_pieces,
-                PieceColor::Black => attacked_squares & board_state.board.all_black_pieces,
-            };
+impl TempoEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        const TEMPO_BONUS: i32 = 10;
+        if board_state.side == color {
+            TEMPO_BONUS
+        } else {
+            0
+        }
+    }
+}
 
-            defended_pieces.bit_count() as i32 * 5
-        };
+pub struct PieceConnectivityEvaluation;
+impl EvaluationFunction for PieceConnectivityEvaluation {
+    fn eval(board_state: &ChessBoardState) -> i32 {
+        Self::eval_side(board_state, PieceColor::White)
+            - Self::eval_side(board_state, PieceColor::Black)
+    }
+}
 
-        eval_connectivity(PieceColor::White) - eval_connectivity(PieceColor::Black)
+impl PieceConnectivityEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        let attacked_squares = board_state.board.squares_attacked_by_side(color, false);
+        let defended_pieces = attacked_squares & board_state.board.color_occupancy(color);
+        defended_pieces.bit_count() as i32 * 5
     }
 }
 
 pub struct DoublePawnsEvaluation;
 impl EvaluationFunction for DoublePawnsEvaluation {
     fn eval(board_state: &ChessBoardState) -> i32 {
-        const PUNISHMET_PER_PAWN: i32 = -10;
+        let pawn_structure = PawnStructure::probe(board_state);
+        Self::eval_doubled_pawns(&pawn_structure, PieceColor::White)
+            - Self::eval_doubled_pawns(&pawn_structure, PieceColor::Black)
+    }
+}
 
-        let eval_doubled_pawns = |color: PieceColor| -> i32 {
-            let piece_board = board_state
-                .board
-                .get_piece_bitboard(ChessPiece::Pawn, color);
-            let shifted_board = match color {
-                PieceColor::White => piece_board.s_no(),
-                PieceColor::Black => piece_board.s_so(),
-            };
+impl DoublePawnsEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        Self::eval_doubled_pawns(&PawnStructure::probe(board_state), color)
+    }
 
-            let doubled_pawns = piece_board & shifted_board;
+    fn eval_doubled_pawns(pawn_structure: &PawnStructure, color: PieceColor) -> i32 {
+        const PUNISHMET_PER_PAWN: i32 = -10;
+        pawn_structure.doubled_pawns[color as usize].bit_count() as i32 * PUNISHMET_PER_PAWN
+    }
+}
 
-            doubled_pawns.bit_count() as i32 * PUNISHMET_PER_PAWN
-        };
+// Strategy: Punish isolated and backward pawns, the further back the easier to attack,
+// and every pawn island beyond the first
+pub struct PawnStructureEvaluation;
+impl EvaluationFunction for PawnStructureEvaluation {
+    fn eval(board_state: &ChessBoardState) -> i32 {
+        let pawn_structure = PawnStructure::probe(board_state);
+        Self::eval_pawn_structure(&pawn_structure, PieceColor::White)
+            - Self::eval_pawn_structure(&pawn_structure, PieceColor::Black)
+    }
+}
+
+impl PawnStructureEvaluation {
+    pub fn eval_side(board_state: &ChessBoardState, color: PieceColor) -> i32 {
+        Self::eval_pawn_structure(&PawnStructure::probe(board_state), color)
+    }
 
-        eval_doubled_pawns(PieceColor::White) - eval_doubled_pawns(PieceColor::Black)
+    fn eval_pawn_structure(pawn_structure: &PawnStructure, color: PieceColor) -> i32 {
+        // Indexed by the rank relative to the pawn's side
+        const ISOLATED_PAWN_PENALTY: [i32; 8] = [0, -15, -14, -12, -10, -8, -6, 0];
+        const BACKWARD_PAWN_PENALTY: [i32; 8] = [0, -12, -10, -8, -6, 0, 0, 0];
+        const PAWN_ISLAND_PENALTY: i32 = -8;
+
+        let relative_rank = |pawn: Square| pawn.rank_of().relative_to(color).index() as usize;
+        let isolated: i32 = pawn_structure.isolated_pawns[color as usize]
+            .into_iter()
+            .map(|pawn| ISOLATED_PAWN_PENALTY[relative_rank(pawn)])
+            .sum();
+        let backward: i32 = pawn_structure.backward_pawns[color as usize]
+            .into_iter()
+            .map(|pawn| BACKWARD_PAWN_PENALTY[relative_rank(pawn)])
+            .sum();
+        let islands = pawn_structure.pawn_islands[color as usize].saturating_sub(1) as i32;
+        isolated + backward + islands * PAWN_ISLAND_PENALTY
     }
 }
 
@@ -395,8 +651,9 @@ mod eval_tests {
     use crate::{
         chess::board::ChessBoardState,
         engine::board_eval::{
-            EvaluationFunction, KingPawnShieldEvaluation, PassedPawnEvaluation,
-            PieceCountEvaluation,
+            BishopPairEvaluation, EvaluationFunction, KingPawnShieldEvaluation,
+            PassedPawnEvaluation, PawnStructure, PawnStructureEvaluation, PieceCountEvaluation,
+            TempoEvaluation,
         },
     };
 
@@ -409,35 +666,177 @@ mod eval_tests {
         assert_eq!(PieceCountEvaluation::eval(&start_board), 0);
     }
 
+    #[test]
+    fn eval_tempo() {
+        let white_to_move = ChessBoardState::starting_state();
+        let black_to_move =
+            ChessBoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1")
+                .unwrap();
+        assert!(TempoEvaluation::eval(&white_to_move) > 0);
+        assert_eq!(
+            TempoEvaluation::eval(&black_to_move),
+            -TempoEvaluation::eval(&white_to_move)
+        );
+    }
+
     #[test]
     fn eval_passed_pawn() {
-        let board_state_passer =
-            ChessBoardState::from_fen("4k3/8/6P1/8/8/8/8/4K3 w - - 0 1").unwrap();
+        use crate::chess::{
+            board::{ChessPiece::*, PieceColor::*},
+            board_builder::BoardBuilder,
+            square::Square,
+        };
+        let kings = || {
+            BoardBuilder::new()
+                .piece(Square::E1, King, White)
+                .piece(Square::E8, King, Black)
+        };
+
+        let board_state_passer = kings().piece(Square::G6, Pawn, White).build().unwrap();
         assert!(PassedPawnEvaluation::eval(&board_state_passer) > 0);
 
-        let board_state_no_passer =
-            ChessBoardState::from_fen("4k3/6p1/6P1/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let board_state_no_passer = kings()
+            .piece(Square::G6, Pawn, White)
+            .piece(Square::G7, Pawn, Black)
+            .build()
+            .unwrap();
         assert!(PassedPawnEvaluation::eval(&board_state_no_passer) == 0);
 
-        let board_opposing_passer =
-            ChessBoardState::from_fen("4k3/8/8/8/6p1/8/8/4K3 w - - 0 1").unwrap();
+        let board_opposing_passer = kings().piece(Square::G4, Pawn, Black).build().unwrap();
         assert!(PassedPawnEvaluation::eval(&board_opposing_passer) < 0);
     }
 
+    #[test]
+    fn passed_pawn_masks() {
+        use crate::chess::{bitboard::BitBoard, board::PieceColor, square::Square};
+
+        assert_eq!(
+            PassedPawnEvaluation::mask_infront_of_pawn(Square::E2, PieceColor::White),
+            !(BitBoard::RANK_1 | BitBoard::RANK_2)
+        );
+        assert_eq!(
+            PassedPawnEvaluation::mask_infront_of_pawn(Square::E7, PieceColor::Black),
+            !(BitBoard::RANK_7 | BitBoard::RANK_8)
+        );
+        // Pawns can not stand there, the masks must still not overflow
+        assert!(
+            PassedPawnEvaluation::mask_infront_of_pawn(Square::A8, PieceColor::White).is_empty()
+        );
+        assert!(
+            PassedPawnEvaluation::mask_infront_of_pawn(Square::H1, PieceColor::Black).is_empty()
+        );
+
+        assert_eq!(
+            PassedPawnEvaluation::mask_connected_to_pawn(Square::A1),
+            BitBoard::from_squares(&[Square::B1, Square::B2])
+        );
+    }
+
+    #[test]
+    fn eval_passed_pawn_support() {
+        let eval = |fen: &str| PassedPawnEvaluation::eval(&ChessBoardState::from_fen(fen).unwrap());
+
+        // The e pawns are blocked, only the d pawn is passed
+        let protected = eval("4k3/8/8/3Pp3/4P3/8/8/4K3 w - - 0 1");
+        let unprotected = eval("4k3/8/8/3Pp3/8/8/4P3/4K3 w - - 0 1");
+        assert!(protected > unprotected);
+
+        let connected = eval("4k3/8/8/3PP3/8/8/8/4K3 w - - 0 1");
+        let split = eval("4k3/8/8/2P1P3/8/8/8/4K3 w - - 0 1");
+        assert!(connected > split);
+
+        // Knight on b7 guards d6
+        let free = eval("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+        let stopped = eval("4k3/1n6/8/3P4/8/8/8/4K3 w - - 0 1");
+        let blockaded = eval("4k3/8/3n4/3P4/8/8/8/4K3 w - - 0 1");
+        assert!(free > stopped);
+        assert!(free > blockaded);
+    }
+
     #[test]
     fn eval_king_pawn_shield() {
         let board_white_damaged_shield = ChessBoardState::from_fen(
-            "rnbq2kr/pppppppp/8/4bn2/3Q1N2/1PN1BB2/P1PPPPPP/1KR4R w Kkq - 0 1",
+            "rnbq2kr/pppppppp/8/4bn2/3Q1N2/1PN1BB2/P1PPPPPP/1KR4R w - - 0 1",
         )
         .unwrap();
         assert!(KingPawnShieldEvaluation::eval(&board_white_damaged_shield) < 0);
 
         let board_black_damaged_shield = ChessBoardState::from_fen(
-            "rnbq2kr/pppppp1p/6p1/4bn2/3Q1N2/2N1BB2/PPPPPPPP/1KR4R w Kkq - 0 1",
+            "rnbq2kr/pppppp1p/6p1/4bn2/3Q1N2/2N1BB2/PPPPPPPP/1KR4R w - - 0 1",
         )
         .unwrap();
         assert!(KingPawnShieldEvaluation::eval(&board_black_damaged_shield) > 0);
 
         assert!(KingPawnShieldEvaluation::eval(&ChessBoardState::starting_state()) == 0);
     }
+
+    #[test]
+    fn pawn_structure_weaknesses() {
+        use crate::chess::{bitboard::BitBoard, board::PieceColor, square::Square};
+
+        let structure =
+            |fen: &str| PawnStructure::new(&ChessBoardState::from_fen(fen).unwrap().board);
+        let white = PieceColor::White as usize;
+        let black = PieceColor::Black as usize;
+
+        // White: a2 isolated, c2 backward behind d3 with c3 held by b4. Black: all connected
+        let pawns = structure("4k3/5ppp/8/8/1p6/3P4/P1P5/4K3 w - - 0 1");
+        assert_eq!(
+            pawns.isolated_pawns[white],
+            BitBoard::from_square(Square::A2)
+        );
+        assert_eq!(
+            pawns.backward_pawns[white],
+            BitBoard::from_square(Square::C2)
+        );
+        assert_eq!(pawns.pawn_islands[white], 2);
+        assert_eq!(
+            pawns.isolated_pawns[black],
+            BitBoard::from_square(Square::B4)
+        );
+        assert!(pawns.backward_pawns[black].is_empty());
+        assert_eq!(pawns.pawn_islands[black], 2);
+
+        // Without a pawn attacking its stop square the c pawn can still catch up
+        let pawns = structure("4k3/8/8/8/8/3P4/2P5/4K3 w - - 0 1");
+        assert!(pawns.backward_pawns[white].is_empty());
+
+        let eval =
+            |fen: &str| PawnStructureEvaluation::eval(&ChessBoardState::from_fen(fen).unwrap());
+        assert_eq!(
+            eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            0
+        );
+        // Isolated d pawn against a healthy majority, and three islands against one
+        assert!(eval("4k3/pp3ppp/8/8/3P4/8/PP3PPP/4K3 w - - 0 1") < 0);
+        assert!(eval("4k3/pp1p1p1p/8/8/8/8/PPPP4/4K3 w - - 0 1") > 0);
+        // Isolated pawns further up the board are easier to defend
+        assert!(eval("4k3/8/8/8/8/8/P7/4K3 w - - 0 1") < eval("4k3/8/8/P7/8/8/8/4K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn eval_bishops() {
+        use crate::chess::{board::PieceColor, square::Square};
+
+        assert_eq!(Square::A1.color(), PieceColor::Black);
+        assert_eq!(Square::A8.color(), PieceColor::White);
+        assert_eq!(Square::E4.color(), PieceColor::White);
+        assert_eq!(Square::D4.color(), PieceColor::Black);
+
+        let eval = |fen: &str| BishopPairEvaluation::eval(&ChessBoardState::from_fen(fen).unwrap());
+        assert_eq!(eval("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"), 50);
+        // c1 and e3 are both dark, a promoted bishop does not make a pair
+        assert_eq!(eval("4k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1"), 0);
+        assert_eq!(
+            eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            0
+        );
+
+        // Own pawns fixed on the bishop's color, worse when blocked
+        let free = eval("4k3/8/8/8/8/8/8/4KB2 w - - 0 1");
+        let hemmed_in = eval("4k3/8/8/8/4P3/3P4/8/4KB2 w - - 0 1");
+        let blocked = eval("4k3/8/8/4p3/4P3/3P4/8/4KB2 w - - 0 1");
+        assert!(free > hemmed_in);
+        assert!(hemmed_in > blocked);
+    }
 }
@@ -1,29 +1,101 @@
-use super::{board_eval::EvaluationFunction, time_control::TimeControl};
-use crate::chess::{board::ChessBoardState, chess_move::Move};
+use super::{
+    board_eval::{EvalBreakdown, EvaluationFunction},
+    time_control::TimeControl,
+    uci_option::{UciOption, UciOptionValue},
+};
+use crate::chess::{board::ChessBoardState, chess_move::Move, game::Game};
+use rand::{rngs::StdRng, SeedableRng};
 use std::sync::{atomic::AtomicBool, Arc};
 
-pub trait ChessBot: EvaluationFunction + Default {
+/// Random number generator of the bots, seeded through the `Seed` option so games can
+/// be reproduced.
+pub type BotRng = StdRng;
+
+/// Generator for `seed`, a seed of 0 picks a random one.
+pub fn bot_rng(seed: u64) -> BotRng {
+    if seed == 0 {
+        StdRng::from_entropy()
+    } else {
+        StdRng::seed_from_u64(seed)
+    }
+}
+
+pub fn seed_option() -> UciOption {
+    UciOption::spin("Seed", 0, 0, i64::MAX)
+}
+
+pub trait ChessBot: EvaluationFunction + Default + Send {
+    /// Name the bot is selected by.
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// Approximate playing strength in Elo.
+    const STRENGTH: u32;
+
     fn search_best_move(
         &mut self,
         board_state: &mut ChessBoardState,
         tc: TimeControl,
         stop: &Arc<AtomicBool>,
     ) -> Move;
-    fn set_option(&mut self, name: String, value: String);
-    fn get_options() -> &'static str;
+    /// Options the bot announces to the GUI.
+    fn options() -> Vec<UciOption> {
+        Vec::new()
+    }
+    /// Called with a value already validated against the option named `name`.
+    fn set_option(&mut self, _name: &str, _value: UciOptionValue) {}
+
+    /// Looks up the option by name, validates the raw value and applies it.
+    fn apply_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let option = Self::options()
+            .into_iter()
+            .find(|o| o.matches(name))
+            .ok_or(format!("Unknown option '{}'", name))?;
+        let value = option.parse_value(value)?;
+        self.set_option(&option.name, value);
+        Ok(())
+    }
+
+    /// Evaluation with the bot's current settings, e.g. its evaluation weights, from
+    /// white's perspective.
+    fn evaluate(&self, board_state: &ChessBoardState) -> i32 {
+        Self::eval(board_state)
+    }
+    /// `evaluate` split into its terms.
+    fn evaluate_breakdown(&self, board_state: &ChessBoardState) -> Option<EvalBreakdown> {
+        Self::eval_breakdown(board_state)
+    }
+
+    /// Nodes visited by the last search, 0 for bots which do not count them.
+    fn nodes_searched(&self) -> u64 {
+        0
+    }
+
+    /// Score of the last move in centipawns from the point of view of the side which played
+    /// it, `None` for bots which do not score their moves or for a move from the book.
+    fn best_score(&self) -> Option<i32> {
+        None
+    }
+
+    /// Restricts the root moves of the next search, an empty list allows all moves.
+    fn set_search_moves(&mut self, _moves: Vec<Move>) {}
+
+    /// Stops the searches from printing `info` lines, for callers with output of their own.
+    fn set_quiet(&mut self, _quiet: bool) {}
 
     fn append_to_history(&mut self, board_state: &mut ChessBoardState);
     fn clear_history(&mut self);
-    fn execute_move_list(&mut self, board_state: &mut ChessBoardState, moves: &Vec<String>) {
+    /// Forgets everything learned in the previous game, e.g. the hash table.
+    fn new_game(&mut self) {
+        self.clear_history();
+    }
+    /// Called once before the engine exits, e.g. to persist what was learned.
+    fn quit(&mut self) {}
+    /// Takes over the positions of the game as history for repetition detection.
+    fn set_position(&mut self, game: &Game) {
         self.clear_history();
-        for move_str in moves {
-            if let Ok(mv) = Move::try_from(((*move_str).trim(), &*board_state)) {
-                eprintln!("Got: '{}', Executed: {:?}", move_str, &mv);
-                *board_state = board_state.exec_move(mv);
-                self.append_to_history(board_state);
-            } else {
-                eprintln!("Illegal move: '{}'", move_str);
-            }
+        for ply in 0..=game.moves().len() {
+            let mut state = *game.state_at(ply).unwrap();
+            self.append_to_history(&mut state);
         }
     }
 }
@@ -2,3 +2,65 @@ pub mod nply_bot;
 pub mod nplytranspo_bot;
 pub mod oneply_bot;
 pub mod random_bot;
+
+use super::{
+    bench::{run_bench, BenchResult},
+    bot::ChessBot,
+    uci::UCIReader,
+};
+use nply_bot::NPlyBot;
+use nplytranspo_bot::NPlyTranspoBot;
+use oneply_bot::OnePlyBot;
+use random_bot::RandomBot;
+
+/// A bot selectable by name, described by the constants of its `ChessBot` impl.
+pub struct BotEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Approximate playing strength in Elo
+    pub strength: u32,
+    run_uci: fn(&[(String, String)]),
+    bench: fn(u16) -> BenchResult,
+}
+
+impl BotEntry {
+    fn of<B: ChessBot + 'static>() -> Self {
+        Self {
+            name: B::NAME,
+            description: B::DESCRIPTION,
+            strength: B::STRENGTH,
+            run_uci: |options| {
+                let reader = UCIReader::<B>::default();
+                for (name, value) in options {
+                    reader.set_option(name, Some(value));
+                }
+                reader.run();
+            },
+            bench: |depth| run_bench(&mut B::default(), depth),
+        }
+    }
+
+    /// Runs the UCI loop with this bot after applying `options`.
+    pub fn run_uci(&self, options: &[(String, String)]) {
+        (self.run_uci)(options)
+    }
+
+    /// Runs the bench suite with a fresh bot, see `run_bench`.
+    pub fn bench(&self, depth: u16) -> BenchResult {
+        (self.bench)(depth)
+    }
+}
+
+/// All built in bots, the strongest first.
+pub fn registry() -> Vec<BotEntry> {
+    vec![
+        BotEntry::of::<NPlyTranspoBot>(),
+        BotEntry::of::<NPlyBot>(),
+        BotEntry::of::<OnePlyBot>(),
+        BotEntry::of::<RandomBot>(),
+    ]
+}
+
+pub fn find_bot(name: &str) -> Option<BotEntry> {
+    registry().into_iter().find(|entry| entry.name == name)
+}
@@ -14,6 +14,10 @@ use crate::{
 #[derive(Default)]
 pub struct NPlyBot();
 impl ChessBot for NPlyBot {
+    const NAME: &'static str = "nply";
+    const DESCRIPTION: &'static str = "Plain fixed depth alpha-beta search";
+    const STRENGTH: u32 = 1200;
+
     fn search_best_move(
         &mut self,
         board_state: &mut crate::chess::board::ChessBoardState,
@@ -45,10 +49,6 @@ impl ChessBot for NPlyBot {
         board_state.exec_move(best_move);
         best_move
     }
-    fn set_option(&mut self, _name: String, _value: String) {}
-    fn get_options() -> &'static str {
-        ""
-    }
     fn append_to_history(&mut self, _board_state: &mut ChessBoardState) {}
     fn clear_history(&mut self) {}
 }
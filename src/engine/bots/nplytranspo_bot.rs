@@ -1,48 +1,112 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use rand::Rng;
+use std::{
+    cell::RefCell,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use crate::{
     chess::{board::ChessBoardState, chess_move::Move},
     engine::{
-        board_eval::{
-            BishopPairEvaluation, DoublePawnsEvaluation, EvaluationFunction,
-            KingPawnShieldEvaluation, PassedPawnEvaluation, PieceCountEvaluation,
-            PieceSquareTableEvaluation,
-        },
-        bot::ChessBot,
+        board_eval::{EvalBreakdown, EvaluationFunction},
+        bot::{bot_rng, seed_option, BotRng, ChessBot},
+        eval_cache::EvalCache,
+        eval_config::{CompositeEvaluation, EvalConfig},
         opening::polyglot::{OpeningBook, PolyglotOpeningBook},
-        search::Searcher,
+        search::{Evaluator, Searcher},
         time_control::TimeControl,
-        transposition_table::TranspositionEntry,
+        time_manager::DEFAULT_MOVE_OVERHEAD,
+        transposition_table::{TranspositionEntry, HASH_FILE_RECORD_SIZE},
+        uci_option::{UciOption, UciOptionValue},
     },
+    log_debug, log_info, log_warn,
 };
 
 pub const TABLE_SIZE: usize = 64 * 1024 * 1024;
 pub const TABLE_ENTRY_SIZE: usize = std::mem::size_of::<TranspositionEntry>();
 pub const TABLE_ENTRY_COUNT: usize = TABLE_SIZE / TABLE_ENTRY_SIZE;
+// The table is allocated once at its full size, `Hash` can only use less of it
+const HASH_MB: usize = TABLE_SIZE / (1024 * 1024);
+
+const OPENING_BOOK_DATA: &'static [u8] = include_bytes!("../opening/Openings.bin");
+
+// Hash table of each thread of a split root search, searches split that way are shallow
+const SPLIT_TABLE_ENTRY_COUNT: usize = 1 << 18;
+
+const EVAL_CACHE_SIZE: usize = 64 * 1024;
 
-// include bytes from file /home/faxe/priv/iglo/src/engine/opening/Openings.bin as OPENING_BOOK_DATA
+// Weakest level selectable with UCI_Elo
+const MIN_ELO: u32 = 800;
+// Deepest iteration at the weakest level, every 200 Elo add a ply
+const MIN_ELO_DEPTH: u16 = 1;
+// Chance of playing a random move at the weakest level
+const MIN_ELO_RANDOM_MOVE_CHANCE: f64 = 0.25;
 
-const OPENING_BOOK_DATA: &'static [u8] =
-    include_bytes!("/home/faxe/priv/iglo/src/engine/opening/Openings.bin");
+// UCI options setting the weight of an evaluation term, next to the term's name
+const EVAL_WEIGHT_OPTIONS: [(&str, &str); 9] = [
+    ("MaterialWeight", "material"),
+    ("PieceSquaresWeight", "piece_squares"),
+    ("PassedPawnsWeight", "passed_pawns"),
+    ("BishopPairWeight", "bishop_pair"),
+    ("KingPawnShieldWeight", "king_pawn_shield"),
+    ("DoubledPawnsWeight", "doubled_pawns"),
+    ("PawnStructureWeight", "pawn_structure"),
+    ("ConnectivityWeight", "connectivity"),
+    ("TempoWeight", "tempo"),
+];
+
+thread_local! {
+    // Evaluations next to the weights they were computed with
+    static EVAL_CACHE: RefCell<(EvalConfig, EvalCache<i32, EVAL_CACHE_SIZE>)> =
+        RefCell::new((EvalConfig::DEFAULT, EvalCache::default()));
+}
 
 pub struct NPlyTranspoBot {
     searcher: Searcher<TABLE_ENTRY_COUNT>,
     opening_book: PolyglotOpeningBook,
     use_openening_book: bool,
+    limit_strength: bool,
+    elo: u32,
+    // Threads splitting the root moves of fixed depth searches, set through `Threads`
+    root_split_threads: usize,
+    // Score of the last move, if it was searched
+    best_score: Option<i32>,
+    rng: BotRng,
+    // Hash table kept between sessions, saved on quit with at most the given size in MB
+    hash_file: Option<String>,
+    hash_file_size: usize,
+    // The hash file is loaded by the next search, GUIs clear the table with `ucinewgame`
+    // or `Hash` after setting the options
+    hash_file_pending: bool,
+    // Weights of this bot's evaluation, other bots in the process keep their own
+    eval_config: EvalConfig,
 }
 
 impl Default for NPlyTranspoBot {
     fn default() -> Self {
         let opening_book = PolyglotOpeningBook::from_bytes(OPENING_BOOK_DATA);
         Self {
-            searcher: Searcher::new(Self::eval),
+            searcher: Searcher::with_evaluator(Self::evaluator(EvalConfig::DEFAULT)),
             opening_book,
             use_openening_book: true,
+            limit_strength: false,
+            elo: Self::STRENGTH,
+            root_split_threads: 1,
+            best_score: None,
+            rng: bot_rng(0),
+            hash_file: None,
+            hash_file_size: 16,
+            hash_file_pending: false,
+            eval_config: EvalConfig::DEFAULT,
         }
     }
 }
 
 impl ChessBot for NPlyTranspoBot {
+    const NAME: &'static str = "nplytranspo";
+    const DESCRIPTION: &'static str =
+        "Iterative deepening search with transposition table and opening book";
+    const STRENGTH: u32 = 2200;
+
     fn search_best_move(
         &mut self,
         board_state: &mut ChessBoardState,
@@ -50,9 +114,12 @@ impl ChessBot for NPlyTranspoBot {
         stop: &Arc<AtomicBool>,
     ) -> Move {
         let cur_board_eval = Self::eval(board_state);
-        println!("info score cp {}", cur_board_eval as f32 / 100.0);
+        log_debug!("Static evaluation: {}", cur_board_eval);
+        self.load_pending_hash_file();
+        self.best_score = None;
 
-        if self.use_openening_book {
+        // A book move might not be among the moves the search is restricted to
+        if self.use_openening_book && self.searcher.search_moves().is_empty() {
             let moves = self.opening_book.get(board_state);
             if !moves.is_empty() {
                 let legal_moves = board_state.generate_legal_moves_for_current_player::<false>();
@@ -64,40 +131,316 @@ impl ChessBot for NPlyTranspoBot {
                     }
                 }
 
-                println!("info string book move");
+                log_info!("Book move {:?}", moves[0]);
                 return moves[0];
             }
         }
 
-        self.searcher.search(board_state, tc, stop)
+        self.searcher.max_depth = self.limit_strength.then(|| self.depth_limit());
+        let best_move = match tc {
+            TimeControl::FixedDepth(depth) if self.root_split_threads > 1 => {
+                let depth = self
+                    .searcher
+                    .max_depth
+                    .map_or(depth as u16, |max| max.min(depth as u16));
+                let best = self
+                    .searcher
+                    .split_root_search::<SPLIT_TABLE_ENTRY_COUNT>(
+                        board_state,
+                        depth,
+                        self.root_split_threads,
+                        stop,
+                    )
+                    .first()
+                    .copied();
+                self.best_score = best.map(|(_, score)| score);
+                best.map_or(Move::NULL_MOVE, |(mv, _)| mv)
+            }
+            _ => {
+                let best_move = self.searcher.search(board_state, tc, stop);
+                self.best_score = Some(self.searcher.info.best_score());
+                best_move
+            }
+        };
+
+        // Weaker levels blend in random moves on top of the shallower search
+        if self.limit_strength
+            && best_move != Move::NULL_MOVE
+            && self.rng.gen_bool(self.random_move_chance())
+        {
+            let moves = board_state.generate_legal_moves_for_current_player::<false>();
+            self.best_score = None;
+            return moves[self.rng.gen_range(0..moves.len())];
+        }
+        best_move
     }
 
-    fn set_option(&mut self, name: String, value: String) {
-        match &name as &str {
-            "OpeningBook" => self.use_openening_book = value == "true",
+    fn options() -> Vec<UciOption> {
+        let mut default_config = EvalConfig::default();
+        let weight_options = EVAL_WEIGHT_OPTIONS.map(|(name, term)| {
+            let default = *default_config.weight_mut(term).unwrap();
+            UciOption::spin(name, default as i64, 0, 400)
+        });
+        let mut options = vec![
+            UciOption::check("OpeningBook", true),
+            UciOption::spin("FutilityMargin", 150, 0, 1000),
+            UciOption::spin("ReverseFutilityMargin", 120, 0, 1000),
+            UciOption::spin("Contempt", 0, -100, 100),
+            // Uses part of the table allocated at compile time, it can not grow beyond it
+            UciOption::spin("Hash", HASH_MB as i64, 1, HASH_MB as i64),
+            UciOption::button("Clear Hash"),
+            // Splits the root moves of `go depth` searches, all others use a single thread
+            UciOption::spin("Threads", 1, 1, 64),
+            UciOption::spin("Move Overhead", DEFAULT_MOVE_OVERHEAD as i64, 0, 5000),
+            UciOption::check("UCI_LimitStrength", false),
+            UciOption::spin(
+                "UCI_Elo",
+                Self::STRENGTH as i64,
+                MIN_ELO as i64,
+                Self::STRENGTH as i64,
+            ),
+            seed_option(),
+            UciOption::string("EvalConfigFile", ""),
+            UciOption::string("HashFile", ""),
+            UciOption::spin("HashFileSize", 16, 1, 1024),
+        ];
+        options.extend(weight_options);
+        options
+    }
+    fn set_option(&mut self, name: &str, value: UciOptionValue) {
+        match (name, value) {
+            ("OpeningBook", UciOptionValue::Check(enabled)) => self.use_openening_book = enabled,
+            ("FutilityMargin", UciOptionValue::Spin(margin)) => {
+                self.searcher.margins.futility = margin as i32
+            }
+            ("ReverseFutilityMargin", UciOptionValue::Spin(margin)) => {
+                self.searcher.margins.reverse_futility = margin as i32
+            }
+            ("Contempt", UciOptionValue::Spin(contempt)) => {
+                self.searcher.contempt = contempt as i32
+            }
+            ("Clear Hash", UciOptionValue::Button) => {
+                self.searcher.clear_hash_table();
+                self.hash_file_pending = false;
+            }
+            ("Hash", UciOptionValue::Spin(mb)) => {
+                self.searcher
+                    .resize_hash_table(mb as usize * 1024 * 1024 / TABLE_ENTRY_SIZE);
+                self.hash_file_pending = self.hash_file.is_some();
+            }
+            ("Threads", UciOptionValue::Spin(threads)) => {
+                self.root_split_threads = threads as usize
+            }
+            ("Move Overhead", UciOptionValue::Spin(overhead)) => {
+                self.searcher.move_overhead = overhead as u64
+            }
+            ("UCI_LimitStrength", UciOptionValue::Check(limit)) => self.limit_strength = limit,
+            ("UCI_Elo", UciOptionValue::Spin(elo)) => self.elo = elo as u32,
+            ("Seed", UciOptionValue::Spin(seed)) => self.rng = bot_rng(seed as u64),
+            ("EvalConfigFile", UciOptionValue::String(path)) if !path.is_empty() => {
+                match EvalConfig::load(&path) {
+                    Ok(config) => self.set_eval_config(config),
+                    Err(e) => log_warn!("{}", e),
+                }
+            }
+            ("HashFile", UciOptionValue::String(path)) => {
+                self.hash_file = match path.as_str() {
+                    "" | "<empty>" => None,
+                    path => Some(path.to_string()),
+                };
+                self.hash_file_pending = self.hash_file.is_some();
+            }
+            ("HashFileSize", UciOptionValue::Spin(size)) => self.hash_file_size = size as usize,
+            (name, UciOptionValue::Spin(weight)) => {
+                if let Some((_, term)) = EVAL_WEIGHT_OPTIONS.iter().find(|(n, _)| *n == name) {
+                    let mut config = self.eval_config;
+                    *config.weight_mut(term).unwrap() = weight as i32;
+                    self.set_eval_config(config);
+                }
+            }
             _ => {}
         }
     }
-    fn get_options() -> &'static str {
-        "option name OpeningBook type check default true"
+    fn evaluate(&self, board_state: &ChessBoardState) -> i32 {
+        Self::cached_eval(&self.eval_config, board_state)
+    }
+    fn evaluate_breakdown(&self, board_state: &ChessBoardState) -> Option<EvalBreakdown> {
+        Some(CompositeEvaluation::breakdown_with(
+            &self.eval_config,
+            board_state,
+        ))
+    }
+    fn nodes_searched(&self) -> u64 {
+        self.searcher.info.nodes_searched() as u64
+    }
+    fn best_score(&self) -> Option<i32> {
+        self.best_score
+    }
+    fn set_search_moves(&mut self, moves: Vec<Move>) {
+        self.searcher.set_search_moves(moves);
+    }
+    fn set_quiet(&mut self, quiet: bool) {
+        self.searcher.quiet = quiet;
     }
     fn append_to_history(&mut self, board_state: &mut ChessBoardState) {
         self.searcher.info.history.push(board_state.zhash);
     }
     fn clear_history(&mut self) {
         self.searcher.info.history.clear();
-        // TODO: Hack factor this better
-        self.searcher.incr_hash_table_age();
+    }
+    fn new_game(&mut self) {
+        self.searcher.new_game();
+        self.hash_file_pending = self.hash_file.is_some();
+    }
+    fn quit(&mut self) {
+        let Some(path) = &self.hash_file else {
+            return;
+        };
+        let max_entries = self.hash_file_size * 1024 * 1024 / HASH_FILE_RECORD_SIZE;
+        match self.searcher.save_hash_table(path, max_entries) {
+            Ok(count) => log_info!("Saved {} hash entries to '{}'", count, path),
+            Err(e) => log_warn!("Can not save hash file '{}': {}", path, e),
+        }
+    }
+}
+
+impl NPlyTranspoBot {
+    /// Loads the hash table of an earlier session into the table cleared since the file was
+    /// set, a missing file is created on quit.
+    fn load_pending_hash_file(&mut self) {
+        if !self.hash_file_pending {
+            return;
+        }
+        self.hash_file_pending = false;
+        let Some(path) = &self.hash_file else {
+            return;
+        };
+        match self.searcher.load_hash_table(path) {
+            Ok(count) => log_info!("Loaded {} hash entries from '{}'", count, path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log_warn!("Can not load hash file '{}': {}", path, e),
+        }
+    }
+
+    /// Evaluation with the given weights, cached per thread. The cache starts over when a
+    /// bot with other weights evaluates on the same thread.
+    fn cached_eval(config: &EvalConfig, board_state: &ChessBoardState) -> i32 {
+        EVAL_CACHE.with(|cache| {
+            let (cached_config, cache) = &mut *cache.borrow_mut();
+            if cached_config != config {
+                *cached_config = *config;
+                *cache = EvalCache::default();
+            }
+            cache.get_or_insert_with(board_state.zhash, || {
+                CompositeEvaluation::eval_with(config, board_state)
+            })
+        })
+    }
+
+    fn evaluator(config: EvalConfig) -> Evaluator {
+        Arc::new(move |board_state| Self::cached_eval(&config, board_state))
+    }
+
+    fn set_eval_config(&mut self, config: EvalConfig) {
+        self.eval_config = config;
+        self.searcher.set_evaluator(Self::evaluator(config));
+    }
+
+    /// Search depth allowed at the configured Elo.
+    fn depth_limit(&self) -> u16 {
+        MIN_ELO_DEPTH + (self.elo.saturating_sub(MIN_ELO) / 200) as u16
+    }
+
+    /// Chance of replacing the searched move by a random one, fading out towards
+    /// full strength.
+    fn random_move_chance(&self) -> f64 {
+        let weakness = Self::STRENGTH.saturating_sub(self.elo) as f64;
+        MIN_ELO_RANDOM_MOVE_CHANCE * weakness / (Self::STRENGTH - MIN_ELO) as f64
     }
 }
 
 impl EvaluationFunction for NPlyTranspoBot {
+    /// Evaluation with the default weights, `evaluate` uses the bot's own.
     fn eval(board_state: &crate::chess::board::ChessBoardState) -> i32 {
-        PieceCountEvaluation::eval(board_state)
-            + PieceSquareTableEvaluation::eval(board_state)
-            + PassedPawnEvaluation::eval(board_state)
-            + BishopPairEvaluation::eval(board_state)
-            + KingPawnShieldEvaluation::eval(board_state)
-            + DoublePawnsEvaluation::eval(board_state)
+        Self::cached_eval(&EvalConfig::DEFAULT, board_state)
+    }
+
+    fn eval_breakdown(board_state: &crate::chess::board::ChessBoardState) -> Option<EvalBreakdown> {
+        CompositeEvaluation::eval_breakdown(board_state)
+    }
+}
+
+#[cfg(test)]
+mod nplytranspo_bot_tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use super::NPlyTranspoBot;
+    use crate::{
+        chess::board::ChessBoardState,
+        engine::{
+            board_eval::EvaluationFunction, bot::ChessBot, eval_config::CompositeEvaluation,
+            time_control::TimeControl,
+        },
+    };
+
+    #[test]
+    fn eval_weights_belong_to_the_bot() {
+        let board_state = ChessBoardState::from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        )
+        .unwrap();
+        let mut tuned = NPlyTranspoBot::default();
+        let untouched = NPlyTranspoBot::default();
+        tuned.apply_option("PieceSquaresWeight", Some("0")).unwrap();
+
+        let default_eval = CompositeEvaluation::eval(&board_state);
+        assert_ne!(tuned.evaluate(&board_state), default_eval);
+        assert_eq!(untouched.evaluate(&board_state), default_eval);
+        assert_eq!(NPlyTranspoBot::eval(&board_state), default_eval);
+        // Evaluating with the other weights on the same thread does not mix up the cache
+        assert_ne!(tuned.evaluate(&board_state), default_eval);
+    }
+
+    #[test]
+    fn hash_file_survives_new_game() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let saved = ChessBoardState::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("iglo_hash_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut first = NPlyTranspoBot::default();
+        first.apply_option("OpeningBook", Some("false")).unwrap();
+        first.apply_option("HashFile", Some(path)).unwrap();
+        let mut board_state = saved;
+        first.search_best_move(&mut board_state, TimeControl::FixedDepth(3), &stop);
+        first.quit();
+
+        // Options first, then the GUI starts a game and clears the table
+        let mut second = NPlyTranspoBot::default();
+        second.apply_option("OpeningBook", Some("false")).unwrap();
+        second.apply_option("HashFile", Some(path)).unwrap();
+        second.apply_option("Hash", Some("16")).unwrap();
+        second.new_game();
+        assert!(second.searcher.probe_hash_table(saved.zhash).is_none());
+        board_state = ChessBoardState::starting_state();
+        second.search_best_move(&mut board_state, TimeControl::FixedDepth(1), &stop);
+        assert!(second.searcher.probe_hash_table(saved.zhash).is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scores_searched_moves() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut bot = NPlyTranspoBot::default();
+        assert_eq!(bot.best_score(), None);
+
+        // A queen down for the side to move
+        let mut board_state = ChessBoardState::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        bot.search_best_move(&mut board_state, TimeControl::FixedDepth(2), &stop);
+        assert!(bot.best_score().is_some_and(|score| score < -500));
     }
 }
@@ -1,3 +1,5 @@
+use rand::Rng;
+
 use crate::{
     chess::{
         board::{ChessBoardState, PieceColor},
@@ -5,38 +7,73 @@ use crate::{
     },
     engine::{
         board_eval::{EvaluationFunction, PieceCountEvaluation, PieceSquareTableEvaluation},
-        bot::ChessBot,
+        bot::{bot_rng, seed_option, BotRng, ChessBot},
         time_control::TimeControl,
+        uci_option::{UciOption, UciOptionValue},
     },
 };
 
-#[derive(Default)]
-pub struct OnePlyBot();
+pub struct OnePlyBot {
+    rng: BotRng,
+}
+
+impl OnePlyBot {
+    pub fn with_seed(seed: u64) -> Self {
+        Self { rng: bot_rng(seed) }
+    }
+}
+
+impl Default for OnePlyBot {
+    fn default() -> Self {
+        Self::with_seed(0)
+    }
+}
 impl ChessBot for OnePlyBot {
+    const NAME: &'static str = "oneply";
+    const DESCRIPTION: &'static str = "Picks the move with the best static evaluation";
+    const STRENGTH: u32 = 400;
+
     fn search_best_move(
         &mut self,
         board_state: &mut crate::chess::board::ChessBoardState,
         _tc: TimeControl,
         _stop: &std::sync::Arc<std::sync::atomic::AtomicBool>,
     ) -> Move {
-        let mut moves = board_state.generate_legal_moves_for_current_player::<false>();
-
-        moves.sort_by(|a, b| {
-            if board_state.side == PieceColor::White {
-                Self::eval(&board_state.exec_move(*b)).cmp(&Self::eval(&board_state.exec_move(*a)))
-            } else {
-                Self::eval(&board_state.exec_move(*a)).cmp(&Self::eval(&board_state.exec_move(*b)))
-            }
-        });
+        let moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let sign = if board_state.side == PieceColor::White {
+            1
+        } else {
+            -1
+        };
+        let ratings: Vec<i32> = moves
+            .iter()
+            .map(|mv| sign * Self::eval(&board_state.exec_move(*mv)))
+            .collect();
+        let Some(best_rating) = ratings.iter().max().copied() else {
+            return Move::NULL_MOVE;
+        };
 
-        let selected_move = moves[0];
+        // Equally rated moves are picked at random
+        let best_moves: Vec<Move> = moves
+            .iter()
+            .zip(&ratings)
+            .filter(|(_, rating)| **rating == best_rating)
+            .map(|(mv, _)| *mv)
+            .collect();
+        let selected_move = best_moves[self.rng.gen_range(0..best_moves.len())];
         *board_state = board_state.exec_move(selected_move);
         selected_move
     }
-    fn set_option(&mut self, _name: String, _value: String) {}
-    fn get_options() -> &'static str {
-        ""
+
+    fn options() -> Vec<UciOption> {
+        vec![seed_option()]
+    }
+    fn set_option(&mut self, name: &str, value: UciOptionValue) {
+        if let ("Seed", UciOptionValue::Spin(seed)) = (name, value) {
+            self.rng = bot_rng(seed as u64);
+        }
     }
+
     fn append_to_history(&mut self, _board_state: &mut ChessBoardState) {}
     fn clear_history(&mut self) {}
 }
@@ -1,13 +1,36 @@
-use rand::random;
+use rand::Rng;
 
 use crate::{
     chess::{board::ChessBoardState, chess_move::Move},
-    engine::{board_eval::EvaluationFunction, bot::ChessBot, time_control::TimeControl},
+    engine::{
+        board_eval::EvaluationFunction,
+        bot::{bot_rng, seed_option, BotRng, ChessBot},
+        time_control::TimeControl,
+        uci_option::{UciOption, UciOptionValue},
+    },
 };
 
-#[derive(Default)]
-pub struct RandomBot();
+pub struct RandomBot {
+    rng: BotRng,
+}
+
+impl RandomBot {
+    pub fn with_seed(seed: u64) -> Self {
+        Self { rng: bot_rng(seed) }
+    }
+}
+
+impl Default for RandomBot {
+    fn default() -> Self {
+        Self::with_seed(0)
+    }
+}
+
 impl ChessBot for RandomBot {
+    const NAME: &'static str = "random";
+    const DESCRIPTION: &'static str = "Plays a random legal move";
+    const STRENGTH: u32 = 0;
+
     fn search_best_move(
         &mut self,
         board_state: &mut crate::chess::board::ChessBoardState,
@@ -15,16 +38,24 @@ impl ChessBot for RandomBot {
         _stop: &std::sync::Arc<std::sync::atomic::AtomicBool>,
     ) -> Move {
         let moves = board_state.generate_legal_moves_for_current_player::<false>();
-        let index = random::<usize>() % moves.len();
+        if moves.is_empty() {
+            return Move::NULL_MOVE;
+        }
+        let index = self.rng.gen_range(0..moves.len());
         let selected_move = moves[index];
         *board_state = board_state.exec_move(selected_move);
         selected_move
     }
 
-    fn set_option(&mut self, _name: String, _value: String) {}
-    fn get_options() -> &'static str {
-        ""
+    fn options() -> Vec<UciOption> {
+        vec![seed_option()]
+    }
+    fn set_option(&mut self, name: &str, value: UciOptionValue) {
+        if let ("Seed", UciOptionValue::Spin(seed)) = (name, value) {
+            self.rng = bot_rng(seed as u64);
+        }
     }
+
     fn append_to_history(&mut self, _board_state: &mut ChessBoardState) {}
     fn clear_history(&mut self) {}
 }
@@ -34,3 +65,34 @@ impl EvaluationFunction for RandomBot {
         0
     }
 }
+
+#[cfg(test)]
+mod random_bot_tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use super::RandomBot;
+    use crate::{
+        chess::{board::ChessBoardState, chess_move::Move},
+        engine::{bot::ChessBot, time_control::TimeControl},
+    };
+
+    fn play_game(bot: &mut RandomBot) -> Vec<Move> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut board_state = ChessBoardState::starting_state();
+        (0..40)
+            .map(|_| bot.search_best_move(&mut board_state, TimeControl::Infinite, &stop))
+            .take_while(|mv| *mv != Move::NULL_MOVE)
+            .collect()
+    }
+
+    #[test]
+    fn seeded_games_repeat() {
+        let game = play_game(&mut RandomBot::with_seed(42));
+        assert_eq!(game, play_game(&mut RandomBot::with_seed(42)));
+
+        let mut bot = RandomBot::default();
+        bot.apply_option("Seed", Some("42")).unwrap();
+        assert_eq!(game, play_game(&mut bot));
+        assert_ne!(game, play_game(&mut RandomBot::with_seed(7)));
+    }
+}
@@ -0,0 +1,29 @@
+// std::time::Instant panics on wasm32-unknown-unknown, so the searcher takes its
+// notion of time from here. Native builds simply re-export the std type.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub use std::time::Instant;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub use self::wasm_instant::Instant;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod wasm_instant {
+    use std::time::Duration;
+
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(js_sys::Date::now())
+        }
+
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            Duration::from_secs_f64(((self.0 - earlier.0) / 1000.0).max(0.0))
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Self::now().duration_since(*self)
+        }
+    }
+}
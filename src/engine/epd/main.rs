@@ -0,0 +1,219 @@
+use std::{
+    env, fs, process,
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
+
+use iglo::{
+    chess::{board::ChessBoardState, chess_move::Move},
+    engine::{
+        bot::ChessBot,
+        bots::{
+            nply_bot::NPlyBot, nplytranspo_bot::NPlyTranspoBot, oneply_bot::OnePlyBot,
+            random_bot::RandomBot,
+        },
+        time_control::TimeControl,
+    },
+};
+
+const USAGE: &str = "\
+Usage: epd <file> [options]
+
+Runs a search on every position of an EPD test suite (e.g. WAC or STS) and checks the result
+against the `bm` (best move) and `am` (avoid move) operations.
+
+Options:
+  --bot <name>          random | oneply | nply | nplytranspo (default nplytranspo)
+  --movetime <ms>       search time per position (default 1000)
+  --depth <n>           fixed depth per position instead of a time limit";
+
+#[derive(Debug)]
+struct Settings {
+    path: String,
+    bot: String,
+    time_control: TimeControl,
+}
+
+impl Settings {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut path = None;
+        let mut bot = "nplytranspo".to_string();
+        let mut time_control = TimeControl::FixedTime(1000);
+
+        fn parse<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+            value
+                .ok_or(format!("Missing value for {}", name))?
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value for {}", name))
+        }
+
+        while let Some(arg) = args.next() {
+            match &arg as &str {
+                "--bot" => bot = parse(&arg, args.next())?,
+                "--movetime" => time_control = TimeControl::FixedTime(parse(&arg, args.next())?),
+                "--depth" => time_control = TimeControl::FixedDepth(parse(&arg, args.next())?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown argument '{}'", arg)),
+                _ => path = Some(arg),
+            }
+        }
+
+        Ok(Self {
+            path: path.ok_or("Missing EPD file")?,
+            bot,
+            time_control,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TestPosition {
+    id: String,
+    board_state: ChessBoardState,
+    best_moves: Vec<Move>,
+    avoid_moves: Vec<Move>,
+}
+
+impl TestPosition {
+    fn from_epd(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.splitn(5, ' ').collect();
+        if fields.len() < 4 {
+            return Err(format!("Invalid EPD line '{}'", line));
+        }
+        let board_state = ChessBoardState::from_fen(&format!("{} 0 1", fields[..4].join(" ")))
+            .map_err(|e| format!("Invalid position in '{}': {}", line, e))?;
+
+        let mut position = Self {
+            id: String::new(),
+            board_state,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+        };
+
+        let operations = fields.get(4).copied().unwrap_or("");
+        for operation in operations.split(';') {
+            let (opcode, operands) = operation.trim().split_once(' ').unwrap_or((operation, ""));
+            let parse_moves = |operands: &str| {
+                operands
+                    .split_whitespace()
+                    .map(|san| {
+                        Move::from_san(san, &board_state)
+                            .or_else(|_| Move::try_from((san, &board_state)))
+                            .map_err(|_| format!("Invalid move '{}' in '{}'", san, line))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            match opcode.trim() {
+                "id" => position.id = operands.trim().trim_matches('"').to_string(),
+                "bm" => position.best_moves = parse_moves(operands)?,
+                "am" => position.avoid_moves = parse_moves(operands)?,
+                _ => {}
+            }
+        }
+
+        if position.best_moves.is_empty() && position.avoid_moves.is_empty() {
+            return Err(format!("No bm or am operation in '{}'", line));
+        }
+        Ok(position)
+    }
+
+    fn is_solved_by(&self, mv: Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&mv))
+            && !self.avoid_moves.contains(&mv)
+    }
+}
+
+fn format_moves(moves: &[Move], board_state: &ChessBoardState) -> String {
+    moves
+        .iter()
+        .map(|mv| mv.to_san(board_state))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn run_suite<B: ChessBot>(positions: &[TestPosition], time_control: TimeControl) {
+    let mut bot = B::default();
+    // Book moves would hide what the search finds
+    let _ = bot.apply_option("OpeningBook", Some("false"));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let start = Instant::now();
+    let mut solved = 0;
+    for (i, position) in positions.iter().enumerate() {
+        bot.clear_history();
+        let mut board_state = position.board_state;
+        let mv = bot.search_best_move(&mut board_state, time_control.clone(), &stop);
+
+        let is_solved = position.is_solved_by(mv);
+        if is_solved {
+            solved += 1;
+        }
+
+        let mut expected = String::new();
+        if !position.best_moves.is_empty() {
+            expected.push_str(&format!(
+                "bm {}",
+                format_moves(&position.best_moves, &position.board_state)
+            ));
+        }
+        if !position.avoid_moves.is_empty() {
+            if !expected.is_empty() {
+                expected.push_str(", ");
+            }
+            expected.push_str(&format!(
+                "am {}",
+                format_moves(&position.avoid_moves, &position.board_state)
+            ));
+        }
+        println!(
+            "{:>4}/{} {:<6} {} played {} ({})",
+            i + 1,
+            positions.len(),
+            if is_solved { "ok" } else { "FAILED" },
+            position.id,
+            mv.to_san(&position.board_state),
+            expected
+        );
+    }
+
+    println!(
+        "Solved {}/{} ({:.1}%) in {:.1}s",
+        solved,
+        positions.len(),
+        100.0 * solved as f64 / positions.len().max(1) as f64,
+        start.elapsed().as_secs_f64()
+    );
+}
+
+fn run(settings: Settings) -> Result<(), String> {
+    let content =
+        fs::read_to_string(&settings.path).map_err(|e| format!("{}: {}", settings.path, e))?;
+    let positions = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| TestPosition::from_epd(line.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let time_control = settings.time_control;
+    match &settings.bot as &str {
+        "random" => run_suite::<RandomBot>(&positions, time_control),
+        "oneply" => run_suite::<OnePlyBot>(&positions, time_control),
+        "nply" => run_suite::<NPlyBot>(&positions, time_control),
+        "nplytranspo" => run_suite::<NPlyTranspoBot>(&positions, time_control),
+        _ => return Err(format!("Unknown bot '{}'", settings.bot)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let settings = match Settings::from_args(env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = run(settings) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
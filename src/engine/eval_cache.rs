@@ -0,0 +1,57 @@
+use crate::chess::zobrist_hash::ZHash;
+
+/// Direct mapped cache of evaluation results keyed by a zobrist hash, a new entry simply
+/// replaces whatever was stored in its slot before.
+pub struct EvalCache<V: Copy + Default, const T: usize> {
+    entries: Vec<(ZHash, V)>,
+}
+
+impl<V: Copy + Default, const T: usize> Default for EvalCache<V, T> {
+    fn default() -> Self {
+        Self {
+            // The zero hash can not be told apart from an empty slot, so a slot starts out with a
+            // hash that can never map to it
+            entries: (0..T)
+                .map(|i| (ZHash(i as u64 + 1), V::default()))
+                .collect(),
+        }
+    }
+}
+
+impl<V: Copy + Default, const T: usize> EvalCache<V, T> {
+    #[inline(always)]
+    pub fn get_or_insert_with(&mut self, hash: ZHash, compute: impl FnOnce() -> V) -> V {
+        let entry = &mut self.entries[hash.0 as usize % T];
+        if entry.0 != hash {
+            *entry = (hash, compute());
+        }
+        entry.1
+    }
+}
+
+#[cfg(test)]
+mod eval_cache_tests {
+    use crate::{chess::zobrist_hash::ZHash, engine::eval_cache::EvalCache};
+
+    #[test]
+    fn computes_only_on_miss() {
+        let mut cache = EvalCache::<i32, 16>::default();
+        let mut computed = 0;
+
+        for _ in 0..3 {
+            let value = cache.get_or_insert_with(ZHash(5), || {
+                computed += 1;
+                42
+            });
+            assert_eq!(value, 42);
+        }
+        assert_eq!(computed, 1);
+
+        // The empty hash is a valid key and must not hit the initial entry
+        assert_eq!(cache.get_or_insert_with(ZHash(0), || 7), 7);
+
+        // Same slot, different hash replaces the entry
+        assert_eq!(cache.get_or_insert_with(ZHash(5 + 16), || 1), 1);
+        assert_eq!(cache.get_or_insert_with(ZHash(5), || 2), 2);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    chess::board::{ChessBoardState, PieceColor},
+    engine::board_eval::{
+        endgame_lerp_value, BishopPairEvaluation, DoublePawnsEvaluation, EvalBreakdown, EvalTerm,
+        EvaluationFunction, KingPawnShieldEvaluation, PassedPawnEvaluation,
+        PawnStructureEvaluation, PieceConnectivityEvaluation, PieceCountEvaluation,
+        PieceSquareTableEvaluation, TempoEvaluation,
+    },
+    error::IgloError,
+};
+
+/// Weight of each evaluation term in percent, 100 counts a term as it is and 0 turns it
+/// off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalConfig {
+    pub material: i32,
+    pub piece_squares: i32,
+    pub passed_pawns: i32,
+    pub bishop_pair: i32,
+    pub king_pawn_shield: i32,
+    pub doubled_pawns: i32,
+    pub pawn_structure: i32,
+    pub connectivity: i32,
+    pub tempo: i32,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl EvalConfig {
+    /// The weights the evaluation was tuned with.
+    pub const DEFAULT: Self = Self {
+        material: 100,
+        piece_squares: 100,
+        passed_pawns: 100,
+        bishop_pair: 100,
+        king_pawn_shield: 100,
+        doubled_pawns: 100,
+        pawn_structure: 100,
+        connectivity: 0,
+        tempo: 100,
+    };
+
+    /// Weight of a term by its name in config files.
+    pub fn weight_mut(&mut self, term: &str) -> Option<&mut i32> {
+        match term {
+            "material" => Some(&mut self.material),
+            "piece_squares" => Some(&mut self.piece_squares),
+            "passed_pawns" => Some(&mut self.passed_pawns),
+            "bishop_pair" => Some(&mut self.bishop_pair),
+            "king_pawn_shield" => Some(&mut self.king_pawn_shield),
+            "doubled_pawns" => Some(&mut self.doubled_pawns),
+            "pawn_structure" => Some(&mut self.pawn_structure),
+            "connectivity" => Some(&mut self.connectivity),
+            "tempo" => Some(&mut self.tempo),
+            _ => None,
+        }
+    }
+
+    /// Reads weights from a flat TOML table (`material = 100`) or JSON object
+    /// (`{"material": 100}`), terms which are not mentioned keep their default weight.
+    pub fn parse(text: &str) -> Result<Self, IgloError> {
+        let mut config = Self::default();
+        let entries = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(|line| line.split(','))
+            .map(|entry| entry.trim_matches(|c: char| c.is_whitespace() || c == '{' || c == '}'))
+            .filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            let invalid = || IgloError::InvalidEvalConfig(entry.to_string());
+            let (term, weight) = entry.split_once(['=', ':']).ok_or_else(invalid)?;
+            let term = term.trim().trim_matches('"');
+            *config.weight_mut(term).ok_or_else(invalid)? =
+                weight.trim().parse().map_err(|_| invalid())?;
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Self, IgloError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| IgloError::InvalidEvalConfig(format!("{}: {}", path, e)))?;
+        Self::parse(&text)
+    }
+}
+
+/// Sum of all evaluation terms, each scaled by its weight in the default `EvalConfig`.
+/// `eval_with` takes the weights as an argument, there are no weights shared by all
+/// evaluations.
+pub struct CompositeEvaluation;
+impl EvaluationFunction for CompositeEvaluation {
+    fn eval(board_state: &ChessBoardState) -> i32 {
+        Self::eval_with(&EvalConfig::DEFAULT, board_state)
+    }
+
+    fn eval_breakdown(board_state: &ChessBoardState) -> Option<EvalBreakdown> {
+        Some(Self::breakdown_with(&EvalConfig::DEFAULT, board_state))
+    }
+}
+
+impl CompositeEvaluation {
+    pub fn eval_with(config: &EvalConfig, board_state: &ChessBoardState) -> i32 {
+        // Terms turned off are not computed at all
+        let term = |weight: i32, eval: fn(&ChessBoardState) -> i32| match weight {
+            0 => 0,
+            _ => eval(board_state) * weight / 100,
+        };
+        term(config.material, PieceCountEvaluation::eval)
+            + term(config.piece_squares, PieceSquareTableEvaluation::eval)
+            + term(config.passed_pawns, PassedPawnEvaluation::eval)
+            + term(config.bishop_pair, BishopPairEvaluation::eval)
+            + term(config.king_pawn_shield, KingPawnShieldEvaluation::eval)
+            + term(config.doubled_pawns, DoublePawnsEvaluation::eval)
+            + term(config.pawn_structure, PawnStructureEvaluation::eval)
+            + term(config.connectivity, PieceConnectivityEvaluation::eval)
+            + term(config.tempo, TempoEvaluation::eval)
+    }
+
+    /// Weighted score of every term for both sides, the terms turned off included. The
+    /// total is `eval_with`, which scales the difference of the sides instead of each side
+    /// and may round differently.
+    pub fn breakdown_with(config: &EvalConfig, board_state: &ChessBoardState) -> EvalBreakdown {
+        type SideEval = fn(&ChessBoardState, PieceColor) -> i32;
+        let terms: [(&str, i32, SideEval); 9] = [
+            ("material", config.material, PieceCountEvaluation::eval_side),
+            (
+                "piece_squares",
+                config.piece_squares,
+                PieceSquareTableEvaluation::eval_side,
+            ),
+            (
+                "passed_pawns",
+                config.passed_pawns,
+                PassedPawnEvaluation::eval_side,
+            ),
+            (
+                "bishop_pair",
+                config.bishop_pair,
+                BishopPairEvaluation::eval_side,
+            ),
+            (
+                "king_pawn_shield",
+                config.king_pawn_shield,
+                KingPawnShieldEvaluation::eval_side,
+            ),
+            (
+                "doubled_pawns",
+                config.doubled_pawns,
+                DoublePawnsEvaluation::eval_side,
+            ),
+            (
+                "pawn_structure",
+                config.pawn_structure,
+                PawnStructureEvaluation::eval_side,
+            ),
+            (
+                "connectivity",
+                config.connectivity,
+                PieceConnectivityEvaluation::eval_side,
+            ),
+            ("tempo", config.tempo, TempoEvaluation::eval_side),
+        ];
+
+        EvalBreakdown {
+            terms: terms
+                .iter()
+                .map(|&(name, weight, eval_side)| EvalTerm {
+                    name,
+                    white: eval_side(board_state, PieceColor::White) * weight / 100,
+                    black: eval_side(board_state, PieceColor::Black) * weight / 100,
+                })
+                .collect(),
+            phase: endgame_lerp_value(board_state),
+            total: Self::eval_with(config, board_state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod eval_config_tests {
+    use super::{CompositeEvaluation, EvalConfig};
+    use crate::{
+        chess::board::ChessBoardState,
+        engine::{
+            bench::BENCH_POSITIONS,
+            board_eval::{EvaluationFunction, PieceCountEvaluation},
+        },
+    };
+
+    #[test]
+    fn parse_toml_and_json() {
+        let toml = "# material only\nmaterial = 150\npiece_squares = 0\n";
+        let json = r#"{"material": 150, "piece_squares": 0}"#;
+        let expected = EvalConfig {
+            material: 150,
+            piece_squares: 0,
+            ..EvalConfig::default()
+        };
+        assert_eq!(EvalConfig::parse(toml), Ok(expected));
+        assert_eq!(EvalConfig::parse(json), Ok(expected));
+        assert_eq!(EvalConfig::parse(""), Ok(EvalConfig::default()));
+
+        assert!(EvalConfig::parse("mobility = 100").is_err());
+        assert!(EvalConfig::parse("material = lots").is_err());
+        assert!(EvalConfig::parse("material").is_err());
+    }
+
+    #[test]
+    fn weights_scale_terms() {
+        let board_state = ChessBoardState::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let material_only = EvalConfig {
+            material: 50,
+            piece_squares: 0,
+            passed_pawns: 0,
+            bishop_pair: 0,
+            king_pawn_shield: 0,
+            doubled_pawns: 0,
+            pawn_structure: 0,
+            connectivity: 0,
+            tempo: 0,
+        };
+        assert_eq!(
+            CompositeEvaluation::eval_with(&material_only, &board_state),
+            PieceCountEvaluation::eval(&board_state) / 2
+        );
+    }
+
+    #[test]
+    fn breakdown_sums_to_eval() {
+        let board_state = ChessBoardState::from_fen(
+            "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R b KQ - 2 8",
+        )
+        .unwrap();
+        let config = EvalConfig::default();
+        let breakdown = CompositeEvaluation::breakdown_with(&config, &board_state);
+        assert_eq!(breakdown.terms.len(), 9);
+        assert_eq!(breakdown.terms[0].name, "material");
+        // All default weights are 100 or 0, so nothing is rounded
+        assert_eq!(
+            breakdown.terms.iter().map(|term| term.total()).sum::<i32>(),
+            CompositeEvaluation::eval_with(&config, &board_state)
+        );
+        assert_eq!(breakdown.total, CompositeEvaluation::eval(&board_state));
+        assert!(breakdown.phase < 0.5);
+        assert!(breakdown.to_string().contains("pawn_structure"));
+    }
+
+    #[test]
+    fn eval_is_color_symmetric() {
+        let all_terms = EvalConfig {
+            connectivity: 100,
+            ..EvalConfig::default()
+        };
+        let corpus = BENCH_POSITIONS.iter().copied().chain([
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/pP1pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/1PPBBPPP/R3K2R w KQkq c6 0 2",
+            "4k3/1p4p1/2p1P3/3P4/5p2/1P6/P5PP/4K3 b - - 0 30",
+            "6k1/5ppp/8/8/8/8/Q4PPP/1q4K1 w - - 0 40",
+            "1k1r4/pp3q2/2n5/4p3/8/2P2B2/PP3QPP/3R2K1 w - - 0 25",
+        ]);
+        for fen in corpus {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            let board_flipped = board_state.color_flipped();
+
+            let breakdown = CompositeEvaluation::breakdown_with(&all_terms, &board_state);
+            let breakdown_flipped = CompositeEvaluation::breakdown_with(&all_terms, &board_flipped);
+            for (term, term_flipped) in breakdown.terms.iter().zip(&breakdown_flipped.terms) {
+                assert_eq!(
+                    (term.white, term.black),
+                    (term_flipped.black, term_flipped.white),
+                    "{} of {} and {}",
+                    term.name,
+                    fen,
+                    board_flipped.to_fen()
+                );
+            }
+            assert_eq!(breakdown.total, -breakdown_flipped.total, "{}", fen);
+        }
+    }
+}
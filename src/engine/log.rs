@@ -0,0 +1,105 @@
+use std::{
+    fs::File,
+    io::Write,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+/// Severity of a log message, only messages up to the configured level are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+/// Destination of the engine's diagnostics. Stdout belongs to the UCI protocol, so
+/// sinks must never write there.
+pub trait LogSink: Send {
+    fn log(&mut self, level: LogLevel, message: &str);
+}
+
+/// Writes to stderr, which GUIs either ignore or show as engine output.
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn log(&mut self, level: LogLevel, message: &str) {
+        eprintln!("[{:?}] {}", level, message);
+    }
+}
+
+/// Appends to a file, for GUIs which swallow stderr.
+pub struct FileSink(File);
+
+impl FileSink {
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self(file))
+    }
+}
+
+impl LogSink for FileSink {
+    fn log(&mut self, level: LogLevel, message: &str) {
+        // Losing a diagnostic is better than taking the engine down
+        let _ = writeln!(self.0, "[{:?}] {}", level, message);
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+static SINK: Mutex<Option<Box<dyn LogSink>>> = Mutex::new(None);
+
+/// Replaces the sink, stderr is used until one is set.
+pub fn set_sink(sink: Box<dyn LogSink>) {
+    *SINK.lock().unwrap() = Some(sink);
+}
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Writes `args` to the sink if `level` is enabled, used through the `log_*` macros.
+pub fn log(level: LogLevel, args: std::fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let message = args.to_string();
+    match SINK.lock().unwrap().as_mut() {
+        Some(sink) => sink.log(level, &message),
+        None => StderrSink.log(level, &message),
+    }
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::engine::log::log($crate::engine::log::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::engine::log::log($crate::engine::log::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::engine::log::log($crate::engine::log::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::engine::log::log($crate::engine::log::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
@@ -1,20 +1,62 @@
-use iglo::engine::bots::nply_bot::NPlyBot;
-use iglo::engine::bots::nplytranspo_bot::NPlyTranspoBot;
-use iglo::engine::{bots::oneply_bot::OnePlyBot, bots::random_bot::RandomBot, uci::UCIReader};
+use iglo::engine::{
+    bench::BENCH_DEPTH,
+    bots::{find_bot, registry},
+};
 use std::env;
 
+const USAGE: &str = "\
+Usage: iglo [bot] [options]
+       iglo [bot] bench [depth]   search the bench positions, print the node signature and exit
+
+Options:
+  --list            print the available bots and exit
+  --strength <elo>  limit the playing strength, same as UCI_LimitStrength and UCI_Elo
+";
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut bot_name = "nplytranspo".to_string();
+    let mut options = Vec::new();
+    let mut bench_depth = None;
 
-    if args.len() == 1 {
-        UCIReader::<NPlyTranspoBot>::default().run();
-    } else {
-        match &args[1] as &str {
-            "random" => UCIReader::<RandomBot>::default().run(),
-            "oneply" => UCIReader::<OnePlyBot>::default().run(),
-            "nply" => UCIReader::<NPlyBot>::default().run(),
-            "nplytranspo" => UCIReader::<NPlyTranspoBot>::default().run(),
-            _ => return,
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => {
+                for entry in registry() {
+                    println!(
+                        "{:<12} ~{:>4} Elo  {}",
+                        entry.name, entry.strength, entry.description
+                    );
+                }
+                return;
+            }
+            "--strength" => {
+                let Some(elo) = args.next().filter(|elo| elo.parse::<u32>().is_ok()) else {
+                    eprint!("--strength expects an Elo rating\n\n{}", USAGE);
+                    return;
+                };
+                options.push(("UCI_LimitStrength".to_string(), "true".to_string()));
+                options.push(("UCI_Elo".to_string(), elo));
+            }
+            "bench" => {
+                bench_depth = match args.next_if(|arg| arg.parse::<u16>().is_ok()) {
+                    Some(depth) => depth.parse().ok(),
+                    None => Some(BENCH_DEPTH),
+                };
+            }
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ => bot_name = arg,
         }
-    };
+    }
+
+    match find_bot(&bot_name) {
+        Some(entry) => match bench_depth {
+            Some(depth) => println!("{}", entry.bench(depth)),
+            None => entry.run_uci(&options),
+        },
+        None => eprint!("Unknown bot '{}'\n\n{}", bot_name, USAGE),
+    }
 }
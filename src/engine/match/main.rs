@@ -0,0 +1,613 @@
+mod player;
+mod stats;
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    process,
+    time::Instant,
+};
+
+use iglo::{
+    chess::{
+        board::{ChessBoardState, PieceColor},
+        chess_move::Move,
+        game::{Game, GameClock},
+    },
+    engine::opening::polyglot::{OpeningBook, PolyglotOpeningBook},
+};
+use player::{create_player, MoveLimit, Player};
+use rand::seq::SliceRandom;
+use stats::{MatchStats, Sprt, SprtResult};
+
+const USAGE: &str = "\
+Usage: match --engine1 <player> --engine2 <player> [options]
+
+Players:
+  random | oneply | nply | nplytranspo[:Name=Value,...]   built in bots with UCI options
+  uci:<path>                                              external UCI engine
+
+Options:
+  --games <n>                   number of games, openings are played with both colors
+                                (default 2, unlimited with --sprt)
+  --tc <seconds>+<increment>    clock per game, e.g. 10+0.1
+  --movetime <ms>               fixed time per move
+  --depth <n>                   fixed depth per move (default 4 without time control)
+  --openings <file>             FEN/EPD file with one starting position per line
+  --book <file>                 polyglot book to pick random opening lines from
+  --book-plies <n>              length of the book lines (default 8)
+  --max-plies <n>               adjudicate longer games as draw (default 400)
+  --resign-score <cp>           adjudicate a loss when both engines agree on this score ...
+  --resign-moves <n>            ... for this many consecutive moves (default 4)
+  --draw-score <cp>             adjudicate a draw when both engines stay within this score ...
+  --draw-moves <n>              ... for this many consecutive moves (default 8)
+  --pgn <file>                  append finished games to this PGN file
+  --event <name>                Event tag of the PGN games (default \"Iglo match\")
+  --epd <file>                  append the final positions of adjudicated games to this
+                                EPD file
+  --sprt <elo0>,<elo1>[,<alpha>,<beta>]
+                                stop as soon as the SPRT of engine1 against engine2 accepts
+                                H0 (elo0) or H1 (elo1), alpha and beta default to 0.05";
+
+fn parse_sprt(value: &str) -> Result<Sprt, String> {
+    let bounds = value
+        .split(',')
+        .map(|bound| bound.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Invalid value for --sprt")?;
+    let sprt = match bounds[..] {
+        [elo0, elo1] => Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        },
+        [elo0, elo1, alpha, beta] => Sprt {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        },
+        _ => return Err("--sprt expects <elo0>,<elo1>[,<alpha>,<beta>]".to_string()),
+    };
+
+    let is_probability = |p: f64| p > 0.0 && p < 1.0;
+    if sprt.elo0 >= sprt.elo1 || !is_probability(sprt.alpha) || !is_probability(sprt.beta) {
+        return Err("--sprt needs elo0 < elo1 and alpha, beta between 0 and 1".to_string());
+    }
+    Ok(sprt)
+}
+
+// Clock overrun tolerated before a player loses on time
+const TIME_MARGIN_MS: u64 = 100;
+
+#[derive(Debug)]
+struct Settings {
+    engine1: String,
+    engine2: String,
+    games: u32,
+    sprt: Option<Sprt>,
+    limit: MoveLimit,
+    openings: Option<String>,
+    book: Option<String>,
+    book_plies: u32,
+    max_plies: usize,
+    resign: Option<(i32, usize)>,
+    draw: Option<(i32, usize)>,
+    pgn: Option<String>,
+    event: String,
+    epd: Option<String>,
+}
+
+impl Settings {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut engine1 = None;
+        let mut engine2 = None;
+        let mut games = None;
+        let mut sprt = None;
+        let mut limit = MoveLimit::Depth(4);
+        let mut openings = None;
+        let mut book = None;
+        let mut book_plies = 8;
+        let mut max_plies = 400;
+        let mut resign_score = None;
+        let mut resign_moves = 4;
+        let mut draw_score = None;
+        let mut draw_moves = 8;
+        let mut pgn = None;
+        let mut event = "Iglo match".to_string();
+        let mut epd = None;
+
+        fn parse<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+            value
+                .ok_or(format!("Missing value for {}", name))?
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value for {}", name))
+        }
+
+        while let Some(arg) = args.next() {
+            match &arg as &str {
+                "--engine1" => engine1 = Some(parse::<String>(&arg, args.next())?),
+                "--engine2" => engine2 = Some(parse::<String>(&arg, args.next())?),
+                "--games" => games = Some(parse(&arg, args.next())?),
+                "--tc" => {
+                    let tc = parse::<String>(&arg, args.next())?;
+                    let (base, inc) = tc.split_once('+').unwrap_or((&tc, "0"));
+                    let base: f64 = base.parse().map_err(|_| "Invalid --tc base")?;
+                    let inc: f64 = inc.parse().map_err(|_| "Invalid --tc increment")?;
+                    let (time, inc) = ((base * 1000.0) as u64, (inc * 1000.0) as u64);
+                    limit = MoveLimit::Clock {
+                        white_time: time,
+                        black_time: time,
+                        white_inc: inc,
+                        black_inc: inc,
+                    };
+                }
+                "--movetime" => limit = MoveLimit::MoveTime(parse(&arg, args.next())?),
+                "--depth" => limit = MoveLimit::Depth(parse(&arg, args.next())?),
+                "--openings" => openings = Some(parse(&arg, args.next())?),
+                "--book" => book = Some(parse(&arg, args.next())?),
+                "--book-plies" => book_plies = parse(&arg, args.next())?,
+                "--max-plies" => max_plies = parse(&arg, args.next())?,
+                "--resign-score" => resign_score = Some(parse(&arg, args.next())?),
+                "--resign-moves" => resign_moves = parse(&arg, args.next())?,
+                "--draw-score" => draw_score = Some(parse(&arg, args.next())?),
+                "--draw-moves" => draw_moves = parse(&arg, args.next())?,
+                "--pgn" => pgn = Some(parse(&arg, args.next())?),
+                "--event" => event = parse(&arg, args.next())?,
+                "--epd" => epd = Some(parse(&arg, args.next())?),
+                "--sprt" => sprt = Some(parse_sprt(&parse::<String>(&arg, args.next())?)?),
+                _ => return Err(format!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        Ok(Self {
+            engine1: engine1.ok_or("Missing --engine1")?,
+            engine2: engine2.ok_or("Missing --engine2")?,
+            games: games.unwrap_or(if sprt.is_some() { u32::MAX } else { 2 }),
+            sprt,
+            limit,
+            openings,
+            book,
+            book_plies,
+            max_plies,
+            resign: resign_score.map(|score| (score, resign_moves)),
+            draw: draw_score.map(|score| (score, draw_moves)),
+            pgn,
+            event,
+            epd,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl Outcome {
+    fn as_pgn_str(&self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+
+    fn win_for(color: PieceColor) -> Self {
+        match color {
+            PieceColor::White => Outcome::WhiteWins,
+            PieceColor::Black => Outcome::BlackWins,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GameRecord {
+    game: Game,
+    outcome: Outcome,
+    termination: String,
+}
+
+impl GameRecord {
+    fn is_adjudicated(&self) -> bool {
+        self.termination.starts_with("adjudication")
+    }
+
+    /// Value of the PGN Termination tag, which only knows the kind of the termination.
+    fn pgn_termination(&self) -> &'static str {
+        match self.termination.as_str() {
+            "time forfeit" => "time forfeit",
+            "illegal move" => "rules infraction",
+            "engine failure" => "abandoned",
+            _ if self.is_adjudicated() => "adjudication",
+            _ => "normal",
+        }
+    }
+}
+
+/// Time control in the notation of the PGN TimeControl tag: `base+increment` in seconds,
+/// `1/seconds` for a fixed time per move and `-` for a depth limit.
+fn pgn_time_control(limit: &MoveLimit) -> String {
+    let seconds = |ms: u64| ms as f64 / 1000.0;
+    match limit {
+        MoveLimit::Clock {
+            white_time,
+            white_inc,
+            ..
+        } => format!("{}+{}", seconds(*white_time), seconds(*white_inc)),
+        MoveLimit::MoveTime(ms) => format!("1/{}", seconds(*ms)),
+        MoveLimit::Depth(_) => "-".to_string(),
+    }
+}
+
+fn load_openings(settings: &Settings) -> Result<Vec<ChessBoardState>, String> {
+    let path = match &settings.openings {
+        Some(path) => path,
+        None => return Ok(vec![ChessBoardState::starting_state()]),
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let openings: Vec<ChessBoardState> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            // EPD lines only carry the first four FEN fields followed by operations
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let fen = if fields.len() >= 6 && fields[4].parse::<u16>().is_ok() {
+                fields[..6].join(" ")
+            } else {
+                format!("{} 0 1", fields[..fields.len().min(4)].join(" "))
+            };
+            ChessBoardState::from_fen(&fen)
+                .map_err(|e| format!("Invalid opening '{}': {}", line, e))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if openings.is_empty() {
+        return Err(format!("{} contains no positions", path));
+    }
+    Ok(openings)
+}
+
+// Extends the opening with randomly chosen book moves until the book runs out
+fn play_book_line(
+    book: &PolyglotOpeningBook,
+    start_state: &ChessBoardState,
+    plies: u32,
+) -> ChessBoardState {
+    let mut board_state = *start_state;
+    let mut rng = rand::thread_rng();
+    for _ in 0..plies {
+        let legal_moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let book_moves: Vec<Move> = book
+            .get(&board_state)
+            .into_iter()
+            .filter(|mv| legal_moves.contains(mv))
+            .collect();
+        match book_moves.choose(&mut rng) {
+            Some(mv) => board_state = board_state.exec_move(*mv),
+            None => break,
+        }
+    }
+    board_state
+}
+
+fn play_game(
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    start_state: &ChessBoardState,
+    settings: &Settings,
+) -> GameRecord {
+    let mut game = Game::new(*start_state);
+    let mut limit = settings.limit;
+    // Consecutive plies the resign and draw adjudication conditions held, for resigning
+    // next to the side all of those scores see winning
+    let mut resign_plies = 0;
+    let mut resign_winner = PieceColor::White;
+    let mut draw_plies = 0;
+
+    let finish = |mut game: Game, outcome: Outcome, termination: &str| {
+        game.set_result(outcome.as_pgn_str());
+        GameRecord {
+            game,
+            outcome,
+            termination: termination.to_string(),
+        }
+    };
+
+    for result in [white.new_game(), black.new_game()] {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+    }
+
+    loop {
+        let board_state = *game.current_state();
+        let side = board_state.side;
+        let legal_moves = board_state.generate_legal_moves_for_current_player::<false>();
+        if legal_moves.is_empty() {
+            return if board_state.is_in_check() {
+                finish(game, Outcome::win_for(!side), "checkmate")
+            } else {
+                finish(game, Outcome::Draw, "stalemate")
+            };
+        }
+        // Draws by rule are claimed right away
+        if let Some(reason) = game.draw_reason() {
+            return finish(game, Outcome::Draw, &reason.to_string());
+        }
+        if game.moves().len() >= settings.max_plies {
+            return finish(game, Outcome::Draw, "adjudication: maximum game length");
+        }
+
+        let player: &mut dyn Player = if side == PieceColor::White {
+            &mut *white
+        } else {
+            &mut *black
+        };
+
+        let start = Instant::now();
+        let selected = player.select_move(start_state, game.moves(), limit);
+        let elapsed = start.elapsed().as_millis() as u64;
+
+        let (mv, score) = match selected {
+            Ok(selected) => selected,
+            Err(e) => {
+                eprintln!("{}", e);
+                return finish(game, Outcome::win_for(!side), "engine failure");
+            }
+        };
+
+        if let MoveLimit::Clock {
+            white_time,
+            black_time,
+            white_inc,
+            black_inc,
+        } = &mut limit
+        {
+            let (time, inc) = if side == PieceColor::White {
+                (white_time, *white_inc)
+            } else {
+                (black_time, *black_inc)
+            };
+            if elapsed > *time + TIME_MARGIN_MS {
+                return finish(game, Outcome::win_for(!side), "time forfeit");
+            }
+            *time = time.saturating_sub(elapsed) + inc;
+        }
+        if let MoveLimit::Clock {
+            white_time,
+            black_time,
+            white_inc,
+            black_inc,
+        } = limit
+        {
+            game.clock = Some(GameClock {
+                white_time,
+                black_time,
+                white_inc,
+                black_inc,
+            });
+        }
+
+        // Match by squares and promotion so differing move type flags don't matter
+        let legal_move = legal_moves.iter().find(|legal| {
+            legal.get_src() == mv.get_src()
+                && legal.get_dst() == mv.get_dst()
+                && (!legal.is_promotion() || legal.promotion_target() == mv.promotion_target())
+        });
+        let mv = match legal_move {
+            Some(mv) => *mv,
+            None => {
+                eprintln!("{} played illegal move {:?}", player.name(), mv);
+                return finish(game, Outcome::win_for(!side), "illegal move");
+            }
+        };
+
+        if let Err(e) = game.push_move(mv) {
+            eprintln!("{} played {}", player.name(), e);
+            return finish(game, Outcome::win_for(!side), "illegal move");
+        }
+        if game.repetition_count() >= 3 {
+            return finish(game, Outcome::Draw, "threefold repetition");
+        }
+
+        // Score adjudication requires both engines to agree, so plies are counted per side
+        if let Some(score) = score {
+            match settings.resign {
+                Some((threshold, needed)) if score.abs() >= threshold => {
+                    let winner = if score > 0 { side } else { !side };
+                    if resign_plies > 0 && winner != resign_winner {
+                        resign_plies = 0;
+                    }
+                    resign_plies += 1;
+                    resign_winner = winner;
+                    if resign_plies >= 2 * needed {
+                        return finish(game, Outcome::win_for(winner), "adjudication: resign");
+                    }
+                }
+                _ => resign_plies = 0,
+            }
+            match settings.draw {
+                Some((threshold, needed)) if score.abs() <= threshold => {
+                    draw_plies += 1;
+                    if draw_plies >= 2 * needed {
+                        return finish(game, Outcome::Draw, "adjudication: draw");
+                    }
+                }
+                _ => draw_plies = 0,
+            }
+        } else {
+            resign_plies = 0;
+            draw_plies = 0;
+        }
+    }
+}
+
+fn append_to_file(path: &str, text: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    writeln!(file, "{}", text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn write_pgn(
+    path: &str,
+    record: &GameRecord,
+    settings: &Settings,
+    round: u32,
+    white: &str,
+    black: &str,
+) -> Result<(), String> {
+    let tags = [
+        ("Event", settings.event.clone()),
+        ("Round", round.to_string()),
+        ("White", white.to_string()),
+        ("Black", black.to_string()),
+        ("TimeControl", pgn_time_control(&settings.limit)),
+        ("Termination", record.pgn_termination().to_string()),
+    ];
+    append_to_file(path, &record.game.to_pgn(&tags))
+}
+
+/// Appends the final position of an adjudicated game as an EPD line, with the game in `id`,
+/// the adjudication in `c0` and the result in `c1`.
+fn write_epd(path: &str, record: &GameRecord, round: u32) -> Result<(), String> {
+    let board_state = record.game.current_state();
+    let fen = board_state.to_fen();
+    let position: Vec<&str> = fen.split_whitespace().take(4).collect();
+    let epd = format!(
+        "{} hmvc {}; fmvn {}; id \"game {}\"; c0 \"{}\"; c1 \"{}\";",
+        position.join(" "),
+        board_state.half_moves,
+        board_state.full_moves,
+        round,
+        record.termination,
+        record.outcome.as_pgn_str()
+    );
+    append_to_file(path, &epd)
+}
+
+fn print_stats(stats: &MatchStats, sprt: Option<&Sprt>, engine1: &str, engine2: &str) {
+    println!(
+        "Score of {} vs {}: {} - {} - {} [{:.3}] {}",
+        engine1,
+        engine2,
+        stats.wins,
+        stats.losses,
+        stats.draws,
+        stats.score(),
+        stats.games()
+    );
+    match stats.elo_difference() {
+        Some((elo, margin)) => println!("Elo difference: {:.1} +/- {:.1}", elo, margin),
+        None => println!("Elo difference: -"),
+    }
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "SPRT: llr {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]",
+            sprt.llr(stats),
+            lower,
+            upper,
+            sprt.elo0,
+            sprt.elo1
+        );
+    }
+}
+
+fn run(settings: Settings) -> Result<(), String> {
+    let mut engine1 = create_player(&settings.engine1)?;
+    let mut engine2 = create_player(&settings.engine2)?;
+    let openings = load_openings(&settings)?;
+    let book = match &settings.book {
+        Some(path) => {
+            Some(PolyglotOpeningBook::open(path).map_err(|e| format!("{}: {}", path, e))?)
+        }
+        None => None,
+    };
+
+    let mut stats = MatchStats::default();
+    let mut opening = openings[0];
+
+    for game in 0..settings.games {
+        // Each opening is played twice with colors reversed
+        if game % 2 == 0 {
+            opening = openings[(game as usize / 2) % openings.len()];
+            if let Some(book) = &book {
+                opening = play_book_line(book, &opening, settings.book_plies);
+            }
+        }
+        let engine1_is_white = game % 2 == 0;
+
+        let record = if engine1_is_white {
+            play_game(&mut *engine1, &mut *engine2, &opening, &settings)
+        } else {
+            play_game(&mut *engine2, &mut *engine1, &opening, &settings)
+        };
+
+        let (white, black) = if engine1_is_white {
+            (engine1.name(), engine2.name())
+        } else {
+            (engine2.name(), engine1.name())
+        };
+        println!(
+            "Finished game {} ({} vs {}): {} {{{}}}",
+            game + 1,
+            white,
+            black,
+            record.outcome.as_pgn_str(),
+            record.termination
+        );
+
+        match (record.outcome, engine1_is_white) {
+            (Outcome::Draw, _) => stats.draws += 1,
+            (Outcome::WhiteWins, true) | (Outcome::BlackWins, false) => stats.wins += 1,
+            _ => stats.losses += 1,
+        }
+        print_stats(
+            &stats,
+            settings.sprt.as_ref(),
+            engine1.name(),
+            engine2.name(),
+        );
+
+        if let Some(path) = &settings.pgn {
+            write_pgn(path, &record, &settings, game + 1, white, black)?;
+        }
+        if let Some(path) = settings.epd.as_ref().filter(|_| record.is_adjudicated()) {
+            write_epd(path, &record, game + 1)?;
+        }
+
+        if let Some(sprt) = &settings.sprt {
+            match sprt.result(&stats) {
+                SprtResult::AcceptH0 => println!("SPRT: H0 accepted"),
+                SprtResult::AcceptH1 => println!("SPRT: H1 accepted"),
+                SprtResult::Continue => continue,
+            }
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let settings = match Settings::from_args(env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = run(settings) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
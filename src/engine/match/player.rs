@@ -0,0 +1,344 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::AtomicBool,
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use iglo::{
+    chess::{board::ChessBoardState, chess_move::Move},
+    engine::{
+        bot::ChessBot,
+        bots::{
+            nply_bot::NPlyBot, nplytranspo_bot::NPlyTranspoBot, oneply_bot::OnePlyBot,
+            random_bot::RandomBot,
+        },
+        search::CHECKMATE,
+        time_control::{ClockControl, TimeControl},
+    },
+};
+
+// Time an external engine may exceed its budget before it is considered hung
+const UCI_RESPONSE_GRACE: Duration = Duration::from_secs(5);
+const UCI_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Search limit for a single move, all times in milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub enum MoveLimit {
+    Clock {
+        white_time: u64,
+        black_time: u64,
+        white_inc: u64,
+        black_inc: u64,
+    },
+    MoveTime(u64),
+    Depth(u64),
+}
+
+impl MoveLimit {
+    fn to_time_control(self) -> TimeControl {
+        match self {
+            MoveLimit::Clock {
+                white_time,
+                black_time,
+                white_inc,
+                black_inc,
+            } => TimeControl::Variable(ClockControl {
+                white_time: Some(white_time),
+                black_time: Some(black_time),
+                white_inc: Some(white_inc),
+                black_inc: Some(black_inc),
+                movestogo: None,
+            }),
+            MoveLimit::MoveTime(t) => TimeControl::FixedTime(t),
+            MoveLimit::Depth(d) => TimeControl::FixedDepth(d),
+        }
+    }
+
+    fn to_go_command(self) -> String {
+        match self {
+            MoveLimit::Clock {
+                white_time,
+                black_time,
+                white_inc,
+                black_inc,
+            } => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                white_time, black_time, white_inc, black_inc
+            ),
+            MoveLimit::MoveTime(t) => format!("go movetime {}", t),
+            MoveLimit::Depth(d) => format!("go depth {}", d),
+        }
+    }
+
+    // Upper bound for how long a search may take before the engine is considered unresponsive
+    fn response_timeout(self) -> Duration {
+        match self {
+            MoveLimit::Clock {
+                white_time,
+                black_time,
+                ..
+            } => Duration::from_millis(white_time.max(black_time)) + UCI_RESPONSE_GRACE,
+            MoveLimit::MoveTime(t) => Duration::from_millis(t) + UCI_RESPONSE_GRACE,
+            MoveLimit::Depth(_) => Duration::from_secs(3600),
+        }
+    }
+}
+
+pub trait Player {
+    fn name(&self) -> &str;
+    fn new_game(&mut self) -> Result<(), String>;
+    /// Picks a move for the position reached by playing `moves` from `start_state`. Returns the
+    /// move together with the score the player reported for it, in centipawns from its own
+    /// point of view.
+    fn select_move(
+        &mut self,
+        start_state: &ChessBoardState,
+        moves: &[Move],
+        limit: MoveLimit,
+    ) -> Result<(Move, Option<i32>), String>;
+}
+
+pub struct BotPlayer<B: ChessBot> {
+    name: String,
+    bot: B,
+    stop: Arc<AtomicBool>,
+}
+
+impl<B: ChessBot> BotPlayer<B> {
+    pub fn new(name: &str, options: &[(String, String)]) -> Result<Self, String> {
+        let mut bot = B::default();
+        for (option_name, value) in options {
+            bot.apply_option(option_name, Some(value))
+                .map_err(|e| format!("{} for {}", e, name))?;
+        }
+        Ok(Self {
+            name: name.to_string(),
+            bot,
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+}
+
+impl<B: ChessBot> Player for BotPlayer<B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.bot.new_game();
+        Ok(())
+    }
+
+    fn select_move(
+        &mut self,
+        start_state: &ChessBoardState,
+        moves: &[Move],
+        limit: MoveLimit,
+    ) -> Result<(Move, Option<i32>), String> {
+        self.bot.clear_history();
+        let mut board_state = *start_state;
+        self.bot.append_to_history(&mut board_state);
+        for mv in moves {
+            board_state = board_state.exec_move(*mv);
+            self.bot.append_to_history(&mut board_state);
+        }
+
+        let best_move =
+            self.bot
+                .search_best_move(&mut board_state, limit.to_time_control(), &self.stop);
+        Ok((best_move, self.bot.best_score()))
+    }
+}
+
+pub struct UciEnginePlayer {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl UciEnginePlayer {
+    pub fn spawn(path: &str) -> Result<Self, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Could not start '{}': {}", path, e))?;
+        let stdin = child.stdin.take().ok_or("Missing engine stdin")?;
+        let stdout = child.stdout.take().ok_or("Missing engine stdout")?;
+
+        // Read on a separate thread so hanging engines can be timed out
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = line.map(|line| tx.send(line).is_ok());
+                if !matches!(sent, Ok(true)) {
+                    return;
+                }
+            }
+        });
+
+        let mut player = Self {
+            name: path.to_string(),
+            child,
+            stdin,
+            lines,
+        };
+
+        player.send("uci")?;
+        let mut engine_name = None;
+        player.read_until(UCI_STARTUP_TIMEOUT, |line| {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine_name = Some(name.trim().to_string());
+            }
+            line.trim() == "uciok"
+        })?;
+        if let Some(name) = engine_name {
+            player.name = name;
+        }
+        Ok(player)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).map_err(|e| format!("{}: {}", self.name, e))
+    }
+
+    fn read_until(
+        &mut self,
+        timeout: Duration,
+        mut is_done: impl FnMut(&str) -> bool,
+    ) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    if is_done(&line) {
+                        return Ok(());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("{} did not respond in time", self.name))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format!("{} terminated", self.name))
+                }
+            }
+        }
+    }
+
+    fn is_ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        self.read_until(UCI_STARTUP_TIMEOUT, |line| line.trim() == "readyok")
+    }
+}
+
+// Score of an `info` line in centipawns, mate scores are mapped close to CHECKMATE
+fn parse_info_score(line: &str) -> Option<i32> {
+    let mut tokens = line.split_whitespace().skip_while(|tk| *tk != "score");
+    tokens.next()?;
+    match (tokens.next()?, tokens.next()?.parse::<i32>().ok()?) {
+        ("cp", cp) => Some(cp),
+        ("mate", moves) if moves > 0 => Some(CHECKMATE - 2 * moves),
+        ("mate", moves) => Some(-CHECKMATE - 2 * moves),
+        _ => None,
+    }
+}
+
+impl Player for UciEnginePlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    fn select_move(
+        &mut self,
+        start_state: &ChessBoardState,
+        moves: &[Move],
+        limit: MoveLimit,
+    ) -> Result<(Move, Option<i32>), String> {
+        let mut board_state = *start_state;
+        let mut position = format!("position fen {}", start_state.to_fen());
+        if !moves.is_empty() {
+            position.push_str(" moves");
+            for mv in moves {
+                position.push_str(&format!(" {:?}", mv));
+                board_state = board_state.exec_move(*mv);
+            }
+        }
+        self.send(&position)?;
+        self.send(&limit.to_go_command())?;
+
+        let mut score = None;
+        let mut best_move = String::new();
+        self.read_until(limit.response_timeout(), |line| {
+            if line.starts_with("info") {
+                score = parse_info_score(line).or(score);
+            }
+            match line.strip_prefix("bestmove") {
+                Some(rest) => {
+                    best_move = rest.split_whitespace().next().unwrap_or("").to_string();
+                    true
+                }
+                None => false,
+            }
+        })?;
+
+        let best_move = Move::try_from((best_move.as_str(), &board_state))
+            .map_err(|_| format!("{} sent unparseable move '{}'", self.name, best_move))?;
+        Ok((best_move, score))
+    }
+}
+
+impl Drop for UciEnginePlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        thread::sleep(Duration::from_millis(100));
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Creates a player from its command line description: either one of the built in bots
+/// (`random`, `oneply`, `nply`, `nplytranspo`) optionally followed by `:Name=Value,...` UCI
+/// options, or `uci:<path>` for an external engine.
+pub fn create_player(spec: &str) -> Result<Box<dyn Player>, String> {
+    if let Some(path) = spec.strip_prefix("uci:") {
+        return Ok(Box::new(UciEnginePlayer::spawn(path)?));
+    }
+
+    let (bot_name, options) = match spec.split_once(':') {
+        Some((bot_name, options)) => {
+            let options = options
+                .split(',')
+                .map(|option| {
+                    option
+                        .split_once('=')
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .ok_or(format!("Invalid bot option '{}'", option))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            (bot_name, options)
+        }
+        None => (spec, Vec::new()),
+    };
+
+    Ok(match bot_name {
+        "random" => Box::new(BotPlayer::<RandomBot>::new(bot_name, &options)?),
+        "oneply" => Box::new(BotPlayer::<OnePlyBot>::new(bot_name, &options)?),
+        "nply" => Box::new(BotPlayer::<NPlyBot>::new(bot_name, &options)?),
+        "nplytranspo" => Box::new(BotPlayer::<NPlyTranspoBot>::new(bot_name, &options)?),
+        _ => return Err(format!("Unknown bot '{}'", bot_name)),
+    })
+}
@@ -0,0 +1,153 @@
+// Two sided 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+
+/// Win/loss/draw record from the point of view of the first engine.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct MatchStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    /// Elo difference and the half width of its 95% confidence interval, `None` while the
+    /// score is still 0% or 100% and the difference is unbounded.
+    pub fn elo_difference(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        let score = self.score();
+        if games == 0.0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+
+        let deviation = (self.variance() / games).sqrt();
+
+        let low = elo_from_score((score - CONFIDENCE_Z * deviation).max(f64::EPSILON));
+        let high = elo_from_score((score + CONFIDENCE_Z * deviation).min(1.0 - f64::EPSILON));
+        Some((elo_from_score(score), (high - low) / 2.0))
+    }
+
+    /// Variance of the result of a single game.
+    pub fn variance(&self) -> f64 {
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / self.games().max(1) as f64
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Sequential probability ratio test of H0: elo = elo0 against H1: elo = elo1.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtResult {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    /// Lower and upper log-likelihood ratio bounds at which the test stops.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log-likelihood ratio of the games played so far, using the normal approximation of the
+    /// trinomial (win/draw/loss) distribution.
+    pub fn llr(&self, stats: &MatchStats) -> f64 {
+        // Half a game of every result keeps the variance above 0 while all games ended the
+        // same way, so a lopsided match still reaches a bound
+        let wins = stats.wins as f64 + 0.5;
+        let draws = stats.draws as f64 + 0.5;
+        let losses = stats.losses as f64 + 0.5;
+        let total = wins + draws + losses;
+        let score = (wins + 0.5 * draws) / total;
+        let variance =
+            (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2))
+                / total;
+
+        let score0 = score_from_elo(self.elo0);
+        let score1 = score_from_elo(self.elo1);
+        stats.games() as f64 * (score1 - score0) * (2.0 * score - score0 - score1)
+            / (2.0 * variance)
+    }
+
+    pub fn result(&self, stats: &MatchStats) -> SprtResult {
+        let llr = self.llr(stats);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtResult::AcceptH1
+        } else if llr <= lower {
+            SprtResult::AcceptH0
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::{MatchStats, Sprt, SprtResult};
+
+    const SPRT: Sprt = Sprt {
+        elo0: 0.0,
+        elo1: 5.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    /// Games of the same result until the test stops, at most 1000.
+    fn games_until_decided(add: impl Fn(&mut MatchStats)) -> (u32, SprtResult) {
+        let mut stats = MatchStats::default();
+        while stats.games() < 1000 {
+            add(&mut stats);
+            let result = SPRT.result(&stats);
+            if result != SprtResult::Continue {
+                return (stats.games(), result);
+            }
+        }
+        (stats.games(), SprtResult::Continue)
+    }
+
+    #[test]
+    fn lopsided_matches_end() {
+        assert_eq!(SPRT.result(&MatchStats::default()), SprtResult::Continue);
+
+        let (games, result) = games_until_decided(|stats| stats.wins += 1);
+        assert_eq!(result, SprtResult::AcceptH1);
+        assert!(games > 1, "decided after {} games", games);
+
+        let (_, result) = games_until_decided(|stats| stats.losses += 1);
+        assert_eq!(result, SprtResult::AcceptH0);
+        let (_, result) = games_until_decided(|stats| stats.draws += 1);
+        assert_eq!(result, SprtResult::AcceptH0);
+    }
+}
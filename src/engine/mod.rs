@@ -1,9 +1,16 @@
+pub mod bench;
 pub mod board_eval;
 pub mod bot;
 pub mod bots;
+pub mod clock;
+pub mod eval_cache;
+pub mod eval_config;
+pub mod log;
 pub mod move_ordering;
 pub mod opening;
 pub mod search;
 pub mod time_control;
+pub mod time_manager;
 pub mod transposition_table;
 pub mod uci;
+pub mod uci_option;
@@ -1,35 +1,66 @@
 use crate::chess::{
     board::{ChessBoardState, ChessPiece},
     chess_move::Move,
+    move_list::{MoveList, MAX_MOVES},
 };
 
 use super::search::{SearchInfo, MAX_KILLER_MOVES};
 
-// MVV_VLA[victim][attacker]
+// MVV_LVA[victim][attacker], indexed by `ChessPiece`: the most valuable victim first and
+// among equal victims the least valuable attacker
 pub const MVV_LVA: [[u8; ChessPiece::PIECE_TYPE_COUNT + 1]; ChessPiece::PIECE_TYPE_COUNT + 1] = [
-    [15, 14, 13, 12, 11, 10, 0], // victim P, attacker K, Q, R, B, N, P, None
-    [25, 24, 23, 22, 21, 20, 0], // victim N, attacker K, Q, R, B, N, P, None
-    [35, 34, 33, 32, 31, 30, 0], // victim B, attacker K, Q, R, B, N, P, None
-    [45, 44, 43, 42, 41, 40, 0], // victim R, attacker K, Q, R, B, N, P, None
-    [55, 54, 53, 52, 51, 50, 0], // victim Q, attacker K, Q, R, B, N, P, None
-    [0, 0, 0, 0, 0, 0, 0],       // victim King, attacker K, Q, R, B, N, P, None
-    [0, 0, 0, 0, 0, 0, 0],       // victim None, attacker K, Q, R, B, N, P, None
+    [15, 14, 13, 12, 11, 10, 0], // victim P, attacker P, N, B, R, Q, K, None
+    [25, 24, 23, 22, 21, 20, 0], // victim N, attacker P, N, B, R, Q, K, None
+    [35, 34, 33, 32, 31, 30, 0], // victim B, attacker P, N, B, R, Q, K, None
+    [45, 44, 43, 42, 41, 40, 0], // victim R, attacker P, N, B, R, Q, K, None
+    [55, 54, 53, 52, 51, 50, 0], // victim Q, attacker P, N, B, R, Q, K, None
+    [0, 0, 0, 0, 0, 0, 0],       // victim King, attacker P, N, B, R, Q, K, None
+    [0, 0, 0, 0, 0, 0, 0],       // victim None, attacker P, N, B, R, Q, K, None
 ];
 
+const TT_MOVE_VALUE: u32 = u32::MAX;
 const MVV_LVA_OFFSET: u32 = u32::MAX - 256;
 const KILLER_VALUE: u32 = 10;
 
+/// MVV-LVA score of a capture or promotion, `None` for quiet moves. En passant takes a pawn
+/// and a promotion is scored as if the pawn captured the piece it turns into, on top of
+/// what it captures.
+pub fn mvv_lva(mv: Move, board_state: &ChessBoardState) -> Option<u32> {
+    let victim = mv.get_captured_piece(board_state);
+    if victim.is_none() && !mv.is_promotion() {
+        return None;
+    }
+
+    let attacker = mv.get_moved_piece(board_state) as usize;
+    let capture = victim.map_or(0, |victim| MVV_LVA[victim as usize][attacker] as u32);
+    let promotion = if mv.is_promotion() {
+        MVV_LVA[mv.promotion_target() as usize][ChessPiece::Pawn as usize] as u32
+    } else {
+        0
+    };
+    Some(capture + promotion)
+}
+
 pub fn order_moves(
     moves: &mut Vec<Move>,
     board_state: &ChessBoardState,
     search_info: &SearchInfo,
     ply_from_root: u16,
+    tt_move: Option<Move>,
 ) {
     let ply = ply_from_root as usize;
 
     // Cache the evaluations to avoid repeated calculations
     let mut move_evals: Vec<(Move, u32)> = moves.iter()
-        .map(|&mv| (mv, move_order_eval(mv, board_state, search_info, ply)))
+        .map(|&mv| {
+            // The best move of an earlier search of this position is tried first
+            let eval = if Some(mv) == tt_move {
+                TT_MOVE_VALUE
+            } else {
+                move_order_eval(mv, board_state, search_info, ply)
+            };
+            (mv, eval)
+        })
         .collect();
 
     // Sort the moves based on their evaluations
@@ -41,6 +72,142 @@ pub fn order_moves(
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Stage {
+    TtMove,
+    ScoreCaptures,
+    Captures,
+    Killers,
+    Quiets,
+}
+
+/// Hands out the moves of a node one at a time: the hash move, captures and promotions by
+/// [`mvv_lva`], the killers and then the remaining quiet moves. Only the next best capture
+/// is selected, so a node which cuts off early never orders the rest of its moves.
+pub struct MovePicker<'a> {
+    board_state: &'a ChessBoardState,
+    moves: MoveList,
+    scores: [u32; MAX_MOVES],
+    tt_move: Option<Move>,
+    killers: [Move; MAX_KILLER_MOVES],
+    stage: Stage,
+    // Moves before this index have been handed out
+    next: usize,
+    // End of the captures, which are moved in front of the quiet moves
+    captures_end: usize,
+    killer_index: usize,
+}
+
+impl<'a> MovePicker<'a> {
+    pub fn new(
+        moves: MoveList,
+        board_state: &'a ChessBoardState,
+        search_info: &SearchInfo,
+        ply_from_root: u16,
+        tt_move: Option<Move>,
+    ) -> Self {
+        let ply = ply_from_root as usize;
+        Self {
+            board_state,
+            // The hash move of a colliding position may not be playable here
+            tt_move: tt_move.filter(|mv| moves.contains(mv)),
+            scores: [0; MAX_MOVES],
+            killers: std::array::from_fn(|i| search_info.killer_moves[i][ply]),
+            moves,
+            stage: Stage::TtMove,
+            next: 0,
+            captures_end: 0,
+            killer_index: 0,
+        }
+    }
+
+    /// Whether the hash move is legal in this position, it is then handed out first.
+    pub fn has_tt_move(&self) -> bool {
+        self.tt_move.is_some()
+    }
+
+    /// All moves of the node, in no particular order.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Moves the captures and promotions which are still left in front of the quiet moves
+    /// and scores them.
+    fn partition_captures(&mut self) {
+        self.captures_end = self.next;
+        for i in self.next..self.moves.len() {
+            if let Some(score) = mvv_lva(self.moves[i], self.board_state) {
+                self.moves.swap(i, self.captures_end);
+                self.scores[self.captures_end] = score;
+                self.captures_end += 1;
+            }
+        }
+    }
+
+    /// Swaps the move at `index` to the front of the remaining moves and hands it out.
+    fn take(&mut self, index: usize) -> Move {
+        self.moves.swap(index, self.next);
+        self.scores.swap(index, self.next);
+        self.next += 1;
+        self.moves[self.next - 1]
+    }
+}
+
+impl Iterator for MovePicker<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::ScoreCaptures;
+                    if let Some(tt_move) = self.tt_move {
+                        let index = self.moves.iter().position(|&mv| mv == tt_move).unwrap();
+                        self.moves.swap(index, 0);
+                        self.next = 1;
+                        return Some(tt_move);
+                    }
+                }
+                Stage::ScoreCaptures => {
+                    self.partition_captures();
+                    self.stage = Stage::Captures;
+                }
+                Stage::Captures => {
+                    if self.next < self.captures_end {
+                        let best = (self.next..self.captures_end)
+                            .max_by_key(|&i| (self.scores[i], std::cmp::Reverse(i)))
+                            .unwrap();
+                        return Some(self.take(best));
+                    }
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.killer_index) else {
+                        self.stage = Stage::Quiets;
+                        continue;
+                    };
+                    self.killer_index += 1;
+                    // Killers come from sibling nodes, they are only handed out when this
+                    // position has the same move and it was not played already
+                    if killer == Move::NULL_MOVE || Some(killer) == self.tt_move {
+                        continue;
+                    }
+                    if let Some(offset) =
+                        self.moves[self.next..].iter().position(|&mv| mv == killer)
+                    {
+                        return Some(self.take(self.next + offset));
+                    }
+                }
+                Stage::Quiets => {
+                    let mv = *self.moves.get(self.next)?;
+                    self.next += 1;
+                    return Some(mv);
+                }
+            }
+        }
+    }
+}
+
 #[inline(always)]
 fn move_order_eval(
     mv: Move,
@@ -48,10 +215,8 @@ fn move_order_eval(
     search_info: &SearchInfo,
     ply: usize,
 ) -> u32 {
-    let src_piece = mv.get_moved_piece(board_state) as usize;
-
-    if let Some(captured_piece) = mv.get_captured_piece(board_state) {
-        MVV_LVA_OFFSET + MVV_LVA[captured_piece as usize][src_piece] as u32
+    if let Some(score) = mvv_lva(mv, board_state) {
+        MVV_LVA_OFFSET + score
     } else {
         search_info.killer_moves.iter().enumerate().find_map(|(i, killers)| {
             if mv == killers[ply] {
@@ -74,7 +239,7 @@ mod move_ordering_tests {
         engine::search::SearchInfo,
     };
 
-    use super::order_moves;
+    use super::{mvv_lva, order_moves, MovePicker};
 
     #[test]
     fn check_mvv_lva() {
@@ -90,7 +255,7 @@ mod move_ordering_tests {
             Move::new(Square::G1, Square::F3, MoveType::Capture),
         ];
 
-        order_moves(&mut moves, &board_state, &SearchInfo::default(), 4);
+        order_moves(&mut moves, &board_state, &SearchInfo::default(), 4, None);
 
         assert_eq!(
             moves[0],
@@ -113,4 +278,99 @@ mod move_ordering_tests {
             "Silent Move should be last"
         );
     }
+
+    #[test]
+    fn tt_move_first() {
+        let board_state = ChessBoardState::from_fen(
+            "rnb1kbn1/pp1p1ppp/2p1p3/8/2q1P3/3P1r2/PPPN1PPP/R1BQKBNR b KQq - 1 5",
+        )
+        .unwrap();
+
+        let tt_move = Move::new(Square::E4, Square::E5, MoveType::Silent);
+        let mut moves = vec![
+            Move::new(Square::D2, Square::C4, MoveType::Capture),
+            Move::new(Square::D3, Square::C4, MoveType::Capture),
+            tt_move,
+        ];
+
+        order_moves(
+            &mut moves,
+            &board_state,
+            &SearchInfo::default(),
+            4,
+            Some(tt_move),
+        );
+        assert_eq!(moves[0], tt_move, "TT move should be first");
+        assert_eq!(
+            moves[1],
+            Move::new(Square::D3, Square::C4, MoveType::Capture),
+            "Captures should follow the TT move"
+        );
+    }
+
+    #[test]
+    fn move_picker_stages() {
+        let board_state = ChessBoardState::from_fen(
+            "rnb1kbn1/pp1p1ppp/2p1p3/8/2q1P3/3P1r2/PPPN1PPP/R1BQKBNR w KQq - 1 5",
+        )
+        .unwrap();
+        let moves = board_state.legal_moves::<false>();
+
+        let tt_move = Move::new(Square::A2, Square::A3, MoveType::Silent);
+        let killer = Move::new(Square::H2, Square::H3, MoveType::Silent);
+        let mut search_info = SearchInfo::default();
+        // Not playable in this position, it must be skipped
+        search_info.killer_moves[0][4] = Move::new(Square::B1, Square::C3, MoveType::Silent);
+        search_info.killer_moves[1][4] = killer;
+
+        let picked: Vec<Move> =
+            MovePicker::new(moves, &board_state, &search_info, 4, Some(tt_move)).collect();
+        assert_eq!(picked.len(), moves.len());
+        assert!(moves.iter().all(|mv| picked.contains(mv)));
+
+        assert_eq!(picked[0], tt_move, "TT move should be first");
+        assert_eq!(
+            picked[1],
+            Move::new(Square::D3, Square::C4, MoveType::Capture),
+            "Pawn takes queen should be the best capture"
+        );
+        let captures = moves.iter().filter(|mv| mv.is_capture()).count();
+        assert!(picked[1..=captures].iter().all(|mv| mv.is_capture()));
+        assert_eq!(
+            picked[captures + 1],
+            killer,
+            "Killer should follow the captures"
+        );
+    }
+
+    #[test]
+    fn en_passant_and_promotions() {
+        let board_state =
+            ChessBoardState::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let score = |mv: &str| mvv_lva(Move::try_from((mv, &board_state)).unwrap(), &board_state);
+
+        // En passant takes a pawn like any other pawn capture
+        assert_eq!(score("e5d6"), Some(15));
+        assert_eq!(score("e5e6"), None);
+        assert!(score("b7a8q") > score("b7a8r"));
+        assert!(score("b7a8n") > score("b7b8q"));
+        assert!(score("b7b8q") > score("e5d6"));
+        assert!(score("b7b8n") > score("e5d6"));
+
+        let mut moves = board_state.generate_legal_moves_for_current_player::<false>();
+        order_moves(&mut moves, &board_state, &SearchInfo::default(), 0, None);
+        let expected: Vec<Move> = [
+            "b7a8q", "b7a8r", "b7a8b", "b7a8n", "b7b8q", "b7b8r", "b7b8b", "b7b8n", "e5d6",
+        ]
+        .into_iter()
+        .map(|mv| Move::try_from((mv, &board_state)).unwrap())
+        .collect();
+        assert_eq!(moves[..expected.len()], expected);
+
+        // The picker hands out the captures and promotions in the same order
+        let legal_moves = board_state.legal_moves::<false>();
+        let picked: Vec<Move> =
+            MovePicker::new(legal_moves, &board_state, &SearchInfo::default(), 0, None).collect();
+        assert_eq!(picked[..expected.len()], expected);
+    }
 }
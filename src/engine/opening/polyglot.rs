@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
 
-use crate::chess::{board::{ChessBoardState, ChessPiece, PieceColor}, chess_move::{Move, MoveType}, square::Square};
+use crate::chess::{board::{ChessBoardState, ChessPiece, PieceColor}, chess_move::{Move, MoveType}, square::{self, Rank, Square}};
 
 pub trait OpeningBook {
     fn get(&self, board_state: &ChessBoardState) -> Vec<Move>;
 }
 
+#[derive(Clone, Copy)]
 pub struct PolyglotEntry {
     pub key: u64,
     pub move_: u16,
@@ -13,117 +20,112 @@ pub struct PolyglotEntry {
     pub learn: u32,
 }
 
+// Size of an entry in a polyglot file
+const ENTRY_SIZE: usize = 16;
 
 impl PolyglotEntry {
-    fn to_move(&self, board_state: &ChessBoardState) -> Move {
-        let to_file = (self.move_ & 0b111) as u8;
-        let to_row = 7 - ((self.move_ >> 3) & 0b111) as u8;
-        let from_file = ((self.move_ >> 6) & 0b111) as u8;
-        let from_row = 7 - ((self.move_ >> 9) & 0b111) as u8;
+    fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            move_: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    /// The move of the entry in the position, `None` if the entry can't be a move there.
+    fn to_move(self, board_state: &ChessBoardState) -> Option<Move> {
+        let to_file = square::File::ALL[(self.move_ & 0b111) as usize];
+        let to_rank = Rank::ALL[((self.move_ >> 3) & 0b111) as usize];
+        let from_file = square::File::ALL[((self.move_ >> 6) & 0b111) as usize];
+        let from_rank = Rank::ALL[((self.move_ >> 9) & 0b111) as usize];
         let promotion_piece = ((self.move_ >> 12) & 0b111) as u8;
 
-        let mut to_square = Square::square_from_pos(to_file.into(), to_row.into());
-        let from_square = Square::square_from_pos(from_file.into(), from_row.into());
+        let mut to_square = Square::at(to_file, to_rank);
+        let from_square = Square::at(from_file, from_rank);
 
         // check if the move is capture
-        let is_capture = board_state.board.get_piece_at_pos(to_square as usize ).is_some();
+        let is_capture = board_state.board.piece_on(to_square).is_some();
 
         // check if the move is a double push
-        let piece_at_source_pos = board_state.board.get_piece_at_pos(from_square as usize).unwrap();
-        if piece_at_source_pos.0 == ChessPiece::Pawn && ((to_row == 3 && from_row == 1) || (to_row == 4 && from_row == 6)){
-            return Move::new(from_square, to_square, MoveType::DoublePush);
+        let piece_at_source_pos = board_state.board.piece_on(from_square)?;
+        if piece_at_source_pos.0 == ChessPiece::Pawn && from_square.rank_distance(to_square) == 2 {
+            return Some(Move::new(from_square, to_square, MoveType::DoublePush));
         }
 
         // check if the move is a castle
-        if piece_at_source_pos.0 == ChessPiece::King && from_file == 4 && to_file == 7 {
+        if piece_at_source_pos.0 == ChessPiece::King && from_file == square::File::E && to_file == square::File::H {
             if piece_at_source_pos.1 == PieceColor::White {
                 to_square = Square::G1;
             } else {
                 to_square = Square::G8;
             }
-            return Move::new(from_square, to_square, MoveType::CastleKingSide);
+            return Some(Move::new(from_square, to_square, MoveType::CastleKingSide));
         }
-        if piece_at_source_pos.0 == ChessPiece::King && from_file == 4 && to_file == 0 {
+        if piece_at_source_pos.0 == ChessPiece::King && from_file == square::File::E && to_file == square::File::A {
             if piece_at_source_pos.1 == PieceColor::White {
                 to_square = Square::C1;
             } else {
                 to_square = Square::C8;
             }
-            return Move::new(from_square, to_square, MoveType::CastleQueenSide);
+            return Some(Move::new(from_square, to_square, MoveType::CastleQueenSide));
         }
       
         // check if the move is a en passant capture
-        if piece_at_source_pos.0 == ChessPiece::Pawn && board_state.en_passant_target.is_some() && to_square as u8 == board_state.en_passant_target.unwrap() {
-            return Move::new(from_square, to_square, MoveType::EnPassant);
+        if piece_at_source_pos.0 == ChessPiece::Pawn && board_state.en_passant_target == Some(to_square) {
+            return Some(Move::new(from_square, to_square, MoveType::EnPassant));
         };
 
-        // check if the move is a promotion
+        // check if the move is a promotion, polyglot numbers the pieces from knight = 1 to queen = 4
         if self.move_ & (0b111 << 12) != 0 {
             let move_type: MoveType = match (promotion_piece, is_capture) {
-                (0, false) => MoveType::KnightPromotion,
-                (0, true) => MoveType::KnightCapPromotion,
-                (1, false) => MoveType::BishopPromotion,
-                (1, true) => MoveType::BishopCapPromotion,
-                (2, false) => MoveType::RookPromotion,
-                (2, true) => MoveType::RookCapPromotion,
-                (3, false) => MoveType::QueenPromotion,
-                (3, true) => MoveType::QueenCapPromotion,
-                _ => panic!("Invalid promotion piece"),
+                (1, false) => MoveType::KnightPromotion,
+                (1, true) => MoveType::KnightCapPromotion,
+                (2, false) => MoveType::BishopPromotion,
+                (2, true) => MoveType::BishopCapPromotion,
+                (3, false) => MoveType::RookPromotion,
+                (3, true) => MoveType::RookCapPromotion,
+                (4, false) => MoveType::QueenPromotion,
+                (4, true) => MoveType::QueenCapPromotion,
+                _ => return None,
             };
-            return Move::new(from_square, to_square, move_type);
+            return Some(Move::new(from_square, to_square, move_type));
         }
 
         if is_capture {
-            return Move::new(from_square, to_square, MoveType::Capture);
+            return Some(Move::new(from_square, to_square, MoveType::Capture));
         }
 
-        return Move::new(from_square, to_square, MoveType::Silent);
+        Some(Move::new(from_square, to_square, MoveType::Silent))
     }
 }
 
+/// Where the entries of a book are looked up.
+enum BookSource {
+    /// All entries loaded up front, grouped by key
+    Memory(HashMap<u64, Vec<PolyglotEntry>>),
+    /// Entries read from the file on every probe, polyglot files are sorted by key so
+    /// a binary search finds them
+    File { file: Mutex<File>, entry_count: u64 },
+}
+
 pub struct PolyglotOpeningBook {
-    entries: HashMap<u64, Vec<PolyglotEntry>>,
+    source: BookSource,
 }
 
 impl PolyglotOpeningBook {
     pub fn new(entries: HashMap<u64, Vec<PolyglotEntry>>) -> Self {
-        Self { entries }
+        Self {
+            source: BookSource::Memory(entries),
+        }
     }
 
+    /// Loads all entries into memory.
     pub fn from_bytes(bytes: &[u8]) -> Self {
         let mut entries = HashMap::new();
-        for i in 0..bytes.len() / 16 {
-            let key = u64::from_be_bytes([
-                bytes[i * 16],
-                bytes[i * 16 + 1],
-                bytes[i * 16 + 2],
-                bytes[i * 16 + 3],
-                bytes[i * 16 + 4],
-                bytes[i * 16 + 5],
-                bytes[i * 16 + 6],
-                bytes[i * 16 + 7],
-            ]);
-            let move_ = u16::from_be_bytes([
-                bytes[i * 16 + 8],
-                bytes[i * 16 + 9],
-            ]);
-            let weight = u16::from_be_bytes([
-                bytes[i * 16 + 10],
-                bytes[i * 16 + 11],
-            ]);
-            let learn = u32::from_be_bytes([
-                bytes[i * 16 + 12],
-                bytes[i * 16 + 13],
-                bytes[i * 16 + 14],
-                bytes[i * 16 + 15],
-            ]);
-
-            entries.entry(key).or_insert(Vec::new()).push(PolyglotEntry {
-                key,
-                move_,
-                weight,
-                learn,
-            });
+        for chunk in bytes.chunks_exact(ENTRY_SIZE) {
+            let entry = PolyglotEntry::from_bytes(chunk.try_into().unwrap());
+            entries.entry(entry.key).or_insert(Vec::new()).push(entry);
         }
 
         // Sort descending by weight
@@ -134,18 +136,74 @@ impl PolyglotOpeningBook {
         Self::new(entries)
     }
 
+    /// Opens a book which stays on disk, each probe binary searches the file. Meant for
+    /// large books which would take long to load and a lot of memory to keep.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let entry_count = file.metadata()?.len() / ENTRY_SIZE as u64;
+        Ok(Self {
+            source: BookSource::File {
+                file: Mutex::new(file),
+                entry_count,
+            },
+        })
+    }
 
-   
+    /// Entries for `key` sorted descending by weight.
+    fn entries(&self, key: u64) -> Vec<PolyglotEntry> {
+        match &self.source {
+            BookSource::Memory(entries) => entries.get(&key).cloned().unwrap_or_default(),
+            BookSource::File { file, entry_count } => {
+                let mut file = file.lock().unwrap();
+                // A broken file behaves like a book without the position
+                let mut entries =
+                    Self::read_entries(&mut file, *entry_count, key).unwrap_or_default();
+                entries.sort_by_key(|e| std::cmp::Reverse(e.weight));
+                entries
+            }
+        }
+    }
+
+    fn read_entry(file: &mut File, index: u64) -> io::Result<PolyglotEntry> {
+        let mut bytes = [0; ENTRY_SIZE];
+        file.seek(SeekFrom::Start(index * ENTRY_SIZE as u64))?;
+        file.read_exact(&mut bytes)?;
+        Ok(PolyglotEntry::from_bytes(&bytes))
+    }
+
+    fn read_entries(file: &mut File, entry_count: u64, key: u64) -> io::Result<Vec<PolyglotEntry>> {
+        // Find the first entry with the key
+        let (mut low, mut high) = (0, entry_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if Self::read_entry(file, mid)?.key < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut entries = Vec::new();
+        for index in low..entry_count {
+            let entry = Self::read_entry(file, index)?;
+            if entry.key != key {
+                break;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
 }
 
 impl OpeningBook for PolyglotOpeningBook {
     fn get(&self, board_state: &ChessBoardState) -> Vec<Move> {
         let mut moves = Vec::new();
         let key = board_state.zhash.0;
-       
-        if let Some(entries) = self.entries.get(&key) {
-            for entry in entries {
-                moves.push(entry.to_move(board_state));
+
+        // Entries which aren't a move in the position are skipped
+        for entry in self.entries(key) {
+            if let Some(mv) = entry.to_move(board_state) {
+                moves.push(mv);
             }
         }
 
@@ -157,7 +215,9 @@ impl OpeningBook for PolyglotOpeningBook {
 mod tests {
     use crate::{chess::{board::ChessBoardState, chess_move::MoveType, square::Square}, engine::opening::polyglot::PolyglotOpeningBook};
 
-    use super::OpeningBook;
+    use std::collections::HashMap;
+
+    use super::{BookSource, OpeningBook, PolyglotEntry};
 
     #[test]
     fn test_polyglot_deserialization() {
@@ -168,7 +228,10 @@ mod tests {
 
         let book = PolyglotOpeningBook::from_bytes(&bytes);
 
-        assert_eq!(book.entries.len(), 2);
+        let BookSource::Memory(entries) = &book.source else {
+            panic!("Book should be in memory");
+        };
+        assert_eq!(entries.len(), 2);
     }
 
     #[test]
@@ -212,5 +275,76 @@ mod tests {
         assert_eq!(moves[0].get_type(), MoveType::Capture);
     }
 
+    #[test]
+    fn test_promotions() {
+        let board_state = ChessBoardState::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w q - 0 1").unwrap();
+        // Files and ranks count from a1, b7 is file 1 on rank 6
+        let entry = |to_file: u16, promotion: u16| PolyglotEntry {
+            key: 0,
+            move_: to_file | (7 << 3) | (1 << 6) | (6 << 9) | (promotion << 12),
+            weight: 1,
+            learn: 0,
+        };
+
+        let expected = [
+            (1, MoveType::KnightPromotion, MoveType::KnightCapPromotion),
+            (2, MoveType::BishopPromotion, MoveType::BishopCapPromotion),
+            (3, MoveType::RookPromotion, MoveType::RookCapPromotion),
+            (4, MoveType::QueenPromotion, MoveType::QueenCapPromotion),
+        ];
+        for (promotion, push, capture) in expected {
+            let mv = entry(1, promotion).to_move(&board_state).unwrap();
+            assert_eq!((mv.get_dst(), mv.get_type()), (Square::B8, push));
+            let mv = entry(0, promotion).to_move(&board_state).unwrap();
+            assert_eq!((mv.get_dst(), mv.get_type()), (Square::A8, capture));
+            assert!(board_state.is_legal(mv));
+        }
+    }
+
+    #[test]
+    fn test_broken_entries_are_skipped() {
+        let board_state = ChessBoardState::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w q - 0 1").unwrap();
+        let entry = |move_: u16| PolyglotEntry { key: board_state.zhash.0, move_, weight: 1, learn: 0 };
+        // b7b8 with promotion pieces polyglot doesn't know, and a move from the empty a1
+        let broken = [5, 6, 7].map(|promotion| 1 | (7 << 3) | (1 << 6) | (6 << 9) | (promotion << 12));
+        let entries = broken.iter().chain(&[1 << 3]).map(|move_| entry(*move_)).collect();
+
+        let book = PolyglotOpeningBook::new(HashMap::from([(board_state.zhash.0, entries)]));
+        assert!(book.get(&board_state).is_empty());
+    }
 
+    #[test]
+    fn test_file_book_matches_memory_book() {
+        // Entries sorted by key as in a polyglot file, the starting position has two moves
+        let bytes: Vec<u8> = vec![
+            0x06, 0x64, 0x9B, 0xA6, 0x9B, 0x8C, 0x9F, 0xF8, 0x00, 0xA6, 0x00, 0xC2, 0x00, 0x00, 0x00, 0x00,
+            0x46, 0x3B, 0x96, 0x18, 0x16, 0x91, 0xFC, 0x9C, 0x03, 0x1C, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0x46, 0x3B, 0x96, 0x18, 0x16, 0x91, 0xFC, 0x9C, 0x03, 0x1C, 0x3F, 0x95, 0x00, 0x00, 0x00, 0x00,
+            0xC2, 0x63, 0x96, 0xEE, 0x70, 0x0C, 0x22, 0xF2, 0x08, 0xDC, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let path = std::env::temp_dir().join(format!("iglo_book_{}.bin", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let memory_book = PolyglotOpeningBook::from_bytes(&bytes);
+        let file_book = PolyglotOpeningBook::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3p4/3PP3/8/PPP2PPP/RNBQKBNR b KQkq e3 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        ] {
+            let board_state = ChessBoardState::from_fen(fen).unwrap();
+            assert_eq!(
+                file_book.get(&board_state),
+                memory_book.get(&board_state),
+                "{}",
+                fen
+            );
+        }
+        let starting_moves = file_book.get(&ChessBoardState::starting_state());
+        assert_eq!(starting_moves.len(), 2);
+        assert_eq!(starting_moves[0].get_dst(), Square::E4);
+    }
 }
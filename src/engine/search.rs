@@ -1,53 +1,204 @@
 use super::{
-    move_ordering::order_moves,
+    clock::Instant,
+    move_ordering::{order_moves, MovePicker},
     time_control::TimeControl,
-    transposition_table::{NodeType, TranspositionTable},
+    time_manager::{GamePhase, TimeManager, DEFAULT_MOVE_OVERHEAD},
+    transposition_table::{NodeType, TranspositionEntry, TranspositionTable},
 };
-use crate::chess::{
-    board::{self, ChessBoardState, PieceColor},
-    chess_move::Move,
-    zobrist_hash::ZHash,
+use crate::{
+    chess::{
+        board::{ChessBoardState, ChessPiece, PieceColor},
+        chess_move::Move,
+        zobrist_hash::ZHash,
+    },
+    log_debug,
 };
 use std::{
+    cmp::Reverse,
     sync::{atomic::AtomicBool, Arc},
-    time::Instant,
+    thread,
 };
 
 const INFINITY: i32 = 50000;
 pub const CHECKMATE: i32 = 49000;
-const MAX_EXTENSIONS: usize = 3;
 pub const MATE_DISTANCE: i32 = CHECKMATE - MAX_PLY as i32;
 pub const DEPTH_REDUCTION: u16 = 1;
 
-pub const MAX_QUISCIENCE_DEPTH: u16 = 4;
+// Safety margin on top of the captured material before a capture is delta pruned
+const DELTA_MARGIN: i32 = 200;
+
+// Minimum remaining depth at which the hash move is tested for singularity
+const SINGULAR_EXTENSION_DEPTH: u16 = 4;
+// Margin per ply of remaining depth by which all alternatives must fall short of the hash move
+const SINGULAR_MARGIN: i32 = 2;
+
+// Minimum remaining depth of a PV node without hash move for internal iterative deepening
+const IID_DEPTH: u16 = 6;
+// Depth by which the search finding a first move for such a node is reduced
+const IID_REDUCTION: u16 = 2;
+
+// Deepest remaining depth at which frontier nodes are futility pruned
+const FUTILITY_DEPTH: u16 = 2;
+// Deepest remaining depth at which reverse futility pruning is applied
+const REVERSE_FUTILITY_DEPTH: u16 = 3;
+
+// Root score from which the engine tries to avoid draws by repetition or the fifty-move rule
+const WINNING_SCORE: i32 = 300;
+// Penalty for a root move returning to a position of the game history while winning
+const REPETITION_PENALTY: i32 = 100;
+// Half move clock after which moves without progress are penalized while winning
+const FIFTY_MOVE_THRESHOLD: u16 = 70;
+// Penalty per half move above the threshold
+const FIFTY_MOVE_PENALTY: i32 = 3;
+
+// Milliseconds between two progress reports, also the delay before the root moves are reported
+const INFO_INTERVAL: u128 = 1000;
 
 pub const MAX_PLY: u16 = 128;
 pub const MAX_KILLER_MOVES: usize = 2;
 type KillerMoves = [[Move; MAX_PLY as usize]; MAX_KILLER_MOVES];
+type PVTable = [[Move; MAX_PLY as usize]; MAX_PLY as usize];
+
+/// Called after every fully searched iteration of the iterative deepening.
+pub type IterationCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
+/// Static evaluation from white's perspective. Evaluations with settings of their own
+/// capture them, so searchers with different settings can run side by side.
+pub type Evaluator = Arc<dyn Fn(&ChessBoardState) -> i32 + Send + Sync>;
+
+/// Formats a root score from the side to move's perspective for UCI `info`
+/// output, mates are counted in moves and negative when being mated.
+pub fn uci_score(score: i32) -> String {
+    if score >= MATE_DISTANCE {
+        format!("mate {}", (CHECKMATE - score) / 2 + 1)
+    } else if score <= -MATE_DISTANCE {
+        format!("mate -{}", (CHECKMATE + score + 1) / 2)
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Per-ply margins of the static-eval based pruning, exposed as engine options
+/// so they can be tuned in self-play.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PruningMargins {
+    /// Quiet moves are skipped when the static eval plus this margin per ply
+    /// cannot reach alpha.
+    pub futility: i32,
+    /// A node fails high when the static eval minus this margin per ply still
+    /// beats beta.
+    pub reverse_futility: i32,
+}
+
+impl Default for PruningMargins {
+    fn default() -> Self {
+        Self {
+            futility: 150,
+            reverse_futility: 120,
+        }
+    }
+}
 
-enum GamePhase {
-    Opening,
-    Middle,
-    Endgame,
+/// Counters judging the move ordering of the main search, a good ordering fails high
+/// on the first move in most cut nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrderingStats {
+    /// Nodes which failed high
+    pub cutoffs: usize,
+    /// Cutoffs caused by the first move searched
+    pub first_move_cutoffs: usize,
+    /// Sum of the indices of the moves which caused the cutoffs
+    pub cutoff_index_sum: usize,
+    /// Nodes whose hash move was among the legal moves
+    pub tt_move_nodes: usize,
+    /// Nodes whose hash move failed high or stayed the best move
+    pub tt_move_hits: usize,
+}
+
+impl OrderingStats {
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        self.first_move_cutoffs as f64 / self.cutoffs.max(1) as f64
+    }
+
+    pub fn average_cutoff_index(&self) -> f64 {
+        self.cutoff_index_sum as f64 / self.cutoffs.max(1) as f64
+    }
+
+    pub fn tt_move_hit_rate(&self) -> f64 {
+        self.tt_move_hits as f64 / self.tt_move_nodes.max(1) as f64
+    }
+
+    fn record_cutoff(&mut self, move_index: usize) {
+        self.cutoffs += 1;
+        self.cutoff_index_sum += move_index;
+        if move_index == 0 {
+            self.first_move_cutoffs += 1;
+        }
+    }
 }
 
 pub struct SearchInfo {
-    nodes_searched: usize,
-    sel_depth: usize,
+    // Every position the search enters is one node, counted once on entry whether it is
+    // answered by the hash table, ends the game or gets searched. Positions entered with
+    // no depth left count towards the quiescence search, all others, the root included,
+    // towards the main search.
+    main_nodes: usize,
+    qsearch_nodes: usize,
+    // Depth of the iteration in progress
+    depth: u16,
+    // Plies from the root of the deepest node of the iteration in progress, quiescence
+    // search included
+    iteration_sel_depth: usize,
+    // Selective depth of the last fully searched depth
+    completed_sel_depth: usize,
+    best_score: i32,
+    // Best move of the last fully searched depth, or one proven better since
+    best_move: Move,
+    // Best move of every fully searched depth, starting at depth 1
+    iteration_best_moves: Vec<Move>,
+    // Score of the runner-up root move of the last fully searched depth
+    second_best_score: Option<i32>,
+    completed_depth: u16,
+    // Principal variation of the last fully searched depth
+    pv: Vec<Move>,
+    // Triangular table collecting the principal variation during the search
+    pv_table: PVTable,
+    pv_length: [usize; MAX_PLY as usize],
+    // Positions of the game up to and including the root
     pub history: Vec<ZHash>,
+    // Positions on the path from the root to the current node, indexed by ply with the
+    // positions after the root moves at ply 0
+    search_keys: [ZHash; MAX_PLY as usize],
     pub killer_moves: KillerMoves,
+    ordering_stats: OrderingStats,
     search_start_time: Instant,
+    // Time of the last progress report
+    last_report_time: Instant,
     self_color: PieceColor,
 }
 
 impl Default for SearchInfo {
     fn default() -> Self {
         Self {
-            nodes_searched: 0,
-            sel_depth: 0,
+            main_nodes: 0,
+            qsearch_nodes: 0,
+            depth: 0,
+            iteration_sel_depth: 0,
+            completed_sel_depth: 0,
+            best_score: 0,
+            best_move: Move::NULL_MOVE,
+            iteration_best_moves: Vec::new(),
+            second_best_score: None,
+            completed_depth: 0,
+            pv: Vec::new(),
+            pv_table: [[Move::NULL_MOVE; MAX_PLY as usize]; MAX_PLY as usize],
+            pv_length: [0; MAX_PLY as usize],
             search_start_time: Instant::now(),
+            last_report_time: Instant::now(),
             history: Default::default(),
+            search_keys: [ZHash::default(); MAX_PLY as usize],
             killer_moves: [[Move::NULL_MOVE; MAX_PLY as usize]; MAX_KILLER_MOVES],
+            ordering_stats: OrderingStats::default(),
             self_color: PieceColor::White,
         }
     }
@@ -55,12 +206,166 @@ impl Default for SearchInfo {
 
 impl SearchInfo {
     fn reset(&mut self) {
-        self.nodes_searched = 0;
-        self.sel_depth = 0;
+        self.main_nodes = 0;
+        self.qsearch_nodes = 0;
+        self.depth = 0;
+        self.iteration_sel_depth = 0;
+        self.completed_sel_depth = 0;
+        self.best_score = 0;
+        self.best_move = Move::NULL_MOVE;
+        self.iteration_best_moves.clear();
+        self.second_best_score = None;
+        self.completed_depth = 0;
+        self.pv.clear();
         self.search_start_time = Instant::now();
+        self.last_report_time = self.search_start_time;
+        self.ordering_stats = OrderingStats::default();
+        self.age_killers();
+    }
+
+    /// Forgets everything learned about the game, for a new one.
+    fn clear(&mut self) {
+        self.history.clear();
         self.killer_moves = [[Move::NULL_MOVE; MAX_PLY as usize]; MAX_KILLER_MOVES];
     }
 
+    /// Moves the killers two plies towards the root, the next search of the game starts
+    /// after our move and the opponent's reply.
+    fn age_killers(&mut self) {
+        for killers in self.killer_moves.iter_mut() {
+            killers.copy_within(2.., 0);
+            killers[MAX_PLY as usize - 2..].fill(Move::NULL_MOVE);
+        }
+    }
+
+    /// Drops the positions before the last capture or pawn move, which can never repeat.
+    /// The history ends with the current position, which `half_moves` counts from.
+    fn trim_history(&mut self, half_moves: u16) {
+        let keep = half_moves as usize + 1;
+        if self.history.len() > keep {
+            self.history.drain(..self.history.len() - keep);
+        }
+    }
+
+    /// Remembers the position at the given ply of the current search path.
+    fn set_search_key(&mut self, ply_from_root: u16, key: ZHash) {
+        if let Some(slot) = self.search_keys.get_mut(ply_from_root as usize) {
+            *slot = key;
+        }
+    }
+
+    /// Whether the position at `ply_from_root` counts as a draw by repetition. A single
+    /// repetition of a position after the root is enough, the side which repeated it can
+    /// keep doing so, while the root and the game before it have to hold the position
+    /// twice like the threefold rule demands. Only positions with the same side to move
+    /// since the last irreversible move can repeat.
+    fn is_repetition(&self, key: ZHash, ply_from_root: u16, half_moves: u16) -> bool {
+        let ply = (ply_from_root as usize).min(MAX_PLY as usize - 1);
+        let reversible = half_moves as usize;
+
+        let in_tree = (2..=ply.min(reversible))
+            .step_by(2)
+            .any(|distance| self.search_keys[ply - distance] == key);
+        if in_tree {
+            return true;
+        }
+
+        // The root's moves lead to ply 0, so the root is `ply + 1` plies above
+        let in_game = self
+            .history
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, earlier)| (ply + 1 + i, earlier))
+            .take_while(|(distance, _)| *distance <= reversible)
+            .filter(|(distance, earlier)| distance % 2 == 0 && **earlier == key)
+            .count();
+        in_game >= 2
+    }
+
+    /// Score of the best move from the side to move's perspective, taken from the
+    /// last fully searched depth.
+    pub fn best_score(&self) -> i32 {
+        self.best_score
+    }
+
+    pub fn ordering_stats(&self) -> &OrderingStats {
+        &self.ordering_stats
+    }
+
+    /// Best move verified by the search so far.
+    pub fn best_move(&self) -> Move {
+        self.best_move
+    }
+
+    /// Best move of each fully searched depth, starting at depth 1.
+    pub fn iteration_best_moves(&self) -> &[Move] {
+        &self.iteration_best_moves
+    }
+
+    /// Nodes of the main and the quiescence search together, what the UCI `nodes` field
+    /// and a node limit count.
+    pub fn nodes_searched(&self) -> usize {
+        self.main_nodes + self.qsearch_nodes
+    }
+
+    /// Nodes with depth left, the root included.
+    pub fn main_nodes(&self) -> usize {
+        self.main_nodes
+    }
+
+    /// Nodes of the quiescence search, starting with the positions at the horizon.
+    pub fn qsearch_nodes(&self) -> usize {
+        self.qsearch_nodes
+    }
+
+    pub fn completed_depth(&self) -> u16 {
+        self.completed_depth
+    }
+
+    /// Selective depth of the last fully searched depth: the most plies from the root
+    /// any of its lines reached, quiescence search included.
+    pub fn sel_depth(&self) -> usize {
+        self.completed_sel_depth
+    }
+
+    /// Nodes per second since the start of the search.
+    pub fn nps(&self) -> u128 {
+        let millis = self.search_start_time.elapsed().as_millis();
+        1000 * self.nodes_searched() as u128 / (millis + 1)
+    }
+
+    /// Counts a node `ply_from_root` plies below the root's moves towards the selective
+    /// depth, the root's moves themselves are one ply deep.
+    fn reach_ply(&mut self, ply_from_root: u16) {
+        self.iteration_sel_depth = self.iteration_sel_depth.max(ply_from_root as usize + 1);
+    }
+
+    /// Best line found by the last fully searched depth, starting with the best move.
+    pub fn pv(&self) -> &[Move] {
+        &self.pv
+    }
+
+    fn clear_pv(&mut self, ply_from_root: u16) {
+        let ply = ply_from_root as usize;
+        if ply < MAX_PLY as usize {
+            self.pv_length[ply] = ply;
+        }
+    }
+
+    fn update_pv(&mut self, mv: Move, ply_from_root: u16) {
+        let ply = ply_from_root as usize;
+        if ply + 1 >= MAX_PLY as usize {
+            return;
+        }
+        let child_length = self.pv_length[ply + 1].max(ply + 1);
+        self.pv_table[ply][ply] = mv;
+        for i in (ply + 1)..child_length {
+            self.pv_table[ply][i] = self.pv_table[ply + 1][i];
+        }
+        self.pv_length[ply] = child_length;
+    }
+
     fn store_killer_move(&mut self, current_move: Move, ply_from_root: u16) {
         let ply = ply_from_root as usize;
         let first_killer = self.killer_moves[0][ply];
@@ -83,100 +388,158 @@ impl SearchInfo {
 pub struct Searcher<const T: usize> {
     transposition_table: Box<TranspositionTable<T>>,
     pub info: SearchInfo,
-    eval_fn: fn(&ChessBoardState) -> i32,
-    pub stop: Arc<AtomicBool>,
-    time_control: TimeControl,
+    evaluator: Evaluator,
+    // Set once the current search has to unwind, either on request or on time
+    stop: Arc<AtomicBool>,
+    // Stop request of the caller, only ever read by the searcher
+    stop_request: Arc<AtomicBool>,
+    time_manager: TimeManager,
     game_phase: GamePhase,
+    iteration_callback: Option<IterationCallback>,
+    search_moves: Vec<Move>,
+    pub margins: PruningMargins,
+    /// Centipawns the engine is willing to give up to avoid a draw, negative values
+    /// make it seek draws instead.
+    pub contempt: i32,
+    /// Deepest iteration searched regardless of the time control, limits the strength.
+    pub max_depth: Option<u16>,
+    /// Milliseconds of the clock kept back for the delay of the GUI or network.
+    pub move_overhead: u64,
+    /// Leaves stdout to the caller, no `info` lines are printed.
+    pub quiet: bool,
 }
 
 impl<const T: usize> Searcher<T> {
     pub fn new(eval_fn: fn(&ChessBoardState) -> i32) -> Self {
+        Self::with_evaluator(Arc::new(eval_fn))
+    }
+
+    pub fn with_evaluator(evaluator: Evaluator) -> Self {
         let transposition_table = unsafe {
             let layout = std::alloc::Layout::new::<TranspositionTable<T>>();
             let ptr = std::alloc::alloc_zeroed(layout) as *mut TranspositionTable<T>;
-            Box::from_raw(ptr)
+            let mut table = Box::from_raw(ptr);
+            // Zeroed memory is an empty table of no size
+            table.resize(T);
+            table
         };
         Self {
             transposition_table,
             info: SearchInfo::default(),
-            eval_fn,
+            evaluator,
             stop: Arc::new(false.into()),
-            time_control: TimeControl::FixedDepth(5),
+            stop_request: Arc::new(false.into()),
+            time_manager: TimeManager::new(
+                &TimeControl::FixedDepth(5),
+                PieceColor::White,
+                &GamePhase::Opening,
+                DEFAULT_MOVE_OVERHEAD,
+            ),
             game_phase: GamePhase::Opening,
+            iteration_callback: None,
+            search_moves: Vec::new(),
+            margins: PruningMargins::default(),
+            contempt: 0,
+            max_depth: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            quiet: false,
         }
     }
 
+    /// Evaluation of the next searches, the hash table still holds scores of the old one.
+    pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+        self.evaluator = evaluator;
+    }
+
+    pub fn set_iteration_callback(&mut self, callback: Option<IterationCallback>) {
+        self.iteration_callback = callback;
+    }
+
     pub fn clear_hash_table(&mut self) {
         self.transposition_table.clear();
     }
 
+    /// Limits the hash table to `entries` entries, which clears it.
+    pub fn resize_hash_table(&mut self, entries: usize) {
+        self.transposition_table.resize(entries);
+    }
+
     pub fn incr_hash_table_age(&mut self) {
         self.transposition_table.increment_age();
     }
 
-    fn get_game_phase(board_state: &ChessBoardState) -> GamePhase {
-        let piece_count = board_state.total_piece_count();
-        let full_moves = board_state.full_moves;
+    /// Writes the deepest entries of the hash table to a file, see `TranspositionTable::save`.
+    pub fn save_hash_table(&self, path: &str, max_entries: usize) -> std::io::Result<usize> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.transposition_table.save(file, max_entries)
+    }
 
-        if full_moves <= 15 && piece_count >= 28 {
-            GamePhase::Opening
-        } else if full_moves <= 40 && piece_count > 16 {
-            GamePhase::Middle
-        } else {
-            GamePhase::Endgame
+    /// Adds the entries of a file written by `save_hash_table` to the hash table.
+    pub fn load_hash_table(&mut self, path: &str) -> std::io::Result<usize> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        self.transposition_table.load(file)
+    }
+
+    /// Entry of the hash table for the position, see `TranspositionTable::probe`.
+    pub fn probe_hash_table(&self, hash: ZHash) -> Option<&TranspositionEntry> {
+        self.transposition_table.probe(hash)
+    }
+
+    /// Clears the hash table, killers and game history. Between searches of the same game
+    /// the hash table only ages and the killers move towards the root.
+    pub fn new_game(&mut self) {
+        self.clear_hash_table();
+        self.info.clear();
+    }
+
+    /// Stores a search result, unless the search was stopped and the result may be incomplete.
+    fn store_entry(
+        &mut self,
+        board_state: &ChessBoardState,
+        eval: i32,
+        depth: u16,
+        ply_from_root: u16,
+        node_type: NodeType,
+        best_move: Move,
+    ) {
+        if self.stop.load(std::sync::atomic::Ordering::SeqCst) {
+            return;
         }
+        self.transposition_table.add_entry(
+            board_state,
+            eval,
+            depth,
+            ply_from_root,
+            node_type,
+            best_move,
+        );
     }
 
     fn should_stop(&mut self) -> bool {
         if self.stop.load(std::sync::atomic::Ordering::SeqCst) {
             return true;
         }
+        if self.stop_request.load(std::sync::atomic::Ordering::SeqCst) {
+            self.stop.store(true, std::sync::atomic::Ordering::SeqCst);
+            return true;
+        }
+        // Checked before every node is counted, so the search ends on the limit exactly
+        if self
+            .time_manager
+            .node_limit_reached(self.info.nodes_searched())
+        {
+            self.stop.store(true, std::sync::atomic::Ordering::SeqCst);
+            return true;
+        }
 
-        if self.info.nodes_searched % 4096 != 0 {
+        if !self.info.nodes_searched().is_multiple_of(4096) {
             return false;
         }
+        self.report_progress();
 
-        let should_stop = match &self.time_control {
-            TimeControl::Infinite => false,
-            TimeControl::FixedDepth(_) => false, // Handeled by the iterative deepening
-            TimeControl::FixedNodes(n) => self.info.nodes_searched >= *n as usize,
-            TimeControl::FixedTime(t) => {
-                let duration = Instant::now().duration_since(self.info.search_start_time);
-                duration.as_millis() >= *t as u128
-            }
-            TimeControl::Variable(cc) => {
-                let duration = Instant::now().duration_since(self.info.search_start_time);
-
-                let (time, inc) = if self.info.self_color == PieceColor::White {
-                    (cc.white_time.unwrap(), cc.white_inc.unwrap_or(0))
-                } else {
-                    (cc.black_time.unwrap(), cc.black_inc.unwrap_or(0))
-                };
-
-                const OVERHEAD: u64 = 50;
-                let time = time - OVERHEAD.min(time);
-                let inc = if time < OVERHEAD { 0 } else { inc };
-
-                let duration_for_move = if let Some(moves) = cc.movestogo {
-                    let phase_factor = match self.game_phase {
-                        GamePhase::Opening => 0.6,
-                        GamePhase::Middle => 0.7,
-                        GamePhase::Endgame => 0.8,
-                    };
-                    let scale = phase_factor / (moves.min(40) as f64);
-                    let max_time = 0.8 * time as f64;
-                    let opt_time = (scale * time as f64).min(max_time);
-                    opt_time
-                } else {
-                    let incremental_allocation = ((time / 20) + (inc * 3 / 4)) as f64;
-                    let emergency_buffer = time as f64 * 0.02; // Reserve 2% as a safety buffer.
-                    incremental_allocation * 0.6 - emergency_buffer
-                };
-
-                duration.as_millis() >= duration_for_move as u128
-            }
-        };
-
+        let should_stop = self
+            .time_manager
+            .hard_limit_reached(self.info.nodes_searched());
         if should_stop {
             self.stop
                 .store(should_stop, std::sync::atomic::Ordering::SeqCst);
@@ -185,6 +548,46 @@ impl<const T: usize> Searcher<T> {
         should_stop
     }
 
+    /// Prints an `info` line with the search statistics once per interval, so GUIs show
+    /// progress during long searches.
+    fn report_progress(&mut self) {
+        let now = Instant::now();
+        if self.quiet || now.duration_since(self.info.last_report_time).as_millis() < INFO_INTERVAL
+        {
+            return;
+        }
+        self.info.last_report_time = now;
+
+        println!(
+            "info depth {} seldepth {} time {} nodes {} nps {} hashfull {}",
+            self.info.depth,
+            self.info.iteration_sel_depth,
+            now.duration_since(self.info.search_start_time).as_millis(),
+            self.info.nodes_searched(),
+            self.info.nps(),
+            self.transposition_table.hashfull()
+        );
+    }
+
+    /// Prints the `info` line of the last fully searched depth with the principal variation.
+    fn report_iteration(&self) {
+        if self.quiet {
+            return;
+        }
+        let pv: Vec<String> = self.info.pv.iter().map(|mv| format!("{:?}", mv)).collect();
+        println!(
+            "info depth {} seldepth {} score {} time {} nodes {} nps {} hashfull {} pv {}",
+            self.info.completed_depth,
+            self.info.completed_sel_depth,
+            uci_score(self.info.best_score),
+            self.info.search_start_time.elapsed().as_millis(),
+            self.info.nodes_searched(),
+            self.info.nps(),
+            self.transposition_table.hashfull(),
+            pv.join(" ")
+        );
+    }
+
     fn depth_from_time_control(&mut self, time_control: &TimeControl) -> u16 {
         match time_control {
             TimeControl::Infinite => MAX_PLY,
@@ -192,9 +595,97 @@ impl<const T: usize> Searcher<T> {
             TimeControl::FixedNodes(_) => MAX_PLY,
             TimeControl::FixedTime(_) => MAX_PLY,
             TimeControl::Variable(_) => MAX_PLY,
+            // Mate in n moves needs 2n - 1 plies, the root move included
+            TimeControl::Mate(n) => (2 * (*n).min(MAX_PLY as u64 / 2) as u16)
+                .saturating_sub(2)
+                .max(1),
         }
     }
 
+    /// Restricts the root moves of the next search, an empty list allows all moves.
+    pub fn set_search_moves(&mut self, moves: Vec<Move>) {
+        self.search_moves = moves;
+    }
+
+    pub fn search_moves(&self) -> &[Move] {
+        &self.search_moves
+    }
+
+    /// Searches to a fixed depth with the root moves split across `threads` threads. Each
+    /// thread searches its share with a searcher of its own, configured like this one and
+    /// with a hash table of `S` entries. Much simpler than sharing a table and good enough
+    /// for analysing many positions at a shallow depth. Returns the best move of every
+    /// share with its score, best first. The threads search quietly, a single `info` line
+    /// reports the best share with the nodes of all of them.
+    pub fn split_root_search<const S: usize>(
+        &mut self,
+        board_state: &ChessBoardState,
+        depth: u16,
+        threads: usize,
+        stop: &Arc<AtomicBool>,
+    ) -> Vec<(Move, i32)> {
+        let mut moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let search_moves = std::mem::take(&mut self.search_moves);
+        if moves.iter().any(|mv| search_moves.contains(mv)) {
+            moves.retain(|mv| search_moves.contains(mv));
+        }
+        // Dealt out in turns, so no thread only gets the moves of a single piece
+        let threads = threads.clamp(1, moves.len().max(1));
+        let shares: Vec<Vec<Move>> = (0..threads)
+            .map(|i| moves.iter().skip(i).step_by(threads).copied().collect())
+            .collect();
+
+        let start_time = Instant::now();
+        let (evaluator, margins, contempt) = (&self.evaluator, self.margins, self.contempt);
+        let history = &self.info.history;
+        let mut shares: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = shares
+                .into_iter()
+                .filter(|share| !share.is_empty())
+                .map(|share| {
+                    scope.spawn(move || {
+                        let mut searcher = Searcher::<S>::with_evaluator(evaluator.clone());
+                        searcher.margins = margins;
+                        searcher.contempt = contempt;
+                        searcher.info.history = history.clone();
+                        searcher.quiet = true;
+                        searcher.set_search_moves(share);
+                        let mut board_state = *board_state;
+                        let tc = TimeControl::FixedDepth(depth as u64);
+                        let mv = searcher.search(&mut board_state, tc, stop);
+                        (mv, searcher.info)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        shares.sort_by_key(|(_, info)| Reverse(info.best_score));
+        let nodes: usize = shares.iter().map(|(_, info)| info.nodes_searched()).sum();
+        if let Some((_, info)) = shares.first().filter(|_| !self.quiet) {
+            let millis = start_time.elapsed().as_millis();
+            let pv: Vec<String> = info.pv.iter().map(|mv| format!("{:?}", mv)).collect();
+            println!(
+                "info depth {} seldepth {} score {} time {} nodes {} nps {} pv {}",
+                info.completed_depth,
+                info.completed_sel_depth,
+                uci_score(info.best_score),
+                millis,
+                nodes,
+                1000 * nodes as u128 / (millis + 1),
+                pv.join(" ")
+            );
+        }
+
+        shares
+            .iter()
+            .map(|(mv, info)| (*mv, info.best_score))
+            .collect()
+    }
+
     pub fn search(
         &mut self,
         board_state: &mut ChessBoardState,
@@ -202,61 +693,146 @@ impl<const T: usize> Searcher<T> {
         stop: &Arc<AtomicBool>,
     ) -> Move {
         let mut moves = board_state.generate_legal_moves_for_current_player::<false>();
-        // Sort moves by expected value
-        order_moves(&mut moves, board_state, &self.info, 0);
-
-        let search_depth = self.depth_from_time_control(&time_control);
-        self.stop = stop.clone();
+        let search_moves = std::mem::take(&mut self.search_moves);
+        if moves.iter().any(|mv| search_moves.contains(mv)) {
+            moves.retain(|mv| search_moves.contains(mv));
+        }
+        if moves.is_empty() {
+            return Move::NULL_MOVE;
+        }
+        // Sort moves by expected value, starting with the best move of an earlier search
+        let (_, tt_move) =
+            self.transposition_table
+                .lookup(board_state.zhash, 0, 0, -INFINITY, INFINITY);
+        order_moves(&mut moves, board_state, &self.info, 0, tt_move);
+
+        let search_depth = self
+            .depth_from_time_control(&time_control)
+            .min(self.max_depth.unwrap_or(MAX_PLY));
+        // The caller's flag is left untouched, so a stop sent right after starting
+        // the search is not lost
+        self.stop_request = stop.clone();
         self.stop.store(false, std::sync::atomic::Ordering::SeqCst);
         self.info.reset();
-        self.time_control = time_control;
+        if self.info.history.last() != Some(&board_state.zhash) {
+            self.info.history.push(board_state.zhash);
+        }
+        self.info.trim_history(board_state.half_moves);
+        self.incr_hash_table_age();
         self.info.self_color = board_state.side;
-        self.game_phase = Self::get_game_phase(board_state);
+        self.game_phase = GamePhase::of(board_state);
+        self.time_manager = TimeManager::new(
+            &time_control,
+            board_state.side,
+            &self.game_phase,
+            self.move_overhead,
+        );
 
         // Iterative deepening
+        let mut interrupted = false;
         for d in 1..=search_depth {
+            self.info.depth = d;
+            self.info.iteration_sel_depth = 0;
             self.minimax_root(board_state, &mut moves, d);
-        }
+            if self.info.completed_depth != d {
+                interrupted = true;
+                break;
+            }
+            self.report_iteration();
+            log_debug!(
+                "Depth {} done: {:?} score {} nodes {} ({} quiescence)",
+                d,
+                self.info.best_move,
+                self.info.best_score,
+                self.info.nodes_searched(),
+                self.info.qsearch_nodes
+            );
+            if let Some(callback) = &mut self.iteration_callback {
+                callback(&self.info);
+            }
 
-        let search_duration = Instant::now().duration_since(self.info.search_start_time);
-        let nps = (1000 * self.info.nodes_searched as u128) / (search_duration.as_millis() + 1);
+            self.time_manager.update(
+                self.info.best_move,
+                self.info.best_score,
+                self.info.second_best_score,
+            );
+            if self.time_manager.soft_limit_reached() {
+                break;
+            }
+            if let TimeControl::Mate(n) = time_control {
+                let mate_in = (CHECKMATE - self.info.best_score) / 2 + 1;
+                if self.info.best_score >= MATE_DISTANCE && mate_in as u64 <= n {
+                    break;
+                }
+            }
+        }
 
-        println!(
-            "info time {} nodes {} nps {} hashfull {} depth {} seldepth {}",
-            search_duration.as_millis(),
-            self.info.nodes_searched,
-            nps,
-            self.transposition_table.hashfull(),
-            search_depth,
-            self.info.sel_depth
+        // Every full depth was reported already. An interrupted one does not count, but may
+        // have proven a better move, reported with the depths of the last full one.
+        if interrupted && self.info.best_move != Move::NULL_MOVE {
+            self.report_iteration();
+        }
+        let stats = &self.info.ordering_stats;
+        log_debug!(
+            "Ordering first move cutoffs {:.1}% average cutoff index {:.2} tt move hits {:.1}%",
+            100.0 * stats.first_move_cutoff_rate(),
+            stats.average_cutoff_index(),
+            100.0 * stats.tt_move_hit_rate()
         );
 
-        let best_move = moves[0];
-        board_state.exec_move(best_move);
-        self.info.history.push(board_state.zhash);
-        best_move
+        // Without any searched move the ordering, led by the TT move, is all there is
+        if self.info.best_move != Move::NULL_MOVE {
+            self.info.best_move
+        } else {
+            moves[0]
+        }
     }
 
-    fn is_draw(&self, board_state: &ChessBoardState, depth: u16) -> bool {
-        board_state.half_moves >= 100 || self.is_repetition(board_state, depth)
+    /// Score of a drawn position from the side to move's perspective. A draw is
+    /// worth less than zero to the engine and more to its opponent, with the bias
+    /// halved in the endgame where draws are the more natural outcome.
+    fn draw_score(&self, board_state: &ChessBoardState) -> i32 {
+        let contempt = match self.game_phase {
+            GamePhase::Opening | GamePhase::Middle => self.contempt,
+            GamePhase::Endgame => self.contempt / 2,
+        };
+        if board_state.side == self.info.self_color {
+            -contempt
+        } else {
+            contempt
+        }
     }
 
-    fn is_repetition(&self, board_state: &ChessBoardState, depth: u16) -> bool {
-        let rollback = 1 + (depth as usize).min(board_state.half_moves as usize);
-
-        // Rollback == 1 implies we only look at the opponent's position.
-        if rollback == 1 {
-            return false;
+    /// Draw by repetition or the fifty-move rule, a mate on the last move of the fifty
+    /// still counts. Both depend on the path to the position, so their scores are never
+    /// stored in the hash table.
+    fn is_draw(&self, board_state: &ChessBoardState, ply_from_root: u16) -> bool {
+        if self
+            .info
+            .is_repetition(board_state.zhash, ply_from_root, board_state.half_moves)
+        {
+            return true;
         }
+        board_state.half_moves >= 100
+            && !(board_state.is_in_check() && board_state.legal_moves::<false>().is_empty())
+    }
 
-        self.info
-            .history
-            .iter()
-            .rev() // step through history in reverse
-            .take(rollback) // only check elements within rollback
-            .skip(1) // first element is opponent, skip.
-            .step_by(2) // don't check opponent moves
-            .any(|b| *b == board_state.zhash) // stop at first repetition
+    /// Penalty for a root move when the engine is winning: a winning position should
+    /// be converted, so moves repeating an earlier position or letting the fifty-move
+    /// counter run out are less attractive. Pawn moves and captures reset the counter
+    /// and count as progress.
+    fn root_draw_penalty(&self, board_new: &ChessBoardState, rating: i32) -> i32 {
+        if !(WINNING_SCORE..MATE_DISTANCE).contains(&rating) {
+            return 0;
+        }
+        let mut penalty = 0;
+        if self.info.history.contains(&board_new.zhash) {
+            penalty += REPETITION_PENALTY;
+        }
+        if board_new.half_moves > FIFTY_MOVE_THRESHOLD {
+            penalty += (board_new.half_moves - FIFTY_MOVE_THRESHOLD) as i32 * FIFTY_MOVE_PENALTY;
+        }
+        penalty.min(rating - WINNING_SCORE / 2)
     }
 
     pub fn minimax_root(
@@ -265,24 +841,84 @@ impl<const T: usize> Searcher<T> {
         moves: &mut Vec<Move>,
         depth: u16,
     ) {
-        let mut ratings = if board_state.side == PieceColor::White {
-            vec![i32::MIN; moves.len()]
-        } else {
-            vec![i32::MAX; moves.len()]
-        };
+        if self.should_stop() {
+            return;
+        }
+        self.info.main_nodes += 1;
+        let mut ratings = vec![-INFINITY - 1; moves.len()];
+        let mut searched = 0;
+
+        let mut best_pv = Vec::new();
+        let mut best_rating = -INFINITY - 1;
 
         for (mv_index, mv) in moves.iter().enumerate() {
+            // Short searches would only flood the GUI with root moves
+            if !self.quiet && self.info.search_start_time.elapsed().as_millis() >= INFO_INTERVAL {
+                println!(
+                    "info depth {} currmove {:?} currmovenumber {}",
+                    depth,
+                    mv,
+                    mv_index + 1
+                );
+            }
             let board_new = board_state.exec_move(*mv);
-            ratings[mv_index] = -self.minimax(&board_new, depth, 0, -INFINITY, INFINITY, 0);
+            let rating = -self.minimax(&board_new, depth, 0, -INFINITY, INFINITY, 0);
+            // The score of an interrupted search is meaningless
+            if self.stop.load(std::sync::atomic::Ordering::SeqCst) {
+                break;
+            }
+            let rating = rating - self.root_draw_penalty(&board_new, rating);
+            ratings[mv_index] = rating;
+            searched += 1;
+
+            if rating > best_rating {
+                best_rating = rating;
+                best_pv.clear();
+                best_pv.push(*mv);
+                best_pv.extend_from_slice(&self.info.pv_table[0][..self.info.pv_length[0]]);
+            }
         }
 
-        if self.should_stop() {
+        if searched < moves.len() {
+            // The previous best move is searched first, a partial iteration can only
+            // replace it by a move which scored better at this depth. Without a
+            // previous iteration whatever was searched is the best there is.
+            let proves_better_move = searched > 1 && best_pv.first() != Some(&moves[0]);
+            if searched > 0 && (self.info.best_move == Move::NULL_MOVE || proves_better_move) {
+                self.info.best_move = best_pv[0];
+                self.info.best_score = best_rating;
+                self.info.pv = best_pv;
+            }
             return;
         }
 
-        // Combine moves and ratings into a single vector for sorting
+        // Seed the root entry with the best move so the next iteration and the final
+        // move choice start from it
+        if let Some(best_move) = best_pv.first() {
+            self.store_entry(
+                board_state,
+                best_rating,
+                depth + 1,
+                0,
+                NodeType::Exact,
+                *best_move,
+            );
+        }
+        self.info.pv = best_pv;
+
+        // Combine moves and ratings into a single vector for sorting, the stable sort
+        // keeps the previous best move in front on equal scores
         let mut zipped: Vec<_> = moves.iter().cloned().zip(ratings).collect();
-        zipped.sort_unstable_by(|(_, a_rt), (_, b_rt)| b_rt.cmp(a_rt));
+        zipped.sort_by(|(_, a_rt), (_, b_rt)| b_rt.cmp(a_rt));
+
+        if let Some((best_move, best_score)) = zipped.first() {
+            self.info.best_move = *best_move;
+            self.info.best_score = *best_score;
+            self.info.completed_depth = depth;
+            self.info.completed_sel_depth = self.info.iteration_sel_depth;
+            self.info.iteration_best_moves.push(*best_move);
+        }
+        self.info.second_best_score = zipped.get(1).map(|(_, score)| *score);
 
         // Update moves in place
         for (i, (mv, _)) in zipped.into_iter().enumerate() {
@@ -293,11 +929,11 @@ impl<const T: usize> Searcher<T> {
     fn quiescience_search(
         &mut self,
         board_state: &ChessBoardState,
-        ply_remaining: u16,
         ply_from_root: u16,
         mut alpha: i32,
         beta: i32,
     ) -> i32 {
+        self.info.clear_pv(ply_from_root);
         if self.should_stop() {
             return 0;
         }
@@ -308,45 +944,64 @@ impl<const T: usize> Searcher<T> {
             -1
         };
 
-        self.info.sel_depth = self.info.sel_depth.max(ply_from_root as usize);
-        self.info.nodes_searched += 1;
+        self.info.reach_ply(ply_from_root);
+        self.info.qsearch_nodes += 1;
 
-        if ply_from_root >= MAX_PLY || ply_remaining == 0 {
-            return sf * (self.eval_fn)(&board_state);
+        if ply_from_root >= MAX_PLY {
+            return sf * (self.evaluator)(board_state);
         }
 
         if self.is_draw(board_state, ply_from_root) {
-            return 0;
+            return self.draw_score(board_state);
         }
+        self.info.set_search_key(ply_from_root, board_state.zhash);
 
-        if let Some(score) = self.transposition_table.lookup(
-            board_state.zhash,
-            ply_remaining,
-            ply_from_root,
-            alpha,
-            beta,
-        ) {
+        let (tt_score, tt_move) =
+            self.transposition_table
+                .lookup(board_state.zhash, 0, ply_from_root, alpha, beta);
+        if let Some(score) = tt_score {
             return score;
         }
 
-        let mut score = sf * (self.eval_fn)(&board_state);
-        if score >= beta {
-            return beta;
-        }
-        if alpha < score {
-            alpha = score;
-        }
+        // When in check every evasion has to be looked at and standing pat is not an option
+        let is_in_check = board_state.is_in_check();
+        let stand_pat = sf * (self.evaluator)(board_state);
+        let moves = if is_in_check {
+            let evasions = board_state.legal_moves::<false>();
+            if evasions.is_empty() {
+                return -CHECKMATE + ply_from_root as i32;
+            }
+            evasions
+        } else {
+            if stand_pat >= beta {
+                return beta;
+            }
+            if alpha < stand_pat {
+                alpha = stand_pat;
+            }
+            board_state.legal_moves::<true>()
+        };
+        let moves = MovePicker::new(moves, board_state, &self.info, ply_from_root, tt_move);
+
+        for mv in moves {
+            // Delta pruning: skip captures which cannot raise alpha even when the
+            // captured piece is won for free
+            if !is_in_check {
+                let captured_value = mv
+                    .get_captured_piece(board_state)
+                    .map_or(0, |p| p.eval_value() as i32);
+                let promotion_value = if mv.is_promotion() {
+                    mv.promotion_target().eval_value() as i32 - ChessPiece::Pawn.eval_value() as i32
+                } else {
+                    0
+                };
+                if stand_pat + captured_value + promotion_value + DELTA_MARGIN < alpha {
+                    continue;
+                }
+            }
 
-        let moves = board_state.generate_legal_moves_for_current_player::<true>();
-        for mv in &moves {
-            let new_board = board_state.exec_move(*mv);
-            score = -self.quiescience_search(
-                &new_board,
-                ply_remaining - 1,
-                ply_from_root + 1,
-                -beta,
-                -alpha,
-            );
+            let new_board = board_state.exec_move(mv);
+            let score = -self.quiescience_search(&new_board, ply_from_root + 1, -beta, -alpha);
             if score >= beta {
                 return beta;
             }
@@ -357,84 +1012,216 @@ impl<const T: usize> Searcher<T> {
         return alpha;
     }
 
+    /// Extensions are budgeted per line: a line may only be extended while it
+    /// has used at most half as many extensions as plies played from the root.
+    fn can_extend(ply_from_root: u16, extensions: usize) -> bool {
+        extensions <= ply_from_root as usize / 2 && ply_from_root + 1 < MAX_PLY
+    }
+
+    /// Checks whether the hash move is the only move that holds the stored score,
+    /// by searching all alternatives at reduced depth against a lowered bound.
+    fn is_singular(
+        &mut self,
+        board_state: &ChessBoardState,
+        moves: &[Move],
+        ply_remaining: u16,
+        ply_from_root: u16,
+        extensions: usize,
+    ) -> Option<Move> {
+        if ply_remaining < SINGULAR_EXTENSION_DEPTH || !Self::can_extend(ply_from_root, extensions)
+        {
+            return None;
+        }
+
+        let entry = *self.transposition_table.probe(board_state.zhash)?;
+        let tt_eval = TranspositionTable::<T>::correct_fetched_score(entry.eval, ply_from_root);
+        if entry.node_type == NodeType::UpperBound
+            || entry.depth + 3 < ply_remaining
+            || tt_eval.abs() >= MATE_DISTANCE
+            || !moves.contains(&entry.best_move)
+        {
+            return None;
+        }
+
+        let singular_beta = tt_eval - SINGULAR_MARGIN * ply_remaining as i32;
+        let reduced_depth = (ply_remaining - 1) / 2;
+        for mv in moves.iter().filter(|mv| **mv != entry.best_move) {
+            let new_board = board_state.exec_move(*mv);
+            let score = -self.minimax(
+                &new_board,
+                reduced_depth,
+                ply_from_root + 1,
+                -singular_beta,
+                -singular_beta + 1,
+                extensions,
+            );
+            if score >= singular_beta || self.should_stop() {
+                return None;
+            }
+        }
+        Some(entry.best_move)
+    }
+
     fn minimax(
         &mut self,
         board_state: &ChessBoardState,
-        mut ply_remaining: u16,
+        ply_remaining: u16,
         ply_from_root: u16,
         mut alpha: i32,
-        beta: i32,
-        mut extensions: usize,
+        mut beta: i32,
+        extensions: usize,
     ) -> i32 {
+        // The quiescence search takes over, and counts, the nodes at the horizon and at
+        // the last ply the killer and PV tables hold, which extensions may reach
+        if ply_remaining == 0 || ply_from_root >= MAX_PLY - 1 {
+            return self.quiescience_search(board_state, ply_from_root, alpha, beta);
+        }
+        self.info.clear_pv(ply_from_root);
         if self.should_stop() {
             return 0;
         }
+        self.info.main_nodes += 1;
+
+        // Mate distance pruning: no line from here can be better than mating on the
+        // next move or worse than being mated right now.
+        alpha = alpha.max(-CHECKMATE + ply_from_root as i32);
+        beta = beta.min(CHECKMATE - ply_from_root as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
 
-        if let Some(eval) = self.transposition_table.lookup(
+        // Lines ending in a hash table hit reach this deep as well
+        self.info.reach_ply(ply_from_root);
+
+        // Checked before the hash table, an entry of another path would hide the draw
+        if self.is_draw(board_state, ply_from_root) {
+            return self.draw_score(board_state);
+        }
+        let (tt_eval, mut tt_move) = self.transposition_table.lookup(
             board_state.zhash,
             ply_remaining,
             ply_from_root,
             alpha,
             beta,
-        ) {
+        );
+        if let Some(eval) = tt_eval {
             return eval;
         }
 
-        // Extend the search
         let is_in_check = board_state.is_in_check();
-        if is_in_check && extensions < MAX_EXTENSIONS {
-            ply_remaining += 1;
-            extensions += 1;
-        }
-
-        if ply_remaining == 0 {
-            return self.quiescience_search(
-                board_state,
-                MAX_QUISCIENCE_DEPTH,
-                ply_from_root,
-                alpha,
-                beta,
-            );
-        }
-
-        self.info.nodes_searched += 1;
-        self.info.sel_depth = self.info.sel_depth.max(ply_from_root as usize);
-        let mut moves = board_state.generate_legal_moves_for_current_player::<false>();
+        let moves = board_state.legal_moves::<false>();
 
         // No moves, either draw or checkmate
         if moves.len() == 0 {
             let score = if is_in_check {
                 -CHECKMATE + ply_from_root as i32
             } else {
-                0
+                self.draw_score(board_state)
             };
             return score;
         }
 
-        // Check for drawing moves
-        if self.is_draw(board_state, ply_remaining) {
-            self.transposition_table.add_entry(
+        self.info.set_search_key(ply_from_root, board_state.zhash);
+
+        // Internal iterative deepening: without a hash move a PV node is searched shallower
+        // first, its best move then leads the ordering of the full search
+        let is_pv_node = beta - alpha > 1;
+        if tt_move.is_none() && is_pv_node && ply_remaining >= IID_DEPTH {
+            self.minimax(
                 board_state,
-                0,
-                ply_remaining,
+                ply_remaining - IID_REDUCTION,
                 ply_from_root,
-                NodeType::Exact,
-                &self.stop,
+                alpha,
+                beta,
+                extensions,
             );
-            return 0;
+            if self.should_stop() {
+                return 0;
+            }
+            tt_move = self
+                .transposition_table
+                .lookup(board_state.zhash, 0, ply_from_root, -INFINITY, INFINITY)
+                .1;
         }
 
-        // Sort moves by expected value
-        order_moves(&mut moves, board_state, &self.info, ply_from_root);
+        // Static eval is only needed for the shallow pruning decisions below
+        let static_eval = if !is_in_check && ply_remaining <= REVERSE_FUTILITY_DEPTH {
+            let sf = if board_state.side == PieceColor::White {
+                1
+            } else {
+                -1
+            };
+            Some(sf * (self.evaluator)(board_state))
+        } else {
+            None
+        };
+
+        // Reverse futility pruning: the position is so good that even a margin
+        // for the opponent's best reply will not bring it back below beta.
+        if let Some(eval) = static_eval {
+            if beta.abs() < MATE_DISTANCE
+                && eval - self.margins.reverse_futility * ply_remaining as i32 >= beta
+            {
+                return beta;
+            }
+        }
+
+        // Futility pruning: at frontier nodes quiet moves cannot raise a hopeless
+        // static eval above alpha.
+        let futile = match static_eval {
+            Some(eval) => {
+                ply_remaining <= FUTILITY_DEPTH
+                    && alpha.abs() < MATE_DISTANCE
+                    && eval + self.margins.futility * ply_remaining as i32 <= alpha
+            }
+            None => false,
+        };
+
+        // Moves are handed out best first as they are needed
+        let moves = MovePicker::new(moves, board_state, &self.info, ply_from_root, tt_move);
+
+        let singular_move = self.is_singular(
+            board_state,
+            moves.moves(),
+            ply_remaining,
+            ply_from_root,
+            extensions,
+        );
+        if self.should_stop() {
+            return 0;
+        }
 
         let mut node_type = NodeType::UpperBound;
+        let mut best_move = Move::NULL_MOVE;
+        // The picker hands out a legal hash move first
+        let has_tt_move = moves.has_tt_move();
+        if has_tt_move {
+            self.info.ordering_stats.tt_move_nodes += 1;
+        }
+
+        for (i, mv) in moves.enumerate() {
+            let new_board: ChessBoardState = board_state.exec_move(mv);
+            self.transposition_table.prefetch(new_board.zhash);
+            if futile && i > 0 && !mv.is_capture() && !mv.is_promotion() && !new_board.is_in_check()
+            {
+                continue;
+            }
 
-        for (i, mv) in moves.iter().enumerate() {
-            let new_board: ChessBoardState = board_state.exec_move(*mv);
             let mut needs_full_search = true;
             let mut score = 0;
 
-            if extensions == 0 && ply_remaining >= 3 && i >= 3 && !mv.is_capture() {
+            // Extend forced moves and moves giving check, within the line's budget
+            let extension = if Self::can_extend(ply_from_root, extensions)
+                && (singular_move == Some(mv) || new_board.is_in_check())
+            {
+                1
+            } else {
+                0
+            };
+            let child_depth = ply_remaining - 1 + extension;
+            let child_extensions = extensions + extension as usize;
+
+            if extension == 0 && !is_in_check && ply_remaining >= 3 && i >= 3 && !mv.is_capture() {
                 // reduce the detph of the search
                 score = -self.minimax(
                     &new_board,
@@ -442,7 +1229,7 @@ impl<const T: usize> Searcher<T> {
                     ply_from_root + 1,
                     -alpha - 1,
                     -alpha,
-                    extensions,
+                    child_extensions,
                 );
                 // If the evaluation is better than expected, we'd better to a full-depth search to get a more accurate evaluation
                 needs_full_search = score > alpha;
@@ -451,11 +1238,11 @@ impl<const T: usize> Searcher<T> {
             if needs_full_search {
                 score = -self.minimax(
                     &new_board,
-                    ply_remaining - 1,
+                    child_depth,
                     ply_from_root + 1,
                     -beta,
                     -alpha,
-                    extensions,
+                    child_extensions,
                 );
             }
 
@@ -464,14 +1251,18 @@ impl<const T: usize> Searcher<T> {
             }
 
             if score >= beta {
-                self.info.store_killer_move(*mv, ply_from_root);
-                self.transposition_table.add_entry(
+                self.info.ordering_stats.record_cutoff(i);
+                if has_tt_move && i == 0 {
+                    self.info.ordering_stats.tt_move_hits += 1;
+                }
+                self.info.store_killer_move(mv, ply_from_root);
+                self.store_entry(
                     board_state,
                     beta,
                     ply_remaining,
                     ply_from_root,
                     NodeType::LowerBound,
-                    &self.stop,
+                    mv,
                 );
                 return beta;
             }
@@ -479,17 +1270,315 @@ impl<const T: usize> Searcher<T> {
             if score > alpha {
                 node_type = NodeType::Exact;
                 alpha = score;
+                best_move = mv;
+                self.info.update_pv(mv, ply_from_root);
             }
         }
 
-        self.transposition_table.add_entry(
+        if has_tt_move && Some(best_move) == tt_move {
+            self.info.ordering_stats.tt_move_hits += 1;
+        }
+        self.store_entry(
             board_state,
             alpha,
             ply_remaining,
             ply_from_root,
             node_type,
-            &self.stop,
+            best_move,
         );
         alpha
     }
 }
+
+#[cfg(test)]
+mod search_tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use super::{
+        Searcher, FIFTY_MOVE_PENALTY, FIFTY_MOVE_THRESHOLD, INFINITY, MAX_PLY, REPETITION_PENALTY,
+    };
+    use crate::{
+        chess::{board::ChessBoardState, chess_move::Move, zobrist_hash::ZHash},
+        engine::{
+            board_eval::{EvaluationFunction, PieceCountEvaluation},
+            time_control::TimeControl,
+        },
+    };
+
+    #[test]
+    fn node_limit_is_exact() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let stop = Arc::new(AtomicBool::new(false));
+        for nodes in [1, 777, 5000] {
+            let mut board_state = ChessBoardState::starting_state();
+            let best_move =
+                searcher.search(&mut board_state, TimeControl::FixedNodes(nodes), &stop);
+            assert_eq!(searcher.info.nodes_searched(), nodes as usize);
+            assert_ne!(best_move, Move::NULL_MOVE);
+        }
+    }
+
+    #[test]
+    fn main_and_quiescence_nodes() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut board_state = ChessBoardState::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let root_moves = board_state.legal_moves::<false>().len();
+        searcher.search(&mut board_state, TimeControl::FixedDepth(1), &stop);
+
+        // The root and its moves, whose replies are all left to the quiescence search as
+        // no check extends them
+        assert_eq!(searcher.info.main_nodes(), 1 + root_moves);
+        assert!(searcher.info.qsearch_nodes() > root_moves);
+        assert_eq!(
+            searcher.info.nodes_searched(),
+            searcher.info.main_nodes() + searcher.info.qsearch_nodes()
+        );
+    }
+
+    #[test]
+    fn horizon_nodes_answered_by_the_hash_table() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let board_state = ChessBoardState::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        )
+        .unwrap();
+        searcher.minimax(&board_state, 2, 1, -INFINITY, INFINITY, 0);
+        assert!(searcher.probe_hash_table(board_state.zhash).is_some());
+
+        let (main_nodes, qsearch_nodes) =
+            (searcher.info.main_nodes(), searcher.info.qsearch_nodes());
+        for ply_remaining in [0, 1] {
+            searcher.minimax(&board_state, ply_remaining, 1, -INFINITY, INFINITY, 0);
+        }
+        assert_eq!(searcher.info.main_nodes(), main_nodes + 1);
+        assert_eq!(searcher.info.qsearch_nodes(), qsearch_nodes + 1);
+    }
+
+    #[test]
+    fn repetitions_are_not_hidden_by_the_hash_table() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        // White is a queen up, far from a draw
+        let board_state = ChessBoardState::from_fen(
+            "r1b1kbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        )
+        .unwrap();
+        let score = searcher.minimax(&board_state, 2, 1, -INFINITY, INFINITY, 0);
+        let stored = *searcher.probe_hash_table(board_state.zhash).unwrap();
+        assert_ne!(score, searcher.draw_score(&board_state));
+
+        // Two plies further down the same position is a repetition, which is neither
+        // answered from nor written to the table
+        searcher.info.set_search_key(1, board_state.zhash);
+        let score = searcher.minimax(&board_state, 2, 3, -INFINITY, INFINITY, 0);
+        assert_eq!(score, searcher.draw_score(&board_state));
+        let entry = searcher.probe_hash_table(board_state.zhash).unwrap();
+        assert_eq!((entry.eval, entry.depth), (stored.eval, stored.depth));
+    }
+
+    #[test]
+    fn fifty_move_rule_yields_to_mate() {
+        let searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let mated = ChessBoardState::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 100 80").unwrap();
+        assert!(!searcher.is_draw(&mated, 1));
+        let checked = ChessBoardState::from_fen("7k/8/6K1/8/8/8/8/Q7 b - - 100 80").unwrap();
+        assert!(searcher.is_draw(&checked, 1));
+    }
+
+    #[test]
+    fn deepest_plies_end_in_quiescence() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let board_state = ChessBoardState::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        )
+        .unwrap();
+        for ply_from_root in [MAX_PLY - 1, MAX_PLY] {
+            searcher.minimax(&board_state, 3, ply_from_root, -INFINITY, INFINITY, 0);
+        }
+        assert_eq!(searcher.info.main_nodes(), 0);
+    }
+
+    #[test]
+    fn completed_and_selective_depth() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut board_state = ChessBoardState::from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        )
+        .unwrap();
+        searcher.search(&mut board_state, TimeControl::FixedDepth(3), &stop);
+        assert_eq!(searcher.info.completed_depth(), 3);
+        // The root's moves and three plies below them, captures go deeper
+        assert!(searcher.info.sel_depth() > 4);
+
+        // An interrupted iteration is not reported
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        searcher.search(&mut board_state, TimeControl::FixedNodes(3000), &stop);
+        let depth = searcher.info.completed_depth();
+        assert_eq!(searcher.info.depth, depth + 1);
+        assert!(searcher.info.sel_depth() > depth as usize);
+    }
+
+    #[test]
+    fn split_root_search() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        // Back rank mate with Ra8
+        let board_state =
+            ChessBoardState::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let results = searcher.split_root_search::<1024>(&board_state, 2, 3, &stop);
+
+        assert_eq!(results.len(), 3);
+        assert!(results.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(
+            results[0].0,
+            Move::try_from(("a1a8", &board_state)).unwrap()
+        );
+    }
+
+    #[test]
+    fn ordering_stats() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let mut board_state = ChessBoardState::from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        )
+        .unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        searcher.search(&mut board_state, TimeControl::FixedDepth(4), &stop);
+
+        let stats = searcher.info.ordering_stats();
+        assert!(stats.cutoffs > 0);
+        assert!(stats.first_move_cutoffs <= stats.cutoffs);
+        assert!(stats.tt_move_hits <= stats.tt_move_nodes);
+        assert!((0.0..=1.0).contains(&stats.first_move_cutoff_rate()));
+        assert!((0.0..=1.0).contains(&stats.tt_move_hit_rate()));
+    }
+
+    #[test]
+    fn root_draw_penalty_when_winning() {
+        let mut searcher = Searcher::<1024>::new(|_| 0);
+        let board_state = ChessBoardState::from_fen("6k1/8/6K1/8/8/8/8/Q7 w - - 10 60").unwrap();
+        let mv = Move::try_from(("a1a2", &board_state)).unwrap();
+        let board_new = board_state.exec_move(mv);
+
+        assert_eq!(searcher.root_draw_penalty(&board_new, 900), 0);
+        searcher.info.history.push(board_new.zhash);
+        assert_eq!(
+            searcher.root_draw_penalty(&board_new, 900),
+            REPETITION_PENALTY
+        );
+        // Equal positions and mates are left alone
+        assert_eq!(searcher.root_draw_penalty(&board_new, 50), 0);
+        assert_eq!(
+            searcher.root_draw_penalty(&board_new, super::CHECKMATE - 3),
+            0
+        );
+
+        searcher.info.history.clear();
+        let board_state = ChessBoardState::from_fen("6k1/8/6K1/8/8/8/8/Q7 w - - 90 60").unwrap();
+        let board_new = board_state.exec_move(mv);
+        assert_eq!(
+            searcher.root_draw_penalty(&board_new, 900),
+            (91 - FIFTY_MOVE_THRESHOLD) as i32 * FIFTY_MOVE_PENALTY
+        );
+    }
+
+    #[test]
+    fn killer_and_history_lifetimes() {
+        let mut searcher = Searcher::<1024>::new(|_| 0);
+        let board_state = ChessBoardState::starting_state();
+        let mv = Move::try_from(("g1f3", &board_state)).unwrap();
+
+        // Killers move two plies towards the root between searches
+        searcher.info.store_killer_move(mv, 2);
+        searcher.info.reset();
+        assert_eq!(searcher.info.killer_moves[0][0], mv);
+        assert_eq!(searcher.info.killer_moves[0][2], Move::NULL_MOVE);
+
+        // Positions before the last irreversible move can not repeat
+        searcher.info.history = (0..10).map(ZHash).collect();
+        searcher.info.trim_history(3);
+        assert_eq!(
+            searcher.info.history,
+            (6..10).map(ZHash).collect::<Vec<_>>()
+        );
+
+        searcher.new_game();
+        assert!(searcher.info.history.is_empty());
+        assert_eq!(searcher.info.killer_moves[0][0], Move::NULL_MOVE);
+    }
+
+    #[test]
+    fn repetitions_in_tree_and_game() {
+        let mut searcher = Searcher::<1024>::new(|_| 0);
+        let (root, a, b) = (ZHash(1), ZHash(2), ZHash(3));
+        searcher.info.history = vec![ZHash(4), ZHash(5), root];
+        searcher.info.set_search_key(0, a);
+        searcher.info.set_search_key(1, b);
+
+        // Repeating a position after the root once is a draw
+        assert!(searcher.info.is_repetition(a, 2, 10));
+        assert!(!searcher.info.is_repetition(b, 2, 10));
+        // unless a capture or pawn move came in between
+        assert!(!searcher.info.is_repetition(a, 2, 1));
+
+        // The root only occurred once in the game
+        assert!(!searcher.info.is_repetition(root, 1, 10));
+        searcher.info.history = vec![root, ZHash(4), ZHash(5), ZHash(6), root];
+        assert!(searcher.info.is_repetition(root, 1, 10));
+        assert!(!searcher.info.is_repetition(root, 1, 5));
+        // Positions with the other side to move never repeat
+        assert!(!searcher.info.is_repetition(root, 0, 10));
+    }
+
+    #[test]
+    fn search_history_ends_with_root() {
+        let mut searcher = Searcher::<1024>::new(PieceCountEvaluation::eval);
+        let mut board_state = ChessBoardState::starting_state();
+        let stop = Arc::new(AtomicBool::new(false));
+        searcher.search(&mut board_state, TimeControl::FixedDepth(2), &stop);
+        assert_eq!(searcher.info.history, vec![board_state.zhash]);
+        // The next search of the same position does not count it twice
+        searcher.search(&mut board_state, TimeControl::FixedDepth(2), &stop);
+        assert_eq!(searcher.info.history, vec![board_state.zhash]);
+    }
+
+    #[test]
+    fn concurrent_searches_are_independent() {
+        fn assert_send<S: Send>() {}
+        assert_send::<Searcher<1024>>();
+
+        // Different positions with different evaluations
+        type Search = (&'static str, fn(&ChessBoardState) -> i32);
+        let searches: [Search; 2] = [
+            (
+                "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+                PieceCountEvaluation::eval,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", |board_state| {
+                PieceCountEvaluation::eval(board_state) / 2
+            }),
+        ];
+        let stop = Arc::new(AtomicBool::new(false));
+        let run = |(fen, eval_fn): Search| {
+            let mut searcher = Searcher::<1024>::new(eval_fn);
+            let mut board_state = ChessBoardState::from_fen(fen).unwrap();
+            let mv = searcher.search(&mut board_state, TimeControl::FixedDepth(4), &stop);
+            (
+                mv,
+                searcher.info.best_score(),
+                searcher.info.nodes_searched(),
+            )
+        };
+
+        let alone: Vec<_> = searches.iter().map(|search| run(*search)).collect();
+        let side_by_side: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = searches
+                .iter()
+                .map(|search| scope.spawn(|| run(*search)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(alone, side_by_side);
+    }
+}
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-#[derive(PartialEq, Debug, Default)]
+#[derive(PartialEq, Debug, Default, Clone)]
 pub struct ClockControl {
     pub white_time: Option<u64>,
     pub black_time: Option<u64>,
@@ -9,19 +9,26 @@ pub struct ClockControl {
     pub movestogo: Option<u64>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum TimeControl {
     Infinite,
     FixedDepth(u64),
     FixedNodes(u64),
     FixedTime(u64),
     Variable(ClockControl),
+    /// Search for a forced mate in the given number of moves
+    Mate(u64),
 }
 
 impl FromStr for TimeControl {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A plain go searches until stopped
+        if s.trim().is_empty() {
+            return Ok(Self::Infinite);
+        }
+
         let mut tokens = s.split(" ");
         let mut cc = ClockControl::default();
 
@@ -40,6 +47,7 @@ impl FromStr for TimeControl {
                 "depth" => return Ok(Self::FixedDepth(parse_numeric(&mut tokens)?)),
                 "nodes" => return Ok(Self::FixedNodes(parse_numeric(&mut tokens)?)),
                 "movetime" => return Ok(Self::FixedTime(parse_numeric(&mut tokens)?)),
+                "mate" => return Ok(Self::Mate(parse_numeric(&mut tokens)?)),
                 "wtime" => cc.white_time = Some(parse_numeric::<i32>(&mut tokens)?.max(0) as u64),
                 "btime" => cc.black_time = Some(parse_numeric::<i32>(&mut tokens)?.max(0) as u64),
                 "winc" => cc.white_inc = Some(parse_numeric(&mut tokens)?),
@@ -0,0 +1,314 @@
+use super::{clock::Instant, time_control::TimeControl};
+use crate::chess::{
+    board::{ChessBoardState, PieceColor},
+    chess_move::Move,
+};
+
+/// Milliseconds reserved per move for the communication with the GUI unless the `Move
+/// Overhead` option asks for more or less.
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 50;
+// Root score drop between iterations which counts as a fail-low
+const FAIL_LOW_MARGIN: i32 = 30;
+// Lead over the second best root move at which the best move is considered obvious
+const OBVIOUS_MOVE_MARGIN: i32 = 200;
+// Iterations with the same best move after which the search may finish early
+const STABLE_ITERATIONS: u32 = 3;
+
+pub enum GamePhase {
+    Opening,
+    Middle,
+    Endgame,
+}
+
+impl GamePhase {
+    pub fn of(board_state: &ChessBoardState) -> Self {
+        let piece_count = board_state.total_piece_count();
+        let full_moves = board_state.full_moves;
+
+        if full_moves <= 15 && piece_count >= 28 {
+            GamePhase::Opening
+        } else if full_moves <= 40 && piece_count > 16 {
+            GamePhase::Middle
+        } else {
+            GamePhase::Endgame
+        }
+    }
+}
+
+/// Decides how long a search may run. The hard limit is checked during the search
+/// and aborts it, the soft limit is checked between iterations and adapts to how
+/// settled the best move is.
+pub struct TimeManager {
+    start: Instant,
+    // Milliseconds after which no new iteration is started
+    soft_limit: Option<u64>,
+    // Milliseconds after which the search is aborted
+    hard_limit: Option<u64>,
+    node_limit: Option<u64>,
+    soft_scale: f64,
+    best_move: Move,
+    best_score: i32,
+    stable_iterations: u32,
+}
+
+impl TimeManager {
+    /// `move_overhead` is taken off the remaining clock time, it covers the delay between
+    /// the engine sending its move and the GUI stopping the clock.
+    pub fn new(
+        time_control: &TimeControl,
+        side: PieceColor,
+        game_phase: &GamePhase,
+        move_overhead: u64,
+    ) -> Self {
+        let (soft_limit, hard_limit, node_limit) = match time_control {
+            TimeControl::Infinite | TimeControl::FixedDepth(_) | TimeControl::Mate(_) => {
+                (None, None, None)
+            }
+            TimeControl::FixedNodes(n) => (None, None, Some(*n)),
+            TimeControl::FixedTime(t) => (Some(*t), Some(*t), None),
+            TimeControl::Variable(cc) => {
+                let (time, inc) = if side == PieceColor::White {
+                    (cc.white_time.unwrap_or(0), cc.white_inc.unwrap_or(0))
+                } else {
+                    (cc.black_time.unwrap_or(0), cc.black_inc.unwrap_or(0))
+                };
+
+                let time = time - move_overhead.min(time);
+                let inc = if time < move_overhead { 0 } else { inc };
+                let max_time = 0.8 * time as f64;
+
+                let soft = if let Some(moves) = cc.movestogo {
+                    let phase_factor = match game_phase {
+                        GamePhase::Opening => 0.6,
+                        GamePhase::Middle => 0.7,
+                        GamePhase::Endgame => 0.8,
+                    };
+                    let scale = phase_factor / (moves.clamp(1, 40) as f64);
+                    (scale * time as f64).min(max_time)
+                } else {
+                    let incremental_allocation = ((time / 20) + (inc * 3 / 4)) as f64;
+                    let emergency_buffer = time as f64 * 0.02; // Reserve 2% as a safety buffer.
+                    (incremental_allocation * 0.6 - emergency_buffer).max(0.0)
+                };
+                let hard = (4.0 * soft).min(max_time).max(soft);
+
+                (Some(soft as u64), Some(hard as u64), None)
+            }
+        };
+
+        Self {
+            start: Instant::now(),
+            soft_limit,
+            hard_limit,
+            node_limit,
+            soft_scale: 1.0,
+            best_move: Move::NULL_MOVE,
+            best_score: 0,
+            stable_iterations: 0,
+        }
+    }
+
+    pub fn elapsed(&self) -> u64 {
+        Instant::now().duration_since(self.start).as_millis() as u64
+    }
+
+    /// Current soft limit in milliseconds, scaled by the search progress.
+    pub fn soft_limit(&self) -> Option<u64> {
+        let soft = (self.soft_limit? as f64 * self.soft_scale) as u64;
+        Some(soft.min(self.hard_limit.unwrap_or(u64::MAX)))
+    }
+
+    pub fn hard_limit(&self) -> Option<u64> {
+        self.hard_limit
+    }
+
+    /// Whether the search has to be aborted right away.
+    pub fn hard_limit_reached(&self, nodes_searched: usize) -> bool {
+        self.node_limit_reached(nodes_searched)
+            || self.hard_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+
+    /// Whether the node limit is used up, cheap enough to be checked on every node.
+    #[inline(always)]
+    pub fn node_limit_reached(&self, nodes_searched: usize) -> bool {
+        self.node_limit
+            .is_some_and(|nodes| nodes_searched as u64 >= nodes)
+    }
+
+    /// Whether another iteration is not worth starting.
+    pub fn soft_limit_reached(&self) -> bool {
+        self.soft_limit()
+            .is_some_and(|limit| self.elapsed() >= limit)
+    }
+
+    /// Adapts the soft limit to a completed iteration: a best move that stays the same
+    /// or is far ahead of the alternatives saves time, a dropping score asks for more.
+    pub fn update(&mut self, best_move: Move, best_score: i32, second_best_score: Option<i32>) {
+        let is_first_iteration = self.best_move == Move::NULL_MOVE;
+        if best_move == self.best_move {
+            self.stable_iterations += 1;
+        } else {
+            self.stable_iterations = 0;
+        }
+        let failed_low = !is_first_iteration && best_score < self.best_score - FAIL_LOW_MARGIN;
+        let is_obvious =
+            second_best_score.is_some_and(|second| best_score - second >= OBVIOUS_MOVE_MARGIN);
+
+        self.soft_scale = if failed_low {
+            2.0
+        } else if is_obvious && self.stable_iterations > 0 {
+            0.4
+        } else if self.stable_iterations >= STABLE_ITERATIONS {
+            0.6
+        } else if self.stable_iterations == 0 && !is_first_iteration {
+            1.3
+        } else {
+            1.0
+        };
+
+        self.best_move = best_move;
+        self.best_score = best_score;
+    }
+}
+
+#[cfg(test)]
+mod time_manager_tests {
+    use crate::{
+        chess::{
+            board::PieceColor,
+            chess_move::{Move, MoveType},
+            square::Square,
+        },
+        engine::time_control::{ClockControl, TimeControl},
+    };
+
+    use super::{GamePhase, TimeManager, DEFAULT_MOVE_OVERHEAD};
+
+    #[test]
+    fn limits_from_time_control() {
+        let tm = TimeManager::new(
+            &TimeControl::Infinite,
+            PieceColor::White,
+            &GamePhase::Opening,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        assert_eq!((tm.soft_limit(), tm.hard_limit()), (None, None));
+        assert!(!tm.hard_limit_reached(usize::MAX));
+
+        let tm = TimeManager::new(
+            &TimeControl::FixedNodes(1000),
+            PieceColor::White,
+            &GamePhase::Opening,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        assert!(!tm.hard_limit_reached(999));
+        assert!(tm.hard_limit_reached(1000));
+
+        let tm = TimeManager::new(
+            &TimeControl::FixedTime(500),
+            PieceColor::Black,
+            &GamePhase::Middle,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        assert_eq!((tm.soft_limit(), tm.hard_limit()), (Some(500), Some(500)));
+
+        let cc = ClockControl {
+            white_time: Some(60_000),
+            black_time: Some(1_000),
+            white_inc: Some(1_000),
+            black_inc: Some(1_000),
+            movestogo: None,
+        };
+        for (side, remaining) in [(PieceColor::White, 60_000), (PieceColor::Black, 1_000)] {
+            let tm = TimeManager::new(
+                &TimeControl::Variable(cc.clone()),
+                side,
+                &GamePhase::Middle,
+                DEFAULT_MOVE_OVERHEAD,
+            );
+            let (soft, hard) = (tm.soft_limit().unwrap(), tm.hard_limit().unwrap());
+            assert!(soft > 0 && soft <= hard, "{} {}", soft, hard);
+            assert!(hard < remaining, "{} {}", hard, remaining);
+        }
+
+        // The last move before the time control may use most of the clock
+        let cc = ClockControl {
+            movestogo: Some(1),
+            ..cc
+        };
+        let tm = TimeManager::new(
+            &TimeControl::Variable(cc),
+            PieceColor::White,
+            &GamePhase::Endgame,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        assert!(tm.soft_limit().unwrap() > 30_000);
+        assert!(tm.hard_limit().unwrap() < 60_000);
+    }
+
+    #[test]
+    fn move_overhead_shortens_limits() {
+        let tc = TimeControl::Variable(ClockControl {
+            white_time: Some(10_000),
+            white_inc: Some(100),
+            ..Default::default()
+        });
+        let hard_limit = |overhead| {
+            TimeManager::new(&tc, PieceColor::White, &GamePhase::Middle, overhead)
+                .hard_limit()
+                .unwrap()
+        };
+        assert!(hard_limit(2_000) < hard_limit(DEFAULT_MOVE_OVERHEAD));
+        assert!(hard_limit(0) > hard_limit(DEFAULT_MOVE_OVERHEAD));
+        // Less time left than the overhead, the move has to be played right away
+        assert_eq!(hard_limit(20_000), 0);
+    }
+
+    #[test]
+    fn soft_limit_follows_iterations() {
+        let tc = TimeControl::FixedTime(1000);
+        let mut tm = TimeManager::new(
+            &tc,
+            PieceColor::White,
+            &GamePhase::Middle,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        let d4 = Move::new(Square::D2, Square::D4, MoveType::DoublePush);
+
+        // A time limit which is also the hard limit can not be extended
+        tm.update(e4, 20, Some(10));
+        tm.update(d4, -40, Some(-50));
+        assert_eq!(tm.soft_limit(), Some(1000));
+
+        let tc = TimeControl::Variable(ClockControl {
+            white_time: Some(100_000),
+            black_time: Some(100_000),
+            ..Default::default()
+        });
+        let mut tm = TimeManager::new(
+            &tc,
+            PieceColor::White,
+            &GamePhase::Middle,
+            DEFAULT_MOVE_OVERHEAD,
+        );
+        let base = tm.soft_limit().unwrap();
+
+        tm.update(e4, 20, Some(10));
+        assert_eq!(tm.soft_limit(), Some(base));
+        for _ in 0..3 {
+            tm.update(e4, 20, Some(10));
+        }
+        assert!(
+            tm.soft_limit().unwrap() < base,
+            "stable best move saves time"
+        );
+
+        tm.update(e4, 20, Some(-300));
+        let obvious = tm.soft_limit().unwrap();
+        assert!(obvious < base, "obvious move saves time");
+
+        tm.update(d4, -40, Some(-50));
+        assert!(tm.soft_limit().unwrap() > base, "fail-low extends the time");
+    }
+}
@@ -1,6 +1,6 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::io::{self, Read, Write};
 
-use crate::chess::{board::ChessBoardState, zobrist_hash::ZHash};
+use crate::chess::{board::ChessBoardState, chess_move::Move, zobrist_hash::ZHash};
 
 use super::search::MATE_DISTANCE;
 
@@ -18,17 +18,44 @@ impl Default for NodeType {
     }
 }
 
+impl TryFrom<u8> for NodeType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Exact),
+            1 => Ok(Self::LowerBound),
+            2 => Ok(Self::UpperBound),
+            _ => Err(value),
+        }
+    }
+}
+
 #[derive(Default, Copy, Clone)]
 pub struct TranspositionEntry {
     pub zhash: ZHash,
     pub eval: i32,
     pub depth: u16,
     pub node_type: NodeType,
+    pub best_move: Move,
     age: u8,
 }
 
+/// Number of entries sharing one hash index
+pub const BUCKET_SIZE: usize = 4;
+
+// Start of a hash file, the version changes whenever the record layout or the zobrist keys do
+const HASH_FILE_MAGIC: &[u8; 4] = b"IGTT";
+const HASH_FILE_VERSION: u16 = 1;
+/// Bytes per entry in a hash file: hash, score, depth, node type and best move.
+pub const HASH_FILE_RECORD_SIZE: usize = 8 + 4 + 2 + 1 + 2;
+
+/// Hash table of searched positions. Entries are grouped into buckets of
+/// `BUCKET_SIZE`, so a position can be stored next to the ones colliding with it.
 pub struct TranspositionTable<const T: usize> {
     entries: [TranspositionEntry; T],
+    // Entries in use, at most `T`
+    len: usize,
     occupancy: usize,
     age: u8,
 }
@@ -37,6 +64,7 @@ impl<const T: usize> Default for TranspositionTable<T> {
     fn default() -> Self {
         Self {
             entries: [TranspositionEntry::default(); T],
+            len: T,
             occupancy: 0,
             age: 0,
         }
@@ -44,6 +72,21 @@ impl<const T: usize> Default for TranspositionTable<T> {
 }
 
 impl<const T: usize> TranspositionTable<T> {
+    #[inline(always)]
+    fn bucket_start(&self, hash: ZHash) -> usize {
+        (hash.0 as usize % (self.len / BUCKET_SIZE)) * BUCKET_SIZE
+    }
+
+    #[inline(always)]
+    fn find(&self, hash: ZHash) -> Option<&TranspositionEntry> {
+        let start = self.bucket_start(hash);
+        self.entries[start..start + BUCKET_SIZE]
+            .iter()
+            .find(|entry| entry.zhash == hash)
+    }
+
+    /// Looks up the position, returning a score if the stored bound settles the
+    /// window and the best move found for the position, if any.
     pub fn lookup(
         &self,
         hash: ZHash,
@@ -51,31 +94,65 @@ impl<const T: usize> TranspositionTable<T> {
         ply_from_root: u16,
         alpha: i32,
         beta: i32,
-    ) -> Option<i32> {
-        let entry = &self.entries[hash.0 as usize % T];
-        if entry.zhash == hash && entry.depth >= depth {
+    ) -> (Option<i32>, Option<Move>) {
+        let entry = match self.find(hash) {
+            Some(entry) => entry,
+            None => return (None, None),
+        };
+
+        let best_move = if entry.best_move != Move::NULL_MOVE {
+            Some(entry.best_move)
+        } else {
+            None
+        };
+
+        if entry.depth >= depth {
             let eval = Self::correct_fetched_score(entry.eval, ply_from_root);
 
             if entry.node_type == NodeType::Exact {
-                return Some(eval);
-            } else if entry.node_type == NodeType::UpperBound && entry.eval <= alpha {
-                return Some(alpha);
-            } else if entry.node_type == NodeType::LowerBound && entry.eval >= beta {
-                return Some(beta);
+                return (Some(eval), best_move);
+            } else if entry.node_type == NodeType::UpperBound && eval <= alpha {
+                return (Some(alpha), best_move);
+            } else if entry.node_type == NodeType::LowerBound && eval >= beta {
+                return (Some(beta), best_move);
             }
         }
-        None
+        (None, best_move)
+    }
+
+    /// Returns the stored entry for `hash` regardless of its depth or bound.
+    pub fn probe(&self, hash: ZHash) -> Option<&TranspositionEntry> {
+        self.find(hash)
+    }
+
+    /// Hints the CPU to load the bucket of `hash` into the cache ahead of its lookup.
+    #[inline(always)]
+    pub fn prefetch(&self, hash: ZHash) {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket = self.entries.as_ptr().add(self.bucket_start(hash));
+            _mm_prefetch::<_MM_HINT_T0>(bucket as *const i8);
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = hash;
     }
 
     pub fn capacity(&self) -> usize {
-        T
+        self.len
+    }
+
+    /// Uses only the first `entries` entries, at least one bucket and at most all `T`.
+    /// Clears the table, since entries of the old size are in the wrong buckets.
+    pub fn resize(&mut self, entries: usize) {
+        self.len = entries.clamp(BUCKET_SIZE, T) / BUCKET_SIZE * BUCKET_SIZE;
+        self.clear();
     }
 
     pub fn clear(&mut self) {
-        self.entries.iter_mut().for_each(|m| {
-            m.zhash.0 = 0;
-            m.eval = 0;
-        });
+        self.entries
+            .iter_mut()
+            .for_each(|m| *m = TranspositionEntry::default());
         self.occupancy = 0;
     }
 
@@ -83,10 +160,19 @@ impl<const T: usize> TranspositionTable<T> {
         self.occupancy
     }
 
+    /// Permill of entries written by the current search, sampled from the first
+    /// thousand entries as is customary for the UCI `hashfull` info.
     pub fn hashfull(&self) -> usize {
-        (1000 * self.occupancy) / T
+        let sample = &self.entries[..self.len.min(1000)];
+        let used = sample
+            .iter()
+            .filter(|entry| entry.zhash.0 != 0 && entry.age == self.age)
+            .count();
+        (1000 * used) / sample.len()
     }
 
+    /// Mate scores are stored relative to the node instead of the root, so they
+    /// stay correct when the position is reached at a different ply.
     pub fn correct_eval_for_storage(eval: i32, ply_from_root: u16) -> i32 {
         if eval >= MATE_DISTANCE {
             eval + ply_from_root as i32
@@ -111,6 +197,16 @@ impl<const T: usize> TranspositionTable<T> {
         }
     }
 
+    /// Lower values are replaced first: empty slots, then entries of earlier
+    /// searches, then shallow ones.
+    fn replacement_priority(entry: &TranspositionEntry, age: u8) -> i32 {
+        if entry.zhash.0 == 0 {
+            i32::MIN
+        } else {
+            entry.depth as i32 - 8 * age.wrapping_sub(entry.age) as i32
+        }
+    }
+
     pub fn add_entry(
         &mut self,
         board_state: &ChessBoardState,
@@ -118,31 +214,334 @@ impl<const T: usize> TranspositionTable<T> {
         depth: u16,
         ply_from_root: u16,
         node_type: NodeType,
-        stop: &Arc<AtomicBool>,
+        best_move: Move,
+    ) {
+        let eval = Self::correct_eval_for_storage(eval, ply_from_root);
+        self.store(
+            board_state.zhash,
+            eval,
+            depth,
+            node_type,
+            best_move,
+            self.age,
+        );
+    }
+
+    /// Whether a result of the current search is kept over a new one for the same
+    /// position: it is deeper, or as deep and exact while the new one is only a bound.
+    fn outranks(entry: &TranspositionEntry, depth: u16, node_type: NodeType, age: u8) -> bool {
+        entry.age == age
+            && (entry.depth > depth
+                || (entry.depth == depth
+                    && entry.node_type == NodeType::Exact
+                    && node_type != NodeType::Exact))
+    }
+
+    fn store(
+        &mut self,
+        zhash: ZHash,
+        eval: i32,
+        depth: u16,
+        node_type: NodeType,
+        best_move: Move,
+        age: u8,
     ) {
-        if stop.load(std::sync::atomic::Ordering::SeqCst) {
-            return;
-        }
-        let entry = &mut self.entries[board_state.zhash.0 as usize % T];
-
-        let slot_is_empty = entry.zhash.0 == 0;
-        let slot_matches = entry.zhash == board_state.zhash;
-        let slot_depth_smaller = entry.depth < depth;
-        let slot_has_different_age = entry.age != self.age;
-
-        if slot_is_empty {
-            entry.zhash = board_state.zhash;
-            entry.eval = Self::correct_eval_for_storage(eval, ply_from_root);
-            entry.depth = depth;
-            entry.age = self.age;
-            entry.node_type = node_type;
+        let start = self.bucket_start(zhash);
+        let bucket = &mut self.entries[start..start + BUCKET_SIZE];
+
+        let slot = match bucket.iter().position(|entry| entry.zhash == zhash) {
+            Some(i) if Self::outranks(&bucket[i], depth, node_type, age) => return,
+            Some(i) => i,
+            None => bucket
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| Self::replacement_priority(entry, age))
+                .map(|(i, _)| i)
+                .unwrap(),
+        };
+
+        let entry = &mut bucket[slot];
+        if entry.zhash.0 == 0 {
             self.occupancy += 1;
-        } else if slot_matches && (slot_depth_smaller || slot_has_different_age) {
-            entry.zhash = board_state.zhash;
-            entry.eval = Self::correct_eval_for_storage(eval, ply_from_root);
-            entry.depth = depth;
-            entry.age = self.age;
-            entry.node_type = node_type;
         }
+        // Fail-low nodes have no best move, keep the one of the earlier search
+        if best_move != Move::NULL_MOVE || entry.zhash != zhash {
+            entry.best_move = best_move;
+        }
+        entry.zhash = zhash;
+        entry.eval = eval;
+        entry.depth = depth;
+        entry.age = age;
+        entry.node_type = node_type;
+    }
+
+    /// Writes up to `max_entries` entries to a hash file, the deepest ones first. Returns
+    /// the number of entries written.
+    pub fn save(&self, mut writer: impl Write, max_entries: usize) -> io::Result<usize> {
+        let mut entries: Vec<&TranspositionEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.zhash.0 != 0)
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.depth));
+        entries.truncate(max_entries);
+
+        writer.write_all(HASH_FILE_MAGIC)?;
+        writer.write_all(&HASH_FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for entry in &entries {
+            writer.write_all(&entry.zhash.0.to_le_bytes())?;
+            writer.write_all(&entry.eval.to_le_bytes())?;
+            writer.write_all(&entry.depth.to_le_bytes())?;
+            writer.write_all(&[entry.node_type as u8])?;
+            writer.write_all(&entry.best_move.0.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(entries.len())
+    }
+
+    /// Adds the entries of a hash file written by `save`. They count as entries of an
+    /// earlier search, so the current search replaces them first. Returns the number of
+    /// entries read.
+    pub fn load(&mut self, mut reader: impl Read) -> io::Result<usize> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != HASH_FILE_MAGIC {
+            return Err(invalid("not a hash file"));
+        }
+        if u16::from_le_bytes([header[4], header[5]]) != HASH_FILE_VERSION {
+            return Err(invalid("unsupported hash file version"));
+        }
+        let count = u32::from_le_bytes(header[6..10].try_into().unwrap()) as usize;
+
+        let age = self.age.wrapping_sub(1);
+        let mut record = [0; HASH_FILE_RECORD_SIZE];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            let zhash = ZHash(u64::from_le_bytes(record[0..8].try_into().unwrap()));
+            let eval = i32::from_le_bytes(record[8..12].try_into().unwrap());
+            let depth = u16::from_le_bytes([record[12], record[13]]);
+            let node_type =
+                NodeType::try_from(record[14]).map_err(|_| invalid("invalid node type"))?;
+            let best_move = Move(u16::from_le_bytes([record[15], record[16]]));
+            if zhash.0 != 0 {
+                self.store(zhash, eval, depth, node_type, best_move, age);
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod transposition_table_tests {
+    use crate::{
+        chess::{board::ChessBoardState, chess_move::Move, zobrist_hash::ZHash},
+        engine::search::CHECKMATE,
+    };
+
+    use super::{NodeType, TranspositionTable, BUCKET_SIZE, HASH_FILE_RECORD_SIZE};
+
+    #[test]
+    fn mate_scores_are_ply_adjusted() {
+        let mut table = TranspositionTable::<64>::default();
+        let board_state = ChessBoardState::starting_state();
+
+        // Mate in 2 plies found at ply 3 from the root
+        table.add_entry(
+            &board_state,
+            CHECKMATE - 5,
+            4,
+            3,
+            NodeType::Exact,
+            Move::NULL_MOVE,
+        );
+        assert_eq!(
+            table
+                .lookup(board_state.zhash, 4, 3, -CHECKMATE, CHECKMATE)
+                .0,
+            Some(CHECKMATE - 5)
+        );
+        // The same position reached at ply 7 is still mate in 2 plies from there
+        assert_eq!(
+            table
+                .lookup(board_state.zhash, 4, 7, -CHECKMATE, CHECKMATE)
+                .0,
+            Some(CHECKMATE - 9)
+        );
+
+        // Being mated is adjusted the other way
+        let mut table = TranspositionTable::<64>::default();
+        table.add_entry(
+            &board_state,
+            -CHECKMATE + 6,
+            4,
+            2,
+            NodeType::UpperBound,
+            Move::NULL_MOVE,
+        );
+        assert_eq!(
+            table
+                .lookup(board_state.zhash, 4, 4, -CHECKMATE + 9, CHECKMATE)
+                .0,
+            Some(-CHECKMATE + 9)
+        );
+        assert_eq!(
+            table
+                .lookup(board_state.zhash, 4, 4, -CHECKMATE + 7, CHECKMATE)
+                .0,
+            None
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut table = TranspositionTable::<64>::default();
+        let mut board_state = ChessBoardState::starting_state();
+        let best_move = Move::try_from(("e2e4", &board_state)).unwrap();
+        for depth in 1..=3 {
+            board_state.zhash = ZHash(depth as u64);
+            table.add_entry(
+                &board_state,
+                CHECKMATE - 3,
+                depth,
+                2,
+                NodeType::LowerBound,
+                best_move,
+            );
+        }
+
+        // Only room for the two deepest entries
+        let mut file = Vec::new();
+        assert_eq!(table.save(&mut file, 2).unwrap(), 2);
+        assert_eq!(file.len(), 10 + 2 * HASH_FILE_RECORD_SIZE);
+
+        let mut loaded = TranspositionTable::<64>::default();
+        assert_eq!(loaded.load(file.as_slice()).unwrap(), 2);
+        assert_eq!(loaded.size(), 2);
+        assert_eq!(loaded.hashfull(), 0);
+        for hash in [2, 3] {
+            assert_eq!(
+                loaded.lookup(ZHash(hash), 0, 2, -CHECKMATE, CHECKMATE),
+                table.lookup(ZHash(hash), 0, 2, -CHECKMATE, CHECKMATE)
+            );
+        }
+        assert_eq!(loaded.lookup(ZHash(1), 0, 0, -100, 100), (None, None));
+
+        file[4] = 99;
+        assert!(loaded.load(file.as_slice()).is_err());
+        assert!(loaded.load(&b"IGTT"[..]).is_err());
+    }
+
+    #[test]
+    fn resize() {
+        let mut table = TranspositionTable::<64>::default();
+        table.resize(10);
+        assert_eq!(table.capacity(), 8);
+        table.resize(1000);
+        assert_eq!(table.capacity(), 64);
+        table.resize(0);
+        assert_eq!(table.capacity(), BUCKET_SIZE);
+
+        let mut board_state = ChessBoardState::starting_state();
+        for hash in 1..=8 {
+            board_state.zhash = ZHash(hash);
+            table.add_entry(&board_state, 0, 1, 0, NodeType::Exact, Move::NULL_MOVE);
+        }
+        // All hashes share the single bucket, which keeps the last ones
+        assert_eq!(table.size(), BUCKET_SIZE);
+        assert_eq!(table.lookup(ZHash(8), 1, 0, -100, 100).0, Some(0));
+    }
+
+    #[test]
+    fn best_move_is_kept_on_fail_low() {
+        let mut table = TranspositionTable::<64>::default();
+        let board_state = ChessBoardState::starting_state();
+        let best_move = Move::try_from(("e2e4", &board_state)).unwrap();
+
+        table.add_entry(&board_state, 20, 2, 0, NodeType::Exact, best_move);
+        assert_eq!(
+            table.lookup(board_state.zhash, 2, 0, -100, 100),
+            (Some(20), Some(best_move))
+        );
+
+        // A deeper fail-low search has no best move of its own
+        table.add_entry(
+            &board_state,
+            -50,
+            4,
+            0,
+            NodeType::UpperBound,
+            Move::NULL_MOVE,
+        );
+        assert_eq!(
+            table.lookup(board_state.zhash, 4, 0, 0, 100),
+            (Some(0), Some(best_move))
+        );
+        assert_eq!(
+            table.lookup(board_state.zhash, 6, 0, 0, 100),
+            (None, Some(best_move))
+        );
+    }
+
+    #[test]
+    fn exact_results_replace_bounds_of_the_same_depth() {
+        let mut table = TranspositionTable::<64>::default();
+        let board_state = ChessBoardState::starting_state();
+        let best_move = Move::try_from(("e2e4", &board_state)).unwrap();
+
+        // A re-search with a wider window settles the score of the first one
+        table.add_entry(&board_state, 30, 3, 0, NodeType::LowerBound, best_move);
+        table.add_entry(&board_state, 45, 3, 0, NodeType::Exact, best_move);
+        assert_eq!(table.lookup(board_state.zhash, 3, 0, -100, 100).0, Some(45));
+
+        // A bound doesn't displace the exact score, a shallower result nothing
+        table.add_entry(&board_state, -10, 3, 0, NodeType::UpperBound, best_move);
+        table.add_entry(&board_state, 80, 2, 0, NodeType::Exact, best_move);
+        assert_eq!(table.lookup(board_state.zhash, 3, 0, -100, 100).0, Some(45));
+    }
+
+    #[test]
+    fn colliding_positions_share_a_bucket() {
+        // Two buckets, every hash below maps to the first one
+        let mut table = TranspositionTable::<{ 2 * BUCKET_SIZE }>::default();
+        let mut board_state = ChessBoardState::starting_state();
+
+        for i in 0..BUCKET_SIZE as u64 {
+            board_state.zhash = ZHash(2 * (i + 1));
+            table.add_entry(
+                &board_state,
+                i as i32,
+                i as u16 + 1,
+                0,
+                NodeType::Exact,
+                Move::NULL_MOVE,
+            );
+        }
+        assert_eq!(table.size(), BUCKET_SIZE);
+        for i in 0..BUCKET_SIZE as u64 {
+            assert_eq!(
+                table.lookup(ZHash(2 * (i + 1)), 0, 0, -100, 100).0,
+                Some(i as i32)
+            );
+        }
+
+        // A new position evicts the shallowest entry
+        board_state.zhash = ZHash(100);
+        table.add_entry(&board_state, 42, 3, 0, NodeType::Exact, Move::NULL_MOVE);
+        assert_eq!(table.lookup(ZHash(100), 0, 0, -100, 100).0, Some(42));
+        assert_eq!(table.lookup(ZHash(2), 0, 0, -100, 100).0, None);
+        assert_eq!(table.size(), BUCKET_SIZE);
+
+        // Entries of an earlier search are replaced before deeper current ones
+        table.increment_age();
+        board_state.zhash = ZHash(4);
+        table.add_entry(&board_state, 7, 2, 0, NodeType::Exact, Move::NULL_MOVE);
+        board_state.zhash = ZHash(102);
+        table.add_entry(&board_state, 9, 1, 0, NodeType::Exact, Move::NULL_MOVE);
+        assert_eq!(table.lookup(ZHash(4), 0, 0, -100, 100).0, Some(7));
+        assert_eq!(table.lookup(ZHash(102), 0, 0, -100, 100).0, Some(9));
+        assert_eq!(table.hashfull(), 250);
     }
 }
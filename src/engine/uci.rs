@@ -4,41 +4,113 @@ use std::{
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc, Arc,
+        mpsc, Arc, Mutex,
     },
-    thread,
+    thread::{self, JoinHandle},
 };
 
-use crate::chess::{board::ChessBoardState, perft::perft};
+use crate::{
+    chess::{
+        board::{ChessBoardState, FenError},
+        chess_move::Move,
+        game::Game,
+        perft::perft_divide,
+    },
+    error::IgloError,
+    log_debug, log_warn,
+};
 
-use super::{bot::ChessBot, time_control::TimeControl};
+use super::{
+    bench::{run_bench, BENCH_DEPTH},
+    bot::ChessBot,
+    log::{self, FileSink, LogLevel, LogSink, StderrSink},
+    time_control::TimeControl,
+    uci_option::UciOption,
+};
 
 const ENGINE_NAME: &str = env!("CARGO_PKG_NAME");
 const ENGINE_AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Option handled by the reader itself, diagnostics are appended to the given file
+const LOG_FILE_OPTION: &str = "Debug Log File";
+// Answer to commands which need a board after a rejected `position`
+const NO_POSITION: &str = "no position set, the last one was rejected";
+
+// First tokens of the commands the engine understands, the UCI ones and its own extensions
+const COMMANDS: [&str; 16] = [
+    "uci",
+    "debug",
+    "isready",
+    "setoption",
+    "register",
+    "ucinewgame",
+    "position",
+    "go",
+    "stop",
+    "ponderhit",
+    "quit",
+    "perft",
+    "eval",
+    "print",
+    "zhash",
+    "bench",
+];
+
+/// Stop flag of a single search. Every `go` gets a fresh one, so a `stop` reaches the
+/// search it was sent for even when a later `go` is already read.
+#[derive(Debug, Default, Clone)]
+struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the search it belongs to has not ended yet, the controller drops its copy
+    /// once the search is answered.
+    fn is_pending(&self) -> bool {
+        Arc::strong_count(&self.0) > 1
+    }
+}
+
+impl PartialEq for StopToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.load(Ordering::SeqCst) == other.0.load(Ordering::SeqCst)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum UCICommand {
     UCI,
     Debug(bool),
     IsReady,
-    SetOption(String, String),
+    SetOption(String, Option<String>),
+    Register,
     UCINewGame,
-    Position(ChessBoardState, Vec<String>),
+    Position(Game),
     Peft(u32),
     Eval,
     Print,
-    Go(TimeControl),
+    Go(TimeControl, Vec<String>, StopToken),
     ZHash,
+    Bench(u16),
     Quit,
     Stop,
+    PonderHit,
+    // Not sent by the GUI, the reader passes it on in place of a rejected `position`
+    ClearPosition,
 }
 
 impl TryFrom<&str> for UCICommand {
-    type Error = ();
+    type Error = IgloError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut tokens = value.split_whitespace();
+        let invalid = || IgloError::InvalidCommand(value.to_string());
+        // Unknown tokens in front of a command are skipped, e.g. "joho debug on" is "debug on"
+        let mut tokens = value
+            .split_whitespace()
+            .skip_while(|token| !COMMANDS.contains(token));
 
         match tokens.next() {
             Some("uci") => Ok(UCICommand::UCI),
@@ -46,65 +118,147 @@ impl TryFrom<&str> for UCICommand {
             Some("debug") => match tokens.next() {
                 Some("on") => Ok(UCICommand::Debug(true)),
                 Some("off") => Ok(UCICommand::Debug(false)),
-                _ => Err(()),
+                _ => Err(invalid()),
             },
             Some("setoption") => {
-                let opt_name = tokens.next();
-                let opt_val = tokens.next();
-                if opt_name.is_none() || opt_val.is_none() {
-                    Err(())
-                } else {
-                    Ok(UCICommand::SetOption(
-                        opt_name.unwrap().to_string(),
-                        opt_val.unwrap().to_string(),
-                    ))
+                // setoption name <id> [value <x>], both may contain spaces
+                if tokens.next() != Some("name") {
+                    return Err(invalid());
+                }
+                let rest = tokens.collect::<Vec<&str>>();
+                let (name, value) = match rest.iter().position(|t| *t == "value") {
+                    Some(i) => (&rest[..i], Some(rest[i + 1..].join(" "))),
+                    None => (&rest[..], None),
+                };
+                if name.is_empty() {
+                    return Err(invalid());
                 }
+                Ok(UCICommand::SetOption(name.join(" "), value))
             }
+            // Nothing to register, "register later" and "register name <x> code <y>" alike
+            Some("register") => Ok(UCICommand::Register),
             Some("ucinewgame") => Ok(UCICommand::UCINewGame),
             Some("position") => {
                 let chessboard_state = match tokens.next() {
                     Some("startpos") => ChessBoardState::starting_state(),
                     Some("fen") => {
-                        let fen_str = tokens.by_ref().take(6).collect::<Vec<&str>>().join(" ");
-                        ChessBoardState::from_fen(&fen_str)?
+                        // GUIs sometimes leave out trailing fields, missing castling rights and
+                        // en passant square default to none, the move counters to 0 and 1
+                        let mut fields: Vec<&str> =
+                            tokens.by_ref().take_while(|t| *t != "moves").collect();
+                        if !(2..=6).contains(&fields.len()) {
+                            return Err(FenError::WrongFieldCount(fields.len()).into());
+                        }
+                        fields.extend(["-", "-", "0", "1"][fields.len() - 2..].iter());
+                        let chessboard_state = ChessBoardState::from_fen(&fields.join(" "))?;
+                        // take_while already consumed "moves"
+                        return parse_move_list(chessboard_state, tokens);
                     }
-                    Some(_) | None => return Err(()),
+                    Some(_) | None => return Err(invalid()),
                 };
 
-                let move_list: Vec<String> = if let Some("moves") = tokens.next() {
-                    tokens.map(|x| x.to_string()).collect()
-                } else {
-                    vec![]
-                };
-
-                Ok(UCICommand::Position(chessboard_state, move_list))
+                match tokens.next() {
+                    Some("moves") => parse_move_list(chessboard_state, tokens),
+                    None => Ok(UCICommand::Position(Game::new(chessboard_state))),
+                    Some(_) => Err(invalid()),
+                }
             }
             Some("quit") => Ok(UCICommand::Quit),
             Some("stop") => Ok(UCICommand::Stop),
+            Some("ponderhit") => Ok(UCICommand::PonderHit),
             Some("perft") => {
                 let depth = tokens.next().unwrap_or("1").parse::<u32>();
                 if let Ok(d) = depth {
                     Ok(UCICommand::Peft(d))
                 } else {
-                    Err(())
+                    Err(invalid())
                 }
             }
             Some("go") => {
-                let tc = TimeControl::from_str(&tokens.collect::<Vec<&str>>().join(" "));
+                // searchmoves lists moves until the next go parameter
+                const GO_PARAMETERS: [&str; 10] = [
+                    "infinite",
+                    "depth",
+                    "nodes",
+                    "movetime",
+                    "mate",
+                    "wtime",
+                    "btime",
+                    "winc",
+                    "binc",
+                    "movestogo",
+                ];
+                let mut limits = Vec::new();
+                let mut search_moves = Vec::new();
+                let mut in_search_moves = false;
+                for token in tokens {
+                    if token == "searchmoves" {
+                        in_search_moves = true;
+                    } else if in_search_moves && !GO_PARAMETERS.contains(&token) {
+                        search_moves.push(token.to_string());
+                    } else {
+                        in_search_moves = false;
+                        limits.push(token);
+                    }
+                }
+
+                let tc = TimeControl::from_str(&limits.join(" "));
                 if let Ok(tc) = tc {
-                    Ok(UCICommand::Go(tc))
+                    Ok(UCICommand::Go(tc, search_moves, StopToken::default()))
                 } else {
-                    Err(())
+                    Err(invalid())
                 }
             }
             Some("eval") => Ok(UCICommand::Eval),
             Some("print") => Ok(UCICommand::Print),
             Some("zhash") => Ok(UCICommand::ZHash),
-            _ => Err(()),
+            Some("bench") => match tokens.next() {
+                Some(depth) => depth
+                    .parse::<u16>()
+                    .map(UCICommand::Bench)
+                    .map_err(|_| invalid()),
+                None => Ok(UCICommand::Bench(BENCH_DEPTH)),
+            },
+            _ => Err(IgloError::UnknownCommand(value.to_string())),
         }
     }
 }
 
+/// Plays the moves of a `position` command, failing on the first one which is not legal
+/// so a GUI never gets a search on a position it did not ask for.
+fn parse_move_list<'a>(
+    start_state: ChessBoardState,
+    moves: impl Iterator<Item = &'a str>,
+) -> Result<UCICommand, IgloError> {
+    let mut game = Game::new(start_state);
+    for move_str in moves {
+        let mv = Move::try_from((move_str, game.current_state()))?;
+        game.push_move(mv)?;
+    }
+    Ok(UCICommand::Position(game))
+}
+
+/// Move to answer a `go` with, `None` when the side to move has no moves. A search result
+/// the position does not allow is a bug of the bot: debug builds stop on it, release builds
+/// report it and play the first legal move instead of sending the GUI an illegal one.
+fn arbitrate_best_move(board_state: &ChessBoardState, best_move: Move) -> Option<Move> {
+    if best_move != Move::NULL_MOVE && board_state.is_legal(best_move) {
+        return Some(best_move);
+    }
+    let fallback = board_state.legal_moves::<false>().first().copied();
+    if best_move == Move::NULL_MOVE && fallback.is_none() {
+        return None;
+    }
+
+    let error = IgloError::IllegalMove(format!("{:?}", best_move));
+    if cfg!(debug_assertions) {
+        panic!("search returned {} in {}", error, board_state.to_fen());
+    }
+    log_warn!("Search returned {} in {}", error, board_state.to_fen());
+    println!("info string error: search returned {}", error);
+    fallback
+}
+
 struct UCIController<B>
 where
     B: ChessBot,
@@ -113,94 +267,249 @@ where
 }
 
 pub struct UCIReader<B: ChessBot> {
-    stop: Arc<AtomicBool>,
+    // Tokens of the searches handed to the controller which have not ended yet
+    searches: Mutex<Vec<StopToken>>,
     controller_tx: mpsc::Sender<UCICommand>,
+    controller: Mutex<Option<JoinHandle<()>>>,
     phantom: PhantomData<B>,
 }
 
 impl<B: ChessBot> Default for UCIReader<B> {
     fn default() -> Self {
         let (tx, rx) = mpsc::channel::<UCICommand>();
-        let stop = Arc::new(AtomicBool::new(false));
-        let thread_stop = stop.clone();
-        thread::spawn(move || UCIController::<B>::run(rx, thread_stop));
+        let controller = thread::spawn(move || UCIController::<B>::run(rx));
 
         Self {
-            stop,
+            searches: Mutex::new(Vec::new()),
             controller_tx: tx,
+            controller: Mutex::new(Some(controller)),
             phantom: PhantomData,
         }
     }
 }
 
 impl<B: ChessBot> UCIReader<B> {
+    /// Applies an option as if it was sent with `setoption`, e.g. for command line flags.
+    pub fn set_option(&self, name: &str, value: Option<&str>) {
+        let command = UCICommand::SetOption(name.to_string(), value.map(str::to_string));
+        self.controller_tx.send(command).unwrap();
+    }
+
     /// Start UCI I/O loop
     pub fn run(&self) {
         println!("{ENGINE_NAME} v{ENGINE_VERSION} by {ENGINE_AUTHOR}");
 
         let stream = stdin().lock();
 
-        for line in stream.lines().map(|l| l.expect("Parsing error!")) {
+        for line in stream.lines() {
+            // A line which is not valid UTF-8 is skipped like any other bad input
+            let Ok(line) = line else {
+                log_warn!("Unreadable input line");
+                continue;
+            };
+            log_debug!("<< {}", line);
+            if line.trim().is_empty() {
+                continue;
+            }
             match UCICommand::try_from(line.as_ref()) {
                 Ok(command) => {
                     match command {
                         UCICommand::UCI => {
                             println!("id name {ENGINE_NAME} {ENGINE_VERSION}");
                             println!("id author {ENGINE_AUTHOR}");
-                            if !B::get_options().is_empty() {
-                                println!("{}", B::get_options());
+                            for option in B::options() {
+                                println!("{}", option);
                             }
+                            println!("{}", UciOption::string(LOG_FILE_OPTION, ""));
                             println!("uciok");
                         }
                         UCICommand::IsReady => {
                             println!("readyok");
                         }
-                        UCICommand::Stop => self.stop.store(true, Ordering::SeqCst), // strict ordering
-                        UCICommand::Quit => return,
+                        UCICommand::Stop => {
+                            // Ends a running search, and the wait of an infinite search
+                            // which finished early
+                            self.stop_searches();
+                            self.controller_tx.send(command).unwrap();
+                        }
+                        UCICommand::Go(_, _, ref stop) => {
+                            let mut searches = self.searches.lock().unwrap();
+                            searches.retain(StopToken::is_pending);
+                            searches.push(stop.clone());
+                            drop(searches);
+                            self.controller_tx.send(command).unwrap();
+                        }
+                        UCICommand::Debug(enabled) => {
+                            log::set_level(if enabled {
+                                LogLevel::Debug
+                            } else {
+                                LogLevel::Warn
+                            });
+                        }
+                        UCICommand::SetOption(name, Some(path))
+                            if name.eq_ignore_ascii_case(LOG_FILE_OPTION) =>
+                        {
+                            let sink = match path.as_str() {
+                                "<empty>" => Ok(Box::new(StderrSink) as Box<dyn LogSink>),
+                                path => FileSink::create(path).map(|s| Box::new(s) as _),
+                            };
+                            match sink {
+                                Ok(sink) => log::set_sink(sink),
+                                Err(e) => log_warn!("Can not open log file '{}': {}", path, e),
+                            }
+                        }
+                        // Pondering is never offered, a stray ponderhit has no search to
+                        // switch over
+                        UCICommand::Register | UCICommand::PonderHit => {}
+                        UCICommand::Quit => break,
                         _ => self.controller_tx.send(command).unwrap(),
                     }
                 }
-                Err(_e) => println!("Error parsing {line}"),
+                Err(e @ IgloError::UnknownCommand(_)) => {
+                    log_debug!("Ignoring {}", e);
+                    println!("info string ignoring {}", e);
+                }
+                Err(e) => {
+                    log_warn!("Ignoring {}", e);
+                    println!("info string error: {}", e);
+                    // The board the GUI meant is unknown, a later `go` must not search
+                    // the previous one
+                    let mut tokens = line.split_whitespace();
+                    if tokens.find(|token| COMMANDS.contains(token)) == Some("position") {
+                        self.controller_tx.send(UCICommand::ClearPosition).unwrap();
+                    }
+                }
             };
         }
+        self.quit();
+    }
+
+    /// Aborts a running search and waits until the bot has shut down, also when stdin
+    /// ends without a `quit`.
+    fn quit(&self) {
+        self.stop_searches();
+        let Some(controller) = self.controller.lock().unwrap().take() else {
+            return;
+        };
+        if self.controller_tx.send(UCICommand::Quit).is_ok() {
+            let _ = controller.join();
+        }
+    }
+
+    /// Stops every search handed over so far, also one still waiting in the queue.
+    fn stop_searches(&self) {
+        self.searches
+            .lock()
+            .unwrap()
+            .iter()
+            .for_each(StopToken::stop);
     }
 }
 
 impl<B: ChessBot> UCIController<B> {
-    fn run(rx: mpsc::Receiver<UCICommand>, stop: Arc<AtomicBool>) {
-        let mut board_state = ChessBoardState::starting_state();
+    /// Runs on its own thread and owns the bot, searches block only this thread while
+    /// the reader keeps answering `isready` and stops them through the token of their `go`.
+    /// After a rejected `position` there is no board until the next valid one.
+    fn run(rx: mpsc::Receiver<UCICommand>) {
+        let mut board_state = Some(ChessBoardState::starting_state());
         let mut chessbot = B::default();
 
         for command in &rx {
             match command {
                 UCICommand::UCINewGame => {
-                    board_state = ChessBoardState::starting_state();
+                    board_state = Some(ChessBoardState::starting_state());
+                    chessbot.new_game();
                 }
                 UCICommand::SetOption(name, value) => {
-                    chessbot.set_option(name, value);
+                    if let Err(e) = chessbot.apply_option(&name, value.as_deref()) {
+                        println!("info string {}", e);
+                    }
+                }
+                UCICommand::Position(game) => {
+                    board_state = Some(*game.current_state());
+                    chessbot.set_position(&game);
+                    if let Some(reason) = game.draw_reason() {
+                        println!("info string draw detected: {}", reason);
+                    }
                 }
-                UCICommand::Position(new_state, move_list) => {
-                    board_state = new_state;
-                    chessbot.execute_move_list(&mut board_state, &move_list);
+                UCICommand::ClearPosition => board_state = None,
+                UCICommand::Peft(_) | UCICommand::Eval | UCICommand::Print | UCICommand::ZHash
+                    if board_state.is_none() =>
+                {
+                    println!("info string {}", NO_POSITION);
                 }
                 UCICommand::Peft(depth) => {
-                    let nodes = perft(&board_state, depth);
-                    println!("Nodes searched: {}", nodes);
+                    let board_state = board_state.as_ref().unwrap();
+                    let mut nodes = 0;
+                    for (mv, count) in perft_divide(board_state, depth) {
+                        println!("{:?}: {}", mv, count);
+                        nodes += count;
+                    }
+                    println!("\nNodes searched: {}", nodes);
                 }
-                UCICommand::Go(tc) => {
-                    let best_move = chessbot.search_best_move(&mut board_state, tc, &stop);
-                    println!("bestmove {:?}", best_move);
+                UCICommand::Go(tc, search_moves, stop) => {
+                    let infinite = tc == TimeControl::Infinite;
+                    let best_move = match board_state.as_mut() {
+                        Some(board_state) => {
+                            let search_moves = search_moves
+                                .iter()
+                                .filter_map(|mv| Move::try_from((mv.as_str(), &*board_state)).ok())
+                                .collect();
+                            chessbot.set_search_moves(search_moves);
+                            let best_move = chessbot.search_best_move(board_state, tc, &stop.0);
+                            arbitrate_best_move(board_state, best_move)
+                        }
+                        None => {
+                            println!("info string {}", NO_POSITION);
+                            None
+                        }
+                    };
+                    // An infinite search may only report its move once it is told to stop
+                    let quit = infinite && Self::wait_for_stop(&rx);
+                    match best_move {
+                        Some(best_move) => println!("bestmove {:?}", best_move),
+                        None => println!("bestmove 0000"),
+                    }
+                    if quit {
+                        chessbot.quit();
+                        return;
+                    }
                 }
                 UCICommand::Eval => {
-                    println!("Static evaluation: {}", B::eval(&board_state));
+                    let board_state = board_state.as_ref().unwrap();
+                    if let Some(breakdown) = chessbot.evaluate_breakdown(board_state) {
+                        println!("{}\n", breakdown);
+                    }
+                    println!("Static evaluation: {}", chessbot.evaluate(board_state));
                 }
                 UCICommand::Print => {
-                    println!("{}", board_state.to_fen());
+                    println!("{}", board_state.unwrap().to_fen());
                 }
                 UCICommand::ZHash => {
-                    println!("Hash: {:x}", board_state.zhash.0);
+                    println!("Hash: {:x}", board_state.unwrap().zhash.0);
                 }
-                _ => eprintln!("Unexpected UCI command!"),
+                UCICommand::Bench(depth) => {
+                    // Run on its own bot, the hash table of the game stays untouched
+                    println!("{}", run_bench(&mut B::default(), depth));
+                }
+                UCICommand::Quit => {
+                    chessbot.quit();
+                    return;
+                }
+                // The search it was meant for already ended
+                UCICommand::Stop => {}
+                _ => log_warn!("Unexpected UCI command {:?}", command),
+            }
+        }
+    }
+
+    /// Blocks until the reader forwards a `stop`, true when the engine quits instead.
+    fn wait_for_stop(rx: &mpsc::Receiver<UCICommand>) -> bool {
+        loop {
+            match rx.recv() {
+                Ok(UCICommand::Stop) => return false,
+                Ok(UCICommand::Quit) | Err(_) => return true,
+                Ok(command) => log_warn!("Ignoring {:?} during an infinite search", command),
             }
         }
     }
@@ -211,10 +520,50 @@ mod uci_tests {
     use crate::chess::{
         board::ChessBoardState,
         chess_move::{Move, MoveType},
+        game::Game,
         square::Square,
     };
 
-    use super::UCICommand;
+    use super::{arbitrate_best_move, StopToken, UCICommand};
+    use crate::{engine::time_control::TimeControl, error::IgloError};
+
+    #[test]
+    fn legal_best_moves_pass() {
+        let board_state = ChessBoardState::starting_state();
+        let e4 = Move::new(Square::E2, Square::E4, MoveType::DoublePush);
+        assert_eq!(arbitrate_best_move(&board_state, e4), Some(e4));
+
+        let stalemate = ChessBoardState::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(arbitrate_best_move(&stalemate, Move::NULL_MOVE), None);
+    }
+
+    // Debug builds keep panicking so the bug is noticed during development
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic)]
+    fn illegal_best_moves_are_replaced() {
+        let board_state = ChessBoardState::starting_state();
+        let e5 = Move::new(Square::E2, Square::E5, MoveType::Silent);
+        let fallback = arbitrate_best_move(&board_state, e5).unwrap();
+        assert!(board_state.is_legal(fallback));
+        assert!(arbitrate_best_move(&board_state, Move::NULL_MOVE).is_some());
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        assert_eq!(
+            UCICommand::try_from("bogus"),
+            Err(IgloError::UnknownCommand("bogus".into()))
+        );
+        assert_eq!(
+            UCICommand::try_from("debug loud"),
+            Err(IgloError::InvalidCommand("debug loud".into()))
+        );
+        assert!(matches!(
+            UCICommand::try_from("position fen 8/8/8/8/8/8/8/8 w - - 0 1"),
+            Err(IgloError::Fen(_))
+        ));
+        assert!(UCICommand::try_from("go depth x").is_err());
+    }
 
     #[test]
     fn test_simple_commands() {
@@ -232,20 +581,103 @@ mod uci_tests {
             UCICommand::Debug(false)
         );
         assert_eq!(
-            UCICommand::try_from("setoption foo bar").unwrap(),
-            UCICommand::SetOption("foo".into(), "bar".into())
+            UCICommand::try_from("setoption name foo value bar").unwrap(),
+            UCICommand::SetOption("foo".into(), Some("bar".into()))
+        );
+        assert_eq!(
+            UCICommand::try_from("setoption name Clear Hash").unwrap(),
+            UCICommand::SetOption("Clear Hash".into(), None)
+        );
+        assert_eq!(
+            UCICommand::try_from("setoption name Book File value my book.bin").unwrap(),
+            UCICommand::SetOption("Book File".into(), Some("my book.bin".into()))
+        );
+        assert!(UCICommand::try_from("setoption foo bar").is_err());
+        assert_eq!(
+            UCICommand::try_from("bench").unwrap(),
+            UCICommand::Bench(super::BENCH_DEPTH)
         );
+        assert_eq!(
+            UCICommand::try_from("bench 4").unwrap(),
+            UCICommand::Bench(4)
+        );
+        assert!(UCICommand::try_from("bench deep").is_err());
         assert_eq!(
             UCICommand::try_from("ucinewgame").unwrap(),
             UCICommand::UCINewGame
         );
     }
 
+    #[test]
+    fn skips_unknown_tokens() {
+        assert_eq!(
+            UCICommand::try_from("joho debug on").unwrap(),
+            UCICommand::Debug(true)
+        );
+        assert_eq!(
+            UCICommand::try_from("  xboard  isready ").unwrap(),
+            UCICommand::IsReady
+        );
+        assert_eq!(
+            UCICommand::try_from("register later").unwrap(),
+            UCICommand::Register
+        );
+        assert_eq!(
+            UCICommand::try_from("register name Iglo code 1234").unwrap(),
+            UCICommand::Register
+        );
+        assert_eq!(
+            UCICommand::try_from("ponderhit").unwrap(),
+            UCICommand::PonderHit
+        );
+        assert_eq!(
+            UCICommand::try_from("copyprotection checking"),
+            Err(IgloError::UnknownCommand("copyprotection checking".into()))
+        );
+    }
+
+    #[test]
+    fn test_go() {
+        assert_eq!(
+            UCICommand::try_from("go").unwrap(),
+            UCICommand::Go(TimeControl::Infinite, Vec::new(), StopToken::default())
+        );
+        assert_eq!(
+            UCICommand::try_from("go mate 3").unwrap(),
+            UCICommand::Go(TimeControl::Mate(3), Vec::new(), StopToken::default())
+        );
+        assert_eq!(
+            UCICommand::try_from("go nodes 5000").unwrap(),
+            UCICommand::Go(
+                TimeControl::FixedNodes(5000),
+                Vec::new(),
+                StopToken::default()
+            )
+        );
+        assert_eq!(
+            UCICommand::try_from("go searchmoves e2e4 d2d4 depth 6").unwrap(),
+            UCICommand::Go(
+                TimeControl::FixedDepth(6),
+                vec!["e2e4".into(), "d2d4".into()],
+                StopToken::default()
+            )
+        );
+        assert_eq!(
+            UCICommand::try_from("go infinite searchmoves g1f3").unwrap(),
+            UCICommand::Go(
+                TimeControl::Infinite,
+                vec!["g1f3".into()],
+                StopToken::default()
+            )
+        );
+        assert!(UCICommand::try_from("go sometime 6").is_err());
+    }
+
     #[test]
     fn test_position_start() {
         assert_eq!(
             UCICommand::try_from("position startpos").unwrap(),
-            UCICommand::Position(ChessBoardState::starting_state(), Vec::new())
+            UCICommand::Position(Game::default())
         )
     }
 
@@ -254,16 +686,19 @@ mod uci_tests {
         let board_after_moves =
             UCICommand::try_from("position startpos moves c2c4 g8f6 d1a4 g7g6 g1f3 f8h6 a4a3 e8g8")
                 .unwrap();
-        let expected_board = UCICommand::Position(
-            ChessBoardState::starting_state(),
-            vec![
-                "c2c4", "g8f6", "d1a4", "g7g6", "g1f3", "f8h6", "a4a3", "e8g8",
-            ]
-            .iter()
-            .map(|s| s.to_string())
-            .collect(),
+        let UCICommand::Position(game) = board_after_moves else {
+            panic!("Expected a position command");
+        };
+        assert_eq!(game.moves().len(), 8);
+        assert_eq!(
+            game.current_state().to_fen(),
+            "rnbq1rk1/pppppp1p/5npb/8/2P5/Q4N2/PP1PPPPP/RNB1KB1R w KQ - 4 4"
         );
-        assert_eq!(board_after_moves, expected_board);
+
+        // Nothing is played if any move of the list is not legal
+        assert!(UCICommand::try_from("position startpos moves e2e4 e2e4").is_err());
+        assert!(UCICommand::try_from("position startpos moves e2e4 e7e5 xyz").is_err());
+        assert!(UCICommand::try_from("position startpos e2e4").is_err());
     }
 
     #[test]
@@ -274,14 +709,34 @@ mod uci_tests {
         .unwrap();
         assert_eq!(
             UCICommand::try_from("position fen r1bqk1nr/pp1p1ppp/2nb4/2p1p3/Q1P5/2N2P2/PP1PP1PP/R1B1KBNR w KQkq - 0 1").unwrap(),
-            UCICommand::Position(expected_state, Vec::new())
+            UCICommand::Position(Game::new(expected_state))
+        );
+
+        // Missing move counters default to 0 and 1
+        let UCICommand::Position(game) = UCICommand::try_from(
+            "position fen r1bqk1nr/pp1p1ppp/2nb4/2p1p3/Q1P5/2N2P2/PP1PP1PP/R1B1KBNR w KQkq - moves a4a3",
         )
+        .unwrap() else {
+            panic!("Expected a position command");
+        };
+        assert_eq!(game.start_state(), &expected_state);
+        assert_eq!(game.moves().len(), 1);
+        let UCICommand::Position(game) =
+            UCICommand::try_from("position fen 4k3/8/8/8/8/8/4P3/4K3 w moves e2e4").unwrap()
+        else {
+            panic!("Expected a position command");
+        };
+        assert_eq!(
+            game.current_state().to_fen(),
+            "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"
+        );
+        assert!(UCICommand::try_from("position fen 4k3/8/8/8/8/8/4P3/4K3").is_err());
     }
 
     #[test]
     fn check_move_deserialization() {
         let board =
-            ChessBoardState::from_fen("r3k2r/pPppp2p/8/2Rr1pP1/8/8/PPPPP1PP/R3K2R w KQkq f6 0 1")
+            ChessBoardState::from_fen("r3k2r/pPppp2p/8/2Rr1pP1/8/8/1PPPP1P1/R3K2R w KQkq f6 0 1")
                 .unwrap();
 
         let assert_mv = |str, src, dst, ty| {
@@ -317,5 +772,29 @@ mod uci_tests {
             MoveType::KnightCapPromotion,
         );
         assert_mv("b7a8q", Square::B7, Square::A8, MoveType::QueenCapPromotion);
+        assert_mv(
+            "b7a8N",
+            Square::B7,
+            Square::A8,
+            MoveType::KnightCapPromotion,
+        );
+        assert!(Move::try_from(("b7a8x", &board)).is_err());
+    }
+
+    #[test]
+    fn promotion_round_trips() {
+        for fen in [
+            "r3k2r/pPppp2p/8/2Rr1pP1/8/8/1PPPP1P1/R3K2R w KQkq f6 0 1",
+            "1n2k3/2P5/8/8/8/8/5p2/K3N1N1 b - - 0 1",
+        ] {
+            let board = ChessBoardState::from_fen(fen).unwrap();
+            let moves = board.generate_legal_moves_for_current_player::<false>();
+            assert!(moves.iter().filter(|mv| mv.is_promotion()).count() >= 8);
+            for mv in moves {
+                let uci = format!("{:?}", mv);
+                assert_eq!(Move::try_from((uci.as_str(), &board)), Ok(mv));
+                assert_eq!(Move::from_san(&mv.to_san(&board), &board), Ok(mv));
+            }
+        }
     }
 }
@@ -0,0 +1,198 @@
+use std::fmt::Display;
+
+/// Kind of a UCI option together with its default and allowed values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciOptionType {
+    Check { default: bool },
+    Spin { default: i64, min: i64, max: i64 },
+    Combo { default: String, vars: Vec<String> },
+    Button,
+    String { default: String },
+}
+
+/// Value sent by the GUI with `setoption`, already checked against the option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciOptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+/// An engine parameter announced to the GUI with `option name ... type ...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UciOption {
+    pub name: String,
+    pub option_type: UciOptionType,
+}
+
+impl UciOption {
+    pub fn check(name: &str, default: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            option_type: UciOptionType::Check { default },
+        }
+    }
+
+    pub fn spin(name: &str, default: i64, min: i64, max: i64) -> Self {
+        Self {
+            name: name.to_string(),
+            option_type: UciOptionType::Spin { default, min, max },
+        }
+    }
+
+    pub fn combo(name: &str, default: &str, vars: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            option_type: UciOptionType::Combo {
+                default: default.to_string(),
+                vars: vars.iter().map(|v| v.to_string()).collect(),
+            },
+        }
+    }
+
+    pub fn button(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            option_type: UciOptionType::Button,
+        }
+    }
+
+    pub fn string(name: &str, default: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            option_type: UciOptionType::String {
+                default: default.to_string(),
+            },
+        }
+    }
+
+    /// Option names are matched case insensitively as the protocol demands.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// Parses the value of a `setoption` command, spin values outside of the allowed
+    /// range and unknown combo entries are rejected.
+    pub fn parse_value(&self, value: Option<&str>) -> Result<UciOptionValue, String> {
+        let invalid = || {
+            format!(
+                "Invalid value '{}' for option '{}'",
+                value.unwrap_or_default(),
+                self.name
+            )
+        };
+        match (&self.option_type, value) {
+            (UciOptionType::Button, _) => Ok(UciOptionValue::Button),
+            (UciOptionType::Check { .. }, Some(value)) => match value {
+                "true" => Ok(UciOptionValue::Check(true)),
+                "false" => Ok(UciOptionValue::Check(false)),
+                _ => Err(invalid()),
+            },
+            (UciOptionType::Spin { min, max, .. }, Some(value)) => {
+                let value = value.parse::<i64>().map_err(|_| invalid())?;
+                if value < *min || value > *max {
+                    return Err(invalid());
+                }
+                Ok(UciOptionValue::Spin(value))
+            }
+            (UciOptionType::Combo { vars, .. }, Some(value)) => vars
+                .iter()
+                .find(|v| v.eq_ignore_ascii_case(value))
+                .map(|v| UciOptionValue::Combo(v.clone()))
+                .ok_or_else(invalid),
+            (UciOptionType::String { .. }, Some(value)) => {
+                Ok(UciOptionValue::String(value.to_string()))
+            }
+            (_, None) => Err(invalid()),
+        }
+    }
+}
+
+impl Display for UciOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.option_type {
+            UciOptionType::Check { default } => write!(f, "check default {}", default),
+            UciOptionType::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            UciOptionType::Combo { default, vars } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            UciOptionType::Button => write!(f, "button"),
+            // An empty default would leave the command without a value
+            UciOptionType::String { default } if default.is_empty() => {
+                write!(f, "string default <empty>")
+            }
+            UciOptionType::String { default } => write!(f, "string default {}", default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod uci_option_tests {
+    use super::{UciOption, UciOptionValue};
+
+    #[test]
+    fn option_declarations() {
+        assert_eq!(
+            UciOption::check("OpeningBook", true).to_string(),
+            "option name OpeningBook type check default true"
+        );
+        assert_eq!(
+            UciOption::spin("Contempt", 0, -100, 100).to_string(),
+            "option name Contempt type spin default 0 min -100 max 100"
+        );
+        assert_eq!(
+            UciOption::combo("Style", "Normal", &["Solid", "Normal", "Risky"]).to_string(),
+            "option name Style type combo default Normal var Solid var Normal var Risky"
+        );
+        assert_eq!(
+            UciOption::button("Clear Hash").to_string(),
+            "option name Clear Hash type button"
+        );
+        assert_eq!(
+            UciOption::string("Book File", "book.bin").to_string(),
+            "option name Book File type string default book.bin"
+        );
+        assert_eq!(
+            UciOption::string("Debug Log File", "").to_string(),
+            "option name Debug Log File type string default <empty>"
+        );
+    }
+
+    #[test]
+    fn option_values() {
+        let spin = UciOption::spin("Contempt", 0, -100, 100);
+        assert_eq!(spin.parse_value(Some("-20")), Ok(UciOptionValue::Spin(-20)));
+        assert!(spin.parse_value(Some("101")).is_err());
+        assert!(spin.parse_value(Some("abc")).is_err());
+        assert!(spin.parse_value(None).is_err());
+
+        let check = UciOption::check("OpeningBook", true);
+        assert_eq!(
+            check.parse_value(Some("false")),
+            Ok(UciOptionValue::Check(false))
+        );
+        assert!(check.parse_value(Some("no")).is_err());
+
+        let combo = UciOption::combo("Style", "Normal", &["Solid", "Normal", "Risky"]);
+        assert_eq!(
+            combo.parse_value(Some("risky")),
+            Ok(UciOptionValue::Combo("Risky".into()))
+        );
+        assert!(combo.parse_value(Some("Wild")).is_err());
+
+        assert_eq!(
+            UciOption::button("Clear Hash").parse_value(None),
+            Ok(UciOptionValue::Button)
+        );
+        assert!(UciOption::button("Clear Hash").matches("clear hash"));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::chess::board::FenError;
+
+/// Error of the fallible public APIs. Input from GUIs, files or users is checked
+/// against it instead of panicking, so bad input never takes the engine down.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum IgloError {
+    Fen(FenError),
+    // Text which does not describe a move at all
+    InvalidMove(String),
+    // A well-formed move which can not be played in the position
+    IllegalMove(String),
+    InvalidCommand(String),
+    // Line without any command the engine knows, GUIs get it ignored as UCI asks for
+    UnknownCommand(String),
+    // Line of an evaluation config which names no term or has no valid weight
+    InvalidEvalConfig(String),
+    // SDL failed to draw or save a board image
+    Render(String),
+}
+
+impl std::fmt::Display for IgloError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IgloError::Fen(e) => write!(f, "invalid FEN: {}", e),
+            IgloError::InvalidMove(text) => write!(f, "invalid move '{}'", text),
+            IgloError::IllegalMove(text) => write!(f, "illegal move '{}'", text),
+            IgloError::InvalidCommand(text) => write!(f, "invalid command '{}'", text),
+            IgloError::UnknownCommand(text) => write!(f, "unknown command '{}'", text),
+            IgloError::InvalidEvalConfig(text) => write!(f, "invalid eval config '{}'", text),
+            IgloError::Render(text) => write!(f, "rendering failed: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for IgloError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IgloError::Fen(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FenError> for IgloError {
+    fn from(e: FenError) -> Self {
+        IgloError::Fen(e)
+    }
+}
@@ -1,3 +1,8 @@
 #![feature(hint_must_use)]
 pub mod chess;
 pub mod engine;
+pub mod error;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "wasm")]
+pub mod wasm;
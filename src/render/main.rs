@@ -0,0 +1,56 @@
+use std::{env, path::Path, process::exit};
+
+use iglo::{
+    chess::{board::ChessBoardState, square::Square},
+    render::{save_board_png, RenderOptions},
+};
+
+const USAGE: &str = "usage: iglo-render \"FEN\" out.png [--flip] [--size <pixels per square>] \
+[--no-coordinates] [--lastmove <e2e4>] [--arrow <g1f3>]...";
+
+fn parse_squares(text: Option<String>) -> Option<(Square, Square)> {
+    let text = text?;
+    let src = Square::try_from(text.get(0..2)?).ok()?;
+    let dst = Square::try_from(text.get(2..4)?).ok()?;
+    Some((src, dst))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(1)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let fen = args.next().unwrap_or_else(|| fail("Missing FEN"));
+    let out = args.next().unwrap_or_else(|| fail("Missing output file"));
+
+    let mut options = RenderOptions::default();
+    while let Some(arg) = args.next() {
+        match &arg as &str {
+            "--flip" => options.flipped = true,
+            "--no-coordinates" => options.coordinates = false,
+            "--size" => {
+                options.square_size = args
+                    .next()
+                    .and_then(|size| size.parse().ok())
+                    .filter(|size| *size > 0)
+                    .unwrap_or_else(|| fail("Invalid square size"))
+            }
+            "--lastmove" => {
+                options.last_move =
+                    Some(parse_squares(args.next()).unwrap_or_else(|| fail("Invalid last move")))
+            }
+            "--arrow" => options
+                .arrows
+                .push(parse_squares(args.next()).unwrap_or_else(|| fail("Invalid arrow"))),
+            _ => fail(&format!("Unknown argument {}", arg)),
+        }
+    }
+
+    let board_state =
+        ChessBoardState::from_fen(&fen).unwrap_or_else(|e| fail(&format!("Invalid FEN: {}", e)));
+    if let Err(e) = save_board_png(&board_state, &options, Path::new(&out)) {
+        fail(&e.to_string());
+    }
+}
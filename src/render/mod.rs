@@ -0,0 +1,405 @@
+//! Board drawing with SDL2, shared by the UI and the headless renderer which saves
+//! positions as PNG images for docs and bug reports. Needs the `render` feature.
+
+use std::path::Path;
+
+use sdl2::{
+    image::{LoadTexture, SaveSurface},
+    pixels::{Color, PixelFormatEnum},
+    rect::{Point, Rect},
+    render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator},
+    rwops::RWops,
+    surface::Surface,
+    ttf::Font,
+};
+
+use crate::{
+    chess::{
+        board::{ChessBoardState, ChessPiece, PieceColor},
+        square::{File, Rank, Square},
+    },
+    error::IgloError,
+};
+
+/// Piece set the UI and the renderer fall back to, see `PieceSprites` for the layout.
+pub const DEFAULT_SPRITES: &[u8] = include_bytes!("../ui/sprites.png");
+/// Font the coordinates are written in unless another one is given.
+pub const DEFAULT_FONT: &[u8] = include_bytes!("../ui/font.ttf");
+
+pub const COLOR_LIGHT_SQUARE: Color = Color::RGBA(235, 236, 208, 255);
+pub const COLOR_DARK_SQUARE: Color = Color::RGBA(119, 149, 86, 255);
+pub const COLOR_LAST_MOVE: Color = Color::RGBA(246, 246, 105, 130);
+pub const COLOR_ARROW: Color = Color::RGBA(21, 120, 27, 200);
+
+const COORDINATE_MARGIN: i32 = 5;
+const COORDINATE_POINT_SIZE: u16 = 18;
+
+const ARROW_SHAFT_WIDTH: f32 = 6.0;
+const ARROW_HEAD_WIDTH: f32 = 18.0;
+const ARROW_HEAD_LENGTH: f32 = 30.0;
+
+/// Where the board is drawn: the top left corner, the edge length of a square and whether
+/// it is seen from black's side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardLayout {
+    pub x: i32,
+    pub y: i32,
+    pub square_size: u32,
+    pub flipped: bool,
+}
+
+impl BoardLayout {
+    /// Column and row of a square counted from the top left corner.
+    fn cell(&self, square: Square) -> (i32, i32) {
+        let (column, row) = (square.file() as i32, 7 - square.rank() as i32);
+        if self.flipped {
+            (7 - column, 7 - row)
+        } else {
+            (column, row)
+        }
+    }
+
+    pub fn square_rect(&self, square: Square) -> Rect {
+        let (column, row) = self.cell(square);
+        let size = self.square_size as i32;
+        Rect::new(
+            self.x + column * size,
+            self.y + row * size,
+            self.square_size,
+            self.square_size,
+        )
+    }
+
+    /// Square under a point, None outside of the board.
+    pub fn square_at(&self, x: i32, y: i32) -> Option<Square> {
+        let size = self.square_size as i32;
+        if x < self.x || y < self.y {
+            return None;
+        }
+        let (column, row) = ((x - self.x) / size, (y - self.y) / size);
+        if column > 7 || row > 7 {
+            return None;
+        }
+        let (file, rank) = if self.flipped {
+            (7 - column, row)
+        } else {
+            (column, 7 - row)
+        };
+        Square::from_file_rank(file as u8, rank as u8)
+    }
+}
+
+/// Sheet with all pieces, white in the upper row and black below, from left to right king,
+/// queen, bishop, knight, rook and pawn. The sprites are square and may have any size.
+pub struct PieceSprites<'a> {
+    pub texture: Texture<'a>,
+    pub size: u32,
+}
+
+impl<'a> PieceSprites<'a> {
+    pub fn load<T>(texture_creator: &'a TextureCreator<T>, png: &[u8]) -> Result<Self, String> {
+        let texture = texture_creator.load_texture_bytes(png)?;
+        // Six pieces side by side in each row
+        let size = texture.query().width / 6;
+        Ok(Self { texture, size })
+    }
+
+    pub fn rect(&self, piece: ChessPiece, color: PieceColor) -> Rect {
+        let row = match color {
+            PieceColor::White => 0,
+            PieceColor::Black => 1,
+        };
+        let column = match piece {
+            ChessPiece::King => 0,
+            ChessPiece::Queen => 1,
+            ChessPiece::Bishop => 2,
+            ChessPiece::Knight => 3,
+            ChessPiece::Rook => 4,
+            ChessPiece::Pawn => 5,
+        };
+        Rect::new(
+            (column * self.size) as i32,
+            (row * self.size) as i32,
+            self.size,
+            self.size,
+        )
+    }
+}
+
+pub fn draw_squares<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    layout: &BoardLayout,
+    light: Color,
+    dark: Color,
+) -> Result<(), String> {
+    for square in Square::ALL {
+        canvas.set_draw_color(if square.color() == PieceColor::White {
+            light
+        } else {
+            dark
+        });
+        canvas.fill_rect(layout.square_rect(square))?;
+    }
+    Ok(())
+}
+
+/// Writes the ranks into the left and the files into the bottom squares, each in the color
+/// of the other kind of square.
+pub fn draw_coordinates<T: RenderTarget, C>(
+    canvas: &mut Canvas<T>,
+    texture_creator: &TextureCreator<C>,
+    font: &Font,
+    layout: &BoardLayout,
+    light: Color,
+    dark: Color,
+) -> Result<(), String> {
+    let (left_file, bottom_rank) = if layout.flipped {
+        (File::H, Rank::Eighth)
+    } else {
+        (File::A, Rank::First)
+    };
+
+    for square in Square::ALL {
+        let text_color = if square.color() == PieceColor::White {
+            dark
+        } else {
+            light
+        };
+        let rect = layout.square_rect(square);
+        let mut labels = Vec::new();
+        if square.file_of() == left_file {
+            labels.push((square.rank_of().to_string(), true));
+        }
+        if square.rank_of() == bottom_rank {
+            labels.push((square.file_of().to_string(), false));
+        }
+
+        for (text, is_rank) in labels {
+            let surface = font
+                .render(&text)
+                .blended(text_color)
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            // Ranks in the upper left corner, files in the lower right one
+            let (x, y) = if is_rank {
+                (rect.x() + COORDINATE_MARGIN, rect.y() + COORDINATE_MARGIN)
+            } else {
+                (
+                    rect.right() - surface.width() as i32 - COORDINATE_MARGIN,
+                    rect.bottom() - surface.height() as i32 - COORDINATE_MARGIN,
+                )
+            };
+            canvas.copy(
+                &texture,
+                surface.rect(),
+                Rect::new(x, y, surface.width(), surface.height()),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_piece<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    sprites: &PieceSprites,
+    piece: ChessPiece,
+    color: PieceColor,
+    rect: Rect,
+) -> Result<(), String> {
+    canvas.copy(&sprites.texture, sprites.rect(piece, color), rect)
+}
+
+/// Draws every piece on the board except those on squares `skip` returns true for.
+pub fn draw_pieces<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    sprites: &PieceSprites,
+    layout: &BoardLayout,
+    board_state: &ChessBoardState,
+    skip: impl Fn(Square) -> bool,
+) -> Result<(), String> {
+    for color in [PieceColor::White, PieceColor::Black] {
+        for (square, piece) in board_state.board.pieces(color) {
+            if !skip(square) {
+                draw_piece(canvas, sprites, piece, color, layout.square_rect(square))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_arrow<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    from: Point,
+    to: Point,
+    color: Color,
+) -> Result<(), String> {
+    let (dx, dy) = ((to.x() - from.x()) as f32, (to.y() - from.y()) as f32);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length <= ARROW_HEAD_LENGTH {
+        return Ok(());
+    }
+    let (ux, uy) = (dx / length, dy / length);
+    let point_at = |along: f32, across: f32| {
+        Point::new(
+            (from.x() as f32 + ux * along - uy * across) as i32,
+            (from.y() as f32 + uy * along + ux * across) as i32,
+        )
+    };
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(color);
+
+    // Shaft and head are filled with lines perpendicular to the arrow direction
+    let shaft_length = length - ARROW_HEAD_LENGTH;
+    let mut along = 0.0;
+    while along < length {
+        let half_width = if along < shaft_length {
+            ARROW_SHAFT_WIDTH / 2.0
+        } else {
+            ARROW_HEAD_WIDTH * (length - along) / ARROW_HEAD_LENGTH
+        };
+        canvas.draw_line(point_at(along, -half_width), point_at(along, half_width))?;
+        along += 0.5;
+    }
+    Ok(())
+}
+
+/// What the headless renderer draws besides the position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub square_size: u32,
+    pub flipped: bool,
+    pub coordinates: bool,
+    /// Source and destination of the last move, which are highlighted
+    pub last_move: Option<(Square, Square)>,
+    pub arrows: Vec<(Square, Square)>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            square_size: 80,
+            flipped: false,
+            coordinates: true,
+            last_move: None,
+            arrows: Vec::new(),
+        }
+    }
+}
+
+/// Draws the position offscreen, without a window or video subsystem. Coordinates need
+/// SDL_ttf, which this initializes itself, so they can not be drawn while another ttf
+/// context is alive.
+pub fn render_board(
+    board_state: &ChessBoardState,
+    options: &RenderOptions,
+) -> Result<Surface<'static>, IgloError> {
+    draw_offscreen(board_state, options).map_err(IgloError::Render)
+}
+
+/// Renders the position and saves it as a PNG image.
+pub fn save_board_png(
+    board_state: &ChessBoardState,
+    options: &RenderOptions,
+    path: &Path,
+) -> Result<(), IgloError> {
+    render_board(board_state, options)?
+        .save(path)
+        .map_err(|e| IgloError::Render(format!("{}: {}", path.display(), e)))
+}
+
+fn draw_offscreen(
+    board_state: &ChessBoardState,
+    options: &RenderOptions,
+) -> Result<Surface<'static>, String> {
+    let size = options.square_size * 8;
+    let mut canvas = Surface::new(size, size, PixelFormatEnum::RGBA32)?.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let sprites = PieceSprites::load(&texture_creator, DEFAULT_SPRITES)?;
+    let layout = BoardLayout {
+        x: 0,
+        y: 0,
+        square_size: options.square_size,
+        flipped: options.flipped,
+    };
+
+    draw_squares(&mut canvas, &layout, COLOR_LIGHT_SQUARE, COLOR_DARK_SQUARE)?;
+    if let Some((src, dst)) = options.last_move {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(COLOR_LAST_MOVE);
+        canvas.fill_rect(layout.square_rect(src))?;
+        canvas.fill_rect(layout.square_rect(dst))?;
+    }
+    if options.coordinates {
+        let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+        let font = ttf_context
+            .load_font_from_rwops(RWops::from_bytes(DEFAULT_FONT)?, COORDINATE_POINT_SIZE)?;
+        draw_coordinates(
+            &mut canvas,
+            &texture_creator,
+            &font,
+            &layout,
+            COLOR_LIGHT_SQUARE,
+            COLOR_DARK_SQUARE,
+        )?;
+    }
+    draw_pieces(&mut canvas, &sprites, &layout, board_state, |_| false)?;
+    for (src, dst) in &options.arrows {
+        let from = layout.square_rect(*src).center();
+        let to = layout.square_rect(*dst).center();
+        draw_arrow(&mut canvas, from, to, COLOR_ARROW)?;
+    }
+
+    canvas.present();
+    Ok(canvas.into_surface())
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::BoardLayout;
+    use crate::chess::square::Square;
+
+    #[test]
+    fn layout_maps_squares_both_ways() {
+        for flipped in [false, true] {
+            let layout = BoardLayout {
+                x: 20,
+                y: 10,
+                square_size: 50,
+                flipped,
+            };
+            for square in Square::ALL {
+                let rect = layout.square_rect(square);
+                assert_eq!(layout.square_at(rect.x(), rect.y()), Some(square));
+                assert_eq!(
+                    layout.square_at(rect.right() - 1, rect.bottom() - 1),
+                    Some(square)
+                );
+            }
+            assert_eq!(layout.square_at(19, 10), None);
+            assert_eq!(layout.square_at(20, 410), None);
+        }
+
+        let a1 = Square::try_from("a1").unwrap();
+        let white = BoardLayout {
+            x: 0,
+            y: 0,
+            square_size: 10,
+            flipped: false,
+        };
+        assert_eq!(
+            (white.square_rect(a1).x(), white.square_rect(a1).y()),
+            (0, 70)
+        );
+        let black = BoardLayout {
+            flipped: true,
+            ..white
+        };
+        assert_eq!(
+            (black.square_rect(a1).x(), black.square_rect(a1).y()),
+            (70, 0)
+        );
+    }
+}
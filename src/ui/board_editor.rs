@@ -0,0 +1,91 @@
+use iglo::chess::{
+    board::{CastlingRights, ChessBoardState, ChessPiece, PieceColor},
+    square::Square,
+};
+
+#[derive(Debug)]
+pub struct BoardEditor {
+    pub board_state: ChessBoardState,
+    pub selected_piece: (ChessPiece, PieceColor),
+    // Reason the last attempt to leave the editor was rejected
+    pub last_error: Option<String>,
+}
+
+impl BoardEditor {
+    pub fn new(board_state: &ChessBoardState) -> Self {
+        Self {
+            board_state: *board_state,
+            selected_piece: (ChessPiece::Pawn, PieceColor::White),
+            last_error: None,
+        }
+    }
+
+    /// Places the selected piece on the square, clicking the same piece again removes it.
+    pub fn place_selected_piece(&mut self, square: Square) {
+        let existing = self.board_state.board.piece_on(square);
+        self.remove_piece(square);
+        if existing != Some(self.selected_piece) {
+            let (piece, color) = self.selected_piece;
+            self.board_state.board.place_piece_of_color(
+                piece,
+                color,
+                square,
+                &mut self.board_state.zhash,
+            );
+        }
+    }
+
+    pub fn remove_piece(&mut self, square: Square) {
+        if let Some((piece, color)) = self.board_state.board.piece_on(square) {
+            self.board_state.board.remove_piece_at_pos(
+                piece,
+                color,
+                square,
+                &mut self.board_state.zhash,
+            );
+        }
+        // Any edit invalidates the en passant target of the original position
+        self.board_state.en_passant_target = None;
+    }
+
+    pub fn select_piece(&mut self, piece: ChessPiece) {
+        self.selected_piece.0 = piece;
+    }
+
+    pub fn toggle_piece_color(&mut self) {
+        self.selected_piece.1 = !self.selected_piece.1;
+    }
+
+    pub fn toggle_side(&mut self) {
+        self.board_state.side = !self.board_state.side;
+        self.board_state.en_passant_target = None;
+    }
+
+    /// Toggles a single castling right given by its FEN character (`K`, `Q`, `k` or `q`).
+    pub fn toggle_castling_right(&mut self, right: char) {
+        let rights = &mut self.board_state.castling_rights;
+        match right {
+            'K' => rights.set_white_king_side(!rights.white_king_side()),
+            'Q' => rights.set_white_queen_side(!rights.white_queen_side()),
+            'k' => rights.set_black_king_side(!rights.black_king_side()),
+            'q' => rights.set_black_queen_side(!rights.black_queen_side()),
+            _ => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for index in 0..Square::NUM {
+            self.remove_piece(Square::from_index(index));
+        }
+        self.board_state.castling_rights = CastlingRights::none();
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.board_state.to_fen()
+    }
+
+    /// Validates the edited position and returns it with a freshly computed hash.
+    pub fn finish(&self) -> Result<ChessBoardState, String> {
+        ChessBoardState::from_fen(&self.to_fen()).map_err(|e| e.to_string())
+    }
+}
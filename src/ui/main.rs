@@ -1,45 +1,76 @@
+mod board_editor;
+mod theme;
+
+use board_editor::BoardEditor;
 use core::time::Duration;
 use iglo::{
     chess::{
         board::{ChessBoardState, ChessPiece, PieceColor},
         chess_move::Move,
+        game::{Game, GameClock},
         move_generator::generate_legal_moves,
-        square::Square,
+        pgn::pgn_date_today,
+        square::{File, Square},
+        zobrist_hash::ZHash,
+    },
+    engine::{
+        board_eval::{EvaluationFunction, PieceCountEvaluation, PieceSquareTableEvaluation},
+        bots::nplytranspo_bot::{NPlyTranspoBot, TABLE_ENTRY_COUNT},
+        search::{Searcher, CHECKMATE, MATE_DISTANCE},
+        time_control::{ClockControl, TimeControl},
     },
-    engine::board_eval::{EvaluationFunction, PieceCountEvaluation, PieceSquareTableEvaluation},
+    render::{self, BoardLayout, PieceSprites},
 };
 use sdl2::{
     audio::{AudioCVT, AudioCallback, AudioDevice, AudioSpecDesired, AudioSpecWAV},
     event::Event,
-    image::{self, InitFlag, LoadSurface, LoadTexture},
-    keyboard::Keycode,
+    image::{self, ImageRWops, InitFlag},
+    keyboard::{Keycode, Mod},
     mouse::MouseButton,
     pixels::Color,
     rect::{Point, Rect},
-    render::{BlendMode, Canvas, Texture, TextureCreator},
-    surface::Surface,
-    ttf::Font,
+    render::{BlendMode, Canvas, TextureCreator},
+    rwops::RWops,
+    ttf::{Font, Sdl2TtfContext},
     video::{Window, WindowContext},
     AudioSubsystem,
 };
-use std::env;
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+use theme::Theme;
 
 const SQUARE_SIZE: i32 = 100;
 const MIN_MARGIN: i32 = 20;
 const WIDTH_STATS_RIGHT: u32 = 240;
+const EVAL_BAR_WIDTH: i32 = 24;
 
-const WINDOW_WIDTH: u32 = SQUARE_SIZE as u32 * 8 + MIN_MARGIN as u32 * 2 + WIDTH_STATS_RIGHT;
+const WINDOW_WIDTH: u32 =
+    SQUARE_SIZE as u32 * 8 + MIN_MARGIN as u32 * 3 + EVAL_BAR_WIDTH as u32 + WIDTH_STATS_RIGHT;
 const WINDOW_HEIGHT: u32 = SQUARE_SIZE as u32 * 8 + MIN_MARGIN as u32 * 2;
 
-const COLOR_BLACK_FIELD: Color = Color::RGBA(119, 149, 86, 255);
-const COLOR_WHITE_FIELD: Color = Color::RGBA(235, 236, 208, 255);
 const COLOR_BACKGROUND: Color = Color::RGBA(18, 18, 18, 255);
 const COLOR_MOVEMENT_INDICATOR: Color = Color::RGBA(17, 102, 0, 153);
 const COLOR_PROMOTION_PROMPT_COLOR: Color = Color::RGBA(230, 230, 230, 200);
 const COLOR_CHECK_BACKGROUND: Color = Color::RGBA(230, 0, 0, 200);
-
-const PIECE_SPRITE_SIZE: u32 = 320;
-const DESIGNATOR_MARGIN: i32 = 5;
+const COLOR_PREMOVE_BACKGROUND: Color = Color::RGBA(20, 85, 160, 150);
+const COLOR_EVAL_BAR_WHITE: Color = Color::RGBA(240, 240, 240, 255);
+const COLOR_EVAL_BAR_BLACK: Color = Color::RGBA(60, 60, 60, 255);
+const COLOR_ANNOTATION_ARROW: Color = Color::RGBA(21, 120, 27, 200);
+const COLOR_ANNOTATION_HIGHLIGHT: Color = Color::RGBA(235, 97, 80, 160);
+// Arrows for the first moves of the analysed line, fading out with depth
+const COLOR_ANALYSIS_ARROWS: [Color; 3] = [
+    Color::RGBA(255, 170, 0, 220),
+    Color::RGBA(255, 200, 90, 170),
+    Color::RGBA(255, 225, 170, 130),
+];
 
 const PROMOTION_PROMPT_HEIGHT: i32 = 250;
 const PROMOTION_PIECE_SIZE: u32 = 120;
@@ -48,6 +79,12 @@ const CAPTURE_INDICATOR_THICKNESS: u32 = 5;
 const CAPTURE_INDICATOR_MARGIN: i32 = 3;
 const CAPTURE_INDICATOR_SIDE_LEN: u32 = SQUARE_SIZE as u32 / 5;
 
+const MOVE_ANIMATION_MS: u128 = 150;
+const FRAMES_PER_SECOND: u64 = 120;
+const DEFAULT_ENGINE_MOVE_TIME_MS: u64 = 1000;
+// How often the running clock is redrawn, often enough to show tenths of a second
+const CLOCK_REDRAW_MS: u128 = 100;
+
 pub struct EvaluationEngine;
 impl EvaluationFunction for EvaluationEngine {
     fn eval(board_state: &ChessBoardState) -> i32 {
@@ -56,21 +93,450 @@ impl EvaluationFunction for EvaluationEngine {
 }
 
 struct AssetPack<'a> {
-    sprite_texture: Texture<'a>,
+    sprites: PieceSprites<'a>,
     font: Font<'a, 'a>,
     capture_sound: AudioDevice<Sound>,
     move_sound: AudioDevice<Sound>,
+    light_square: Color,
+    dark_square: Color,
+}
+
+impl<'a> AssetPack<'a> {
+    fn load(
+        theme: &'a Theme,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        ttf_context: &'a Sdl2TtfContext,
+        audio_subsystem: &AudioSubsystem,
+    ) -> Result<Self, String> {
+        let sprites = PieceSprites::load(texture_creator, &theme.sprites)?;
+
+        let mut font = ttf_context.load_font_from_rwops(RWops::from_bytes(&theme.font)?, 18)?;
+        font.set_style(sdl2::ttf::FontStyle::BOLD);
+
+        Ok(Self {
+            sprites,
+            font,
+            capture_sound: create_audio_device_sound(&theme.capture_sound, audio_subsystem)?,
+            move_sound: create_audio_device_sound(&theme.move_sound, audio_subsystem)?,
+            light_square: theme.light_square,
+            dark_square: theme.dark_square,
+        })
+    }
 }
 
 #[derive(Default, Debug)]
 struct GameUIState {
     flipped: bool,
     moves_for_selected_piece: Vec<Move>,
-    last_clicked_square: Option<u16>,
+    last_clicked_square: Option<Square>,
     dragging_piece_pos: Option<(i32, i32)>,
     promotion_prompt: Option<(PieceColor, Vec<Move>)>,
     white_in_check: bool,
     black_in_check: bool,
+    game: Game,
+    // Ply of the game while browsing through it, None shows the live position
+    viewed_ply: Option<usize>,
+    premove: Option<(Square, Square)>,
+    // Side played by the engine, None when two humans play
+    engine_color: Option<PieceColor>,
+    // Score (white's perspective) and depth of the engine's last search
+    engine_stats: Option<(i32, u16)>,
+    animation: Option<MoveAnimation>,
+    // Latest result of the background analysis
+    analysis_line: Option<AnalysisUpdate>,
+    // Base time and increment in milliseconds, the remaining times are kept in game.clock
+    time_control: Option<(u64, u64)>,
+    // When the clock of the side to move was started, None while it is stopped
+    clock_running_since: Option<Instant>,
+    // Side that ran out of time
+    flagged: Option<PieceColor>,
+    // Arrows and highlighted squares drawn with the right mouse button
+    annotations: Vec<Annotation>,
+    // Square the right mouse button was pressed on
+    annotation_start: Option<Square>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Annotation {
+    Arrow(Square, Square),
+    Highlight(Square),
+}
+
+/// Draws the annotation of a right button drag, or removes it if it is already drawn.
+fn toggle_annotation(ui_state: &mut GameUIState, annotation: Annotation) {
+    match ui_state.annotations.iter().position(|a| *a == annotation) {
+        Some(index) => {
+            ui_state.annotations.remove(index);
+        }
+        None => ui_state.annotations.push(annotation),
+    }
+}
+
+/// Slides the pieces of a move that was just played from their old squares to the new ones
+/// and fades out the piece it captured.
+#[derive(Debug)]
+struct MoveAnimation {
+    // Piece, source and destination of every moving piece, king and rook when castling
+    sliding: Vec<(ChessPiece, PieceColor, Square, Square)>,
+    captured: Option<(ChessPiece, PieceColor, Square)>,
+    start: Instant,
+}
+
+impl MoveAnimation {
+    /// Animation of a move played in the given position.
+    fn new(board_state: &ChessBoardState, mv: Move) -> Self {
+        let (src, dst) = (mv.get_src(), mv.get_dst());
+        let mut sliding = Vec::new();
+        if let Some((piece, color)) = board_state.board.piece_on(src) {
+            sliding.push((piece, color, src, dst));
+            if piece == ChessPiece::King && src.file_distance(dst) == 2 {
+                let (rook_src, rook_dst) = if dst.file_of() == File::G {
+                    (File::H, File::F)
+                } else {
+                    (File::A, File::D)
+                };
+                sliding.push((
+                    ChessPiece::Rook,
+                    color,
+                    Square::at(rook_src, src.rank_of()),
+                    Square::at(rook_dst, src.rank_of()),
+                ));
+            }
+        }
+
+        // The pawn taken en passant stands next to the source square
+        let captured_square = if mv.is_en_passant() {
+            Square::at(dst.file_of(), src.rank_of())
+        } else {
+            dst
+        };
+        let captured = board_state
+            .board
+            .piece_on(captured_square)
+            .map(|(piece, color)| (piece, color, captured_square));
+
+        Self {
+            sliding,
+            captured,
+            start: Instant::now(),
+        }
+    }
+
+    fn progress(&self) -> f32 {
+        (self.start.elapsed().as_millis() as f32 / MOVE_ANIMATION_MS as f32).min(1.0)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.start.elapsed().as_millis() >= MOVE_ANIMATION_MS
+    }
+
+    fn hides(&self, square: Square) -> bool {
+        self.sliding.iter().any(|(_, _, _, dst)| *dst == square)
+    }
+}
+
+/// Sleeps away the rest of every frame, so the main loop runs at a steady rate no matter
+/// how long handling events and drawing took.
+struct FrameTimer {
+    frame: Duration,
+    next_frame: Instant,
+}
+
+impl FrameTimer {
+    fn new(frames_per_second: u64) -> Self {
+        Self {
+            frame: Duration::from_micros(1_000_000 / frames_per_second),
+            next_frame: Instant::now(),
+        }
+    }
+
+    fn wait(&mut self) {
+        let now = Instant::now();
+        self.next_frame += self.frame;
+        match self.next_frame.checked_duration_since(now) {
+            Some(remaining) => thread::sleep(remaining),
+            // Frames that took too long are not made up for
+            None => self.next_frame = now,
+        }
+    }
+}
+
+struct EngineRequest {
+    board_state: ChessBoardState,
+    history: Vec<ZHash>,
+    time_control: TimeControl,
+}
+
+struct EngineResult {
+    zhash: ZHash,
+    best_move: Move,
+    score: i32,
+    depth: u16,
+}
+
+struct EngineOpponent {
+    move_time: u64,
+    stop: Arc<AtomicBool>,
+    request_tx: Sender<EngineRequest>,
+    result_rx: Receiver<EngineResult>,
+    // Hash of the position the engine is currently searching
+    thinking_on: Option<ZHash>,
+}
+
+impl EngineOpponent {
+    fn spawn(move_time: u64) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<EngineRequest>();
+        let (result_tx, result_rx) = mpsc::channel::<EngineResult>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        thread::spawn(move || {
+            let mut searcher = Searcher::<TABLE_ENTRY_COUNT>::new(NPlyTranspoBot::eval);
+            for mut request in request_rx {
+                let zhash = request.board_state.zhash;
+                searcher.info.history = request.history;
+                // Stops are meant for the previous request
+                thread_stop.store(false, Ordering::SeqCst);
+                let best_move =
+                    searcher.search(&mut request.board_state, request.time_control, &thread_stop);
+                let result = EngineResult {
+                    zhash,
+                    best_move,
+                    score: searcher.info.best_score(),
+                    depth: searcher.info.completed_depth(),
+                };
+                if result_tx.send(result).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            move_time,
+            stop,
+            request_tx,
+            result_rx,
+            thinking_on: None,
+        }
+    }
+
+    /// Plays on the game clock if there is one, otherwise with a fixed time per move.
+    fn time_control(&self, ui_state: &GameUIState) -> TimeControl {
+        match ui_state.game.clock {
+            Some(clock) => TimeControl::Variable(ClockControl {
+                white_time: remaining_time(ui_state, PieceColor::White),
+                black_time: remaining_time(ui_state, PieceColor::Black),
+                white_inc: Some(clock.white_inc),
+                black_inc: Some(clock.black_inc),
+                movestogo: None,
+            }),
+            None => TimeControl::FixedTime(self.move_time),
+        }
+    }
+
+    fn request_move(&mut self, board_state: &ChessBoardState, ui_state: &GameUIState) {
+        self.thinking_on = Some(board_state.zhash);
+        self.request_tx
+            .send(EngineRequest {
+                board_state: *board_state,
+                history: position_history(board_state, ui_state, ui_state.game.moves().len()),
+                time_control: self.time_control(ui_state),
+            })
+            .expect("Engine thread died");
+    }
+}
+
+#[derive(Debug)]
+struct AnalysisUpdate {
+    zhash: ZHash,
+    // Score from the side to move's perspective
+    score: i32,
+    depth: u16,
+    pv: Vec<Move>,
+}
+
+struct Analysis {
+    stop: Arc<AtomicBool>,
+    // Hash of the position that should currently be analysed
+    latest_zhash: Arc<AtomicU64>,
+    request_tx: Sender<EngineRequest>,
+    update_rx: Receiver<AnalysisUpdate>,
+    analysing: Option<ZHash>,
+}
+
+impl Analysis {
+    fn spawn() -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<EngineRequest>();
+        let (update_tx, update_rx) = mpsc::channel::<AnalysisUpdate>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let latest_zhash = Arc::new(AtomicU64::new(0));
+        let thread_stop = stop.clone();
+        let thread_latest_zhash = latest_zhash.clone();
+
+        thread::spawn(move || {
+            let mut searcher = Searcher::<TABLE_ENTRY_COUNT>::new(NPlyTranspoBot::eval);
+            while let Ok(mut request) = request_rx.recv() {
+                // Only the most recent position is worth analysing
+                while let Ok(newer_request) = request_rx.try_recv() {
+                    request = newer_request;
+                }
+
+                let zhash = request.board_state.zhash;
+                let update_tx = update_tx.clone();
+                let latest_zhash = thread_latest_zhash.clone();
+                let stop = thread_stop.clone();
+                searcher.set_iteration_callback(Some(Box::new(move |info| {
+                    // A stop for a newer position may have raced with the start of this search
+                    if latest_zhash.load(Ordering::SeqCst) != zhash.0 {
+                        stop.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                    let _ = update_tx.send(AnalysisUpdate {
+                        zhash,
+                        score: info.best_score(),
+                        depth: info.completed_depth(),
+                        pv: info.pv().to_vec(),
+                    });
+                })));
+
+                searcher.info.history = request.history;
+                thread_stop.store(false, Ordering::SeqCst);
+                searcher.search(&mut request.board_state, request.time_control, &thread_stop);
+            }
+        });
+
+        Self {
+            stop,
+            latest_zhash,
+            request_tx,
+            update_rx,
+            analysing: None,
+        }
+    }
+
+    fn analyse(&mut self, board_state: &ChessBoardState, history: Vec<ZHash>) {
+        self.analysing = Some(board_state.zhash);
+        self.latest_zhash
+            .store(board_state.zhash.0, Ordering::SeqCst);
+        self.stop.store(true, Ordering::SeqCst);
+
+        // Searching a position without moves has nothing to report
+        if board_state
+            .generate_legal_moves_for_current_player::<false>()
+            .is_empty()
+        {
+            return;
+        }
+
+        self.request_tx
+            .send(EngineRequest {
+                board_state: *board_state,
+                history,
+                time_control: TimeControl::Infinite,
+            })
+            .expect("Analysis thread died");
+    }
+
+    fn stop(&self) {
+        self.latest_zhash.store(0, Ordering::SeqCst);
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+// Hashes of all positions up to the given ply followed by the position itself
+fn position_history(
+    board_state: &ChessBoardState,
+    ui_state: &GameUIState,
+    ply: usize,
+) -> Vec<ZHash> {
+    let mut history: Vec<ZHash> = ui_state.game.zobrist_keys().take(ply).collect();
+    history.push(board_state.zhash);
+    history
+}
+
+fn is_human_turn(board_state: &ChessBoardState, ui_state: &GameUIState) -> bool {
+    ui_state.engine_color != Some(board_state.side)
+}
+
+fn clock_time_mut(clock: &mut GameClock, color: PieceColor) -> (&mut u64, u64) {
+    match color {
+        PieceColor::White => (&mut clock.white_time, clock.white_inc),
+        PieceColor::Black => (&mut clock.black_time, clock.black_inc),
+    }
+}
+
+/// Sets both clocks to the base time and starts the one of the side to move.
+fn reset_clock(ui_state: &mut GameUIState) {
+    ui_state.game.clock = ui_state.time_control.map(|(base, inc)| GameClock {
+        white_time: base,
+        black_time: base,
+        white_inc: inc,
+        black_inc: inc,
+    });
+    ui_state.flagged = None;
+    start_clock(ui_state);
+}
+
+fn start_clock(ui_state: &mut GameUIState) {
+    if ui_state.game.clock.is_some() && ui_state.flagged.is_none() {
+        ui_state.clock_running_since = Some(Instant::now());
+    }
+}
+
+/// Stops the running clock and takes the time used from the side to move.
+fn stop_clock(ui_state: &mut GameUIState) {
+    let side = ui_state.game.current_state().side;
+    if let (Some(clock), Some(since)) = (&mut ui_state.game.clock, ui_state.clock_running_since) {
+        let (time, _) = clock_time_mut(clock, side);
+        *time = time.saturating_sub(since.elapsed().as_millis() as u64);
+    }
+    ui_state.clock_running_since = None;
+}
+
+/// Time left for the given side including the time used by the running clock, None
+/// without time control.
+fn remaining_time(ui_state: &GameUIState, color: PieceColor) -> Option<u64> {
+    let clock = ui_state.game.clock?;
+    let time = match color {
+        PieceColor::White => clock.white_time,
+        PieceColor::Black => clock.black_time,
+    };
+    match ui_state.clock_running_since {
+        Some(since) if ui_state.game.current_state().side == color => {
+            Some(time.saturating_sub(since.elapsed().as_millis() as u64))
+        }
+        _ => Some(time),
+    }
+}
+
+/// Ends the game once the side to move has no time left.
+fn check_flag_fall(ui_state: &mut GameUIState) -> bool {
+    let side = ui_state.game.current_state().side;
+    if ui_state.flagged.is_some() || remaining_time(ui_state, side) != Some(0) {
+        return false;
+    }
+
+    stop_clock(ui_state);
+    ui_state.flagged = Some(side);
+    ui_state.premove = None;
+    ui_state.promotion_prompt = None;
+    ui_state.moves_for_selected_piece.clear();
+    ui_state.game.set_result(match side {
+        PieceColor::White => "0-1",
+        PieceColor::Black => "1-0",
+    });
+    println!("{} lost on time", side.as_display_str());
+    true
+}
+
+fn format_clock(time_ms: u64) -> String {
+    let (minutes, seconds) = (time_ms / 60_000, time_ms / 1000 % 60);
+    // Tenths only matter in time trouble
+    if time_ms < 10_000 {
+        format!("{}:{:02}.{}", minutes, seconds, time_ms / 100 % 10)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
 }
 
 struct Sound {
@@ -103,54 +569,17 @@ impl AudioCallback for Sound {
     }
 }
 
-fn get_square_by_pos(x: i32, mut y: i32, ui_state: &GameUIState) -> Rect {
-    let x_orig = MIN_MARGIN;
-    let y_orig = MIN_MARGIN;
-
-    if ui_state.flipped {
-        y = 7 - y;
+fn board_layout(ui_state: &GameUIState) -> BoardLayout {
+    BoardLayout {
+        x: MIN_MARGIN,
+        y: MIN_MARGIN,
+        square_size: SQUARE_SIZE as u32,
+        flipped: ui_state.flipped,
     }
-
-    Rect::new(
-        x_orig + x * SQUARE_SIZE,
-        y_orig + y * SQUARE_SIZE,
-        SQUARE_SIZE as u32,
-        SQUARE_SIZE as u32,
-    )
 }
 
-fn get_square_by_index(index: usize, ui_state: &GameUIState) -> Rect {
-    let x = (index % 8) as i32;
-    let y = (index / 8) as i32;
-
-    get_square_by_pos(x, y, ui_state)
-}
-
-fn get_designator_rect(
-    x: i32,
-    y: i32,
-    ui_state: &GameUIState,
-    is_vertical: bool,
-    designator_size: (u32, u32),
-) -> Rect {
-    let mut rect = get_square_by_pos(x, y, ui_state);
-
-    if is_vertical {
-        rect.set_x(rect.x() + DESIGNATOR_MARGIN);
-        rect.set_y(rect.y() + DESIGNATOR_MARGIN);
-    } else {
-        rect.set_x(
-            (rect.x() + rect.width() as i32) - (designator_size.0 as i32 + DESIGNATOR_MARGIN),
-        );
-        rect.set_y(
-            (rect.y() + rect.height() as i32) - (designator_size.1 as i32 + DESIGNATOR_MARGIN),
-        );
-    }
-
-    rect.set_width(designator_size.0);
-    rect.set_height(designator_size.1);
-
-    rect
+fn get_square_by_index(square: Square, ui_state: &GameUIState) -> Rect {
+    board_layout(ui_state).square_rect(square)
 }
 
 fn draw_stats_bar(
@@ -158,46 +587,239 @@ fn draw_stats_bar(
     board_state: &ChessBoardState,
     asset_pack: &AssetPack,
     texture_creator: &TextureCreator<WindowContext>,
+    ui_state: &GameUIState,
 ) -> Result<(), String> {
     let evaluation = EvaluationEngine::eval(board_state);
 
     let enpassant_text = if let Some(target) = board_state.en_passant_target {
-        Square::designator_str_from_index(target as u16)
+        target.to_string().to_uppercase()
     } else {
         "None".to_string()
     };
 
-    let text_blocks = [
+    let (engine_score_text, engine_depth_text) = match ui_state.engine_stats {
+        Some((score, depth)) => (format_score(score), depth.to_string()),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    let (analysis_score_text, analysis_depth_text) = match analysis_for(board_state, ui_state) {
+        Some(line) => (
+            format_score(white_score(board_state, line.score)),
+            line.depth.to_string(),
+        ),
+        None => ("-".to_string(), "-".to_string()),
+    };
+
+    let check_info = board_state.board.check_info(board_state.side);
+    let mut text_blocks = vec![
         format!("Turn: {}", board_state.side.as_display_str()),
         format!("Evaluation: {}", evaluation),
         format!("Castling: {}", board_state.castling_rights.to_string()),
         format!("En Passant: {}", enpassant_text),
         format!("Fullmoves: {}", board_state.full_moves),
         format!("Halfmoves: {}", board_state.half_moves),
-        format!(
-            "King Attackers: {}",
-            board_state.board.king_attackers(board_state.side)[6].0
-        ),
+        format!("King Attackers: {}", check_info.checker_count()),
+        format!("Pinned Pieces: {}", check_info.pinned().bit_count()),
         format!(
             "Legal Move Count: {}",
             generate_legal_moves::<false>(board_state, board_state.side).len()
         ),
+        format!("Engine Score: {}", engine_score_text),
+        format!("Engine Depth: {}", engine_depth_text),
+        format!("Analysis Score: {}", analysis_score_text),
+        format!("Analysis Depth: {}", analysis_depth_text),
     ];
 
+    if ui_state.game.clock.is_some() {
+        text_blocks.push("".to_string());
+        for color in [PieceColor::White, PieceColor::Black] {
+            let time = remaining_time(ui_state, color).unwrap_or_default();
+            let paused = ui_state.clock_running_since.is_none()
+                && ui_state.flagged.is_none()
+                && ui_state.game.current_state().side == color;
+            text_blocks.push(format!(
+                "{} Clock: {}{}",
+                color.as_display_str(),
+                format_clock(time),
+                if paused { " (paused)" } else { "" }
+            ));
+        }
+        if let Some(color) = ui_state.flagged {
+            text_blocks.push(format!(
+                "{} lost on time: {}",
+                color.as_display_str(),
+                ui_state.game.result()
+            ));
+        }
+    }
+
+    draw_text_blocks(canvas, asset_pack, texture_creator, &text_blocks)
+}
+
+// Analysis result for the given position, if the analysis has caught up with it
+fn analysis_for<'a>(
+    board_state: &ChessBoardState,
+    ui_state: &'a GameUIState,
+) -> Option<&'a AnalysisUpdate> {
+    ui_state
+        .analysis_line
+        .as_ref()
+        .filter(|line| line.zhash == board_state.zhash)
+}
+
+fn white_score(board_state: &ChessBoardState, score: i32) -> i32 {
+    if board_state.side == PieceColor::White {
+        score
+    } else {
+        -score
+    }
+}
+
+fn format_score(score: i32) -> String {
+    if score.abs() >= MATE_DISTANCE {
+        // Mate scores count plies below the root move
+        let mate_in = (CHECKMATE - score.abs() + 2) / 2;
+        format!("#{}{}", if score > 0 { "" } else { "-" }, mate_in)
+    } else {
+        format!("{:+.2}", score as f32 / 100.0)
+    }
+}
+
+fn draw_eval_bar(
+    canvas: &mut Canvas<Window>,
+    board_state: &ChessBoardState,
+    ui_state: &GameUIState,
+) -> Result<(), String> {
+    let line = match analysis_for(board_state, ui_state) {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+
+    // Map the score to the expected share of white, saturating for large advantages
+    let score = white_score(board_state, line.score) as f32;
+    let white_share = 1.0 / (1.0 + 10f32.powf(-score / 400.0));
+
+    let bar_height = SQUARE_SIZE * 8;
+    let white_height = (bar_height as f32 * white_share) as i32;
+    let x = MIN_MARGIN * 2 + SQUARE_SIZE * 8;
+
+    // White's share grows from the side white is playing from
+    let (white_y, black_y) = if ui_state.flipped {
+        (MIN_MARGIN, MIN_MARGIN + white_height)
+    } else {
+        (MIN_MARGIN + bar_height - white_height, MIN_MARGIN)
+    };
+
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(COLOR_EVAL_BAR_BLACK);
+    canvas.fill_rect(Rect::new(
+        x,
+        black_y,
+        EVAL_BAR_WIDTH as u32,
+        (bar_height - white_height) as u32,
+    ))?;
+    canvas.set_draw_color(COLOR_EVAL_BAR_WHITE);
+    canvas.fill_rect(Rect::new(
+        x,
+        white_y,
+        EVAL_BAR_WIDTH as u32,
+        white_height as u32,
+    ))?;
+    Ok(())
+}
+
+fn draw_annotations(canvas: &mut Canvas<Window>, ui_state: &GameUIState) -> Result<(), String> {
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(COLOR_ANNOTATION_HIGHLIGHT);
+    for annotation in &ui_state.annotations {
+        if let Annotation::Highlight(square) = annotation {
+            canvas.fill_rect(get_square_by_index(*square, ui_state))?;
+        }
+    }
+
+    // Arrows go on top of the highlights they may start or end in
+    for annotation in &ui_state.annotations {
+        if let Annotation::Arrow(src, dst) = annotation {
+            let src = get_square_by_index(*src, ui_state).center();
+            let dst = get_square_by_index(*dst, ui_state).center();
+            render::draw_arrow(canvas, src, dst, COLOR_ANNOTATION_ARROW)?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_analysis_arrows(
+    canvas: &mut Canvas<Window>,
+    board_state: &ChessBoardState,
+    ui_state: &GameUIState,
+) -> Result<(), String> {
+    let line = match analysis_for(board_state, ui_state) {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+
+    for (mv, color) in line.pv.iter().zip(COLOR_ANALYSIS_ARROWS) {
+        let src = get_square_by_index(mv.get_src(), ui_state).center();
+        let dst = get_square_by_index(mv.get_dst(), ui_state).center();
+        render::draw_arrow(canvas, src, dst, color)?;
+    }
+    Ok(())
+}
+
+fn draw_editor_bar(
+    canvas: &mut Canvas<Window>,
+    asset_pack: &AssetPack,
+    texture_creator: &TextureCreator<WindowContext>,
+    board_editor: &BoardEditor,
+) -> Result<(), String> {
+    let (piece, color) = board_editor.selected_piece;
+    let text_blocks = [
+        "Setup Mode".to_string(),
+        format!("Piece: {} {:?}", color.as_display_str(), piece),
+        format!("Turn: {}", board_editor.board_state.side.as_display_str()),
+        format!(
+            "Castling: {}",
+            board_editor.board_state.castling_rights.to_string()
+        ),
+        board_editor.last_error.clone().unwrap_or_default(),
+        "".to_string(),
+        "P N B R Q K: Piece".to_string(),
+        "Tab: Piece Color".to_string(),
+        "S: Side To Move".to_string(),
+        "1 2 3 4: Castling".to_string(),
+        "X: Clear Board".to_string(),
+        "C: Copy FEN".to_string(),
+        "E: Start Game".to_string(),
+    ];
+
+    draw_text_blocks(canvas, asset_pack, texture_creator, &text_blocks)
+}
+
+fn draw_text_blocks(
+    canvas: &mut Canvas<Window>,
+    asset_pack: &AssetPack,
+    texture_creator: &TextureCreator<WindowContext>,
+    text_blocks: &[String],
+) -> Result<(), String> {
     let mut y_offset = 0;
 
-    for text_block in &text_blocks {
+    for text_block in text_blocks {
+        // Empty lines can not be rendered but still take up space
+        if text_block.is_empty() {
+            y_offset += asset_pack.font.height() + 5;
+            continue;
+        }
+
         let surface = asset_pack
             .font
-            .render(&text_block)
-            .blended(COLOR_WHITE_FIELD)
+            .render(text_block)
+            .blended(asset_pack.light_square)
             .map_err(|e| e.to_string())?;
         let texture = texture_creator
             .create_texture_from_surface(&surface)
             .map_err(|e| e.to_string())?;
 
         let stats_rect = Rect::new(
-            MIN_MARGIN * 2 + SQUARE_SIZE * 8,
+            MIN_MARGIN * 3 + SQUARE_SIZE * 8 + EVAL_BAR_WIDTH,
             MIN_MARGIN + y_offset,
             surface.width(),
             surface.height(),
@@ -218,78 +840,17 @@ fn draw_grid(
     canvas.set_draw_color(COLOR_BACKGROUND);
     canvas.clear();
 
-    let draw_designator = |canvas: &mut Canvas<Window>,
-                           x: i32,
-                           y: i32,
-                           text: &str,
-                           is_vertical,
-                           designator_color: Color|
-     -> Result<(), String> {
-        let surface = asset_pack
-            .font
-            .render(text)
-            .blended(designator_color)
-            .unwrap();
-        let texture = texture_creator
-            .create_texture_from_surface(&surface)
-            .unwrap();
-        canvas.copy(
-            &texture,
-            surface.rect(),
-            get_designator_rect(x, y, ui_state, is_vertical, surface.size()),
-        )?;
-        Ok(())
-    };
-
-    for x in 0..8 {
-        for y in 0..8 {
-            let (field_color, designator_color) = if (x + y) % 2 == 0 {
-                (COLOR_WHITE_FIELD, COLOR_BLACK_FIELD)
-            } else {
-                (COLOR_BLACK_FIELD, COLOR_WHITE_FIELD)
-            };
-
-            canvas.set_draw_color(field_color);
-            canvas.fill_rect(get_square_by_pos(x, y, ui_state))?;
-
-            if x == 0 {
-                draw_designator(
-                    canvas,
-                    x,
-                    y,
-                    &(7 - y + 1).to_string(),
-                    true,
-                    designator_color,
-                )?;
-            }
-
-            if y == 7 {
-                let char_designator = char::from_u32(x as u32 + 'a' as u32).unwrap().to_string();
-                draw_designator(canvas, x, y, &char_designator, false, designator_color)?;
-            }
-        }
-    }
-    Ok(())
-}
-
-fn get_sprite_rect(piece: &ChessPiece, color: &PieceColor) -> Rect {
-    let y = if *color == PieceColor::White {
-        0
-    } else {
-        PIECE_SPRITE_SIZE
-    };
-
-    let x = PIECE_SPRITE_SIZE
-        * match piece {
-            ChessPiece::King => 0,
-            ChessPiece::Queen => 1,
-            ChessPiece::Bishop => 2,
-            ChessPiece::Knight => 3,
-            ChessPiece::Rook => 4,
-            ChessPiece::Pawn => 5,
-        };
-
-    Rect::new(x as i32, y as i32, PIECE_SPRITE_SIZE, PIECE_SPRITE_SIZE)
+    let layout = board_layout(ui_state);
+    let (light, dark) = (asset_pack.light_square, asset_pack.dark_square);
+    render::draw_squares(canvas, &layout, light, dark)?;
+    render::draw_coordinates(
+        canvas,
+        texture_creator,
+        &asset_pack.font,
+        &layout,
+        light,
+        dark,
+    )
 }
 
 fn draw_piece_at_location(
@@ -299,11 +860,7 @@ fn draw_piece_at_location(
     color: PieceColor,
     rct: Rect,
 ) -> Result<(), String> {
-    canvas.copy(
-        &asset_pack.sprite_texture,
-        get_sprite_rect(&piece, &color),
-        rct,
-    )
+    render::draw_piece(canvas, &asset_pack.sprites, piece, color, rct)
 }
 
 fn draw_chess_board(
@@ -312,37 +869,30 @@ fn draw_chess_board(
     asset_pack: &AssetPack,
     ui_state: &GameUIState,
 ) -> Result<(), String> {
-    let piece_boards = [
-        (&board_state.board.white_pieces, PieceColor::White),
-        (&board_state.board.black_pieces, PieceColor::Black),
-    ];
-
-    for (bitboard_array, piece_color) in &piece_boards {
-        for (piece_index, bitboard) in bitboard_array.iter().enumerate() {
-            let piece = ChessPiece::from(piece_index);
-            for i in 0..64 {
-                if bitboard.get_bit(i) {
-                    let dst_rct = get_square_by_index(i, ui_state);
-
-                    // Do not draw dragged piece
-                    if ui_state.dragging_piece_pos.is_some()
-                        && ui_state.last_clicked_square == Some(i as u16)
-                    {
-                        continue;
-                    }
-
-                    if piece == ChessPiece::King
-                        && ((*piece_color == PieceColor::White && ui_state.white_in_check)
-                            || (*piece_color == PieceColor::Black && ui_state.black_in_check))
-                    {
-                        canvas.set_blend_mode(BlendMode::Blend);
-                        canvas.set_draw_color(COLOR_CHECK_BACKGROUND);
-                        canvas.fill_rect(dst_rct)?;
-                    }
-
-                    draw_piece_at_location(canvas, asset_pack, piece, *piece_color, dst_rct)?;
+    for piece_color in [PieceColor::White, PieceColor::Black] {
+        for (square, piece) in board_state.board.pieces(piece_color) {
+            // Do not draw dragged or animated piece
+            if ui_state.dragging_piece_pos.is_some() && ui_state.last_clicked_square == Some(square)
+            {
+                continue;
+            }
+            if let Some(animation) = current_animation(ui_state) {
+                if animation.hides(square) {
+                    continue;
                 }
             }
+
+            let dst_rct = get_square_by_index(square, ui_state);
+            if piece == ChessPiece::King
+                && ((piece_color == PieceColor::White && ui_state.white_in_check)
+                    || (piece_color == PieceColor::Black && ui_state.black_in_check))
+            {
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(COLOR_CHECK_BACKGROUND);
+                canvas.fill_rect(dst_rct)?;
+            }
+
+            draw_piece_at_location(canvas, asset_pack, piece, piece_color, dst_rct)?;
         }
     }
     Ok(())
@@ -355,7 +905,7 @@ fn draw_single_move_indicator(
 ) -> Result<(), String> {
     canvas.set_draw_color(COLOR_MOVEMENT_INDICATOR);
 
-    let mut rect = get_square_by_index(piece_move.get_dst() as usize, ui_state);
+    let mut rect = get_square_by_index(piece_move.get_dst(), ui_state);
 
     let (x, y, w, h, s, m, l, t) = (
         rect.x,
@@ -396,6 +946,19 @@ fn draw_single_move_indicator(
     Ok(())
 }
 
+fn draw_premove_indicator(
+    canvas: &mut Canvas<Window>,
+    ui_state: &GameUIState,
+) -> Result<(), String> {
+    if let Some((src, dst)) = ui_state.premove {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(COLOR_PREMOVE_BACKGROUND);
+        canvas.fill_rect(get_square_by_index(src, ui_state))?;
+        canvas.fill_rect(get_square_by_index(dst, ui_state))?;
+    }
+    Ok(())
+}
+
 fn draw_moves_indicator(canvas: &mut Canvas<Window>, ui_state: &GameUIState) -> Result<(), String> {
     for piece_move in &ui_state.moves_for_selected_piece {
         draw_single_move_indicator(canvas, *piece_move, ui_state)?;
@@ -415,10 +978,7 @@ fn draw_dragged_piece(
     let last_clicked_pos = ui_state.last_clicked_square.unwrap();
     let cursor_pos = ui_state.dragging_piece_pos.unwrap();
 
-    if let Some((piece, piece_col)) = board_state
-        .board
-        .get_piece_at_pos(last_clicked_pos as usize)
-    {
+    if let Some((piece, piece_col)) = board_state.board.piece_on(last_clicked_pos) {
         let dst_rect = Rect::new(
             cursor_pos.0 - SQUARE_SIZE / 2,
             cursor_pos.1 - SQUARE_SIZE / 2,
@@ -431,6 +991,51 @@ fn draw_dragged_piece(
     Ok(())
 }
 
+/// Animation of the last move, unless an earlier position of the game is shown.
+fn current_animation(ui_state: &GameUIState) -> Option<&MoveAnimation> {
+    ui_state
+        .animation
+        .as_ref()
+        .filter(|_| ui_state.viewed_ply.is_none())
+}
+
+fn draw_animated_pieces(
+    canvas: &mut Canvas<Window>,
+    asset_pack: &mut AssetPack,
+    ui_state: &GameUIState,
+) -> Result<(), String> {
+    let animation = match current_animation(ui_state) {
+        Some(animation) => animation,
+        None => return Ok(()),
+    };
+    let progress = animation.progress();
+
+    if let Some((piece, piece_col, square)) = animation.captured {
+        let rct = get_square_by_index(square, ui_state);
+        asset_pack
+            .sprites
+            .texture
+            .set_alpha_mod(((1.0 - progress) * 255.0) as u8);
+        let result = draw_piece_at_location(canvas, asset_pack, piece, piece_col, rct);
+        asset_pack.sprites.texture.set_alpha_mod(255);
+        result?;
+    }
+
+    let interpolate = |from: i32, to: i32| from + ((to - from) as f32 * progress) as i32;
+    for &(piece, piece_col, src, dst) in &animation.sliding {
+        let src_rect = get_square_by_index(src, ui_state);
+        let dst_rect = get_square_by_index(dst, ui_state);
+        let rct = Rect::new(
+            interpolate(src_rect.x(), dst_rect.x()),
+            interpolate(src_rect.y(), dst_rect.y()),
+            SQUARE_SIZE as u32,
+            SQUARE_SIZE as u32,
+        );
+        draw_piece_at_location(canvas, asset_pack, piece, piece_col, rct)?;
+    }
+    Ok(())
+}
+
 fn promotion_prompt_rects() -> [(Rect, ChessPiece); 4] {
     let x = MIN_MARGIN + 4 * SQUARE_SIZE - 2 * (PROMOTION_PIECE_SIZE as i32 + MIN_MARGIN);
     let y = MIN_MARGIN + (SQUARE_SIZE * 4) - (PROMOTION_PIECE_SIZE as i32 / 2);
@@ -485,29 +1090,77 @@ fn draw_promotion_prompt(
     Ok(())
 }
 
-fn get_square_from_cursor_pos(x: i32, y: i32, ui_state: &GameUIState) -> Option<u16> {
-    let x = (x - MIN_MARGIN) / SQUARE_SIZE;
-    if x < 0 || x > 7 {
-        return None;
-    }
+fn get_square_from_cursor_pos(x: i32, y: i32, ui_state: &GameUIState) -> Option<Square> {
+    board_layout(ui_state).square_at(x, y)
+}
 
-    let mut y = (y - MIN_MARGIN) / SQUARE_SIZE;
-    if y < 0 || y > 7 {
-        return None;
+fn update_check_indicators(board_state: &ChessBoardState, ui_state: &mut GameUIState) {
+    // Only the side to move can be in check
+    ui_state.black_in_check = board_state.side == PieceColor::Black && board_state.is_in_check();
+    ui_state.white_in_check = board_state.side == PieceColor::White && board_state.is_in_check();
+}
+
+fn play_move(
+    board_state: &mut ChessBoardState,
+    ui_state: &mut GameUIState,
+    asset_pack: &mut AssetPack,
+    move_to_play: Move,
+) {
+    println!("{}", move_to_play.to_annotated_string(board_state));
+    stop_clock(ui_state);
+    if let Err(e) = ui_state.game.push_move(move_to_play) {
+        eprintln!("{}", e);
+        start_clock(ui_state);
+        return;
+    }
+    if let Some(clock) = &mut ui_state.game.clock {
+        let (time, inc) = clock_time_mut(clock, board_state.side);
+        *time += inc;
     }
-    if ui_state.flipped {
-        y = 7 - y;
+    start_clock(ui_state);
+    // A move played while the last one is still animated cuts it short
+    ui_state.animation = Some(MoveAnimation::new(board_state, move_to_play));
+    ui_state.annotations.clear();
+    *board_state = *ui_state.game.current_state();
+
+    if move_to_play.is_capture() {
+        play_sound(&mut asset_pack.capture_sound);
+    } else {
+        play_sound(&mut asset_pack.move_sound);
     }
+    println!("{:x}", board_state.zhash.0);
+
+    update_check_indicators(board_state, ui_state);
+}
 
-    Some(x as u16 + y as u16 * 8)
+fn try_execute_premove(
+    board_state: &mut ChessBoardState,
+    ui_state: &mut GameUIState,
+    asset_pack: &mut AssetPack,
+) {
+    let (src, dst) = match ui_state.premove.take() {
+        Some(premove) => premove,
+        None => return,
+    };
+
+    // Premoves onto the promotion rank always promote to a queen
+    let premove = board_state
+        .moves_from(src)
+        .into_iter()
+        .filter(|mv| mv.get_dst() == dst)
+        .max_by_key(|mv| mv.is_promotion() && mv.promotion_target() == ChessPiece::Queen);
+
+    if let Some(mv) = premove {
+        play_move(board_state, ui_state, asset_pack, mv);
+    }
 }
 
 fn execute_move_with_src_and_dst(
     board_state: &mut ChessBoardState,
     ui_state: &mut GameUIState,
     asset_pack: &mut AssetPack,
-    src: u16,
-    dst: u16,
+    src: Square,
+    dst: Square,
 ) {
     let moves: Vec<Move> = ui_state
         .moves_for_selected_piece
@@ -517,34 +1170,135 @@ fn execute_move_with_src_and_dst(
         .collect();
 
     if moves.is_empty() {
-    } else if moves.len() == 1 {
-        let move_to_play = moves[0];
-        println!("{:?}", &move_to_play);
-        *board_state = board_state.exec_move(move_to_play);
-
-        if move_to_play.is_capture() {
-            play_sound(&mut asset_pack.capture_sound);
-        } else {
-            play_sound(&mut asset_pack.move_sound);
+        // Moving a piece of the side not to move queues it as premove
+        match board_state.board.piece_on(src) {
+            Some((_, color))
+                if color != board_state.side
+                    && ui_state.engine_color != Some(color)
+                    && src != dst =>
+            {
+                ui_state.premove = Some((src, dst));
+            }
+            _ => {}
         }
-        println!("{:x}", board_state.zhash.0);
+    } else if moves.len() == 1 {
+        play_move(board_state, ui_state, asset_pack, moves[0]);
+        try_execute_premove(board_state, ui_state, asset_pack);
     } else {
+        // Picking the piece is not counted as thinking time
+        stop_clock(ui_state);
         ui_state.promotion_prompt = Some((board_state.side, moves))
     }
 
-    ui_state.black_in_check = !board_state.board.king_attackers(PieceColor::Black)[6].is_empty();
-    ui_state.white_in_check = !board_state.board.king_attackers(PieceColor::White)[6].is_empty();
+    ui_state.last_clicked_square = None;
+    ui_state.moves_for_selected_piece.clear();
+}
 
+fn undo_move(board_state: &mut ChessBoardState, ui_state: &mut GameUIState) {
+    // Taking back moves does not give back the time used for them
+    stop_clock(ui_state);
+    if ui_state.game.undo().is_some() {
+        *board_state = *ui_state.game.current_state();
+    }
+    ui_state.flagged = None;
+    start_clock(ui_state);
+    ui_state.viewed_ply = None;
+    ui_state.premove = None;
+    ui_state.animation = None;
+    ui_state.promotion_prompt = None;
     ui_state.last_clicked_square = None;
+    ui_state.dragging_piece_pos = None;
     ui_state.moves_for_selected_piece.clear();
+    update_check_indicators(board_state, ui_state);
 }
 
-fn generate_possible_moves_for_piece(board_state: &ChessBoardState, pos: u16) -> Vec<Move> {
-    generate_legal_moves::<false>(board_state, board_state.side)
-        .iter()
-        .filter(|mv| mv.get_src() == pos)
-        .map(|&x| x)
-        .collect()
+fn navigate_history(ui_state: &mut GameUIState, keycode: Keycode) {
+    let history_len = ui_state.game.moves().len();
+    if history_len == 0 {
+        return;
+    }
+
+    ui_state.viewed_ply = match (keycode, ui_state.viewed_ply) {
+        (Keycode::Left, None) => Some(history_len - 1),
+        (Keycode::Left, Some(ply)) => Some(ply.saturating_sub(1)),
+        (Keycode::Right, Some(ply)) if ply + 1 < history_len => Some(ply + 1),
+        (Keycode::Right, _) => None,
+        (Keycode::Up, _) => Some(0),
+        (Keycode::Down, _) => None,
+        (_, viewed_ply) => viewed_ply,
+    };
+}
+
+fn displayed_board_state<'a>(
+    board_state: &'a ChessBoardState,
+    ui_state: &'a GameUIState,
+) -> &'a ChessBoardState {
+    match ui_state.viewed_ply {
+        Some(ply) => ui_state.game.state_at(ply).unwrap_or(board_state),
+        None => board_state,
+    }
+}
+
+fn start_from_position(
+    board_state: &mut ChessBoardState,
+    ui_state: &mut GameUIState,
+    new_state: ChessBoardState,
+) {
+    *board_state = new_state;
+    ui_state.game = Game::new(new_state);
+    reset_clock(ui_state);
+    ui_state.viewed_ply = None;
+    ui_state.premove = None;
+    ui_state.engine_stats = None;
+    ui_state.animation = None;
+    ui_state.annotations.clear();
+    update_check_indicators(board_state, ui_state);
+}
+
+fn is_ctrl_pressed(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+}
+
+fn save_game_as_pgn(ui_state: &GameUIState) -> Result<String, String> {
+    let (white, black) = match ui_state.engine_color {
+        Some(PieceColor::White) => ("Iglo", "Human"),
+        Some(PieceColor::Black) => ("Human", "Iglo"),
+        None => ("Human", "Human"),
+    };
+    let tags = [
+        ("Event", "Iglo UI game".to_string()),
+        ("Date", pgn_date_today()),
+        ("White", white.to_string()),
+        ("Black", black.to_string()),
+    ];
+    let pgn = ui_state.game.to_pgn(&tags);
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = format!("iglo_game_{}.pgn", timestamp);
+    std::fs::write(&path, pgn).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn handle_editor_key(board_editor: &mut BoardEditor, keycode: Keycode) {
+    match keycode {
+        Keycode::P => board_editor.select_piece(ChessPiece::Pawn),
+        Keycode::N => board_editor.select_piece(ChessPiece::Knight),
+        Keycode::B => board_editor.select_piece(ChessPiece::Bishop),
+        Keycode::R => board_editor.select_piece(ChessPiece::Rook),
+        Keycode::Q => board_editor.select_piece(ChessPiece::Queen),
+        Keycode::K => board_editor.select_piece(ChessPiece::King),
+        Keycode::Tab => board_editor.toggle_piece_color(),
+        Keycode::S => board_editor.toggle_side(),
+        Keycode::Num1 => board_editor.toggle_castling_right('K'),
+        Keycode::Num2 => board_editor.toggle_castling_right('Q'),
+        Keycode::Num3 => board_editor.toggle_castling_right('k'),
+        Keycode::Num4 => board_editor.toggle_castling_right('q'),
+        Keycode::X => board_editor.clear(),
+        _ => {}
+    }
 }
 
 fn play_sound(audio_device: &mut AudioDevice<Sound>) {
@@ -555,48 +1309,85 @@ fn play_sound(audio_device: &mut AudioDevice<Sound>) {
     audio_device.resume()
 }
 
-fn create_audio_device_sound(path: &str, audio_subsystem: &AudioSubsystem) -> AudioDevice<Sound> {
+fn create_audio_device_sound(
+    wav: &[u8],
+    audio_subsystem: &AudioSubsystem,
+) -> Result<AudioDevice<Sound>, String> {
     let audio_spec = AudioSpecDesired {
         freq: Some(48_000),
         channels: Some(1), // mono
         samples: None,     // default
     };
-    let sound_wav = AudioSpecWAV::load_wav(path).expect("Could not load capture WAV file");
-
-    audio_subsystem
-        .open_playback(None, &audio_spec, |spec| {
-            let cvt = AudioCVT::new(
-                sound_wav.format,
-                sound_wav.channels,
-                sound_wav.freq,
-                spec.format,
-                spec.channels,
-                spec.freq,
-            )
-            .expect("Could not convert WAV file");
+    let sound_wav = AudioSpecWAV::load_wav_rw(&mut RWops::from_bytes(wav)?)?;
+
+    audio_subsystem.open_playback(None, &audio_spec, |spec| {
+        let cvt = AudioCVT::new(
+            sound_wav.format,
+            sound_wav.channels,
+            sound_wav.freq,
+            spec.format,
+            spec.channels,
+            spec.freq,
+        )
+        .expect("Could not convert WAV file");
 
-            let data = cvt.convert(sound_wav.buffer().to_vec());
+        let data = cvt.convert(sound_wav.buffer().to_vec());
 
-            // initialize the audio callback
-            Sound {
-                data: data,
-                volume: 1.0,
-                pos: 0,
-            }
-        })
-        .expect("Audio device not openable to playback")
+        // initialize the audio callback
+        Sound {
+            data,
+            volume: 1.0,
+            pos: 0,
+        }
+    })
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// Time given in seconds on the command line, in milliseconds
+fn parse_seconds(arg: Option<String>, error: &str) -> u64 {
+    let seconds = arg.and_then(|s| s.parse::<f64>().ok()).expect(error);
+    (seconds * 1000.0) as u64
+}
 
-    let mut board_state = if args.len() < 2 {
-        ChessBoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w QKqk - 0 0")
-    } else {
-        ChessBoardState::from_fen(&args[1])
+fn main() {
+    let mut fen = None;
+    let mut engine_color = None;
+    let mut engine_move_time = DEFAULT_ENGINE_MOVE_TIME_MS;
+    let mut theme_dir = None;
+    let mut base_time = None;
+    let mut increment = 0;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg as &str {
+            "--engine" => {
+                let color = args.next().expect("Missing engine side [w,b]");
+                engine_color =
+                    Some(PieceColor::try_from(color.as_str()).expect("Invalid engine side [w,b]"));
+            }
+            "--movetime" => {
+                engine_move_time = args
+                    .next()
+                    .and_then(|t| t.parse::<u64>().ok())
+                    .expect("Invalid engine move time");
+            }
+            "--theme" => theme_dir = Some(args.next().expect("Missing theme directory")),
+            "--time" => base_time = Some(parse_seconds(args.next(), "Invalid base time")),
+            "--increment" => increment = parse_seconds(args.next(), "Invalid increment"),
+            _ => fen = Some(arg),
+        }
     }
+
+    let mut board_state = ChessBoardState::from_fen(
+        fen.as_deref()
+            .unwrap_or("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w QKqk - 0 0"),
+    )
     .expect("Error parsing FEN");
 
+    let theme = Theme::select(theme_dir.as_deref()).expect("Error loading theme");
+
+    let mut engine_opponent = engine_color.map(|_| EngineOpponent::spawn(engine_move_time));
+    let mut analysis: Option<Analysis> = None;
+
     let sdl_context = sdl2::init().expect("Error creating context");
     let video_subsystem = sdl_context.video().expect("Error creating video subsystem");
 
@@ -608,7 +1399,11 @@ fn main() {
         .map_err(|e| e.to_string())
         .expect("Error building Window");
 
-    window.set_icon(Surface::from_file("iglo.png").unwrap());
+    window.set_icon(
+        RWops::from_bytes(theme::WINDOW_ICON)
+            .and_then(|icon| icon.load_png())
+            .expect("Error loading window icon"),
+    );
 
     let mut canvas = window
         .into_canvas()
@@ -624,46 +1419,158 @@ fn main() {
         .map_err(|e| e.to_string())
         .expect("Error creating image context");
     let texture_creator = canvas.texture_creator();
-    let sprite_texture = texture_creator
-        .load_texture("sprites.png")
-        .expect("Error loading texture");
-
-    let mut font = ttf_context
-        .load_font("font.ttf", 18)
-        .expect("Error loading ttf");
-    font.set_style(sdl2::ttf::FontStyle::BOLD);
-
     let audio_subsystem = sdl_context.audio().expect("Error creating audio context");
 
-    let mut asset_pack = AssetPack {
-        sprite_texture,
-        font,
-        capture_sound: create_audio_device_sound("capture.wav", &audio_subsystem),
-        move_sound: create_audio_device_sound("move.wav", &audio_subsystem),
+    let mut asset_pack = AssetPack::load(&theme, &texture_creator, &ttf_context, &audio_subsystem)
+        .expect("Error loading assets");
+
+    let mut game_ui_state = GameUIState {
+        game: Game::new(board_state),
+        engine_color,
+        // Keep the human's pieces at the bottom of the board
+        flipped: engine_color == Some(PieceColor::White),
+        time_control: base_time.map(|base| (base, increment)),
+        ..Default::default()
     };
+    reset_clock(&mut game_ui_state);
+    let mut last_clock_redraw = Instant::now();
 
-    let mut game_ui_state = GameUIState::default();
+    let clipboard = video_subsystem.clipboard();
+    let mut board_editor: Option<BoardEditor> = None;
 
     let mut redraw_board = |board_state: &ChessBoardState,
                             game_ui_state: &GameUIState,
-                            asset_pack: &AssetPack|
+                            asset_pack: &mut AssetPack,
+                            board_editor: Option<&BoardEditor>|
      -> Result<(), String> {
+        if let Some(board_editor) = board_editor {
+            let editor_ui_state = GameUIState {
+                flipped: game_ui_state.flipped,
+                ..Default::default()
+            };
+            draw_grid(&mut canvas, asset_pack, &texture_creator, &editor_ui_state)?;
+            draw_chess_board(
+                &mut canvas,
+                &board_editor.board_state,
+                asset_pack,
+                &editor_ui_state,
+            )?;
+            draw_editor_bar(&mut canvas, asset_pack, &texture_creator, board_editor)?;
+            canvas.present();
+            return Ok(());
+        }
+
+        let board_state = displayed_board_state(board_state, game_ui_state);
         draw_grid(&mut canvas, asset_pack, &texture_creator, game_ui_state)?;
+        draw_premove_indicator(&mut canvas, game_ui_state)?;
         draw_chess_board(&mut canvas, &board_state, asset_pack, game_ui_state)?;
         draw_moves_indicator(&mut canvas, game_ui_state)?;
+        draw_analysis_arrows(&mut canvas, board_state, game_ui_state)?;
+        draw_annotations(&mut canvas, game_ui_state)?;
+        draw_eval_bar(&mut canvas, board_state, game_ui_state)?;
         draw_dragged_piece(&mut canvas, asset_pack, board_state, game_ui_state)?;
+        draw_animated_pieces(&mut canvas, asset_pack, game_ui_state)?;
         draw_promotion_prompt(&mut canvas, asset_pack, board_state, game_ui_state)?;
-        draw_stats_bar(&mut canvas, &board_state, asset_pack, &texture_creator)?;
+        draw_stats_bar(
+            &mut canvas,
+            &board_state,
+            asset_pack,
+            &texture_creator,
+            game_ui_state,
+        )?;
         canvas.present();
         Ok(())
     };
 
-    redraw_board(&board_state, &game_ui_state, &asset_pack).expect("Error redrawing board");
+    redraw_board(
+        &board_state,
+        &game_ui_state,
+        &mut asset_pack,
+        board_editor.as_ref(),
+    )
+    .expect("Error redrawing board");
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut frame_timer = FrameTimer::new(FRAMES_PER_SECOND);
 
     'running: loop {
         for event in event_pump.poll_iter() {
+            if let Some(editor) = &mut board_editor {
+                match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => break 'running,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::E),
+                        ..
+                    } => match editor.finish() {
+                        Ok(new_state) => {
+                            println!("{}", new_state.to_fen());
+                            if let Some(engine) = &mut engine_opponent {
+                                engine.stop.store(true, Ordering::SeqCst);
+                                engine.thinking_on = None;
+                            }
+                            start_from_position(&mut board_state, &mut game_ui_state, new_state);
+                            board_editor = None;
+                        }
+                        Err(e) => editor.last_error = Some(e),
+                    },
+                    Event::KeyDown {
+                        keycode: Some(Keycode::C),
+                        ..
+                    } => {
+                        let fen = editor.to_fen();
+                        println!("{}", fen);
+                        if let Err(e) = clipboard.set_clipboard_text(&fen) {
+                            editor.last_error = Some(e);
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F),
+                        ..
+                    } => game_ui_state.flipped = !game_ui_state.flipped,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => handle_editor_key(editor, keycode),
+                    Event::MouseButtonDown {
+                        x, y, mouse_btn, ..
+                    } => {
+                        if let Some(square) = get_square_from_cursor_pos(x, y, &game_ui_state) {
+                            match mouse_btn {
+                                MouseButton::Left => editor.place_selected_piece(square),
+                                MouseButton::Right => editor.remove_piece(square),
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => continue,
+                }
+                redraw_board(
+                    &board_state,
+                    &game_ui_state,
+                    &mut asset_pack,
+                    board_editor.as_ref(),
+                )
+                .expect("Error redrawing board");
+                continue;
+            }
+
             match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } if !game_ui_state.annotations.is_empty() => {
+                    game_ui_state.annotations.clear();
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
@@ -675,10 +1582,146 @@ fn main() {
                     ..
                 } => {
                     game_ui_state.flipped = !game_ui_state.flipped;
-                    redraw_board(&board_state, &game_ui_state, &asset_pack).expect("Error redrawing board");
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
                 }
-                Event::MouseButtonDown { x, y, .. } => {
-                    if game_ui_state.promotion_prompt.is_none() {
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
+                    keymod,
+                    ..
+                } if is_ctrl_pressed(keymod) => {
+                    let fen = displayed_board_state(&board_state, &game_ui_state).to_fen();
+                    println!("{}", fen);
+                    if let Err(e) = clipboard.set_clipboard_text(&fen) {
+                        println!("Error copying FEN: {}", e);
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::V),
+                    keymod,
+                    ..
+                } if is_ctrl_pressed(keymod) => {
+                    let pasted = clipboard.clipboard_text().unwrap_or_default();
+                    match ChessBoardState::from_fen(&pasted) {
+                        Ok(new_state) => {
+                            if let Some(engine) = &mut engine_opponent {
+                                engine.stop.store(true, Ordering::SeqCst);
+                                engine.thinking_on = None;
+                            }
+                            start_from_position(&mut board_state, &mut game_ui_state, new_state);
+                        }
+                        Err(e) => {
+                            println!("Clipboard does not contain a valid FEN ({}): {}", e, pasted)
+                        }
+                    }
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::S),
+                    keymod,
+                    ..
+                } if is_ctrl_pressed(keymod) => match save_game_as_pgn(&game_ui_state) {
+                    Ok(path) => println!("Saved game to {}", path),
+                    Err(e) => println!("Error saving game: {}", e),
+                },
+                Event::KeyDown {
+                    keycode: Some(Keycode::A),
+                    ..
+                } => {
+                    if let Some(analysis) = analysis.take() {
+                        analysis.stop();
+                        game_ui_state.analysis_line = None;
+                    } else {
+                        analysis = Some(Analysis::spawn());
+                    }
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::E),
+                    ..
+                } => {
+                    board_editor = Some(BoardEditor::new(&board_state));
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Z),
+                    ..
+                } => {
+                    undo_move(&mut board_state, &mut game_ui_state);
+                    if let Some(engine) = &mut engine_opponent {
+                        // Take back the engine's reply as well, so it is the human's turn again
+                        engine.stop.store(true, Ordering::SeqCst);
+                        engine.thinking_on = None;
+                        game_ui_state.animation = None;
+                        while !is_human_turn(&board_state, &game_ui_state)
+                            && !game_ui_state.game.moves().is_empty()
+                        {
+                            undo_move(&mut board_state, &mut game_ui_state);
+                        }
+                    }
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+                Event::KeyDown {
+                    keycode:
+                        Some(
+                            keycode
+                            @ (Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down),
+                        ),
+                    ..
+                } => {
+                    navigate_history(&mut game_ui_state, keycode);
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+                Event::MouseButtonDown {
+                    x, y, mouse_btn, ..
+                } => {
+                    if mouse_btn == MouseButton::Left {
+                        game_ui_state.annotations.clear();
+                    }
+
+                    if game_ui_state.viewed_ply.is_some() {
+                        // Any click while browsing the game returns to the live position
+                        game_ui_state.viewed_ply = None;
+                    } else if mouse_btn == MouseButton::Right {
+                        game_ui_state.premove = None;
+                        game_ui_state.annotation_start =
+                            get_square_from_cursor_pos(x, y, &game_ui_state);
+                    } else if game_ui_state.promotion_prompt.is_none() {
                         let clicked_square = get_square_from_cursor_pos(x, y, &game_ui_state);
                         match (game_ui_state.last_clicked_square, clicked_square) {
                             (Some(src), Some(dst)) => execute_move_with_src_and_dst(
@@ -695,8 +1738,12 @@ fn main() {
                             }
                             (None, Some(dst)) => {
                                 game_ui_state.last_clicked_square = clicked_square;
-                                game_ui_state.moves_for_selected_piece =
-                                    generate_possible_moves_for_piece(&board_state, dst);
+                                if is_human_turn(&board_state, &game_ui_state)
+                                    && game_ui_state.flagged.is_none()
+                                {
+                                    game_ui_state.moves_for_selected_piece =
+                                        board_state.moves_from(dst);
+                                }
                             }
                             (None, None) => {}
                         };
@@ -720,11 +1767,23 @@ fn main() {
                             .map(|y| *y)
                             .nth(0)
                             .unwrap();
-                        board_state = board_state.exec_move(move_to_exec);
                         game_ui_state.promotion_prompt = None;
+                        play_move(
+                            &mut board_state,
+                            &mut game_ui_state,
+                            &mut asset_pack,
+                            move_to_exec,
+                        );
+                        try_execute_premove(&mut board_state, &mut game_ui_state, &mut asset_pack);
                     }
 
-                    redraw_board(&board_state, &game_ui_state, &asset_pack).expect("Error redrawing board");
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
                 }
                 Event::MouseMotion {
                     x, y, mousestate, ..
@@ -734,13 +1793,37 @@ fn main() {
                         && game_ui_state.promotion_prompt.is_none()
                     {
                         game_ui_state.dragging_piece_pos = Some((x, y));
-                        redraw_board(&board_state, &game_ui_state, &asset_pack).expect("Error redrawing board");
+                        redraw_board(
+                            &board_state,
+                            &game_ui_state,
+                            &mut asset_pack,
+                            board_editor.as_ref(),
+                        )
+                        .expect("Error redrawing board");
                     }
                 }
                 Event::MouseButtonUp {
                     x, y, mouse_btn, ..
                 } => {
-                    if mouse_btn == MouseButton::Left
+                    if mouse_btn == MouseButton::Right {
+                        let src = game_ui_state.annotation_start.take();
+                        let dst = get_square_from_cursor_pos(x, y, &game_ui_state);
+                        if let (Some(src), Some(dst)) = (src, dst) {
+                            let annotation = if src == dst {
+                                Annotation::Highlight(src)
+                            } else {
+                                Annotation::Arrow(src, dst)
+                            };
+                            toggle_annotation(&mut game_ui_state, annotation);
+                            redraw_board(
+                                &board_state,
+                                &game_ui_state,
+                                &mut asset_pack,
+                                board_editor.as_ref(),
+                            )
+                            .expect("Error redrawing board");
+                        }
+                    } else if mouse_btn == MouseButton::Left
                         && game_ui_state.dragging_piece_pos.is_some()
                         && game_ui_state.last_clicked_square.is_some()
                         && game_ui_state.promotion_prompt.is_none()
@@ -754,11 +1837,19 @@ fn main() {
                                 mv_src,
                                 dst_square,
                             );
+                            // The dropped piece already is where it belongs
+                            game_ui_state.animation = None;
                         }
                         game_ui_state.dragging_piece_pos = None;
                         game_ui_state.last_clicked_square = None;
                         game_ui_state.moves_for_selected_piece.clear();
-                        redraw_board(&board_state, &game_ui_state, &asset_pack).expect("Error redrawing board");
+                        redraw_board(
+                            &board_state,
+                            &game_ui_state,
+                            &mut asset_pack,
+                            board_editor.as_ref(),
+                        )
+                        .expect("Error redrawing board");
                     }
                 }
 
@@ -766,7 +1857,111 @@ fn main() {
             }
         }
 
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 240));
-        // The rest of the game loop goes here...
+        if let Some(engine) = &mut engine_opponent {
+            if let Ok(result) = engine.result_rx.try_recv() {
+                // Results for positions that were taken back in the meantime are stale
+                if engine.thinking_on == Some(result.zhash) {
+                    engine.thinking_on = None;
+                    let score = if board_state.side == PieceColor::White {
+                        result.score
+                    } else {
+                        -result.score
+                    };
+                    game_ui_state.engine_stats = Some((score, result.depth));
+                    if result.best_move != Move::NULL_MOVE {
+                        play_move(
+                            &mut board_state,
+                            &mut game_ui_state,
+                            &mut asset_pack,
+                            result.best_move,
+                        );
+                        try_execute_premove(&mut board_state, &mut game_ui_state, &mut asset_pack);
+                    }
+                    redraw_board(
+                        &board_state,
+                        &game_ui_state,
+                        &mut asset_pack,
+                        board_editor.as_ref(),
+                    )
+                    .expect("Error redrawing board");
+                }
+            }
+
+            if engine.thinking_on.is_none()
+                && board_editor.is_none()
+                && !is_human_turn(&board_state, &game_ui_state)
+                && game_ui_state.promotion_prompt.is_none()
+                && game_ui_state.flagged.is_none()
+                && !board_state
+                    .generate_legal_moves_for_current_player::<false>()
+                    .is_empty()
+            {
+                engine.request_move(&board_state, &game_ui_state);
+            }
+        }
+
+        if let Some(analysis) = &mut analysis {
+            // Restart the analysis whenever the shown position changes
+            let displayed = displayed_board_state(&board_state, &game_ui_state);
+            if board_editor.is_none() && analysis.analysing != Some(displayed.zhash) {
+                let ply = game_ui_state
+                    .viewed_ply
+                    .unwrap_or(game_ui_state.game.moves().len());
+                analysis.analyse(displayed, position_history(displayed, &game_ui_state, ply));
+            }
+
+            let mut updated = false;
+            while let Ok(update) = analysis.update_rx.try_recv() {
+                if analysis.analysing == Some(update.zhash) {
+                    game_ui_state.analysis_line = Some(update);
+                    updated = true;
+                }
+            }
+            if updated {
+                redraw_board(
+                    &board_state,
+                    &game_ui_state,
+                    &mut asset_pack,
+                    board_editor.as_ref(),
+                )
+                .expect("Error redrawing board");
+            }
+        }
+
+        if board_editor.is_none() && game_ui_state.clock_running_since.is_some() {
+            if check_flag_fall(&mut game_ui_state) {
+                if let Some(engine) = &mut engine_opponent {
+                    engine.stop.store(true, Ordering::SeqCst);
+                    engine.thinking_on = None;
+                }
+            }
+            if last_clock_redraw.elapsed().as_millis() >= CLOCK_REDRAW_MS
+                || game_ui_state.flagged.is_some()
+            {
+                last_clock_redraw = Instant::now();
+                redraw_board(
+                    &board_state,
+                    &game_ui_state,
+                    &mut asset_pack,
+                    board_editor.as_ref(),
+                )
+                .expect("Error redrawing board");
+            }
+        }
+
+        if let Some(animation) = &game_ui_state.animation {
+            if animation.is_finished() {
+                game_ui_state.animation = None;
+            }
+            redraw_board(
+                &board_state,
+                &game_ui_state,
+                &mut asset_pack,
+                board_editor.as_ref(),
+            )
+            .expect("Error redrawing board");
+        }
+
+        frame_timer.wait();
     }
 }
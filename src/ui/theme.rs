@@ -0,0 +1,146 @@
+//! Themes change the piece set, board colors and sounds of the UI. A theme is a directory
+//! which may contain any of `sprites.png`, `font.ttf`, `move.wav`, `capture.wav` and a
+//! `theme.toml` with the board colors, everything it does not contain is taken from the
+//! default theme built into the binary.
+
+use iglo::render::{COLOR_DARK_SQUARE, COLOR_LIGHT_SQUARE, DEFAULT_FONT, DEFAULT_SPRITES};
+use sdl2::pixels::Color;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_MOVE_SOUND: &[u8] = include_bytes!("move.wav");
+const DEFAULT_CAPTURE_SOUND: &[u8] = include_bytes!("capture.wav");
+pub const WINDOW_ICON: &[u8] = include_bytes!("iglo_small.png");
+
+/// Name of the UI config file, looked up in `$XDG_CONFIG_HOME/iglo` or `~/.config/iglo`.
+const CONFIG_FILE_NAME: &str = "ui.toml";
+
+/// Raw assets of a theme, turned into textures, fonts and audio devices by the UI.
+pub struct Theme {
+    /// Sheet of the pieces, white in the upper row and black below, from left to right
+    /// king, queen, bishop, knight, rook and pawn. The sprites are square and may have
+    /// any size.
+    pub sprites: Vec<u8>,
+    pub font: Vec<u8>,
+    pub move_sound: Vec<u8>,
+    pub capture_sound: Vec<u8>,
+    pub light_square: Color,
+    pub dark_square: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            sprites: DEFAULT_SPRITES.to_vec(),
+            font: DEFAULT_FONT.to_vec(),
+            move_sound: DEFAULT_MOVE_SOUND.to_vec(),
+            capture_sound: DEFAULT_CAPTURE_SOUND.to_vec(),
+            light_square: COLOR_LIGHT_SQUARE,
+            dark_square: COLOR_DARK_SQUARE,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme directory on top of the default theme.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        if !dir.is_dir() {
+            return Err(format!("Theme {} is not a directory", dir.display()));
+        }
+
+        let mut theme = Self::default();
+        let assets = [
+            ("sprites.png", &mut theme.sprites),
+            ("font.ttf", &mut theme.font),
+            ("move.wav", &mut theme.move_sound),
+            ("capture.wav", &mut theme.capture_sound),
+        ];
+        for (name, data) in assets {
+            let path = dir.join(name);
+            if path.exists() {
+                *data = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+        }
+
+        let colors = dir.join("theme.toml");
+        if colors.exists() {
+            let text =
+                fs::read_to_string(&colors).map_err(|e| format!("{}: {}", colors.display(), e))?;
+            for (key, value) in parse_entries(&text)? {
+                let color = parse_color(&value)?;
+                match key.as_str() {
+                    "light_square" => theme.light_square = color,
+                    "dark_square" => theme.dark_square = color,
+                    _ => return Err(format!("Unknown theme color {}", key)),
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Theme directory given on the command line, otherwise the one named by `theme = ...`
+    /// in the config file, otherwise the default theme.
+    pub fn select(cli_dir: Option<&str>) -> Result<Self, String> {
+        let dir = match cli_dir {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => configured_theme_dir()?,
+        };
+        match dir {
+            Some(dir) => Self::load(&dir),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("iglo").join(CONFIG_FILE_NAME))
+}
+
+fn configured_theme_dir() -> Result<Option<PathBuf>, String> {
+    let path = match config_file_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(None),
+    };
+    let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let theme = parse_entries(&text)?
+        .into_iter()
+        .find(|(key, _)| key == "theme")
+        .map(|(_, dir)| PathBuf::from(dir));
+    Ok(theme)
+}
+
+/// `key = value` lines of a flat TOML table, lines starting with `#` are comments.
+fn parse_entries(text: &str) -> Result<Vec<(String, String)>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid config line {}", line))?;
+            Ok((
+                key.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Color in `RRGGBB` hex notation, with or without a leading `#`.
+fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = text.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Invalid color {}", text))
+    };
+    if hex.len() != 6 {
+        return Err(format!("Invalid color {}", text));
+    }
+    Ok(Color::RGB(channel(0)?, channel(2)?, channel(4)?))
+}
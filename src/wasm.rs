@@ -0,0 +1,70 @@
+use std::{
+    cell::RefCell,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    chess::board::ChessBoardState,
+    engine::{bot::ChessBot, bots::nplytranspo_bot::NPlyTranspoBot, time_control::TimeControl},
+};
+
+struct WasmEngine {
+    board_state: ChessBoardState,
+    bot: NPlyTranspoBot,
+    stop: Arc<AtomicBool>,
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        Self {
+            board_state: ChessBoardState::starting_state(),
+            bot: NPlyTranspoBot::default(),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+thread_local! {
+    static ENGINE: RefCell<Option<WasmEngine>> = RefCell::new(None);
+}
+
+fn with_engine<R>(f: impl FnOnce(&mut WasmEngine) -> R) -> R {
+    ENGINE.with(|engine| f(engine.borrow_mut().get_or_insert_with(WasmEngine::default)))
+}
+
+/// Resets the engine to the starting position and forgets the game history.
+#[wasm_bindgen(js_name = newGame)]
+pub fn new_game() {
+    with_engine(|engine| {
+        engine.board_state = ChessBoardState::starting_state();
+        engine.bot.new_game();
+    });
+}
+
+/// Loads the given position, throws if the FEN can not be parsed.
+#[wasm_bindgen(js_name = setFen)]
+pub fn set_fen(fen: &str) -> Result<(), JsValue> {
+    let board_state =
+        ChessBoardState::from_fen(fen).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    with_engine(|engine| {
+        engine.board_state = board_state;
+        engine.bot.clear_history();
+    });
+    Ok(())
+}
+
+/// Searches the current position for `ms` milliseconds and returns the best move
+/// in UCI notation (e.g. `e2e4`).
+#[wasm_bindgen(js_name = bestMove)]
+pub fn best_move(ms: u32) -> String {
+    with_engine(|engine| {
+        let best_move = engine.bot.search_best_move(
+            &mut engine.board_state,
+            TimeControl::FixedTime(ms as u64),
+            &engine.stop,
+        );
+        format!("{:?}", best_move)
+    })
+}
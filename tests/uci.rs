@@ -0,0 +1,346 @@
+//! Black box tests of the UCI protocol: the `iglo` engine is started as a separate process
+//! and spoken to over its standard input and output, like a GUI would.
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use iglo::chess::{board::ChessBoardState, chess_move::Move};
+
+// Generous, the engine may be a debug build on a busy machine
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+// Time in which a `stop` has to be answered with a move
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Path of the engine, built next to the test binary by `cargo test`. A lone `cargo test
+/// --test uci` does not build the examples, then it is built here once.
+fn engine_path() -> &'static PathBuf {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        // target/<profile>/deps/uci-<hash>
+        let exe = env::current_exe().unwrap();
+        let profile_dir = exe.parent().unwrap().parent().unwrap();
+        let path = profile_dir
+            .join("examples")
+            .join(format!("iglo{}", env::consts::EXE_SUFFIX));
+        if !path.exists() {
+            let mut build = Command::new(env!("CARGO"));
+            build.args(["build", "--example", "iglo", "--manifest-path"]);
+            build.arg(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"));
+            if profile_dir.ends_with("release") {
+                build.arg("--release");
+            }
+            assert!(
+                build.status().unwrap().success(),
+                "building the engine failed"
+            );
+        }
+        path
+    })
+}
+
+struct Engine {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    fn spawn() -> Self {
+        let mut child = Command::new(engine_path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read on a thread of its own, so every wait can time out
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            child,
+            stdin,
+            lines,
+        }
+    }
+
+    fn send(&mut self, command: &str) {
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{}", command).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    /// Lines up to and including the first one starting with `prefix`, fails when none
+    /// arrives in time.
+    fn expect(&self, prefix: &str, timeout: Duration) -> Vec<String> {
+        let deadline = Instant::now() + timeout;
+        let mut lines = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    let done = line.starts_with(prefix);
+                    lines.push(line);
+                    if done {
+                        return lines;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    panic!("no '{}' within {:?}, got {:?}", prefix, timeout, lines)
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    panic!("engine exited before '{}', got {:?}", prefix, lines)
+                }
+            }
+        }
+    }
+
+    /// Move of the `bestmove` line ending the search.
+    fn expect_bestmove(&self, timeout: Duration) -> String {
+        let lines = self.expect("bestmove", timeout);
+        let bestmove = lines.last().unwrap().split_whitespace().nth(1);
+        bestmove.expect("bestmove without a move").to_string()
+    }
+
+    fn handshake(&mut self) {
+        self.send("uci");
+        self.expect("uciok", RESPONSE_TIMEOUT);
+        self.send("isready");
+        self.expect("readyok", RESPONSE_TIMEOUT);
+    }
+
+    /// Waits for the process to end by itself.
+    fn wait_for_exit(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status.success();
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn assert_legal(mv: &str, board_state: &ChessBoardState) {
+    let parsed = Move::try_from((mv, board_state));
+    assert!(
+        parsed.is_ok_and(|parsed| board_state.is_legal(parsed)),
+        "illegal move {} in {}",
+        mv,
+        board_state.to_fen()
+    );
+}
+
+#[test]
+fn identifies_itself_and_lists_options() {
+    let mut engine = Engine::spawn();
+    engine.send("uci");
+    let lines = engine.expect("uciok", RESPONSE_TIMEOUT);
+    // The ids first, then only options up to uciok
+    let id_name = lines.iter().position(|line| line.starts_with("id name "));
+    let id_name = id_name.expect("id name missing");
+    assert!(lines[id_name + 1].starts_with("id author "));
+    let options = &lines[id_name + 2..lines.len() - 1];
+    assert!(
+        options.iter().all(|line| line.starts_with("option name ")),
+        "{:?}",
+        lines
+    );
+    for option in ["Hash", "Threads", "Move Overhead"] {
+        assert!(
+            options
+                .iter()
+                .any(|line| line.starts_with(&format!("option name {} type ", option))),
+            "option {} missing in {:?}",
+            option,
+            lines
+        );
+    }
+
+    engine.send("isready");
+    engine.expect("readyok", RESPONSE_TIMEOUT);
+}
+
+#[test]
+fn searches_the_given_position() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    engine.send("ucinewgame");
+    engine.send("setoption name OpeningBook value false");
+
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    engine.send(&format!("position fen {} moves f1c4 g8f6", fen));
+    engine.send("go depth 4");
+    let lines = engine.expect("bestmove", RESPONSE_TIMEOUT);
+    assert!(lines.iter().any(|line| line.contains(" depth ")));
+
+    let mut board_state = ChessBoardState::from_fen(fen).unwrap();
+    for mv in ["f1c4", "g8f6"] {
+        board_state = board_state.exec_move(Move::try_from((mv, &board_state)).unwrap());
+    }
+    let bestmove = lines.last().unwrap().split_whitespace().nth(1).unwrap();
+    assert_legal(bestmove, &board_state);
+}
+
+#[test]
+fn stops_an_infinite_search() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    engine.send("position startpos moves e2e4 c7c5");
+    engine.send("go infinite");
+
+    // The reader keeps answering while the search runs
+    thread::sleep(Duration::from_millis(200));
+    engine.send("isready");
+    let lines = engine.expect("readyok", RESPONSE_TIMEOUT);
+    assert!(lines.iter().all(|line| !line.starts_with("bestmove")));
+
+    engine.send("stop");
+    let bestmove = engine.expect_bestmove(STOP_TIMEOUT);
+    let board_state = ChessBoardState::starting_state();
+    let board_state = board_state.exec_move(Move::try_from(("e2e4", &board_state)).unwrap());
+    let board_state = board_state.exec_move(Move::try_from(("c7c5", &board_state)).unwrap());
+    assert_legal(&bestmove, &board_state);
+}
+
+#[test]
+fn holds_back_the_move_of_a_finished_infinite_search() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    // Stalemate, the search is over right away
+    engine.send("position fen k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+    engine.send("go infinite");
+    thread::sleep(Duration::from_millis(200));
+    engine.send("isready");
+    let lines = engine.expect("readyok", RESPONSE_TIMEOUT);
+    assert!(lines.iter().all(|line| !line.starts_with("bestmove")));
+
+    engine.send("stop");
+    assert_eq!(engine.expect_bestmove(STOP_TIMEOUT), "0000");
+}
+
+#[test]
+fn stop_right_after_go() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    for _ in 0..5 {
+        engine.send("position startpos");
+        engine.send("go infinite");
+        engine.send("stop");
+        let bestmove = engine.expect_bestmove(STOP_TIMEOUT);
+        assert_legal(&bestmove, &ChessBoardState::starting_state());
+    }
+    engine.send("isready");
+    engine.expect("readyok", RESPONSE_TIMEOUT);
+}
+
+#[test]
+fn stop_between_two_searches() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    for _ in 0..5 {
+        // The second go is read before the first search starts, the stop must still end it
+        engine.send("position startpos");
+        engine.send("go infinite");
+        engine.send("stop");
+        engine.send("go depth 3");
+        let bestmove = engine.expect_bestmove(STOP_TIMEOUT);
+        assert_legal(&bestmove, &ChessBoardState::starting_state());
+        let bestmove = engine.expect_bestmove(RESPONSE_TIMEOUT);
+        assert_legal(&bestmove, &ChessBoardState::starting_state());
+    }
+}
+
+#[test]
+fn reports_malformed_input_and_keeps_going() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+
+    // Unknown commands are ignored with a note, the rest of the line is a command again
+    engine.send("bogus");
+    let lines = engine.expect("info string", RESPONSE_TIMEOUT);
+    assert_eq!(lines, ["info string ignoring unknown command 'bogus'"]);
+    for command in ["register later", "ponderhit", "", "joho isready"] {
+        engine.send(command);
+    }
+    let lines = engine.expect("readyok", RESPONSE_TIMEOUT);
+    assert_eq!(lines, ["readyok"]);
+
+    for command in [
+        "go depth x",
+        "position fen 8/8/8/8/8/8/8/8 w - - 0 1",
+        // More moves than a move list holds, impossible to reach in a game
+        "position fen Q4Qbk/1Q1Q2pp/1Q3QQQ/4Q3/Q1Q3Q1/6Q1/1Q1Q4/K5QQ w - - 0 1",
+        "position startpos moves e2e5",
+        "setoption value 3",
+    ] {
+        engine.send(command);
+        let lines = engine.expect("info string error", RESPONSE_TIMEOUT);
+        assert_eq!(lines.len(), 1, "{}: {:?}", command, lines);
+    }
+
+    // After a rejected position there is nothing to search until the next valid one
+    engine.send("position startpos moves e2e4");
+    engine.send("position startpos moves e7e5");
+    engine.expect("info string error", RESPONSE_TIMEOUT);
+    engine.send("go depth 2");
+    let lines = engine.expect("bestmove", RESPONSE_TIMEOUT);
+    assert_eq!(
+        lines,
+        [
+            "info string no position set, the last one was rejected",
+            "bestmove 0000"
+        ]
+    );
+
+    engine.send("position startpos moves e2e4");
+    engine.send("go depth 2");
+    let bestmove = engine.expect_bestmove(RESPONSE_TIMEOUT);
+    let board_state = ChessBoardState::starting_state();
+    let board_state = board_state.exec_move(Move::try_from(("e2e4", &board_state)).unwrap());
+    assert_legal(&bestmove, &board_state);
+}
+
+#[test]
+fn quits_during_a_search() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    engine.send("go infinite");
+    thread::sleep(Duration::from_millis(100));
+    engine.send("quit");
+    assert!(engine.wait_for_exit(STOP_TIMEOUT));
+}
+
+#[test]
+fn exits_at_the_end_of_input() {
+    let mut engine = Engine::spawn();
+    engine.handshake();
+    engine.stdin = None;
+    assert!(engine.wait_for_exit(STOP_TIMEOUT));
+}